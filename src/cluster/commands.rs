@@ -6,10 +6,21 @@
 //! Key principle: Minimal code - only Redis protocol format conversion.
 //! All cluster logic is delegated to AiDb's MetaRaftNode, MultiRaftNode, Router, etc.
 
+use crate::command::migrate;
+use crate::command::transaction::CommandGate;
 use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
+use crate::storage::StorageEngine;
 use bytes::Bytes;
 use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "cluster")]
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "cluster")]
+use std::sync::Mutex;
+#[cfg(feature = "cluster")]
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 #[cfg(feature = "cluster")]
 use aidb::cluster::{
@@ -20,6 +31,24 @@ use aidb::cluster::{
 /// Redis Cluster has 16384 slots
 const TOTAL_SLOTS: u16 = 16384;
 
+/// How often WAIT re-checks the replication progress of its Raft groups
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How often CLUSTER MIGRATE-SLOT re-checks the progress of its migration
+const MIGRATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many keys CLUSTER MIGRATE-SLOT sends to the target at a time
+const MIGRATION_BATCH_SIZE: usize = 100;
+
+/// How long CLUSTER MIGRATE-SLOT waits on each step of sending a batch
+const MIGRATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long handing SCRIPT LOAD or SCRIPT FLUSH to another node waits on each step
+const SCRIPT_PROPAGATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long CLUSTER FORGET keeps a node from being re-added (as in Redis)
+const FORGET_BLACKLIST_TTL: Duration = Duration::from_secs(60);
+
 /// Failover mode for CLUSTER FAILOVER command
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FailoverMode {
@@ -77,6 +106,173 @@ impl NodeInfo {
     }
 }
 
+/// Nodes recently removed with CLUSTER FORGET.
+///
+/// Until its entry expires, a forgotten node cannot be re-added by CLUSTER
+/// MEET, so a peer that has not applied the removal yet cannot bring it
+/// straight back. The server shares one blacklist between all connections.
+#[cfg(feature = "cluster")]
+#[derive(Debug)]
+pub struct ForgetBlacklist {
+    ttl: Duration,
+    entries: Mutex<HashMap<NodeId, Instant>>,
+}
+
+#[cfg(feature = "cluster")]
+impl ForgetBlacklist {
+    /// Create a blacklist with the Redis 60 second window
+    pub fn new() -> Self {
+        Self::with_ttl(FORGET_BLACKLIST_TTL)
+    }
+
+    /// Create a blacklist with a custom window
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Blacklist a node for the full window, restarting it if already listed
+    pub fn add(&self, node_id: NodeId) {
+        self.lock().insert(node_id, Instant::now() + self.ttl);
+    }
+
+    /// Time left before a node may be re-added, or `None` if it is not listed
+    pub fn remaining(&self, node_id: NodeId) -> Option<Duration> {
+        let now = Instant::now();
+        let mut entries = self.lock();
+        entries.retain(|_, expires_at| *expires_at > now);
+        entries.get(&node_id).map(|expires_at| *expires_at - now)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<NodeId, Instant>> {
+        // Entries are plain timestamps a panicking holder cannot corrupt
+        self.entries
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(feature = "cluster")]
+impl Default for ForgetBlacklist {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Slots this node is moving out with CLUSTER MIGRATE-SLOT.
+///
+/// Commands on keys in these slots are refused with -TRYAGAIN until the slot
+/// has been handed over, so no write can land here after the slot's last keys
+/// were sent to the target. The server shares one set between all connections.
+#[cfg(feature = "cluster")]
+#[derive(Debug, Default)]
+pub struct MigratingSlots {
+    slots: Mutex<HashSet<u16>>,
+}
+
+#[cfg(feature = "cluster")]
+impl MigratingSlots {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark a slot as migrating until the returned guard is dropped, or
+    /// return `None` if it is already being migrated
+    pub fn start(&self, slot: u16) -> Option<MigratingSlot<'_>> {
+        // The guard is only built for a new entry, as dropping it removes one
+        if self.lock().insert(slot) {
+            Some(MigratingSlot {
+                slots: self,
+                slot,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Whether a slot is being migrated
+    pub fn contains(&self, slot: u16) -> bool {
+        self.lock().contains(&slot)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashSet<u16>> {
+        // Slot numbers are plain values a panicking holder cannot corrupt
+        self.slots
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// A slot marked as migrating by [`MigratingSlots::start`]
+#[cfg(feature = "cluster")]
+#[derive(Debug)]
+pub struct MigratingSlot<'a> {
+    slots: &'a MigratingSlots,
+    slot: u16,
+}
+
+#[cfg(feature = "cluster")]
+impl Drop for MigratingSlot<'_> {
+    fn drop(&mut self) {
+        self.slots.lock().remove(&self.slot);
+    }
+}
+
+/// Last heartbeat exchanged with each peer, in Unix milliseconds.
+///
+/// Fed from the MetaRaft heartbeat while this node leads the metadata group,
+/// and reported as the ping-sent and pong-recv columns of CLUSTER NODES. A
+/// ping is pending from the moment a peer is seen without an acknowledged
+/// heartbeat until it acknowledges one. The last values are kept when this
+/// node stops leading, so the columns go stale rather than back to zero.
+#[cfg(feature = "cluster")]
+#[derive(Debug, Default)]
+pub struct NodeLiveness {
+    entries: Mutex<HashMap<NodeId, (u64, u64)>>,
+}
+
+#[cfg(feature = "cluster")]
+impl NodeLiveness {
+    /// Create an empty tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a heartbeat sent to a peer, unless one is already pending
+    pub fn record_ping(&self, node_id: NodeId, at_ms: u64) {
+        let mut entries = self.lock();
+        let entry = entries.entry(node_id).or_default();
+        if entry.0 == 0 {
+            entry.0 = at_ms;
+        }
+    }
+
+    /// Record a heartbeat acknowledged by a peer, which ends any pending ping
+    pub fn record_pong(&self, node_id: NodeId, at_ms: u64) {
+        let mut entries = self.lock();
+        let entry = entries.entry(node_id).or_default();
+        if at_ms >= entry.0 {
+            entry.0 = 0;
+        }
+        entry.1 = entry.1.max(at_ms);
+    }
+
+    /// The (ping-sent, pong-recv) columns for a peer, `(0, 0)` if never seen
+    pub fn get(&self, node_id: NodeId) -> (u64, u64) {
+        self.lock().get(&node_id).copied().unwrap_or_default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<NodeId, (u64, u64)>> {
+        // Entries are plain timestamps a panicking holder cannot corrupt
+        self.entries
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
 /// Redis Cluster commands handler.
 ///
 /// This is a thin wrapper around AiDb's Multi-Raft components:
@@ -102,6 +298,22 @@ pub struct ClusterCommands {
 
     /// Optional migration manager for slot migration
     migration_manager: Option<Arc<MigrationManager>>,
+
+    /// Nodes that CLUSTER MEET must not re-add yet
+    forget_blacklist: Arc<ForgetBlacklist>,
+
+    /// Heartbeat timestamps of the peers, for CLUSTER NODES
+    liveness: Arc<NodeLiveness>,
+
+    /// Slots being moved out with CLUSTER MIGRATE-SLOT
+    migrating: Arc<MigratingSlots>,
+
+    /// Gate every command holds while it runs, so MIGRATE-SLOT can wait out
+    /// the commands that checked their keys before a slot was marked
+    gate: CommandGate,
+
+    /// Local keyspace, for the per-slot key counts and listings
+    storage: Option<StorageEngine>,
 }
 
 #[cfg(feature = "cluster")]
@@ -126,6 +338,11 @@ impl ClusterCommands {
             multi_raft,
             router,
             migration_manager: None,
+            forget_blacklist: Arc::new(ForgetBlacklist::new()),
+            liveness: Arc::new(NodeLiveness::new()),
+            migrating: Arc::new(MigratingSlots::new()),
+            gate: CommandGate::new(),
+            storage: None,
         }
     }
 
@@ -134,6 +351,34 @@ impl ClusterCommands {
         self.migration_manager = Some(manager);
     }
 
+    /// Share a FORGET blacklist, so nodes forgotten on one connection are
+    /// blacklisted for all of them
+    pub fn set_forget_blacklist(&mut self, blacklist: Arc<ForgetBlacklist>) {
+        self.forget_blacklist = blacklist;
+    }
+
+    /// Share a heartbeat tracker, so every connection reports the same
+    /// ping-sent and pong-recv columns
+    pub fn set_node_liveness(&mut self, liveness: Arc<NodeLiveness>) {
+        self.liveness = liveness;
+    }
+
+    /// Share the set of migrating slots, so a MIGRATE-SLOT on one connection
+    /// holds off commands on all of them
+    pub fn set_migrating_slots(&mut self, migrating: Arc<MigratingSlots>) {
+        self.migrating = migrating;
+    }
+
+    /// Share the server's command gate
+    pub fn set_command_gate(&mut self, gate: CommandGate) {
+        self.gate = gate;
+    }
+
+    /// Set the storage whose keys GETKEYSINSLOT and COUNTKEYSINSLOT report
+    pub fn set_storage(&mut self, storage: StorageEngine) {
+        self.storage = Some(storage);
+    }
+
     /// Handle CLUSTER INFO command.
     ///
     /// Maps to: `meta_raft.get_cluster_meta()`
@@ -182,11 +427,66 @@ impl ClusterCommands {
         Ok(RespValue::BulkString(Some(Bytes::from(info))))
     }
 
+    /// Find the master a node replicates, or `None` if it is a master itself.
+    ///
+    /// A node that leads a group is a master; one that only follows a leader
+    /// elsewhere is that leader's replica. Nodes in no group yet are masters.
+    fn master_of(meta: &ClusterMeta, node_id: NodeId) -> Option<NodeId> {
+        if meta.groups.values().any(|g| g.leader == Some(node_id)) {
+            return None;
+        }
+        meta.groups
+            .values()
+            .filter(|g| g.replicas.contains(&node_id))
+            .find_map(|g| g.leader)
+    }
+
+    /// Whether this node is currently a replica of another node's group,
+    /// as reported by HELLO and INFO replication
+    pub fn is_replica(&self) -> bool {
+        Self::master_of(&self.meta_raft.get_cluster_meta(), self.node_id).is_some()
+    }
+
+    /// Record the MetaRaft heartbeats this node has exchanged with its peers.
+    ///
+    /// Only the MetaRaft leader sends heartbeats, so followers keep whatever
+    /// they recorded while they last led.
+    fn observe_heartbeats(&self) {
+        let metrics = self.meta_raft.raft().metrics();
+        let current = metrics.borrow();
+        if current.current_leader != Some(self.node_id) {
+            return;
+        }
+        let Some(heartbeats) = current.heartbeat.as_ref() else {
+            return;
+        };
+
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        for (node_id, acked) in heartbeats {
+            if *node_id == self.node_id {
+                continue;
+            }
+            match acked {
+                Some(at) => {
+                    let elapsed_ms = at.elapsed().as_millis() as u64;
+                    self.liveness
+                        .record_pong(*node_id, now_ms.saturating_sub(elapsed_ms));
+                }
+                None => self.liveness.record_ping(*node_id, now_ms),
+            }
+        }
+    }
+
     /// Handle CLUSTER NODES command.
     ///
-    /// Maps to: `meta_raft.get_cluster_meta().nodes` and `.groups`
+    /// Maps to: `meta_raft.get_cluster_meta().nodes` and `.groups`, with the
+    /// ping-sent and pong-recv columns taken from the MetaRaft heartbeats
     pub fn cluster_nodes(&self) -> Result<RespValue> {
         let meta: ClusterMeta = self.meta_raft.get_cluster_meta();
+        self.observe_heartbeats();
         let mut lines = Vec::new();
 
         for (node_id, node_info) in &meta.nodes {
@@ -196,55 +496,69 @@ impl ClusterCommands {
                 _ => "handshake",
             };
 
-            // Find slots for this node by checking which groups it belongs to
+            let master_id = Self::master_of(&meta, *node_id);
+
+            // Masters own the slots of their groups; replicas and freshly met
+            // nodes that belong to no group report none
             let mut slot_ranges = Vec::new();
-            for (group_id, group_meta) in &meta.groups {
-                if group_meta.replicas.contains(node_id) {
-                    // Find slot range for this group
-                    let mut start = None;
-                    let mut end = None;
-                    for (slot_idx, &assigned_group) in meta.slots.iter().enumerate() {
-                        if assigned_group == *group_id {
-                            if start.is_none() {
-                                start = Some(slot_idx);
+            if master_id.is_none() {
+                for (group_id, group_meta) in &meta.groups {
+                    if group_meta.replicas.contains(node_id) {
+                        // Find slot range for this group
+                        let mut start = None;
+                        let mut end = None;
+                        for (slot_idx, &assigned_group) in meta.slots.iter().enumerate() {
+                            if assigned_group == *group_id {
+                                if start.is_none() {
+                                    start = Some(slot_idx);
+                                }
+                                end = Some(slot_idx);
+                            } else if start.is_some() {
+                                slot_ranges.push(format!("{}-{}", start.unwrap(), end.unwrap()));
+                                start = None;
+                                end = None;
                             }
-                            end = Some(slot_idx);
-                        } else if start.is_some() {
-                            slot_ranges.push(format!("{}-{}", start.unwrap(), end.unwrap()));
-                            start = None;
-                            end = None;
                         }
-                    }
-                    if let Some(s) = start {
-                        slot_ranges.push(format!("{}-{}", s, end.unwrap()));
+                        if let Some(s) = start {
+                            slot_ranges.push(format!("{}-{}", s, end.unwrap()));
+                        }
                     }
                 }
             }
 
-            // Check if this node is a master or replica
-            let role = if meta.groups.values().any(|g| g.leader == Some(*node_id)) {
-                "master"
-            } else {
+            let role = if master_id.is_some() {
                 "slave"
+            } else {
+                "master"
             };
+            let master = master_id
+                .map(Self::format_node_id)
+                .unwrap_or_else(|| "-".to_string());
 
             // Format: <id> <ip:port@cport> <flags> <master> <ping-sent> <pong-recv> <config-epoch> <link-state> <slot> <slot> ...
-            let myself_flag = if *node_id == self.node_id {
-                "myself,"
+            // As in Redis, a node reports 0 0 for itself
+            let (myself_flag, (ping_sent, pong_recv)) = if *node_id == self.node_id {
+                ("myself,", (0, 0))
             } else {
-                ""
+                ("", self.liveness.get(*node_id))
             };
-            let node_line = format!(
-                "{:040x} {}@{} {}{} - 0 0 {} {} {}",
-                node_id,
+            let mut node_line = format!(
+                "{} {}@{} {}{} {} {} {} {} {}",
+                Self::format_node_id(*node_id),
                 node_info.addr,
                 Self::extract_cluster_port(&node_info.addr),
                 myself_flag,
                 role,
+                master,
+                ping_sent,
+                pong_recv,
                 meta.config_version,
                 status,
-                slot_ranges.join(" ")
             );
+            for range in &slot_ranges {
+                node_line.push(' ');
+                node_line.push_str(range);
+            }
 
             lines.push(node_line);
         }
@@ -352,7 +666,7 @@ impl ClusterCommands {
         RespValue::Array(Some(vec![
             RespValue::BulkString(Some(Bytes::from(ip))),
             RespValue::Integer(port),
-            RespValue::BulkString(Some(Bytes::from(format!("{:040x}", node_id)))),
+            RespValue::BulkString(Some(Bytes::from(Self::format_node_id(node_id)))),
         ]))
     }
 
@@ -375,14 +689,26 @@ impl ClusterCommands {
         }
     }
 
+    /// Format a node ID the way Redis Cluster reports it (40 hex characters).
+    ///
+    /// Every reply that names a node (MYID, NODES, SLOTS) goes through this so
+    /// clients see a single identity per node.
+    pub fn format_node_id(node_id: NodeId) -> String {
+        format!("{:040x}", node_id)
+    }
+
+    /// Get this node's ID.
+    pub fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
     /// Handle CLUSTER MYID command.
     ///
     /// Maps to: node_id
     pub fn cluster_myid(&self) -> Result<RespValue> {
-        Ok(RespValue::BulkString(Some(Bytes::from(format!(
-            "{:040x}",
-            self.node_id
-        )))))
+        Ok(RespValue::BulkString(Some(Bytes::from(
+            Self::format_node_id(self.node_id),
+        ))))
     }
 
     /// Handle CLUSTER KEYSLOT command.
@@ -410,14 +736,16 @@ impl ClusterCommands {
     ) -> Result<RespValue> {
         let addr = format!("{}:{}", ip, port);
 
-        // Generate node ID if not provided
-        let node_id = node_id_opt.unwrap_or_else(|| {
-            use std::collections::hash_map::DefaultHasher;
-            use std::hash::{Hash, Hasher};
-            let mut hasher = DefaultHasher::new();
-            addr.hash(&mut hasher);
-            hasher.finish()
-        });
+        // Derive the node ID the same way the node derives its own if not provided
+        let node_id = node_id_opt.unwrap_or_else(|| Self::resolve_node_id(&addr));
+
+        if let Some(remaining) = self.forget_blacklist.remaining(node_id) {
+            return Err(AikvError::Invalid(format!(
+                "Node {} was forgotten recently and can be met again in {}s",
+                Self::format_node_id(node_id),
+                remaining.as_secs() + 1
+            )));
+        }
 
         // Add node to cluster metadata via MetaRaft
         // This adds the node to the cluster's node list
@@ -431,13 +759,15 @@ impl ClusterCommands {
 
     /// Handle CLUSTER FORGET command.
     ///
-    /// Maps to: `meta_raft.remove_node(node_id)`
+    /// Maps to: `meta_raft.remove_node(node_id)`, and blacklists the node so
+    /// CLUSTER MEET cannot re-add it for the next 60 seconds.
     pub async fn cluster_forget(&self, node_id: NodeId) -> Result<RespValue> {
         // Remove node via MetaRaft - this will sync to all nodes via Raft consensus
         self.meta_raft
             .remove_node(node_id)
             .await
             .map_err(|e| AikvError::Internal(format!("Failed to remove node: {}", e)))?;
+        self.forget_blacklist.add(node_id);
 
         Ok(RespValue::SimpleString("OK".to_string()))
     }
@@ -450,27 +780,7 @@ impl ClusterCommands {
     /// The group_id is determined by finding which group this node belongs to.
     /// If the node doesn't belong to any group yet, we create one automatically.
     pub async fn cluster_addslots(&self, slots: Vec<u16>) -> Result<RespValue> {
-        let meta = self.meta_raft.get_cluster_meta();
-
-        // Find the group that this node belongs to, or create one if it doesn't exist
-        let group_id = if let Some((gid, _)) = meta
-            .groups
-            .iter()
-            .find(|(_, g)| g.replicas.contains(&self.node_id))
-        {
-            *gid
-        } else {
-            // Auto-create a group for this node using its node_id as the group_id
-            // This matches Redis behavior where each master initially forms its own group
-            let group_id = self.node_id;
-            self.meta_raft
-                .create_group(group_id, vec![self.node_id])
-                .await
-                .map_err(|e| {
-                    AikvError::Internal(format!("Failed to create group for node: {}", e))
-                })?;
-            group_id
-        };
+        let group_id = self.ensure_group(self.node_id).await?;
 
         // Assign each slot to this node's group - sync via Raft consensus
         for slot in slots {
@@ -510,6 +820,249 @@ impl ClusterCommands {
         Ok(RespValue::SimpleString("OK".to_string()))
     }
 
+    /// Handle CLUSTER ADDSLOTSRANGE command.
+    ///
+    /// Maps to: `meta_raft.update_slots(start, end + 1, group_id)` per range,
+    /// so a large assignment is a handful of Raft proposals rather than one
+    /// per slot.
+    pub async fn cluster_addslotsrange(&self, ranges: Vec<(u16, u16)>) -> Result<RespValue> {
+        Self::check_slot_ranges(&ranges)?;
+        let group_id = self.ensure_group(self.node_id).await?;
+
+        for (start, end) in ranges {
+            self.meta_raft
+                .update_slots(start, end + 1, group_id)
+                .await
+                .map_err(|e| {
+                    AikvError::Internal(format!("Failed to assign slots {}-{}: {}", start, end, e))
+                })?;
+        }
+
+        Ok(RespValue::SimpleString("OK".to_string()))
+    }
+
+    /// Handle CLUSTER DELSLOTSRANGE command.
+    ///
+    /// Maps to: `meta_raft.update_slots(start, end + 1, 0)` per range
+    pub async fn cluster_delslotsrange(&self, ranges: Vec<(u16, u16)>) -> Result<RespValue> {
+        Self::check_slot_ranges(&ranges)?;
+
+        for (start, end) in ranges {
+            self.meta_raft
+                .update_slots(start, end + 1, 0)
+                .await
+                .map_err(|e| {
+                    AikvError::Internal(format!("Failed to delete slots {}-{}: {}", start, end, e))
+                })?;
+        }
+
+        Ok(RespValue::SimpleString("OK".to_string()))
+    }
+
+    /// Validate the inclusive ranges of ADDSLOTSRANGE and DELSLOTSRANGE.
+    ///
+    /// Every range must be in order and within the slot space, and no slot may
+    /// be named by two ranges of the same call, as in Redis.
+    fn check_slot_ranges(ranges: &[(u16, u16)]) -> Result<()> {
+        for &(start, end) in ranges {
+            if end >= TOTAL_SLOTS {
+                return Err(AikvError::Invalid(format!("Invalid slot: {}", end)));
+            }
+            if start > end {
+                return Err(AikvError::InvalidArgument(format!(
+                    "ERR start slot number {} is greater than end slot number {}",
+                    start, end
+                )));
+            }
+        }
+
+        let mut sorted = ranges.to_vec();
+        sorted.sort_unstable();
+        for pair in sorted.windows(2) {
+            if pair[1].0 <= pair[0].1 {
+                return Err(AikvError::InvalidArgument(format!(
+                    "ERR Slot {} specified multiple times",
+                    pair[1].0
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle CLUSTER SETSLOT <slot> NODE <node-id> command.
+    ///
+    /// Completes a migration by handing the slot to the target node's group.
+    /// Maps to: `meta_raft.update_slots(slot, slot + 1, group_id)`
+    ///
+    /// Redis bumps the config epoch here so the new owner wins later conflicts.
+    /// Ownership lives in the MetaRaft log instead: the transfer is committed
+    /// after every earlier assignment and advances `config_version` (reported
+    /// as the cluster epoch), so it supersedes the previous owner's claim on
+    /// every node, including when the target is this node.
+    pub async fn cluster_setslot_node(&self, slot: u16, target: NodeId) -> Result<RespValue> {
+        if slot >= TOTAL_SLOTS {
+            return Err(AikvError::Invalid(format!("Invalid slot: {}", slot)));
+        }
+
+        let meta = self.meta_raft.get_cluster_meta();
+        if !meta.nodes.contains_key(&target) {
+            return Err(AikvError::Invalid(format!(
+                "I don't know about node {}",
+                Self::format_node_id(target)
+            )));
+        }
+
+        let group_id = self.ensure_group(target).await?;
+        self.meta_raft
+            .update_slots(slot, slot + 1, group_id)
+            .await
+            .map_err(|e| AikvError::Internal(format!("Failed to assign slot {}: {}", slot, e)))?;
+
+        Ok(RespValue::SimpleString("OK".to_string()))
+    }
+
+    /// Handle CLUSTER SETSLOT <slot> IMPORTING|MIGRATING <node-id> command.
+    ///
+    /// AiKv keeps no intermediate slot state: keys are moved with MIGRATE or
+    /// CLUSTER MIGRATE-SLOT and ownership only changes with SETSLOT NODE. The
+    /// peer is still checked, so a reshard naming an unknown node or a replica
+    /// fails on that first, and a valid request is then refused as unsupported
+    /// rather than acknowledged without effect.
+    pub fn cluster_setslot_migration(
+        &self,
+        slot: u16,
+        action: &str,
+        peer: NodeId,
+    ) -> Result<RespValue> {
+        if slot >= TOTAL_SLOTS {
+            return Err(AikvError::Invalid(format!("Invalid slot: {}", slot)));
+        }
+
+        let meta = self.meta_raft.get_cluster_meta();
+        if !meta.nodes.contains_key(&peer) {
+            return Err(AikvError::Invalid(format!(
+                "I don't know about node {}",
+                Self::format_node_id(peer)
+            )));
+        }
+        if Self::master_of(&meta, peer).is_some() {
+            return Err(AikvError::InvalidArgument(
+                "ERR Target node is not a master".to_string(),
+            ));
+        }
+
+        Err(AikvError::InvalidArgument(format!(
+            "ERR CLUSTER SETSLOT {} is not supported",
+            action
+        )))
+    }
+
+    /// Handle CLUSTER MIGRATE-SLOT <slot> <node-id> command (AiKv-specific).
+    ///
+    /// Moves a slot this node serves to the target node server-side, so
+    /// resharding tools don't have to move its keys with MIGRATE themselves.
+    ///
+    /// The slot's keys are sent to the target in batches as MIGRATE ... KEYS
+    /// would, each keeping its TTL and leaving this node once the target has
+    /// it. A node with a migration manager also moves the slot's AiDb group
+    /// data through `migration_manager.start_migration(slot, from, to)`. The
+    /// slot is then handed over as with SETSLOT NODE, which MetaRaft applies
+    /// on both ends, so MOVED points at the new owner. Until then, commands
+    /// on the slot's keys get -TRYAGAIN, so none is lost behind the batches.
+    pub async fn cluster_migrate_slot(&self, slot: u16, target: NodeId) -> Result<RespValue> {
+        if slot >= TOTAL_SLOTS {
+            return Err(AikvError::Invalid(format!("Invalid slot: {}", slot)));
+        }
+
+        let meta = self.meta_raft.get_cluster_meta();
+        if !meta.nodes.contains_key(&target) {
+            return Err(AikvError::Invalid(format!(
+                "I don't know about node {}",
+                Self::format_node_id(target)
+            )));
+        }
+        let from_group = meta.slots[slot as usize];
+        if !meta
+            .groups
+            .get(&from_group)
+            .is_some_and(|group| group.replicas.contains(&self.node_id))
+        {
+            return Err(AikvError::Invalid(format!(
+                "I'm not the owner of hash slot {}",
+                slot
+            )));
+        }
+        let storage = self.storage.as_ref().ok_or_else(|| {
+            AikvError::Invalid("Slot migration is not enabled on this node".to_string())
+        })?;
+
+        let to_group = self.ensure_group(target).await?;
+        if to_group == from_group {
+            return Ok(RespValue::SimpleString("OK".to_string()));
+        }
+
+        if let Some(manager) = &self.migration_manager {
+            manager
+                .start_migration(slot, from_group, to_group)
+                .await
+                .map_err(|e| {
+                    AikvError::Internal(format!("Failed to start migrating slot {}: {}", slot, e))
+                })?;
+            while manager
+                .get_migration_progress(slot)
+                .is_some_and(|progress| !progress.is_complete())
+            {
+                tokio::time::sleep(MIGRATION_POLL_INTERVAL).await;
+            }
+        }
+
+        // Keys in the slot are refused from here until it is handed over.
+        // Commands that checked their keys before hold the gate, so once it
+        // has been free no write can reach the slot behind the batches below.
+        let _migrating = self.migrating.start(slot).ok_or_else(|| {
+            AikvError::Invalid(format!("Slot {} is already being migrated", slot))
+        })?;
+        let gate = self.gate.clone();
+        tokio::task::spawn_blocking(move || drop(gate.exclusive()))
+            .await
+            .map_err(|e| AikvError::Internal(format!("Failed to wait for commands: {}", e)))?;
+
+        // A batch moving nothing holds only keys that expired meanwhile
+        let addr = &meta.nodes[&target].addr;
+        loop {
+            let keys = storage.get_keys_in_slot(0, slot, MIGRATION_BATCH_SIZE)?;
+            if keys.is_empty()
+                || migrate::migrate_keys(storage, 0, &keys, addr, MIGRATION_TIMEOUT).await? == 0
+            {
+                break;
+            }
+        }
+
+        self.cluster_setslot_node(slot, target).await
+    }
+
+    /// Find the group a node belongs to, creating one if it has none.
+    ///
+    /// Each master initially forms its own group, keyed by its node ID,
+    /// matching Redis where every master owns its slots directly.
+    async fn ensure_group(&self, node_id: NodeId) -> Result<GroupId> {
+        let meta = self.meta_raft.get_cluster_meta();
+        if let Some((gid, _)) = meta
+            .groups
+            .iter()
+            .find(|(_, g)| g.replicas.contains(&node_id))
+        {
+            return Ok(*gid);
+        }
+
+        self.meta_raft
+            .create_group(node_id, vec![node_id])
+            .await
+            .map_err(|e| AikvError::Internal(format!("Failed to create group for node: {}", e)))?;
+        Ok(node_id)
+    }
+
     /// Handle CLUSTER REPLICATE command.
     ///
     /// Sets this node as a replica of the specified master node.
@@ -525,8 +1078,8 @@ impl ClusterCommands {
             .map(|(gid, _)| *gid)
             .ok_or_else(|| {
                 AikvError::Internal(format!(
-                    "Master node {:040x} does not belong to any group",
-                    master_id
+                    "Master node {} does not belong to any group",
+                    Self::format_node_id(master_id)
                 ))
             })?;
 
@@ -555,30 +1108,123 @@ impl ClusterCommands {
         Ok(RespValue::SimpleString("OK".to_string()))
     }
 
+    /// Handle WAIT command.
+    ///
+    /// Captures the last log index of every Raft group this node leads when
+    /// WAIT is called, then polls the leader's replication metrics until
+    /// `numreplicas` replicas of each group have matched that index, or the
+    /// timeout fires (`None` waits forever). Returns how many replicas caught
+    /// up, taking the smallest count across groups.
+    ///
+    /// Only current group members whose matched log index reached the captured
+    /// one are counted, so a replica that fell behind (for example one still
+    /// installing a snapshot) never inflates the count.
+    pub async fn wait_replicas(
+        &self,
+        numreplicas: usize,
+        timeout: Option<Duration>,
+    ) -> Result<RespValue> {
+        let meta = self.meta_raft.get_cluster_meta();
+
+        // (replication metrics, index to reach, replicas to count) per led group
+        let mut targets = Vec::new();
+        for (group_id, group) in meta.groups.iter() {
+            let Some(raft) = self.multi_raft.get_raft_group(*group_id) else {
+                continue;
+            };
+            let metrics = raft.metrics();
+            let (is_leader, index) = {
+                let current = metrics.borrow();
+                (
+                    current.current_leader == Some(self.node_id),
+                    current.last_log_index.unwrap_or(0),
+                )
+            };
+            if !is_leader {
+                continue;
+            }
+
+            let replicas: Vec<NodeId> = group
+                .replicas
+                .iter()
+                .copied()
+                .filter(|id| *id != self.node_id)
+                .collect();
+            targets.push((metrics, index, replicas));
+        }
+
+        if targets.is_empty() {
+            return Ok(RespValue::Integer(0));
+        }
+
+        let deadline = timeout.map(|t| tokio::time::Instant::now() + t);
+        loop {
+            let acked = targets
+                .iter()
+                .map(|(metrics, index, replicas)| {
+                    let current = metrics.borrow();
+                    let Some(replication) = current.replication.as_ref() else {
+                        return 0;
+                    };
+                    replicas
+                        .iter()
+                        .filter(|id| {
+                            replication
+                                .get(id)
+                                .and_then(|matched| matched.as_ref())
+                                .is_some_and(|log_id| log_id.index >= *index)
+                        })
+                        .count()
+                })
+                .min()
+                .unwrap_or(0);
+
+            let timed_out = deadline.is_some_and(|d| tokio::time::Instant::now() >= d);
+            if acked >= numreplicas || timed_out {
+                return Ok(RespValue::Integer(acked as i64));
+            }
+
+            tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+        }
+    }
+
     /// Handle CLUSTER GETKEYSINSLOT command.
     ///
-    /// Maps to: `state_machine.scan_slot_keys_sync(group, slot)`
+    /// Maps to: `storage.get_keys_in_slot(0, slot, count)`
     ///
-    /// Note: This requires access to ShardedStateMachine which we'll need to add
-    pub fn cluster_getkeysinslot(&self, slot: u16, _count: usize) -> Result<RespValue> {
+    /// The storage keeps keys bucketed by slot, so this only visits the keys of
+    /// `slot`. Cluster mode only uses database 0.
+    pub fn cluster_getkeysinslot(&self, slot: u16, count: usize) -> Result<RespValue> {
         if slot >= TOTAL_SLOTS {
-            return Err(AikvError::Invalid(format!("Invalid slot: {}", slot)));
+            return Err(AikvError::InvalidArgument(
+                "ERR Invalid slot or number of keys".to_string(),
+            ));
         }
 
-        // TODO: Implement using ShardedStateMachine.scan_slot_keys_sync()
-        // For now, return empty array
-        Ok(RespValue::Array(Some(vec![])))
+        let keys = match &self.storage {
+            Some(storage) => storage.get_keys_in_slot(0, slot, count)?,
+            None => Vec::new(),
+        };
+        Ok(RespValue::Array(Some(
+            keys.into_iter()
+                .map(|key| RespValue::BulkString(Some(Bytes::from(key))))
+                .collect(),
+        )))
     }
 
     /// Handle CLUSTER COUNTKEYSINSLOT command.
+    ///
+    /// Maps to: `storage.count_keys_in_slot(0, slot)`
     pub fn cluster_countkeysinslot(&self, slot: u16) -> Result<RespValue> {
         if slot >= TOTAL_SLOTS {
-            return Err(AikvError::Invalid(format!("Invalid slot: {}", slot)));
+            return Err(AikvError::InvalidArgument("ERR Invalid slot".to_string()));
         }
 
-        // TODO: Implement using ShardedStateMachine
-        // For now, return 0
-        Ok(RespValue::Integer(0))
+        let count = match &self.storage {
+            Some(storage) => storage.count_keys_in_slot(0, slot)?,
+            None => 0,
+        };
+        Ok(RespValue::Integer(count as i64))
     }
 
     /// Generate a unique node ID.
@@ -597,6 +1243,21 @@ impl ClusterCommands {
         timestamp ^ random
     }
 
+    /// Resolve the node ID for a node listening on `addr`.
+    ///
+    /// This is the single source of node identities: the server uses it for its
+    /// own ID (registered in MetaRaft and reported by MYID and NODES) and CLUSTER
+    /// MEET uses it for peers met without an explicit ID, so both sides agree.
+    ///
+    /// A wildcard address (e.g. `0.0.0.0:6379`) is the same on every host, so
+    /// those nodes get a random ID and must be met with an explicit node ID.
+    pub fn resolve_node_id(addr: &str) -> NodeId {
+        match addr.parse::<std::net::SocketAddr>() {
+            Ok(socket_addr) if socket_addr.ip().is_unspecified() => Self::generate_node_id(),
+            _ => Self::generate_node_id_from_addr(addr),
+        }
+    }
+
     /// Generate a consistent node ID from a peer address.
     /// This ensures all nodes agree on each other's IDs in multi-master setup.
     pub fn generate_node_id_from_addr(addr: &str) -> NodeId {
@@ -634,13 +1295,14 @@ impl ClusterCommands {
                         "CLUSTER GETKEYSINSLOT".to_string(),
                     ));
                 }
-                let slot = String::from_utf8_lossy(&args[1])
-                    .parse::<u16>()
-                    .map_err(|_| AikvError::Invalid("Invalid slot".to_string()))?;
-                let count = String::from_utf8_lossy(&args[2])
-                    .parse::<usize>()
-                    .map_err(|_| AikvError::Invalid("Invalid count".to_string()))?;
-                self.cluster_getkeysinslot(slot, count)
+                let slot = Self::parse_integer(&args[1])?;
+                let count = Self::parse_integer(&args[2])?;
+                if !(0..TOTAL_SLOTS as i64).contains(&slot) || count < 0 {
+                    return Err(AikvError::InvalidArgument(
+                        "ERR Invalid slot or number of keys".to_string(),
+                    ));
+                }
+                self.cluster_getkeysinslot(slot as u16, count as usize)
             }
             "COUNTKEYSINSLOT" => {
                 if args.len() != 2 {
@@ -648,10 +1310,11 @@ impl ClusterCommands {
                         "CLUSTER COUNTKEYSINSLOT".to_string(),
                     ));
                 }
-                let slot = String::from_utf8_lossy(&args[1])
-                    .parse::<u16>()
-                    .map_err(|_| AikvError::Invalid("Invalid slot".to_string()))?;
-                self.cluster_countkeysinslot(slot)
+                let slot = Self::parse_integer(&args[1])?;
+                if !(0..TOTAL_SLOTS as i64).contains(&slot) {
+                    return Err(AikvError::InvalidArgument("ERR Invalid slot".to_string()));
+                }
+                self.cluster_countkeysinslot(slot as u16)
             }
             _ => Err(AikvError::InvalidCommand(format!(
                 "Unknown CLUSTER subcommand: {}",
@@ -660,6 +1323,13 @@ impl ClusterCommands {
         }
     }
 
+    /// Parse an integer argument, rejecting anything else the way Redis does
+    fn parse_integer(arg: &Bytes) -> Result<i64> {
+        String::from_utf8_lossy(arg).parse::<i64>().map_err(|_| {
+            AikvError::InvalidArgument("ERR value is not an integer or out of range".to_string())
+        })
+    }
+
     /// Handle READONLY command.
     ///
     /// Sets connection to read-only mode for replica reads.
@@ -690,6 +1360,72 @@ impl ClusterCommands {
     pub fn ask_error(slot: u16, addr: &str) -> AikvError {
         AikvError::Ask(slot, addr.to_string())
     }
+
+    /// Refuse a key whose slot is being moved out with CLUSTER MIGRATE-SLOT.
+    ///
+    /// Returns -TRYAGAIN, so the client retries once the slot has moved and
+    /// is redirected to its new owner.
+    pub fn check_key_migrating(&self, key: &[u8]) -> Result<()> {
+        let slot = Router::key_to_slot(key);
+        if self.migrating.contains(slot) {
+            return Err(AikvError::TryAgain(slot));
+        }
+        Ok(())
+    }
+
+    /// Hand SCRIPT LOAD or SCRIPT FLUSH on to every other known node.
+    ///
+    /// Each node is sent the command over its client address, the one given
+    /// to CLUSTER MEET, so EVALSHA finds the script on any node. All nodes are
+    /// tried even if one fails; the error names the nodes that did not take it.
+    pub async fn propagate_script(&self, args: &[Bytes]) -> Result<()> {
+        let mut command = vec![Bytes::from_static(b"SCRIPT")];
+        command.extend_from_slice(args);
+
+        let meta = self.meta_raft.get_cluster_meta();
+        let mut failed = Vec::new();
+        for (node_id, node_info) in &meta.nodes {
+            if *node_id == self.node_id {
+                continue;
+            }
+            if let Err(e) =
+                migrate::send_command(&command, &node_info.addr, SCRIPT_PROPAGATION_TIMEOUT).await
+            {
+                failed.push(format!("{} ({})", Self::format_node_id(*node_id), e));
+            }
+        }
+
+        if !failed.is_empty() {
+            return Err(AikvError::Internal(format!(
+                "Failed to send SCRIPT to {}",
+                failed.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check that the slot of a key (or shard channel) is served by this node.
+    ///
+    /// Returns a -MOVED redirection to the owning node otherwise. Slots that
+    /// are not assigned to any group are served locally.
+    pub fn check_key_slot(&self, key: &[u8]) -> Result<()> {
+        let slot = Router::key_to_slot(key);
+        let meta: ClusterMeta = self.meta_raft.get_cluster_meta();
+        let Some(group) = meta.groups.get(&meta.slots[slot as usize]) else {
+            return Ok(());
+        };
+        if group.replicas.contains(&self.node_id) {
+            return Ok(());
+        }
+
+        let owner = group
+            .leader
+            .or_else(|| group.replicas.iter().next().copied());
+        match owner.and_then(|id| meta.nodes.get(&id)) {
+            Some(node_info) => Err(Self::moved_error(slot, &node_info.addr)),
+            None => Ok(()),
+        }
+    }
 }
 
 /// Placeholder struct for when cluster feature is disabled
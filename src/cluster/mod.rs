@@ -38,6 +38,8 @@ mod node;
 
 // Export our implementations
 pub use commands::{ClusterCommands, FailoverMode, NodeInfo, RedirectType};
+#[cfg(feature = "cluster")]
+pub use commands::{ForgetBlacklist, MigratingSlot, MigratingSlots, NodeLiveness};
 pub use node::{ClusterConfig, ClusterNode, GroupId, NodeId};
 
 // Re-export AiDb v0.5.1 cluster types
@@ -0,0 +1,312 @@
+//! Argument specs for COMMAND DOCS
+//!
+//! Each entry of the command table describes its arguments with a syntax
+//! string in the notation of the Redis documentation, which is turned into
+//! the nested argument maps COMMAND DOCS replies with:
+//!
+//! - `key value` are plain arguments, typed by name (`key`, `count`, ...)
+//! - `NX` is a pure token; `EX seconds` is an argument introduced by a token
+//! - `[...]` marks an optional argument and a trailing `...` a repeated one
+//! - `NX|XX` is a choice between alternatives
+//! - `LIMIT offset count` and `[field value]` group several arguments
+//!
+//! A name can be given an explicit type with a suffix, as in `increment:double`.
+
+use crate::protocol::RespValue;
+
+/// Build the COMMAND DOCS argument maps for a syntax string
+pub fn arguments(syntax: &str) -> Vec<RespValue> {
+    split_top_level(syntax, ' ')
+        .into_iter()
+        .map(|item| parse_item(item).to_resp())
+        .collect()
+}
+
+/// A documented argument
+struct Argument {
+    name: String,
+    kind: &'static str,
+    token: Option<String>,
+    optional: bool,
+    multiple: bool,
+    arguments: Vec<Argument>,
+}
+
+impl Argument {
+    fn new(name: String, kind: &'static str) -> Self {
+        Self {
+            name,
+            kind,
+            token: None,
+            optional: false,
+            multiple: false,
+            arguments: Vec::new(),
+        }
+    }
+
+    fn to_resp(&self) -> RespValue {
+        let mut fields = vec![
+            (
+                RespValue::bulk_string("name"),
+                RespValue::bulk_string(self.name.clone()),
+            ),
+            (
+                RespValue::bulk_string("type"),
+                RespValue::bulk_string(self.kind),
+            ),
+        ];
+        if let Some(token) = &self.token {
+            fields.push((
+                RespValue::bulk_string("token"),
+                RespValue::bulk_string(token.clone()),
+            ));
+        }
+
+        let flags: Vec<RespValue> = [("optional", self.optional), ("multiple", self.multiple)]
+            .into_iter()
+            .filter(|(_, set)| *set)
+            .map(|(flag, _)| RespValue::simple_string(flag))
+            .collect();
+        if !flags.is_empty() {
+            fields.push((RespValue::bulk_string("flags"), RespValue::array(flags)));
+        }
+
+        if !self.arguments.is_empty() {
+            fields.push((
+                RespValue::bulk_string("arguments"),
+                RespValue::array(self.arguments.iter().map(Argument::to_resp).collect()),
+            ));
+        }
+        RespValue::map(fields)
+    }
+}
+
+/// Parse one item, which may be optional (`[...]`) and repeated (`...`)
+fn parse_item(item: &str) -> Argument {
+    let (item, multiple) = match item.strip_suffix("...") {
+        Some(item) => (item, true),
+        None => (item, false),
+    };
+    let (item, optional) = match item.strip_prefix('[').and_then(|i| i.strip_suffix(']')) {
+        Some(item) => (item, true),
+        None => (item, false),
+    };
+
+    let mut argument = parse_alternatives(item);
+    argument.optional |= optional;
+    argument.multiple |= multiple;
+    argument
+}
+
+/// Parse `a|b|c`, or a single sequence without alternatives
+fn parse_alternatives(text: &str) -> Argument {
+    let alternatives = split_top_level(text, '|');
+    if alternatives.len() == 1 {
+        return parse_sequence(text);
+    }
+
+    let arguments: Vec<Argument> = alternatives.into_iter().map(parse_sequence).collect();
+    let name = arguments
+        .iter()
+        .map(|a| a.name.as_str())
+        .collect::<Vec<_>>()
+        .join("-");
+    let mut argument = Argument::new(name, "oneof");
+    argument.arguments = arguments;
+    argument
+}
+
+/// Parse a space separated sequence, possibly introduced by a token
+fn parse_sequence(text: &str) -> Argument {
+    let items = split_top_level(text, ' ');
+    let (token, rest) = match items.split_first() {
+        Some((first, rest)) if is_token(first) => (Some(*first), rest),
+        _ => (None, &items[..]),
+    };
+
+    match (token, rest) {
+        (Some(token), []) => {
+            let mut argument = Argument::new(token.to_lowercase(), "pure-token");
+            argument.token = Some(token.to_string());
+            argument
+        }
+        (Some(token), [item]) => {
+            let mut argument = parse_item(item);
+            argument.token = Some(token.to_string());
+            argument
+        }
+        (None, [item]) if item.starts_with('[') || item.ends_with("...") => parse_item(item),
+        (None, [word]) => parse_word(word),
+        (token, items) => {
+            let arguments: Vec<Argument> = items.iter().map(|item| parse_item(item)).collect();
+            let name = match token {
+                Some(token) => token.to_lowercase(),
+                None => arguments
+                    .iter()
+                    .map(|a| a.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join("-"),
+            };
+            let mut argument = Argument::new(name, "block");
+            argument.token = token.map(str::to_string);
+            argument.arguments = arguments;
+            argument
+        }
+    }
+}
+
+/// Parse a plain argument name, typed by an explicit suffix or by its name
+fn parse_word(word: &str) -> Argument {
+    if let Some((name, kind)) = word.split_once(':') {
+        let kind = match kind {
+            "key" => "key",
+            "integer" => "integer",
+            "double" => "double",
+            "pattern" => "pattern",
+            "unix-time" => "unix-time",
+            _ => "string",
+        };
+        return Argument::new(name.to_string(), kind);
+    }
+
+    let kind = match word {
+        "key" | "newkey" | "source" | "destination" => "key",
+        "pattern" => "pattern",
+        "score" | "min" | "max" => "double",
+        "unix-time-seconds" | "unix-time-milliseconds" => "unix-time",
+        "count" | "index" | "index1" | "index2" | "start" | "stop" | "seconds" | "milliseconds"
+        | "db" | "destination-db" | "timeout" | "numkeys" | "numreplicas" | "offset"
+        | "increment" | "decrement" | "port" | "cursor" | "ttl" | "protover" | "frequency" => {
+            "integer"
+        }
+        _ => "string",
+    };
+    Argument::new(word.to_string(), kind)
+}
+
+/// Whether a word is a literal token such as `NX` or `WITHSCORES`
+fn is_token(word: &str) -> bool {
+    word.chars().any(|c| c.is_ascii_uppercase())
+        && word
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Split on `separator`, ignoring separators inside brackets
+fn split_top_level(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            c if c == separator && depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts.into_iter().filter(|part| !part.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(argument: &RespValue, name: &str) -> Option<RespValue> {
+        match argument {
+            RespValue::Map(pairs) => pairs
+                .iter()
+                .find(|(key, _)| *key == RespValue::bulk_string(name.to_string()))
+                .map(|(_, value)| value.clone()),
+            other => panic!("Expected a map, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_plain_and_optional_arguments() {
+        let args = arguments("key [count]");
+        assert_eq!(args.len(), 2);
+        assert_eq!(field(&args[0], "name"), Some(RespValue::bulk_string("key")));
+        assert_eq!(field(&args[0], "type"), Some(RespValue::bulk_string("key")));
+        assert_eq!(field(&args[0], "flags"), None);
+        assert_eq!(
+            field(&args[1], "type"),
+            Some(RespValue::bulk_string("integer"))
+        );
+        assert_eq!(
+            field(&args[1], "flags"),
+            Some(RespValue::array(vec![RespValue::simple_string("optional")]))
+        );
+
+        assert!(arguments("").is_empty());
+    }
+
+    #[test]
+    fn test_tokens_choices_and_blocks() {
+        let args = arguments("key [NX|XX] [EX seconds] [LIMIT offset count] [field value]...");
+        assert_eq!(args.len(), 5);
+
+        assert_eq!(
+            field(&args[1], "type"),
+            Some(RespValue::bulk_string("oneof"))
+        );
+        match field(&args[1], "arguments") {
+            Some(RespValue::Array(Some(choices))) => {
+                assert_eq!(choices.len(), 2);
+                assert_eq!(
+                    field(&choices[0], "type"),
+                    Some(RespValue::bulk_string("pure-token"))
+                );
+                assert_eq!(
+                    field(&choices[0], "token"),
+                    Some(RespValue::bulk_string("NX"))
+                );
+            }
+            other => panic!("Unexpected choices: {:?}", other),
+        }
+
+        assert_eq!(
+            field(&args[2], "name"),
+            Some(RespValue::bulk_string("seconds"))
+        );
+        assert_eq!(field(&args[2], "token"), Some(RespValue::bulk_string("EX")));
+
+        assert_eq!(
+            field(&args[3], "type"),
+            Some(RespValue::bulk_string("block"))
+        );
+        assert_eq!(
+            field(&args[3], "token"),
+            Some(RespValue::bulk_string("LIMIT"))
+        );
+
+        assert_eq!(
+            field(&args[4], "name"),
+            Some(RespValue::bulk_string("field-value"))
+        );
+        assert_eq!(
+            field(&args[4], "flags"),
+            Some(RespValue::array(vec![
+                RespValue::simple_string("optional"),
+                RespValue::simple_string("multiple"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_explicit_type() {
+        let args = arguments("key increment:double member");
+        assert_eq!(
+            field(&args[1], "name"),
+            Some(RespValue::bulk_string("increment"))
+        );
+        assert_eq!(
+            field(&args[1], "type"),
+            Some(RespValue::bulk_string("double"))
+        );
+    }
+}
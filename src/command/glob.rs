@@ -0,0 +1,122 @@
+//! Redis glob-style pattern matching
+//!
+//! Shared by KEYS, SCAN MATCH, HSCAN MATCH, PSUBSCRIBE and PUBSUB CHANNELS.
+//! Matching works on bytes like Redis' `stringmatchlen` and supports:
+//!
+//! - `*` matches any sequence of bytes, including none
+//! - `?` matches exactly one byte
+//! - `[abc]`, `[a-z]` and `[^abc]` match one byte in (or not in) a class
+//! - `\x` matches `x` literally, both inside and outside classes
+
+/// Check whether `string` matches the glob `pattern`
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => string.is_empty(),
+        Some((b'*', rest)) => {
+            // Consecutive stars match the same as a single one
+            let rest = match rest.iter().position(|&c| c != b'*') {
+                Some(start) => &rest[start..],
+                None => return true,
+            };
+            (0..=string.len()).any(|i| glob_match(rest, &string[i..]))
+        }
+        Some((b'?', rest)) => !string.is_empty() && glob_match(rest, &string[1..]),
+        Some((b'[', rest)) => match string.split_first() {
+            Some((&c, tail)) => {
+                let (matched, rest) = match_class(rest, c);
+                matched && glob_match(rest, tail)
+            }
+            None => false,
+        },
+        // A trailing backslash has nothing to escape and matches itself
+        Some((b'\\', [escaped, rest @ ..])) => {
+            string.first() == Some(escaped) && glob_match(rest, &string[1..])
+        }
+        Some((c, rest)) => string.first() == Some(c) && glob_match(rest, &string[1..]),
+    }
+}
+
+/// Match `c` against the class following a `[`, returning whether it matched
+/// and the rest of the pattern after the closing `]`
+///
+/// An unterminated class runs to the end of the pattern, as in Redis.
+fn match_class(class: &[u8], c: u8) -> (bool, &[u8]) {
+    let (negate, mut class) = match class.split_first() {
+        Some((b'^', rest)) => (true, rest),
+        _ => (false, class),
+    };
+
+    let mut matched = false;
+    loop {
+        match class {
+            [] => break,
+            [b']', rest @ ..] => {
+                class = rest;
+                break;
+            }
+            [b'\\', escaped, rest @ ..] => {
+                matched |= *escaped == c;
+                class = rest;
+            }
+            [start, b'-', end, rest @ ..] if *end != b']' => {
+                // Reversed ranges such as [z-a] are accepted
+                let (low, high) = if start <= end {
+                    (*start, *end)
+                } else {
+                    (*end, *start)
+                };
+                matched |= (low..=high).contains(&c);
+                class = rest;
+            }
+            [other, rest @ ..] => {
+                matched |= *other == c;
+                class = rest;
+            }
+        }
+    }
+
+    (matched != negate, class)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, string: &str) -> bool {
+        glob_match(pattern.as_bytes(), string.as_bytes())
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert!(matches("*", ""));
+        assert!(matches("user:*", "user:1000"));
+        assert!(matches("key:1*", "key:10"));
+        assert!(!matches("key:1*", "key:2"));
+        assert!(matches("h?llo", "hello"));
+        assert!(!matches("h?llo", "hllo"));
+        assert!(matches("a**b", "axxb"));
+    }
+
+    #[test]
+    fn test_character_classes() {
+        assert!(matches("h[ae]llo", "hello"));
+        assert!(matches("h[ae]llo", "hallo"));
+        assert!(!matches("h[ae]llo", "hillo"));
+        assert!(matches("h[^e]llo", "hallo"));
+        assert!(!matches("h[^e]llo", "hello"));
+        assert!(matches("h[a-b]llo", "hbllo"));
+        assert!(!matches("h[a-b]llo", "hcllo"));
+        assert!(matches("h[b-a]llo", "hallo"));
+        assert!(matches("[\\]]", "]"));
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert!(matches("a\\*b", "a*b"));
+        assert!(!matches("a\\*b", "axb"));
+        assert!(matches("\\?", "?"));
+        assert!(!matches("\\?", "x"));
+        assert!(matches("\\[x]", "[x]"));
+        assert!(matches("a\\", "a\\"));
+    }
+}
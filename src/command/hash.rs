@@ -1,3 +1,4 @@
+use crate::command::glob::glob_match;
 use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
 use crate::storage::{StorageEngine, StoredValue};
@@ -17,33 +18,11 @@ impl HashCommands {
     }
 
     /// HSET key field value [field value ...]
-    /// Sets field in the hash stored at key to value
+    /// Sets field in the hash stored at key to value, returning the number of
+    /// fields that were added (fields that were only updated are not counted)
     pub fn hset(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
-        if args.len() < 3 || args.len() % 2 == 0 {
-            return Err(AikvError::WrongArgCount("HSET".to_string()));
-        }
-
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        // Migrated: Logic moved from storage layer to command layer
-        let mut hash = if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            stored.as_hash()?.clone()
-        } else {
-            HashMap::new()
-        };
-
-        let mut count = 0;
-        for i in (1..args.len()).step_by(2) {
-            let field = String::from_utf8_lossy(&args[i]).to_string();
-            let value = args[i + 1].clone();
-            if hash.insert(field, value).is_none() {
-                count += 1;
-            }
-        }
-
-        self.storage
-            .set_value(db_index, key, StoredValue::new_hash(hash))?;
-        Ok(RespValue::Integer(count as i64))
+        let added = self.set_fields("HSET", args, db_index)?;
+        Ok(RespValue::Integer(added as i64))
     }
 
     /// HSETNX key field value
@@ -332,9 +311,9 @@ impl HashCommands {
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
         let field = String::from_utf8_lossy(&args[1]).to_string();
-        let increment = String::from_utf8_lossy(&args[2])
-            .parse::<f64>()
-            .map_err(|_| AikvError::InvalidArgument("invalid increment".to_string()))?;
+        let increment = parse_float(&args[2]).ok_or_else(|| {
+            AikvError::InvalidArgument("ERR value is not a valid float".to_string())
+        })?;
 
         // Migrated: Logic moved from storage layer to command layer
         let mut hash = if let Some(stored) = self.storage.get_value(db_index, &key)? {
@@ -343,15 +322,22 @@ impl HashCommands {
             HashMap::new()
         };
 
-        let current_value = if let Some(val_bytes) = hash.get(&field) {
-            String::from_utf8_lossy(val_bytes)
-                .parse::<f64>()
-                .map_err(|_| AikvError::InvalidArgument("hash value is not a float".to_string()))?
-        } else {
-            0.0
+        // A missing field counts as 0
+        let current_value = match hash.get(&field) {
+            Some(val_bytes) => parse_float(val_bytes).ok_or_else(|| {
+                AikvError::InvalidArgument("ERR hash value is not a float".to_string())
+            })?,
+            None => 0.0,
         };
 
         let new_value = current_value + increment;
+        if !new_value.is_finite() {
+            return Err(AikvError::InvalidArgument(
+                "ERR increment would produce NaN or Infinity".to_string(),
+            ));
+        }
+
+        // The shortest form that reads back the same: 10.5 + 0.5 is "11", not "11.0"
         hash.insert(field, Bytes::from(new_value.to_string()));
 
         self.storage
@@ -363,31 +349,40 @@ impl HashCommands {
     /// Sets multiple field-value pairs in the hash stored at key
     /// This command is deprecated in favor of HSET, but still supported for compatibility
     pub fn hmset(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        self.set_fields("HMSET", args, db_index)?;
+
+        // HMSET returns OK, unlike HSET which returns the number of new fields
+        Ok(RespValue::ok())
+    }
+
+    /// Set the field-value pairs of HSET/HMSET, returning how many fields are new
+    fn set_fields(&self, command: &str, args: &[Bytes], db_index: usize) -> Result<usize> {
         if args.len() < 3 || args.len() % 2 == 0 {
-            return Err(AikvError::WrongArgCount("HMSET".to_string()));
+            return Err(AikvError::WrongArgCount(command.to_string()));
         }
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
 
-        // Get existing hash or create new one
+        // Migrated: Logic moved from storage layer to command layer
         let mut hash = if let Some(stored) = self.storage.get_value(db_index, &key)? {
             stored.as_hash()?.clone()
         } else {
             HashMap::new()
         };
 
-        // Set all field-value pairs
+        // A field repeated within one command is only new the first time
+        let mut added = 0;
         for i in (1..args.len()).step_by(2) {
             let field = String::from_utf8_lossy(&args[i]).to_string();
             let value = args[i + 1].clone();
-            hash.insert(field, value);
+            if hash.insert(field, value).is_none() {
+                added += 1;
+            }
         }
 
         self.storage
             .set_value(db_index, key, StoredValue::new_hash(hash))?;
-
-        // HMSET returns OK, unlike HSET which returns the number of new fields
-        Ok(RespValue::ok())
+        Ok(added)
     }
 
     /// HSCAN key cursor [MATCH pattern] [COUNT count]
@@ -460,7 +455,7 @@ impl HashCommands {
         } else {
             fields
                 .into_iter()
-                .filter(|(field, _)| Self::match_pattern(field, &pattern))
+                .filter(|(field, _)| glob_match(pattern.as_bytes(), field.as_bytes()))
                 .collect()
         };
 
@@ -485,46 +480,12 @@ impl HashCommands {
             RespValue::array(result_items),
         ]))
     }
+}
 
-    /// Simple pattern matching helper (supports * and ? wildcards)
-    fn match_pattern(key: &str, pattern: &str) -> bool {
-        if pattern == "*" {
-            return true;
-        }
-
-        let pattern_chars: Vec<char> = pattern.chars().collect();
-        let key_chars: Vec<char> = key.chars().collect();
-
-        Self::match_pattern_recursive(&key_chars, 0, &pattern_chars, 0)
-    }
-
-    fn match_pattern_recursive(key: &[char], ki: usize, pattern: &[char], pi: usize) -> bool {
-        if pi == pattern.len() {
-            return ki == key.len();
-        }
-
-        if pattern[pi] == '*' {
-            // Try matching zero or more characters
-            for i in ki..=key.len() {
-                if Self::match_pattern_recursive(key, i, pattern, pi + 1) {
-                    return true;
-                }
-            }
-            false
-        } else if pattern[pi] == '?' {
-            // Match exactly one character
-            if ki < key.len() {
-                Self::match_pattern_recursive(key, ki + 1, pattern, pi + 1)
-            } else {
-                false
-            }
-        } else {
-            // Exact character match
-            if ki < key.len() && key[ki] == pattern[pi] {
-                Self::match_pattern_recursive(key, ki + 1, pattern, pi + 1)
-            } else {
-                false
-            }
-        }
-    }
+/// Parse a float like Redis: `inf`, `-inf` and exponents are accepted, NaN is not
+fn parse_float(arg: &[u8]) -> Option<f64> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|value| !value.is_nan())
 }
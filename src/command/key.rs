@@ -1,26 +1,38 @@
+use crate::command::glob::glob_match;
+use crate::command::random::CommandRng;
 use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
+use crate::storage::encoding::DEFAULT_MEMORY_SAMPLES;
 use crate::storage::{SerializableStoredValue, StorageEngine, StoredValue};
 use bytes::Bytes;
+use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Default number of databases (matching Redis default)
 const DEFAULT_DB_COUNT: usize = 16;
 
+/// Smallest bucket table SCAN iterates over (matching the Redis dict minimum)
+const SCAN_MIN_BUCKETS: usize = 4;
+
+/// SCAN cursors carry the database's flush generation from this bit up, so a
+/// cursor issued before a FLUSHDB is recognised and ends the iteration
+const SCAN_GENERATION_SHIFT: u32 = 32;
+
 /// Key command handler
 pub struct KeyCommands {
     storage: StorageEngine,
+    rng: CommandRng,
 }
 
 impl KeyCommands {
-    pub fn new(storage: StorageEngine) -> Self {
+    pub fn new(storage: StorageEngine, rng: CommandRng) -> Self {
         Self {
             storage,
+            rng,
         }
     }
 
-    /// KEYS pattern - Find all keys matching pattern
-    /// Note: Simplified implementation, supports only * wildcard
+    /// KEYS pattern - Find all keys matching a glob-style pattern
     pub fn keys(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
         if args.len() != 1 {
             return Err(AikvError::WrongArgCount("KEYS".to_string()));
@@ -29,13 +41,12 @@ impl KeyCommands {
         let pattern = String::from_utf8_lossy(&args[0]).to_string();
         let all_keys = self.storage.get_all_keys_in_db(current_db)?;
 
-        // Simple pattern matching: * matches everything, otherwise exact match
         let matched_keys: Vec<RespValue> = if pattern == "*" {
             all_keys.into_iter().map(RespValue::bulk_string).collect()
         } else {
             all_keys
                 .into_iter()
-                .filter(|k| self.match_pattern(k, &pattern))
+                .filter(|k| glob_match(pattern.as_bytes(), k.as_bytes()))
                 .map(RespValue::bulk_string)
                 .collect()
         };
@@ -43,50 +54,6 @@ impl KeyCommands {
         Ok(RespValue::array(matched_keys))
     }
 
-    /// Simple pattern matching helper (supports * and ? wildcards)
-    fn match_pattern(&self, key: &str, pattern: &str) -> bool {
-        // Simple implementation: exact match or * wildcard
-        if pattern == "*" {
-            return true;
-        }
-
-        // Support basic wildcards
-        let pattern_chars: Vec<char> = pattern.chars().collect();
-        let key_chars: Vec<char> = key.chars().collect();
-
-        Self::match_pattern_recursive(&key_chars, 0, &pattern_chars, 0)
-    }
-
-    fn match_pattern_recursive(key: &[char], ki: usize, pattern: &[char], pi: usize) -> bool {
-        if pi == pattern.len() {
-            return ki == key.len();
-        }
-
-        if pattern[pi] == '*' {
-            // Try matching zero or more characters
-            for i in ki..=key.len() {
-                if Self::match_pattern_recursive(key, i, pattern, pi + 1) {
-                    return true;
-                }
-            }
-            false
-        } else if pattern[pi] == '?' {
-            // Match exactly one character
-            if ki < key.len() {
-                Self::match_pattern_recursive(key, ki + 1, pattern, pi + 1)
-            } else {
-                false
-            }
-        } else {
-            // Exact character match
-            if ki < key.len() && key[ki] == pattern[pi] {
-                Self::match_pattern_recursive(key, ki + 1, pattern, pi + 1)
-            } else {
-                false
-            }
-        }
-    }
-
     /// SCAN cursor \[MATCH pattern\] \[COUNT count\]
     /// Iterate keys using cursor-based iteration
     pub fn scan(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
@@ -138,31 +105,55 @@ impl KeyCommands {
             i += 1;
         }
 
-        // Get all keys and filter by pattern
+        // A cursor from before the database was flushed has nothing left to visit
+        let generation =
+            self.storage.generation_of_db(current_db)? & (u64::MAX >> SCAN_GENERATION_SHIFT);
+        let cursor = cursor as u64;
+        if cursor != 0 && cursor >> SCAN_GENERATION_SHIFT != generation {
+            return Ok(RespValue::array(vec![
+                RespValue::bulk_string("0"),
+                RespValue::array(Vec::new()),
+            ]));
+        }
+
+        // Group the keys into hash buckets, as a Redis dict would hold them
         let all_keys = self.storage.get_all_keys_in_db(current_db)?;
-        let matched_keys: Vec<String> = if pattern == "*" {
-            all_keys
+        let mask = (all_keys.len().max(SCAN_MIN_BUCKETS).next_power_of_two() - 1) as u64;
+        let mut buckets: HashMap<u64, Vec<String>> = HashMap::new();
+        for key in all_keys {
+            buckets
+                .entry(scan_hash(key.as_bytes()) & mask)
+                .or_default()
+                .push(key);
+        }
+
+        // COUNT is the number of buckets to visit, not a cap on the keys
+        // returned: a dense bucket is always returned whole, so one call can
+        // return more than COUNT keys (or none when MATCH filters them all).
+        let mut cursor = cursor & ((1 << SCAN_GENERATION_SHIFT) - 1);
+        let mut keys_to_return = Vec::new();
+        for _ in 0..count {
+            if let Some(bucket) = buckets.get_mut(&(cursor & mask)) {
+                bucket.sort();
+                keys_to_return.extend(
+                    bucket
+                        .iter()
+                        .filter(|k| glob_match(pattern.as_bytes(), k.as_bytes()))
+                        .map(|k| RespValue::bulk_string(k.clone())),
+                );
+            }
+
+            cursor = scan_next_cursor(cursor, mask);
+            if cursor == 0 {
+                break;
+            }
+        }
+        let next_cursor = if cursor == 0 {
+            0
         } else {
-            all_keys
-                .into_iter()
-                .filter(|k| self.match_pattern(k, &pattern))
-                .collect()
+            cursor | generation << SCAN_GENERATION_SHIFT
         };
 
-        // Calculate the range to return
-        let total_keys = matched_keys.len();
-        let start = cursor;
-        let end = std::cmp::min(start + count, total_keys);
-
-        // Determine next cursor (0 means iteration complete)
-        let next_cursor = if end >= total_keys { 0 } else { end };
-
-        // Collect keys for this iteration
-        let keys_to_return: Vec<RespValue> = matched_keys[start..end]
-            .iter()
-            .map(|k| RespValue::bulk_string(k.clone()))
-            .collect();
-
         // Return [cursor, [keys]]
         Ok(RespValue::array(vec![
             RespValue::bulk_string(next_cursor.to_string()),
@@ -172,13 +163,19 @@ impl KeyCommands {
 
     /// RANDOMKEY - Return a random key
     pub fn randomkey(&self, _args: &[Bytes], current_db: usize) -> Result<RespValue> {
-        match self.storage.random_key_in_db(current_db)? {
-            Some(key) => Ok(RespValue::bulk_string(key)),
-            None => Ok(RespValue::null_bulk_string()),
+        let mut keys = self.storage.get_all_keys_in_db(current_db)?;
+        if keys.is_empty() {
+            return Ok(RespValue::null_bulk_string());
         }
+
+        keys.sort();
+        let key = keys.swap_remove(self.rng.index(keys.len()));
+        Ok(RespValue::bulk_string(key))
     }
 
     /// RENAME key newkey - Rename a key
+    ///
+    /// The value keeps its TTL and replaces whatever newkey held, TTL included.
     pub fn rename(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
         if args.len() != 2 {
             return Err(AikvError::WrongArgCount("RENAME".to_string()));
@@ -188,7 +185,7 @@ impl KeyCommands {
         let new_key = String::from_utf8_lossy(&args[1]).to_string();
 
         if !self.storage.exists_in_db(current_db, &old_key)? {
-            return Err(AikvError::KeyNotFound);
+            return Err(AikvError::InvalidArgument("ERR no such key".to_string()));
         }
 
         self.storage.rename_in_db(current_db, &old_key, &new_key)?;
@@ -205,7 +202,7 @@ impl KeyCommands {
         let new_key = String::from_utf8_lossy(&args[1]).to_string();
 
         if !self.storage.exists_in_db(current_db, &old_key)? {
-            return Err(AikvError::KeyNotFound);
+            return Err(AikvError::InvalidArgument("ERR no such key".to_string()));
         }
 
         let renamed = self
@@ -222,13 +219,140 @@ impl KeyCommands {
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
 
-        if !self.storage.exists_in_db(current_db, &key)? {
-            return Ok(RespValue::simple_string("none"));
+        // Storage reads apply lazy expiry, so an expired key reports "none"
+        let type_name = match self.storage.get_value(current_db, &key) {
+            Ok(Some(stored)) => stored.get_type_name().to_string(),
+            Ok(None) => "none".to_string(),
+            // AiDb keeps strings as raw bytes outside the serialized value format
+            Err(_) if self.storage.get_from_db(current_db, &key)?.is_some() => "string".to_string(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(RespValue::simple_string(type_name))
+    }
+
+    /// OBJECT subcommand \[arguments \[arguments ...\]\]
+    /// Inspect the internals of the value stored at key
+    pub fn object(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.is_empty() {
+            return Err(AikvError::WrongArgCount("OBJECT".to_string()));
+        }
+
+        let subcommand = String::from_utf8_lossy(&args[0]).to_uppercase();
+        if subcommand == "HELP" {
+            let help = [
+                "OBJECT <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                "ENCODING <key>",
+                "    Return the kind of internal representation used in order to store the value",
+                "    associated with a <key>.",
+                "FREQ <key>",
+                "    Return the access frequency index of the <key>.",
+                "IDLETIME <key>",
+                "    Return the idle time of the <key>, that is the approximated number of",
+                "    seconds elapsed since the last access to the key.",
+                "REFCOUNT <key>",
+                "    Return the number of references of the value associated with the specified",
+                "    <key>.",
+                "HELP",
+                "    Print this help.",
+            ];
+            return Ok(RespValue::array(
+                help.iter()
+                    .map(|line| RespValue::simple_string(*line))
+                    .collect(),
+            ));
         }
 
-        // For now, we only support string type
-        // In future, we'll need to track the actual type
-        Ok(RespValue::simple_string("string"))
+        if args.len() != 2 {
+            return Err(AikvError::WrongArgCount(format!("OBJECT|{}", subcommand)));
+        }
+
+        let key = String::from_utf8_lossy(&args[1]).to_string();
+        let stored = match self.storage.get_value(current_db, &key)? {
+            Some(stored) => stored,
+            None => return Ok(RespValue::null_bulk_string()),
+        };
+
+        match subcommand.as_str() {
+            "ENCODING" => {
+                let thresholds = self.storage.encoding_thresholds()?;
+                Ok(RespValue::bulk_string(
+                    stored.encoding(&thresholds).as_str(),
+                ))
+            }
+            // Values are never shared and access times are not tracked
+            "REFCOUNT" => Ok(RespValue::integer(1)),
+            "IDLETIME" => Ok(RespValue::integer(0)),
+            "FREQ" => Err(AikvError::InvalidArgument(
+                "ERR An LFU maxmemory policy is not selected, access frequency not tracked"
+                    .to_string(),
+            )),
+            _ => Err(AikvError::InvalidArgument(format!(
+                "ERR unknown subcommand '{}'. Try OBJECT HELP.",
+                subcommand
+            ))),
+        }
+    }
+
+    /// MEMORY subcommand \[arguments \[arguments ...\]\]
+    /// Report memory usage estimates
+    pub fn memory(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.is_empty() {
+            return Err(AikvError::WrongArgCount("MEMORY".to_string()));
+        }
+
+        let subcommand = String::from_utf8_lossy(&args[0]).to_uppercase();
+        match subcommand.as_str() {
+            "USAGE" => self.memory_usage(&args[1..], current_db),
+            "HELP" => {
+                let help = [
+                    "MEMORY <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                    "USAGE <key> [SAMPLES <count>]",
+                    "    Return memory in bytes used by <key> and its value. Nested values are",
+                    "    sampled up to <count> times (default: 5, 0 means sample all).",
+                    "HELP",
+                    "    Print this help.",
+                ];
+                Ok(RespValue::array(
+                    help.iter()
+                        .map(|line| RespValue::simple_string(*line))
+                        .collect(),
+                ))
+            }
+            _ => Err(AikvError::InvalidArgument(format!(
+                "ERR unknown subcommand '{}'. Try MEMORY HELP.",
+                subcommand
+            ))),
+        }
+    }
+
+    /// MEMORY USAGE key \[SAMPLES count\]
+    fn memory_usage(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        let mut samples = DEFAULT_MEMORY_SAMPLES;
+        match args.len() {
+            1 => {}
+            3 if String::from_utf8_lossy(&args[1]).eq_ignore_ascii_case("SAMPLES") => {
+                samples = String::from_utf8_lossy(&args[2])
+                    .parse::<usize>()
+                    .map_err(|_| {
+                        AikvError::InvalidArgument(
+                            "ERR value is out of range, must be positive".to_string(),
+                        )
+                    })?;
+            }
+            0 => return Err(AikvError::WrongArgCount("MEMORY|USAGE".to_string())),
+            _ => return Err(AikvError::InvalidArgument("ERR syntax error".to_string())),
+        }
+
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+        let stored = match self.storage.get_value(current_db, &key)? {
+            Some(stored) => stored,
+            None => return Ok(RespValue::null_bulk_string()),
+        };
+
+        let thresholds = self.storage.encoding_thresholds()?;
+        let usage = stored.memory_usage(&key, &thresholds, samples);
+        Ok(RespValue::integer(usage as i64))
     }
 
     /// COPY source destination \[DB destination-db\] \[REPLACE\]
@@ -287,9 +411,9 @@ impl KeyCommands {
             .map_err(|_| AikvError::InvalidArgument("ERR value is not an integer".to_string()))?;
 
         if seconds <= 0 {
-            // Delete the key immediately if seconds <= 0
-            let deleted = self.storage.delete_from_db(current_db, &key)?;
-            return Ok(RespValue::integer(if deleted { 1 } else { 0 }));
+            // A TTL that isn't positive expires the key immediately
+            let expired = self.storage.set_expire_at_in_db(current_db, &key, 0)?;
+            return Ok(RespValue::integer(if expired { 1 } else { 0 }));
         }
 
         let expire_ms = (seconds as u64) * 1000;
@@ -309,12 +433,8 @@ impl KeyCommands {
             .parse::<i64>()
             .map_err(|_| AikvError::InvalidArgument("ERR value is not an integer".to_string()))?;
 
-        if timestamp <= 0 {
-            let deleted = self.storage.delete_from_db(current_db, &key)?;
-            return Ok(RespValue::integer(if deleted { 1 } else { 0 }));
-        }
-
-        let timestamp_ms = (timestamp as u64) * 1000;
+        // A timestamp in the past expires the key immediately
+        let timestamp_ms = (timestamp.max(0) as u64).saturating_mul(1000);
         let set = self
             .storage
             .set_expire_at_in_db(current_db, &key, timestamp_ms)?;
@@ -334,8 +454,8 @@ impl KeyCommands {
             .map_err(|_| AikvError::InvalidArgument("ERR value is not an integer".to_string()))?;
 
         if milliseconds <= 0 {
-            let deleted = self.storage.delete_from_db(current_db, &key)?;
-            return Ok(RespValue::integer(if deleted { 1 } else { 0 }));
+            let expired = self.storage.set_expire_at_in_db(current_db, &key, 0)?;
+            return Ok(RespValue::integer(if expired { 1 } else { 0 }));
         }
 
         let set = self
@@ -356,14 +476,9 @@ impl KeyCommands {
             .parse::<i64>()
             .map_err(|_| AikvError::InvalidArgument("ERR value is not an integer".to_string()))?;
 
-        if timestamp_ms <= 0 {
-            let deleted = self.storage.delete_from_db(current_db, &key)?;
-            return Ok(RespValue::integer(if deleted { 1 } else { 0 }));
-        }
-
         let set = self
             .storage
-            .set_expire_at_in_db(current_db, &key, timestamp_ms as u64)?;
+            .set_expire_at_in_db(current_db, &key, timestamp_ms.max(0) as u64)?;
         Ok(RespValue::integer(if set { 1 } else { 0 }))
     }
 
@@ -455,29 +570,32 @@ impl KeyCommands {
 
         // Get the value
         match self.storage.get_value(current_db, &key)? {
-            Some(stored_value) => {
-                // Serialize the value
-                let serializable = stored_value.to_serializable();
-                let serialized = bincode::serialize(&serializable)
-                    .map_err(|e| AikvError::Storage(format!("Failed to serialize value: {}", e)))?;
-
-                // Build the dump format:
-                // - serialized value
-                // - 2 bytes RDB version (0x0009 = 9)
-                // - 8 bytes checksum (simplified additive checksum)
-                let mut dump_data = serialized;
-                dump_data.extend_from_slice(&[0x00, 0x09]); // RDB version 9
-
-                // Calculate a simple 64-bit additive checksum for data integrity
-                let checksum = Self::calculate_checksum(&dump_data);
-                dump_data.extend_from_slice(&checksum.to_le_bytes());
-
-                Ok(RespValue::bulk_string(Bytes::from(dump_data)))
-            }
+            Some(stored_value) => Ok(RespValue::bulk_string(Self::dump_payload(&stored_value)?)),
             None => Ok(RespValue::null_bulk_string()),
         }
     }
 
+    /// Serialize a value in the format DUMP replies with and RESTORE reads
+    pub fn dump_payload(stored_value: &StoredValue) -> Result<Bytes> {
+        // Serialize the value
+        let serializable = stored_value.to_serializable();
+        let serialized = bincode::serialize(&serializable)
+            .map_err(|e| AikvError::Storage(format!("Failed to serialize value: {}", e)))?;
+
+        // Build the dump format:
+        // - serialized value
+        // - 2 bytes RDB version (0x0009 = 9)
+        // - 8 bytes checksum (simplified additive checksum)
+        let mut dump_data = serialized;
+        dump_data.extend_from_slice(&[0x00, 0x09]); // RDB version 9
+
+        // Calculate a simple 64-bit additive checksum for data integrity
+        let checksum = Self::calculate_checksum(&dump_data);
+        dump_data.extend_from_slice(&checksum.to_le_bytes());
+
+        Ok(Bytes::from(dump_data))
+    }
+
     /// Calculate a simple 64-bit additive checksum for the data
     fn calculate_checksum(data: &[u8]) -> u64 {
         let mut checksum: u64 = 0;
@@ -532,6 +650,11 @@ impl KeyCommands {
         let ttl = ttl_str
             .parse::<i64>()
             .map_err(|_| AikvError::InvalidArgument("ERR invalid TTL value".to_string()))?;
+        if ttl < 0 {
+            return Err(AikvError::InvalidArgument(
+                "ERR invalid TTL value, must be >= 0".to_string(),
+            ));
+        }
 
         let serialized_value = &args[2];
 
@@ -601,32 +724,27 @@ impl KeyCommands {
 
         let mut stored_value = StoredValue::from_serializable(serializable);
 
-        // Set expiration if TTL is provided
-        if ttl > 0 {
-            let expires_at = if absttl {
-                // TTL is an absolute timestamp
-                ttl as u64
-            } else {
-                // TTL is relative (milliseconds from now)
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis() as u64;
-                now + (ttl as u64)
-            };
-            stored_value.set_expiration(Some(expires_at));
-        } else if ttl == 0 {
-            // No expiration
-            stored_value.set_expiration(None);
-        } else {
-            // Negative TTL is an error
-            return Err(AikvError::InvalidArgument(
-                "ERR invalid TTL value, must be >= 0".to_string(),
-            ));
+        // A TTL of 0 means no expiry; with ABSTTL it is a Unix time in milliseconds
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let expires_at = match ttl {
+            0 => None,
+            ttl if absttl => Some(ttl as u64),
+            ttl => Some(now + ttl as u64),
+        };
+
+        if expires_at.is_some_and(|expires_at| expires_at <= now) {
+            // Already expired: the value is dropped, along with the key it replaces
+            if replace {
+                self.storage.delete_from_db(current_db, &key)?;
+            }
+            return Ok(RespValue::ok());
         }
+        stored_value.set_expiration(expires_at);
 
-        // Store the value
-        self.storage.set_value(current_db, key, stored_value)?;
+        self.storage.replace_value(current_db, key, stored_value)?;
 
         Ok(RespValue::ok())
     }
@@ -748,14 +866,14 @@ impl KeyCommands {
 
                 // Copy to destination
                 self.storage
-                    .set_value(dest_db, key.clone(), stored_value.clone())?;
+                    .replace_value(dest_db, key.clone(), stored_value.clone())?;
 
                 // Delete from source if not COPY mode
                 if !copy {
                     if let Err(e) = self.storage.delete_from_db(current_db, key) {
                         // Rollback: restore destination to previous state
                         if let Some(old_val) = dest_old_value {
-                            let _ = self.storage.set_value(dest_db, key.clone(), old_val);
+                            let _ = self.storage.replace_value(dest_db, key.clone(), old_val);
                         } else if !dest_had_value {
                             let _ = self.storage.delete_from_db(dest_db, key);
                         }
@@ -774,3 +892,20 @@ impl KeyCommands {
         }
     }
 }
+
+/// Stable FNV-1a hash used to place keys into SCAN buckets
+fn scan_hash(key: &[u8]) -> u64 {
+    key.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Advance a SCAN cursor to the next bucket.
+///
+/// Like Redis, the cursor is incremented in reverse bit order, so an iteration
+/// never misses a bucket even if the bucket table grows or shrinks between
+/// calls (it may then return some keys twice). Returns 0 once it is complete.
+fn scan_next_cursor(cursor: u64, mask: u64) -> u64 {
+    let cursor = (cursor | !mask).reverse_bits();
+    cursor.wrapping_add(1).reverse_bits()
+}
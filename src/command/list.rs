@@ -1,8 +1,13 @@
+use crate::command::numkeys;
 use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
 use crate::storage::{StorageEngine, StoredValue};
 use bytes::Bytes;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::oneshot;
 
 /// List command handler
 pub struct ListCommands {
@@ -92,44 +97,50 @@ impl ListCommands {
         }
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
+        // An explicit count (Redis 6.2+) switches the reply to an array
         let count = if args.len() > 1 {
-            String::from_utf8_lossy(&args[1])
-                .parse::<usize>()
-                .map_err(|_| AikvError::InvalidArgument("invalid count".to_string()))?
+            Some(Self::parse_pop_count(&args[1])?)
         } else {
-            1
+            None
         };
 
         // Migrated: Logic moved from storage layer to command layer
-        let mut values = Vec::new();
+        let stored = match self.storage.get_value(db_index, &key)? {
+            Some(stored) => stored,
+            None => {
+                return Ok(match count {
+                    Some(_) => RespValue::null_array(),
+                    None => RespValue::null_bulk_string(),
+                });
+            }
+        };
 
-        if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let mut list = stored.as_list()?.clone();
+        let mut list = stored.as_list()?.clone();
+        let mut values = Vec::new();
 
-            // Pop elements from the front
-            for _ in 0..count.min(list.len()) {
-                if let Some(value) = list.pop_front() {
-                    values.push(value);
-                }
+        // Pop elements from the front
+        for _ in 0..count.unwrap_or(1).min(list.len()) {
+            if let Some(value) = list.pop_front() {
+                values.push(value);
             }
+        }
 
-            // Update or delete the list
-            if list.is_empty() {
-                self.storage.delete_from_db(db_index, &key)?;
-            } else {
-                self.storage
-                    .set_value(db_index, key, StoredValue::new_list(list))?;
-            }
+        // Update or delete the list
+        if list.is_empty() {
+            self.storage.delete_from_db(db_index, &key)?;
+        } else if !values.is_empty() {
+            self.storage
+                .set_value(db_index, key, StoredValue::new_list(list))?;
         }
 
-        if values.is_empty() {
-            Ok(RespValue::Null)
-        } else if count == 1 {
-            Ok(RespValue::bulk_string(values[0].clone()))
-        } else {
-            Ok(RespValue::Array(Some(
+        match count {
+            Some(_) => Ok(RespValue::array(
                 values.into_iter().map(RespValue::bulk_string).collect(),
-            )))
+            )),
+            None => Ok(values
+                .pop()
+                .map(RespValue::bulk_string)
+                .unwrap_or_else(RespValue::null_bulk_string)),
         }
     }
 
@@ -141,45 +152,159 @@ impl ListCommands {
         }
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
+        // An explicit count (Redis 6.2+) switches the reply to an array
         let count = if args.len() > 1 {
-            String::from_utf8_lossy(&args[1])
-                .parse::<usize>()
-                .map_err(|_| AikvError::InvalidArgument("invalid count".to_string()))?
+            Some(Self::parse_pop_count(&args[1])?)
         } else {
-            1
+            None
         };
 
         // Migrated: Logic moved from storage layer to command layer
+        let stored = match self.storage.get_value(db_index, &key)? {
+            Some(stored) => stored,
+            None => {
+                return Ok(match count {
+                    Some(_) => RespValue::null_array(),
+                    None => RespValue::null_bulk_string(),
+                });
+            }
+        };
+
+        let mut list = stored.as_list()?.clone();
         let mut values = Vec::new();
 
-        if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let mut list = stored.as_list()?.clone();
+        // Pop elements from the back
+        for _ in 0..count.unwrap_or(1).min(list.len()) {
+            if let Some(value) = list.pop_back() {
+                values.push(value);
+            }
+        }
 
-            // Pop elements from the back
-            for _ in 0..count.min(list.len()) {
-                if let Some(value) = list.pop_back() {
-                    values.push(value);
-                }
+        // Update or delete the list
+        if list.is_empty() {
+            self.storage.delete_from_db(db_index, &key)?;
+        } else if !values.is_empty() {
+            self.storage
+                .set_value(db_index, key, StoredValue::new_list(list))?;
+        }
+
+        match count {
+            Some(_) => Ok(RespValue::array(
+                values.into_iter().map(RespValue::bulk_string).collect(),
+            )),
+            None => Ok(values
+                .pop()
+                .map(RespValue::bulk_string)
+                .unwrap_or_else(RespValue::null_bulk_string)),
+        }
+    }
+
+    /// Parse the optional count argument of LPOP/RPOP
+    fn parse_pop_count(arg: &Bytes) -> Result<usize> {
+        String::from_utf8_lossy(arg).parse::<usize>().map_err(|_| {
+            AikvError::InvalidArgument("ERR value is out of range, must be positive".to_string())
+        })
+    }
+
+    /// LMPOP numkeys key \[key ...\] LEFT|RIGHT \[COUNT count\]
+    /// Pops up to count elements from the first non-empty list, replying with
+    /// its key and the elements, or nil when every list is empty
+    pub fn lmpop(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        let parsed = numkeys::parse_mpop("LMPOP", args, ["LEFT", "RIGHT"])?;
+
+        for key in parsed.keys {
+            let name = String::from_utf8_lossy(key).to_string();
+            let Some(stored) = self.storage.get_value(db_index, &name)? else {
+                continue;
+            };
+            let mut list = stored.as_list()?.clone();
+            if list.is_empty() {
+                continue;
             }
 
-            // Update or delete the list
+            let count = parsed.count.min(list.len());
+            let values: Vec<Bytes> = if parsed.first_end {
+                list.drain(..count).collect()
+            } else {
+                list.drain(list.len() - count..).rev().collect()
+            };
+
             if list.is_empty() {
-                self.storage.delete_from_db(db_index, &key)?;
+                self.storage.delete_from_db(db_index, &name)?;
             } else {
                 self.storage
-                    .set_value(db_index, key, StoredValue::new_list(list))?;
+                    .set_value(db_index, name, StoredValue::new_list(list))?;
             }
+
+            return Ok(RespValue::array(vec![
+                RespValue::bulk_string(key.clone()),
+                RespValue::array(values.into_iter().map(RespValue::bulk_string).collect()),
+            ]));
         }
 
-        if values.is_empty() {
-            Ok(RespValue::Null)
-        } else if count == 1 {
-            Ok(RespValue::bulk_string(values[0].clone()))
-        } else {
-            Ok(RespValue::Array(Some(
-                values.into_iter().map(RespValue::bulk_string).collect(),
-            )))
+        Ok(RespValue::null_array())
+    }
+
+    /// BLPOP key \[key ...\] timeout
+    /// Pops the head of the first non-empty list, replying nil when every list is empty.
+    ///
+    /// This is the form run inside MULTI; a connection that finds the lists
+    /// empty waits for a push through [`BlockedClients`] instead.
+    pub fn blpop(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        self.pop_first_list("BLPOP", args, db_index, true)
+    }
+
+    /// BRPOP key \[key ...\] timeout
+    /// Pops the tail of the first non-empty list, replying nil when every list is empty.
+    pub fn brpop(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        self.pop_first_list("BRPOP", args, db_index, false)
+    }
+
+    fn pop_first_list(
+        &self,
+        command: &str,
+        args: &[Bytes],
+        db_index: usize,
+        left: bool,
+    ) -> Result<RespValue> {
+        let (keys, _) = Self::parse_blocking_args(command, args)?;
+
+        for key in keys {
+            let name = String::from_utf8_lossy(key).to_string();
+            if let Some(value) = pop_end(&self.storage, db_index, &name, left)? {
+                return Ok(RespValue::array(vec![
+                    RespValue::bulk_string(key.clone()),
+                    RespValue::bulk_string(value),
+                ]));
+            }
         }
+        Ok(RespValue::null_array())
+    }
+
+    /// Split BLPOP/BRPOP arguments into the keys and the timeout (`None` waits forever)
+    pub fn parse_blocking_args<'a>(
+        command: &str,
+        args: &'a [Bytes],
+    ) -> Result<(&'a [Bytes], Option<Duration>)> {
+        let Some((timeout, keys)) = args.split_last().filter(|(_, keys)| !keys.is_empty()) else {
+            return Err(AikvError::WrongArgCount(command.to_string()));
+        };
+
+        let timeout = std::str::from_utf8(timeout)
+            .ok()
+            .and_then(|t| t.parse::<f64>().ok())
+            .filter(|t| t.is_finite())
+            .ok_or_else(|| {
+                AikvError::InvalidArgument("ERR timeout is not a float or out of range".to_string())
+            })?;
+        if timeout < 0.0 {
+            return Err(AikvError::InvalidArgument(
+                "ERR timeout is negative".to_string(),
+            ));
+        }
+
+        let timeout = (timeout > 0.0).then(|| Duration::from_secs_f64(timeout));
+        Ok((keys, timeout))
     }
 
     /// LLEN key
@@ -594,3 +719,192 @@ impl ListCommands {
         }
     }
 }
+
+/// Pop one element from either end of a list, if it holds any
+fn pop_end(
+    storage: &StorageEngine,
+    db_index: usize,
+    key: &str,
+    left: bool,
+) -> Result<Option<Bytes>> {
+    let mut popped = None;
+    storage.update_value(db_index, key, |stored| {
+        let list = stored.as_list_mut()?;
+        popped = if left {
+            list.pop_front()
+        } else {
+            list.pop_back()
+        };
+        Ok(())
+    })?;
+    Ok(popped)
+}
+
+/// Outcome of BLPOP or BRPOP run on a connection
+pub enum BlockingPop {
+    /// The reply to send right away
+    Reply(RespValue),
+    /// Every list was empty: wait on `receiver` for a key and element until
+    /// `timeout` (forever when `None`), then [`BlockedClients::unblock`] `id`
+    Blocked {
+        id: u64,
+        receiver: oneshot::Receiver<(Bytes, Bytes)>,
+        timeout: Option<Duration>,
+    },
+}
+
+/// Where a blocked client's key and element are sent, shared by all its keys
+type ReplySlot = Arc<Mutex<Option<oneshot::Sender<(Bytes, Bytes)>>>>;
+
+/// Blocked clients by database and key
+type Queues = HashMap<(usize, Bytes), VecDeque<Waiter>>;
+
+/// A client waiting in BLPOP or BRPOP
+struct Waiter {
+    /// Identifies one blocking call across all of its keys
+    id: u64,
+    /// BLPOP pops the head, BRPOP the tail
+    left: bool,
+    /// Taken by whichever key serves the client first
+    reply: ReplySlot,
+}
+
+impl Waiter {
+    fn is_waiting(&self) -> bool {
+        self.reply
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .is_some_and(|sender| !sender.is_closed())
+    }
+}
+
+/// Clients blocked in BLPOP and BRPOP, queued per key in arrival order.
+///
+/// The server shares one with every connection. Blocking happens under the
+/// exclusive command gate, so a push can't slip in between a client finding
+/// its lists empty and joining the queue; every command serves the queue once
+/// it is done. Each element pushed goes to the longest-waiting client only.
+#[derive(Clone, Default)]
+pub struct BlockedClients {
+    queues: Arc<Mutex<Queues>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl BlockedClients {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a client on `keys`, returning its id and where its element will arrive
+    pub fn block(
+        &self,
+        db_index: usize,
+        keys: &[Bytes],
+        left: bool,
+    ) -> (u64, oneshot::Receiver<(Bytes, Bytes)>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        let reply = Arc::new(Mutex::new(Some(sender)));
+
+        let mut queues = self.lock();
+        for key in keys {
+            queues
+                .entry((db_index, key.clone()))
+                .or_default()
+                .push_back(Waiter {
+                    id,
+                    left,
+                    reply: Arc::clone(&reply),
+                });
+        }
+        (id, receiver)
+    }
+
+    /// Stop waiting, on timeout or when the client goes away
+    pub fn unblock(&self, id: u64) {
+        let mut queues = self.lock();
+        queues.retain(|_, queue| {
+            queue.retain(|waiter| waiter.id != id);
+            !queue.is_empty()
+        });
+    }
+
+    /// Number of clients currently blocked
+    pub fn count(&self) -> usize {
+        let queues = self.lock();
+        let mut ids: Vec<u64> = queues
+            .values()
+            .flatten()
+            .filter(|waiter| waiter.is_waiting())
+            .map(|waiter| waiter.id)
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids.len()
+    }
+
+    /// Hand elements now in the lists clients wait on to those clients, oldest first.
+    ///
+    /// Returns the `(db, key, left)` of every pop so the caller can count and
+    /// notify them like any other write.
+    pub fn serve(&self, storage: &StorageEngine) -> Vec<(usize, Bytes, bool)> {
+        let mut queues = self.lock();
+        let mut served = Vec::new();
+
+        for ((db_index, key), queue) in queues.iter_mut() {
+            let name = String::from_utf8_lossy(key).to_string();
+            while let Some(waiter) = queue.front() {
+                let mut reply = waiter.reply.lock().unwrap_or_else(PoisonError::into_inner);
+                // Served through another key already, or gone
+                let Some(sender) = reply.take().filter(|sender| !sender.is_closed()) else {
+                    drop(reply);
+                    queue.pop_front();
+                    continue;
+                };
+                let Ok(Some(value)) = pop_end(storage, *db_index, &name, waiter.left) else {
+                    *reply = Some(sender);
+                    break;
+                };
+
+                let left = waiter.left;
+                if let Err((_, value)) = sender.send((key.clone(), value)) {
+                    // The client went away after all: put the element back
+                    let _ = storage.upsert_value(
+                        *db_index,
+                        &name,
+                        StoredValue::new_list(VecDeque::new()),
+                        |stored| {
+                            let list = stored.as_list_mut()?;
+                            if left {
+                                list.push_front(value);
+                            } else {
+                                list.push_back(value);
+                            }
+                            Ok(())
+                        },
+                    );
+                } else {
+                    served.push((*db_index, key.clone(), left));
+                }
+                drop(reply);
+                queue.pop_front();
+            }
+        }
+
+        queues.retain(|_, queue| {
+            queue.retain(Waiter::is_waiting);
+            !queue.is_empty()
+        });
+        served
+    }
+
+    /// Whether no client is blocked, so writes can skip serving
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Queues> {
+        self.queues.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
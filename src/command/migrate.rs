@@ -0,0 +1,143 @@
+//! Moving keys to another server
+//!
+//! Keys are handed over the way Redis MIGRATE does it: each one is sent to the
+//! target as a RESTORE of its DUMP payload carrying its expiry, and deleted
+//! here once the target has acknowledged it. CLUSTER MIGRATE-SLOT moves a
+//! slot's keys to their new owner this way, one batch at a time.
+//!
+//! [`send_command`] runs a single command on another server, which cluster
+//! mode uses to hand SCRIPT LOAD and SCRIPT FLUSH on to the other nodes.
+
+use crate::command::key::KeyCommands;
+use crate::error::{AikvError, Result};
+use crate::protocol::{RespParser, RespValue};
+use crate::storage::StorageEngine;
+use bytes::Bytes;
+use std::future::Future;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Move `keys` of database `db` to database 0 of the server at `addr`,
+/// replacing any the target already has.
+///
+/// The RESTOREs are pipelined over one connection. A key is only deleted once
+/// the target accepted it, so after a failure the keys not yet moved are
+/// still here. Connecting, writing and each reply are limited to `timeout`.
+/// Returns the number of keys moved.
+pub async fn migrate_keys(
+    storage: &StorageEngine,
+    db: usize,
+    keys: &[String],
+    addr: &str,
+    timeout: Duration,
+) -> Result<usize> {
+    let mut request = Vec::new();
+    let mut sent = Vec::new();
+    for key in keys {
+        let Some(value) = storage.get_value(db, key)? else {
+            continue;
+        };
+        // -1 (no expiry) becomes 0, which RESTORE reads as no expiry too
+        let expire_at = storage.get_expire_time_in_db(db, key)?.max(0);
+        let restore = RespValue::array(vec![
+            RespValue::bulk_string("RESTORE"),
+            RespValue::bulk_string(key.clone()),
+            RespValue::bulk_string(expire_at.to_string()),
+            RespValue::bulk_string(KeyCommands::dump_payload(&value)?),
+            RespValue::bulk_string("REPLACE"),
+            RespValue::bulk_string("ABSTTL"),
+        ]);
+        request.extend_from_slice(&restore.serialize());
+        sent.push(key);
+    }
+    if sent.is_empty() {
+        return Ok(0);
+    }
+
+    let mut stream = with_timeout(timeout, "connecting to", TcpStream::connect(addr)).await?;
+    with_timeout(timeout, "writing to", stream.write_all(&request)).await?;
+
+    let mut parser = RespParser::new(4096);
+    let mut moved = 0;
+    for key in sent {
+        let reply = read_reply(&mut stream, &mut parser, timeout).await?;
+
+        if let RespValue::Error(message) = reply {
+            return Err(AikvError::InvalidArgument(format!(
+                "ERR Target instance replied with error: {}",
+                message
+            )));
+        }
+        storage.delete_from_db(db, key)?;
+        moved += 1;
+    }
+
+    Ok(moved)
+}
+
+/// Run `args` as one command on the server at `addr` and return its reply.
+///
+/// An error reply is returned as the error. Connecting, writing and the reply
+/// are each limited to `timeout`.
+pub async fn send_command(args: &[Bytes], addr: &str, timeout: Duration) -> Result<RespValue> {
+    let request = RespValue::array(args.iter().cloned().map(RespValue::bulk_string).collect());
+
+    let mut stream = with_timeout(timeout, "connecting to", TcpStream::connect(addr)).await?;
+    with_timeout(
+        timeout,
+        "writing to",
+        stream.write_all(&request.serialize()),
+    )
+    .await?;
+
+    let mut parser = RespParser::new(4096);
+    match read_reply(&mut stream, &mut parser, timeout).await? {
+        RespValue::Error(message) => Err(AikvError::InvalidArgument(format!(
+            "ERR Target instance replied with error: {}",
+            message
+        ))),
+        reply => Ok(reply),
+    }
+}
+
+/// Read the next reply from the target, waiting at most `timeout` for it
+async fn read_reply(
+    stream: &mut TcpStream,
+    parser: &mut RespParser,
+    timeout: Duration,
+) -> Result<RespValue> {
+    with_timeout(timeout, "reading from", async {
+        loop {
+            let parsed = parser
+                .parse()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+            if let Some(reply) = parsed {
+                return Ok(reply);
+            }
+            if stream.read_buf(parser.buffer_mut()).await? == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+    })
+    .await
+}
+
+/// Run a network step of the migration, failing it after `timeout`
+async fn with_timeout<T>(
+    timeout: Duration,
+    step: &str,
+    future: impl Future<Output = std::io::Result<T>>,
+) -> Result<T> {
+    match tokio::time::timeout(timeout, future).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(e)) => Err(AikvError::InvalidArgument(format!(
+            "ERR error {} target instance: {}",
+            step, e
+        ))),
+        Err(_) => Err(AikvError::InvalidArgument(format!(
+            "ERR timeout {} target instance",
+            step
+        ))),
+    }
+}
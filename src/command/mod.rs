@@ -1,31 +1,68 @@
 pub mod database;
+pub mod docs;
+pub mod glob;
 pub mod hash;
 pub mod json;
 pub mod key;
 pub mod list;
+pub mod migrate;
+pub mod notify;
+pub mod numkeys;
+pub mod pubsub;
+pub mod random;
 pub mod script;
 pub mod server;
 pub mod set;
 pub mod string;
+pub mod transaction;
 pub mod zset;
 
 use self::database::DatabaseCommands;
 use self::hash::HashCommands;
 use self::json::JsonCommands;
 use self::key::KeyCommands;
-use self::list::ListCommands;
+use self::list::{BlockedClients, BlockingPop, ListCommands};
+use self::pubsub::PubSubCommands;
+use self::random::CommandRng;
 use self::script::ScriptCommands;
 use self::server::ServerCommands;
 use self::set::SetCommands;
 use self::string::StringCommands;
+use self::transaction::{CommandGate, Transaction};
 use self::zset::ZSetCommands;
 use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
+use crate::server::pubsub::PubSubBroker;
+#[cfg(debug_assertions)]
+use crate::storage::BatchOp;
 use crate::storage::StorageEngine;
 use bytes::Bytes;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Commands listed in the COMMAND table but handled by the connection itself
+/// rather than by the executor.
+/// Commands that run a Lua script, holding the command gate exclusively
+const SCRIPT_COMMANDS: &[&str] = &["EVAL", "EVALSHA", "EVAL_RO", "EVALSHA_RO"];
+
+pub const CONNECTION_COMMANDS: &[&str] = &[
+    "AUTH",
+    "HELLO",
+    "MONITOR",
+    "SUBSCRIBE",
+    "UNSUBSCRIBE",
+    "PSUBSCRIBE",
+    "PUNSUBSCRIBE",
+    "SSUBSCRIBE",
+    "SUNSUBSCRIBE",
+    "RESET",
+    "QUIT",
+];
 
 /// Command executor with database context
 pub struct CommandExecutor {
+    storage: StorageEngine,
     string_commands: StringCommands,
     json_commands: JsonCommands,
     database_commands: DatabaseCommands,
@@ -36,6 +73,15 @@ pub struct CommandExecutor {
     hash_commands: HashCommands,
     set_commands: SetCommands,
     zset_commands: ZSetCommands,
+    pubsub_commands: PubSubCommands,
+    transaction: Transaction,
+    /// Shared with every connection of a server so EXEC runs atomically
+    gate: CommandGate,
+    /// Clients blocked in BLPOP/BRPOP, shared with every connection of a server
+    blocked: BlockedClients,
+    /// Only read directly by DEBUG SET-RNG-SEED
+    #[cfg_attr(not(debug_assertions), allow(dead_code))]
+    rng: CommandRng,
     #[cfg(feature = "cluster")]
     cluster_commands: Option<crate::cluster::ClusterCommands>,
 }
@@ -46,17 +92,27 @@ impl CommandExecutor {
     }
 
     pub fn with_port(storage: StorageEngine, port: u16) -> Self {
+        let rng = CommandRng::new();
+        let broker = Arc::new(PubSubBroker::new());
+        let mut script_commands = ScriptCommands::new(storage.clone());
+        script_commands.set_broker(broker.clone());
         Self {
+            storage: storage.clone(),
             string_commands: StringCommands::new(storage.clone()),
             json_commands: JsonCommands::new(storage.clone()),
             database_commands: DatabaseCommands::new(storage.clone()),
-            key_commands: KeyCommands::new(storage.clone()),
-            server_commands: ServerCommands::with_port(port),
-            script_commands: ScriptCommands::new(storage.clone()),
+            key_commands: KeyCommands::new(storage.clone(), rng.clone()),
+            server_commands: ServerCommands::with_port(storage.clone(), port),
+            script_commands,
             list_commands: ListCommands::new(storage.clone()),
             hash_commands: HashCommands::new(storage.clone()),
-            set_commands: SetCommands::new(storage.clone()),
+            set_commands: SetCommands::new(storage.clone(), rng.clone()),
             zset_commands: ZSetCommands::new(storage),
+            pubsub_commands: PubSubCommands::new(broker),
+            transaction: Transaction::new(),
+            gate: CommandGate::new(),
+            blocked: BlockedClients::new(),
+            rng,
             #[cfg(feature = "cluster")]
             cluster_commands: None, // Will be set later when cluster is initialized
         }
@@ -71,6 +127,66 @@ impl CommandExecutor {
         self.cluster_commands = Some(cluster_commands);
     }
 
+    /// Attach the server metrics so INFO reports live statistics
+    pub fn set_metrics(&mut self, metrics: Arc<crate::observability::Metrics>) {
+        self.server_commands.set_metrics(metrics);
+    }
+
+    /// Share the server's identity so INFO server reports the running server
+    pub fn set_server_identity(&mut self, identity: server::ServerIdentity) {
+        self.server_commands.set_identity(identity);
+    }
+
+    /// Share the server's Pub/Sub broker with this executor
+    pub fn set_pubsub_broker(&mut self, broker: Arc<PubSubBroker>) {
+        self.script_commands.set_broker(broker.clone());
+        self.pubsub_commands.set_broker(broker);
+    }
+
+    /// Share the server's client registry with this executor
+    pub fn set_client_registry(&mut self, clients: server::ClientRegistry) {
+        self.server_commands.set_client_registry(clients);
+    }
+
+    /// Share the server's save bookkeeping so INFO persistence counts every write
+    pub fn set_save_state(&mut self, save_state: server::SaveState) {
+        self.server_commands.set_save_state(save_state);
+    }
+
+    /// Share the server's command gate so EXEC is atomic across connections
+    pub fn set_command_gate(&mut self, gate: CommandGate) {
+        self.gate = gate;
+    }
+
+    /// Share the server's blocked clients so pushes on any connection serve them
+    pub fn set_blocked_clients(&mut self, blocked: BlockedClients) {
+        self.server_commands.set_blocked_clients(blocked.clone());
+        self.blocked = blocked;
+    }
+
+    /// Share the server's script cache with this executor
+    pub fn set_script_cache(&mut self, cache: script::ScriptCache) {
+        self.script_commands.set_cache(cache);
+    }
+
+    /// Get the Pub/Sub broker used by PUBLISH and the subscribe commands
+    pub fn pubsub_broker(&self) -> &Arc<PubSubBroker> {
+        self.pubsub_commands.broker()
+    }
+
+    /// Check whether the connection has an open MULTI transaction
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_active().unwrap_or(false)
+    }
+
+    /// RESET - Discard the connection's transaction state
+    pub fn reset(&self) -> Result<()> {
+        if self.transaction.is_active()? {
+            self.transaction.discard(&[])?;
+        }
+        Ok(())
+    }
+
     pub fn execute(
         &self,
         command: &str,
@@ -78,7 +194,388 @@ impl CommandExecutor {
         current_db: &mut usize,
         client_id: usize,
     ) -> Result<RespValue> {
-        match command.to_uppercase().as_str() {
+        // SCRIPT KILL has to reach a script that is holding the gate
+        if command.eq_ignore_ascii_case("SCRIPT")
+            && args
+                .first()
+                .is_some_and(|subcommand| subcommand.eq_ignore_ascii_case(b"KILL"))
+        {
+            return self.execute_in_gate(command, args, current_db, client_id);
+        }
+
+        // EXEC keeps every other connection out until its whole queue has run,
+        // and so does a script, which runs against copies of the keys it uses.
+        // Everything else shares the gate, including commands MULTI queues.
+        let name = command.to_uppercase();
+        let run = |current_db: &mut usize| {
+            let result = self.execute_in_gate(command, args, current_db, client_id);
+            self.serve_blocked_clients();
+            result
+        };
+        if SCRIPT_COMMANDS.contains(&name.as_str()) && !self.transaction.is_active()? {
+            // A script can run until SCRIPT KILL, so its thread is handed over
+            return transaction::run_blocking(|| {
+                let _gate = self.gate.exclusive();
+                run(current_db)
+            });
+        }
+        if name == "EXEC" {
+            let _gate = self.gate.exclusive();
+            return run(current_db);
+        }
+        let _gate = self.gate.shared();
+        run(current_db)
+    }
+
+    /// Run BLPOP or BRPOP for a connection, queueing it if every list is empty.
+    ///
+    /// The gate is held throughout, so no push can land between the lists
+    /// being found empty and the client joining the queue.
+    pub fn block_pop(
+        &self,
+        command: &str,
+        args: &[Bytes],
+        current_db: &mut usize,
+        client_id: usize,
+    ) -> Result<BlockingPop> {
+        let _gate = self.gate.exclusive();
+        let (keys, timeout) = ListCommands::parse_blocking_args(command, args)?;
+        let reply = self.execute_in_gate(command, args, current_db, client_id)?;
+        if reply != RespValue::null_array() {
+            return Ok(BlockingPop::Reply(reply));
+        }
+
+        let left = command.eq_ignore_ascii_case("BLPOP");
+        let (id, receiver) = self.blocked.block(*current_db, keys, left);
+        Ok(BlockingPop::Blocked {
+            id,
+            receiver,
+            timeout,
+        })
+    }
+
+    /// Stop a BLPOP or BRPOP from [`Self::block_pop`] waiting
+    pub fn unblock(&self, id: u64) {
+        self.blocked.unblock(id);
+    }
+
+    /// Pop elements for clients blocked on lists a write may have filled
+    fn serve_blocked_clients(&self) {
+        if self.blocked.is_empty() {
+            return;
+        }
+        let notify = self.pubsub_broker().keyspace_events().fires_any();
+        for (db, key, left) in self.blocked.serve(&self.storage) {
+            self.server_commands.record_change();
+            if notify {
+                let name = if left { "LPOP" } else { "RPOP" };
+                let reply = RespValue::ok();
+                self.notify_keyspace_events(name, &[key], db, &reply, &HashSet::new());
+            }
+        }
+    }
+
+    /// Run a command once the command gate is held
+    fn execute_in_gate(
+        &self,
+        command: &str,
+        args: &[Bytes],
+        current_db: &mut usize,
+        client_id: usize,
+    ) -> Result<RespValue> {
+        let name = command.to_uppercase();
+        let result = match name.as_str() {
+            "MULTI" => self.transaction.begin(args),
+            "EXEC" => self.exec(args, current_db, client_id),
+            "DISCARD" => self.transaction.discard(args),
+            _ if self.transaction.is_active()? => self.transaction.queue(&name, args),
+            _ => {
+                let write = server::lookup_command(&name).filter(|info| info.has_flag("write"));
+                let notify = write.is_some() && self.pubsub_broker().keyspace_events().fires_any();
+                let db = *current_db;
+                // Which keys existed decides which keys DEL and the *STORE commands remove
+                let existed = match &write {
+                    Some(info) if notify => self.existing_keys(info, args, db)?,
+                    _ => HashSet::new(),
+                };
+
+                // Keys in a slot being moved out wait until it is handed over
+                #[cfg(feature = "cluster")]
+                if let (Some(cluster_commands), Some(info)) =
+                    (&self.cluster_commands, server::lookup_command(&name))
+                {
+                    for key in info.extract_keys(args) {
+                        cluster_commands.check_key_migrating(key)?;
+                    }
+                }
+
+                let result = self.dispatch(&name, command, args, current_db, client_id);
+                if let (Ok(reply), Some(_)) = (&result, &write) {
+                    self.server_commands.record_change();
+                    if notify {
+                        self.notify_keyspace_events(&name, args, db, reply, &existed);
+                    }
+                }
+                result
+            }
+        };
+
+        // Every dispatched command must be advertised by COMMAND
+        #[cfg(debug_assertions)]
+        if !Self::is_unknown_command(&result) {
+            debug_assert!(
+                server::lookup_command(&name).is_some(),
+                "command {} is dispatched but missing from the COMMAND table",
+                name
+            );
+        }
+
+        result
+    }
+
+    /// Collect the keys of a command that currently exist
+    fn existing_keys(
+        &self,
+        info: &server::CommandInfo,
+        args: &[Bytes],
+        db: usize,
+    ) -> Result<HashSet<Bytes>> {
+        let mut existed = HashSet::new();
+        for key in info.extract_keys(args) {
+            if self
+                .storage
+                .exists_in_db(db, &String::from_utf8_lossy(key))?
+            {
+                existed.insert(key.clone());
+            }
+        }
+        Ok(existed)
+    }
+
+    /// Publish the keyspace events of a successful write command
+    fn notify_keyspace_events(
+        &self,
+        name: &str,
+        args: &[Bytes],
+        db: usize,
+        reply: &RespValue,
+        existed: &HashSet<Bytes>,
+    ) {
+        let events = self.keyspace_events(name, args, db, reply, existed);
+        Self::publish_keyspace_events(self.pubsub_broker(), events);
+    }
+
+    /// Work out the keyspace events of a successful write command from the
+    /// state it left the keys in
+    fn keyspace_events(
+        &self,
+        name: &str,
+        args: &[Bytes],
+        db: usize,
+        reply: &RespValue,
+        existed: &HashSet<Bytes>,
+    ) -> Vec<notify::KeyEvent> {
+        use notify::KeyspaceEvents as Class;
+
+        let mut events = Vec::new();
+        for event in notify::command_events(name, args, db, reply, existed) {
+            let exists = || {
+                self.storage
+                    .exists_in_db(event.db, &String::from_utf8_lossy(&event.key))
+                    .unwrap_or(true)
+            };
+
+            // A deadline in the past removes the key, firing `expired` instead
+            if event.event == "expire" && !exists() {
+                continue;
+            }
+
+            // Storage removes containers once their last element is gone
+            let container =
+                [Class::LIST, Class::SET, Class::HASH, Class::ZSET].contains(&event.class);
+            if container && !exists() {
+                let del = notify::KeyEvent {
+                    class: Class::GENERIC,
+                    event: "del",
+                    db: event.db,
+                    key: event.key.clone(),
+                };
+                events.extend([event, del]);
+            } else {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// Publish keyspace events through `broker`
+    fn publish_keyspace_events(broker: &PubSubBroker, events: Vec<notify::KeyEvent>) {
+        for event in events {
+            if let Err(e) =
+                broker.notify_keyspace_event(event.class, event.event, event.db, &event.key)
+            {
+                warn!("Failed to publish keyspace event {}: {}", event.event, e);
+            }
+        }
+    }
+
+    /// EXEC - Run the queued commands in order, returning one reply per command.
+    ///
+    /// The caller holds the command gate exclusively, so the queue runs without
+    /// commands from other connections in between.
+    ///
+    /// Queued commands share `current_db`, so a queued SELECT takes effect for
+    /// the commands queued after it and for the connection once EXEC returns.
+    fn exec(&self, args: &[Bytes], current_db: &mut usize, client_id: usize) -> Result<RespValue> {
+        let queued = self.transaction.take(args)?;
+
+        let replies = queued
+            .iter()
+            .map(|cmd| {
+                self.execute_in_gate(&cmd.name, &cmd.args, current_db, client_id)
+                    .unwrap_or_else(|e| e.to_resp())
+            })
+            .collect();
+
+        Ok(RespValue::array(replies))
+    }
+
+    /// DEBUG - Testing helpers (debug builds only)
+    ///
+    /// - SET-RNG-SEED seed: make random replies reproducible
+    /// - POPULATE count \[prefix\] \[size\]: create `prefix:0` to `prefix:<count-1>`
+    /// - PROTOCOL type: reply with a sample of a RESP3 type, downgraded for RESP2
+    #[cfg(debug_assertions)]
+    fn debug(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.is_empty() {
+            return Err(AikvError::WrongArgCount("DEBUG".to_string()));
+        }
+
+        let subcommand = String::from_utf8_lossy(&args[0]).to_uppercase();
+        match subcommand.as_str() {
+            "SET-RNG-SEED" => {
+                if args.len() != 2 {
+                    return Err(AikvError::WrongArgCount("DEBUG SET-RNG-SEED".to_string()));
+                }
+                let seed = String::from_utf8_lossy(&args[1])
+                    .parse::<u64>()
+                    .map_err(|_| {
+                        AikvError::InvalidArgument(
+                            "ERR value is not an integer or out of range".to_string(),
+                        )
+                    })?;
+                self.rng.seed(seed);
+                Ok(RespValue::ok())
+            }
+            "POPULATE" => {
+                if !(2..=4).contains(&args.len()) {
+                    return Err(AikvError::WrongArgCount("DEBUG POPULATE".to_string()));
+                }
+                let count = String::from_utf8_lossy(&args[1])
+                    .parse::<u64>()
+                    .map_err(|_| {
+                        AikvError::InvalidArgument(
+                            "ERR value is out of range, must be positive".to_string(),
+                        )
+                    })?;
+                let prefix = args
+                    .get(2)
+                    .map(|prefix| String::from_utf8_lossy(prefix).to_string())
+                    .unwrap_or_else(|| "key".to_string());
+                let size = args
+                    .get(3)
+                    .map(|size| {
+                        String::from_utf8_lossy(size).parse::<usize>().map_err(|_| {
+                            AikvError::InvalidArgument(
+                                "ERR value is out of range, must be positive".to_string(),
+                            )
+                        })
+                    })
+                    .transpose()?;
+
+                // One batch instead of a SET per key; existing keys are kept, as in Redis
+                let mut operations = Vec::new();
+                for i in 0..count {
+                    let key = format!("{}:{}", prefix, i);
+                    if self.storage.exists_in_db(current_db, &key)? {
+                        continue;
+                    }
+                    let mut value = format!("value:{}", i).into_bytes();
+                    if let Some(size) = size {
+                        value.resize(size, 0);
+                    }
+                    operations.push((key, BatchOp::Set(Bytes::from(value))));
+                }
+                self.storage.write_batch(current_db, operations)?;
+                Ok(RespValue::ok())
+            }
+            "PROTOCOL" => {
+                if args.len() != 2 {
+                    return Err(AikvError::WrongArgCount("DEBUG PROTOCOL".to_string()));
+                }
+                let numbers = || (0..3).map(RespValue::integer).collect::<Vec<_>>();
+                let name = String::from_utf8_lossy(&args[1]).to_lowercase();
+                Ok(match name.as_str() {
+                    "string" => RespValue::bulk_string("Hello World"),
+                    "integer" => RespValue::integer(12345),
+                    "double" => RespValue::double(2.5),
+                    "bignum" => RespValue::big_number("1234567999999999999999999999999999999"),
+                    "null" => RespValue::null(),
+                    "array" => RespValue::array(numbers()),
+                    "set" => RespValue::set(numbers()),
+                    "map" => RespValue::map(
+                        numbers()
+                            .into_iter()
+                            .zip([false, true, false].map(RespValue::boolean))
+                            .collect(),
+                    ),
+                    "attrib" => RespValue::attribute(
+                        vec![(
+                            RespValue::bulk_string("key-popularity"),
+                            RespValue::array(vec![
+                                RespValue::bulk_string("key:123"),
+                                RespValue::integer(90),
+                            ]),
+                        )],
+                        RespValue::bulk_string("Some real reply following the attribute"),
+                    ),
+                    "push" => RespValue::push(vec![
+                        RespValue::bulk_string("server-cpu-usage"),
+                        RespValue::integer(42),
+                    ]),
+                    "verbatim" => {
+                        RespValue::verbatim_string("txt", "This is a verbatim\nstring")
+                    }
+                    "true" => RespValue::boolean(true),
+                    "false" => RespValue::boolean(false),
+                    _ => {
+                        return Err(AikvError::InvalidArgument(
+                            "ERR Wrong protocol type name. Please use one of the following: string|integer|double|bignum|null|array|set|map|attrib|push|verbatim|true|false".to_string(),
+                        ))
+                    }
+                })
+            }
+            _ => Err(AikvError::InvalidCommand(format!(
+                "Unknown DEBUG subcommand: {}",
+                subcommand
+            ))),
+        }
+    }
+
+    /// Check whether a result is the executor's unknown-command error
+    pub fn is_unknown_command(result: &Result<RespValue>) -> bool {
+        matches!(result, Err(AikvError::InvalidCommand(msg)) if msg.starts_with("Unknown command"))
+    }
+
+    fn dispatch(
+        &self,
+        name: &str,
+        command: &str,
+        args: &[Bytes],
+        current_db: &mut usize,
+        client_id: usize,
+    ) -> Result<RespValue> {
+        match name {
             // String commands
             "GET" => self.string_commands.get(args, *current_db),
             "SET" => self.string_commands.set(args, *current_db),
@@ -88,6 +585,13 @@ impl CommandExecutor {
             "MSET" => self.string_commands.mset(args, *current_db),
             "STRLEN" => self.string_commands.strlen(args, *current_db),
             "APPEND" => self.string_commands.append(args, *current_db),
+            "SETNX" => self.string_commands.setnx(args, *current_db),
+            "GETDEL" => self.string_commands.getdel(args, *current_db),
+            "GETEX" => self.string_commands.getex(args, *current_db),
+            "INCR" => self.string_commands.incr(args, *current_db),
+            "DECR" => self.string_commands.decr(args, *current_db),
+            "INCRBY" => self.string_commands.incrby(args, *current_db),
+            "DECRBY" => self.string_commands.decrby(args, *current_db),
 
             // JSON commands
             "JSON.GET" => self.json_commands.json_get(args, *current_db),
@@ -99,7 +603,19 @@ impl CommandExecutor {
             "JSON.OBJLEN" => self.json_commands.json_objlen(args, *current_db),
 
             // Database commands
-            "SELECT" => self.database_commands.select(args, current_db),
+            "SELECT" => {
+                let mut index = *current_db;
+                let reply = self.database_commands.select(args, &mut index)?;
+                // A cluster only has database 0, as in Redis
+                #[cfg(feature = "cluster")]
+                if self.cluster_commands.is_some() && index != 0 {
+                    return Err(AikvError::InvalidArgument(
+                        "ERR SELECT is not allowed in cluster mode".to_string(),
+                    ));
+                }
+                *current_db = index;
+                Ok(reply)
+            }
             "DBSIZE" => self.database_commands.dbsize(args, *current_db),
             "FLUSHDB" => self.database_commands.flushdb(args, *current_db),
             "FLUSHALL" => self.database_commands.flushall(args),
@@ -113,6 +629,8 @@ impl CommandExecutor {
             "RENAME" => self.key_commands.rename(args, *current_db),
             "RENAMENX" => self.key_commands.renamenx(args, *current_db),
             "TYPE" => self.key_commands.get_type(args, *current_db),
+            "OBJECT" => self.key_commands.object(args, *current_db),
+            "MEMORY" => self.key_commands.memory(args, *current_db),
             "COPY" => self.key_commands.copy(args, *current_db),
             "DUMP" => self.key_commands.dump(args, *current_db),
             "RESTORE" => self.key_commands.restore(args, *current_db),
@@ -130,16 +648,25 @@ impl CommandExecutor {
             "PEXPIRETIME" => self.key_commands.pexpiretime(args, *current_db),
 
             // Server commands
-            "INFO" => self.server_commands.info(args),
+            "INFO" => self.server_commands.info(args, self.is_replica()),
             "CONFIG" => {
                 if args.is_empty() {
                     return Err(AikvError::WrongArgCount("CONFIG".to_string()));
                 }
                 let subcommand = String::from_utf8_lossy(&args[0]).to_uppercase();
                 match subcommand.as_str() {
-                    "GET" => self.server_commands.config_get(&args[1..]),
-                    "SET" => self.server_commands.config_set(&args[1..]),
+                    "GET" => self.server_commands.config_get(
+                        &args[1..],
+                        self.pubsub_broker(),
+                        self.script_commands.cache(),
+                    ),
+                    "SET" => self.server_commands.config_set(
+                        &args[1..],
+                        self.pubsub_broker(),
+                        self.script_commands.cache(),
+                    ),
                     "REWRITE" => self.server_commands.config_rewrite(&args[1..]),
+                    "RESETSTAT" => self.server_commands.config_resetstat(&args[1..]),
                     _ => Err(AikvError::InvalidCommand(format!(
                         "Unknown CONFIG subcommand: {}",
                         subcommand
@@ -149,9 +676,14 @@ impl CommandExecutor {
             "SLOWLOG" => self.server_commands.slowlog(args),
             "TIME" => self.server_commands.time(args),
             "COMMAND" => self.server_commands.command(args),
-            "SAVE" => self.server_commands.save(args),
-            "BGSAVE" => self.server_commands.bgsave(args),
+            "SAVE" => self
+                .server_commands
+                .save(args, self.script_commands.cache()),
+            "BGSAVE" => self
+                .server_commands
+                .bgsave(args, self.script_commands.cache()),
             "LASTSAVE" => self.server_commands.lastsave(args),
+            "WAIT" => self.server_commands.wait(args),
             "SHUTDOWN" => self.server_commands.shutdown(args),
             "CLIENT" => {
                 if args.is_empty() {
@@ -160,8 +692,14 @@ impl CommandExecutor {
                 let subcommand = String::from_utf8_lossy(&args[0]).to_uppercase();
                 match subcommand.as_str() {
                     "LIST" => self.server_commands.client_list(&args[1..]),
+                    "INFO" => self.server_commands.client_info(&args[1..], client_id),
                     "SETNAME" => self.server_commands.client_setname(&args[1..], client_id),
                     "GETNAME" => self.server_commands.client_getname(&args[1..], client_id),
+                    "KILL" => self.server_commands.client_kill(
+                        &args[1..],
+                        client_id,
+                        self.pubsub_commands.broker(),
+                    ),
                     _ => Err(AikvError::InvalidCommand(format!(
                         "Unknown CLIENT subcommand: {}",
                         subcommand
@@ -172,6 +710,8 @@ impl CommandExecutor {
             // Script commands
             "EVAL" => self.script_commands.eval(args, *current_db),
             "EVALSHA" => self.script_commands.evalsha(args, *current_db),
+            "EVAL_RO" => self.script_commands.eval_ro(args, *current_db),
+            "EVALSHA_RO" => self.script_commands.evalsha_ro(args, *current_db),
             "SCRIPT" => {
                 if args.is_empty() {
                     return Err(AikvError::WrongArgCount("SCRIPT".to_string()));
@@ -194,6 +734,8 @@ impl CommandExecutor {
             "RPUSH" => self.list_commands.rpush(args, *current_db),
             "LPOP" => self.list_commands.lpop(args, *current_db),
             "RPOP" => self.list_commands.rpop(args, *current_db),
+            "BLPOP" => self.list_commands.blpop(args, *current_db),
+            "BRPOP" => self.list_commands.brpop(args, *current_db),
             "LLEN" => self.list_commands.llen(args, *current_db),
             "LRANGE" => self.list_commands.lrange(args, *current_db),
             "LINDEX" => self.list_commands.lindex(args, *current_db),
@@ -202,6 +744,7 @@ impl CommandExecutor {
             "LTRIM" => self.list_commands.ltrim(args, *current_db),
             "LINSERT" => self.list_commands.linsert(args, *current_db),
             "LMOVE" => self.list_commands.lmove(args, *current_db),
+            "LMPOP" => self.list_commands.lmpop(args, *current_db),
 
             // Hash commands
             "HSET" => self.hash_commands.hset(args, *current_db),
@@ -233,6 +776,8 @@ impl CommandExecutor {
             "SUNIONSTORE" => self.set_commands.sunionstore(args, *current_db),
             "SINTERSTORE" => self.set_commands.sinterstore(args, *current_db),
             "SDIFFSTORE" => self.set_commands.sdiffstore(args, *current_db),
+            "SINTERCARD" => self.set_commands.sintercard(args, *current_db),
+            "SMOVE" => self.set_commands.smove(args, *current_db),
 
             // Sorted Set commands
             "ZADD" => self.zset_commands.zadd(args, *current_db),
@@ -247,6 +792,8 @@ impl CommandExecutor {
             "ZCARD" => self.zset_commands.zcard(args, *current_db),
             "ZCOUNT" => self.zset_commands.zcount(args, *current_db),
             "ZINCRBY" => self.zset_commands.zincrby(args, *current_db),
+            "ZINTERCARD" => self.zset_commands.zintercard(args, *current_db),
+            "ZMPOP" => self.zset_commands.zmpop(args, *current_db),
 
             // Cluster commands (only available with cluster feature)
             #[cfg(feature = "cluster")]
@@ -283,6 +830,20 @@ impl CommandExecutor {
                 }
             }
 
+            // Pub/Sub commands
+            "PUBLISH" => self.pubsub_commands.publish(args),
+            "PUBSUB" => self.pubsub_commands.pubsub(args),
+            "SPUBLISH" => {
+                // Shard channels are served by the node owning their slot
+                #[cfg(feature = "cluster")]
+                if let (Some(cluster_commands), Some(channel)) =
+                    (&self.cluster_commands, args.first())
+                {
+                    cluster_commands.check_key_slot(channel)?;
+                }
+                self.pubsub_commands.spublish(args)
+            }
+
             // Utility commands
             "PING" => {
                 if args.is_empty() {
@@ -300,6 +861,8 @@ impl CommandExecutor {
                 }
                 Ok(RespValue::bulk_string(args[0].clone()))
             }
+            #[cfg(debug_assertions)]
+            "DEBUG" => self.debug(args, *current_db),
 
             _ => Err(AikvError::InvalidCommand(format!(
                 "Unknown command: {}",
@@ -312,6 +875,26 @@ impl CommandExecutor {
         &self.server_commands
     }
 
+    /// Whether this node serves as a replica. Only cluster nodes following
+    /// another node's group are; a standalone server is always a master.
+    pub fn is_replica(&self) -> bool {
+        #[cfg(feature = "cluster")]
+        if let Some(cluster_commands) = &self.cluster_commands {
+            return cluster_commands.is_replica();
+        }
+        false
+    }
+
+    /// Check whether SCRIPT `args` would change the script cache.
+    ///
+    /// A cluster node only hands SCRIPT LOAD and SCRIPT FLUSH on to the other
+    /// nodes when they changed something, so the nodes receiving them do not
+    /// send them back.
+    #[cfg(feature = "cluster")]
+    pub fn script_changes_cache(&self, args: &[Bytes]) -> Result<bool> {
+        self.script_commands.changes_cache(args)
+    }
+
     #[cfg(feature = "cluster")]
     pub fn cluster_commands(&self) -> Option<&crate::cluster::ClusterCommands> {
         self.cluster_commands.as_ref()
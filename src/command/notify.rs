@@ -0,0 +1,479 @@
+//! Keyspace notifications
+//!
+//! When `notify-keyspace-events` enables them, writes are announced over
+//! Pub/Sub: `__keyspace@<db>__:<key>` receives the event name and
+//! `__keyevent@<db>__:<event>` receives the key. Every event belongs to a
+//! class, and only the classes named in the config fire:
+//!
+//! - `g` generic commands such as DEL, EXPIRE and RENAME
+//! - `$` string, `l` list, `s` set, `h` hash and `z` sorted set commands
+//! - `x` keys removed because their TTL passed
+//! - `d` module commands (JSON.SET and JSON.DEL)
+//! - `e` evicted, `t` stream, `m` key-miss and `n` new-key events, which are
+//!   accepted for compatibility but never fired
+//!
+//! `K` and `E` pick the keyspace and keyevent channels, at least one of which
+//! must be given for anything to be published. `A` stands for `g$lshzxetd`.
+
+use crate::protocol::RespValue;
+use bytes::Bytes;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::BitOr;
+
+/// A set of keyspace event flags, as configured by `notify-keyspace-events`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyspaceEvents(u32);
+
+impl KeyspaceEvents {
+    /// Nothing is published
+    pub const NONE: Self = Self(0);
+    /// `K`: publish to `__keyspace@<db>__:<key>`
+    pub const KEYSPACE: Self = Self(1);
+    /// `E`: publish to `__keyevent@<db>__:<event>`
+    pub const KEYEVENT: Self = Self(1 << 1);
+    pub const GENERIC: Self = Self(1 << 2);
+    pub const STRING: Self = Self(1 << 3);
+    pub const LIST: Self = Self(1 << 4);
+    pub const SET: Self = Self(1 << 5);
+    pub const HASH: Self = Self(1 << 6);
+    pub const ZSET: Self = Self(1 << 7);
+    pub const EXPIRED: Self = Self(1 << 8);
+    pub const EVICTED: Self = Self(1 << 9);
+    pub const STREAM: Self = Self(1 << 10);
+    pub const MODULE: Self = Self(1 << 11);
+    pub const KEY_MISS: Self = Self(1 << 12);
+    pub const NEW: Self = Self(1 << 13);
+    /// `A`: every class but key-miss and new-key events
+    pub const ALL: Self = Self(
+        Self::GENERIC.0
+            | Self::STRING.0
+            | Self::LIST.0
+            | Self::SET.0
+            | Self::HASH.0
+            | Self::ZSET.0
+            | Self::EXPIRED.0
+            | Self::EVICTED.0
+            | Self::STREAM.0
+            | Self::MODULE.0,
+    );
+
+    /// Flag characters, in the order CONFIG GET reports them
+    const FLAGS: [(char, Self); 14] = [
+        ('g', Self::GENERIC),
+        ('$', Self::STRING),
+        ('l', Self::LIST),
+        ('s', Self::SET),
+        ('h', Self::HASH),
+        ('z', Self::ZSET),
+        ('x', Self::EXPIRED),
+        ('e', Self::EVICTED),
+        ('t', Self::STREAM),
+        ('d', Self::MODULE),
+        ('K', Self::KEYSPACE),
+        ('E', Self::KEYEVENT),
+        ('m', Self::KEY_MISS),
+        ('n', Self::NEW),
+    ];
+
+    /// Parse a `notify-keyspace-events` value, or None if it has an unknown flag
+    pub fn parse(flags: &str) -> Option<Self> {
+        flags.chars().try_fold(Self::NONE, |events, c| {
+            let flag = match c {
+                'A' => Self::ALL,
+                _ => Self::FLAGS.iter().find(|(name, _)| *name == c)?.1,
+            };
+            Some(events | flag)
+        })
+    }
+
+    /// Flags from their bit representation
+    pub fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// The bit representation of the flags
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Whether every flag of `other` is set
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether events of `class` are published on any channel
+    pub fn fires(self, class: Self) -> bool {
+        self.contains(class) && (self.contains(Self::KEYSPACE) || self.contains(Self::KEYEVENT))
+    }
+
+    /// Whether any class is published at all
+    pub fn fires_any(self) -> bool {
+        self.0 & Self::ALL.0 != 0
+            && (self.contains(Self::KEYSPACE) || self.contains(Self::KEYEVENT))
+    }
+}
+
+impl BitOr for KeyspaceEvents {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl fmt::Display for KeyspaceEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let all = self.contains(Self::ALL);
+        if all {
+            write!(f, "A")?;
+        }
+        for (c, flag) in Self::FLAGS {
+            let in_all = Self::ALL.contains(flag);
+            if self.contains(flag) && !(all && in_all) {
+                write!(f, "{}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A keyspace event for one key
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyEvent {
+    pub class: KeyspaceEvents,
+    pub event: &'static str,
+    pub db: usize,
+    pub key: Bytes,
+}
+
+impl KeyEvent {
+    fn new(class: KeyspaceEvents, event: &'static str, db: usize, key: &Bytes) -> Self {
+        Self {
+            class,
+            event,
+            db,
+            key: key.clone(),
+        }
+    }
+}
+
+/// The events a write command fires once it has succeeded with `reply`.
+///
+/// `existed` holds the command's keys that existed before it ran. Commands
+/// whose reply says they changed nothing (nil, or 0 from a command that
+/// counts its changes) fire nothing. Containers a command leaves empty are
+/// removed by the storage layer; the caller adds their `del` events.
+pub fn command_events(
+    name: &str,
+    args: &[Bytes],
+    db: usize,
+    reply: &RespValue,
+    existed: &HashSet<Bytes>,
+) -> Vec<KeyEvent> {
+    let nil = matches!(
+        reply,
+        RespValue::Null | RespValue::BulkString(None) | RespValue::Array(None)
+    );
+    let changed = !nil && *reply != RespValue::Integer(0);
+    let option = |i: usize| {
+        args.get(i)
+            .map(|arg| String::from_utf8_lossy(arg).to_uppercase())
+    };
+    let has_option = |names: &[&str]| {
+        args.iter()
+            .skip(1)
+            .any(|arg| names.contains(&String::from_utf8_lossy(arg).to_uppercase().as_str()))
+    };
+    let Some(key) = args.first() else {
+        return Vec::new();
+    };
+    let on_key = |class, event| vec![KeyEvent::new(class, event, db, key)];
+
+    use KeyspaceEvents as Class;
+    match name {
+        "SET" => {
+            // With GET the reply is the old value rather than whether the write happened
+            let wrote = if !has_option(&["GET"]) {
+                !nil
+            } else if has_option(&["NX"]) {
+                nil
+            } else if has_option(&["XX"]) {
+                !nil
+            } else {
+                true
+            };
+            let mut events = Vec::new();
+            if wrote {
+                events.push(KeyEvent::new(Class::STRING, "set", db, key));
+                if has_option(&["EX", "PX", "EXAT", "PXAT"]) {
+                    events.push(KeyEvent::new(Class::GENERIC, "expire", db, key));
+                }
+            }
+            events
+        }
+        "SETNX" if changed => on_key(Class::STRING, "set"),
+        "MSET" => args
+            .chunks_exact(2)
+            .map(|pair| KeyEvent::new(Class::STRING, "set", db, &pair[0]))
+            .collect(),
+        "APPEND" => on_key(Class::STRING, "append"),
+        "INCR" | "DECR" | "INCRBY" | "DECRBY" => on_key(Class::STRING, "incrby"),
+        "GETDEL" if !nil => on_key(Class::GENERIC, "del"),
+        "GETEX" if !nil => {
+            if has_option(&["EX", "PX", "EXAT", "PXAT"]) {
+                on_key(Class::GENERIC, "expire")
+            } else if has_option(&["PERSIST"]) {
+                on_key(Class::GENERIC, "persist")
+            } else {
+                Vec::new()
+            }
+        }
+        "DEL" => args
+            .iter()
+            .filter(|key| existed.contains(*key))
+            .map(|key| KeyEvent::new(Class::GENERIC, "del", db, key))
+            .collect(),
+        "EXPIRE" | "EXPIREAT" | "PEXPIRE" | "PEXPIREAT" if changed => {
+            on_key(Class::GENERIC, "expire")
+        }
+        "PERSIST" if changed => on_key(Class::GENERIC, "persist"),
+        "RENAME" | "RENAMENX" if changed && args.len() > 1 => vec![
+            KeyEvent::new(Class::GENERIC, "rename_from", db, key),
+            KeyEvent::new(Class::GENERIC, "rename_to", db, &args[1]),
+        ],
+        "COPY" if changed && args.len() > 1 => {
+            // COPY source destination [DB destination-db] [REPLACE]
+            let dest_db = args
+                .iter()
+                .position(|arg| arg.eq_ignore_ascii_case(b"DB"))
+                .and_then(|i| option(i + 1)?.parse().ok())
+                .unwrap_or(db);
+            vec![KeyEvent::new(Class::GENERIC, "copy_to", dest_db, &args[1])]
+        }
+        "MOVE" if changed => match option(1).and_then(|target| target.parse().ok()) {
+            Some(target) => vec![
+                KeyEvent::new(Class::GENERIC, "move_from", db, key),
+                KeyEvent::new(Class::GENERIC, "move_to", target, key),
+            ],
+            None => Vec::new(),
+        },
+        "RESTORE" => on_key(Class::GENERIC, "restore"),
+        "LPUSH" => on_key(Class::LIST, "lpush"),
+        "RPUSH" => on_key(Class::LIST, "rpush"),
+        "LPOP" if !nil => on_key(Class::LIST, "lpop"),
+        "RPOP" if !nil => on_key(Class::LIST, "rpop"),
+        "BLPOP" | "BRPOP" => match popped_key(reply) {
+            Some(key) => {
+                let event = if name == "BLPOP" { "lpop" } else { "rpop" };
+                vec![KeyEvent::new(Class::LIST, event, db, key)]
+            }
+            None => Vec::new(),
+        },
+        "LMPOP" => match popped_key(reply) {
+            Some(key) => {
+                let event = if option(1 + numkeys(args)).as_deref() == Some("LEFT") {
+                    "lpop"
+                } else {
+                    "rpop"
+                };
+                vec![KeyEvent::new(Class::LIST, event, db, key)]
+            }
+            None => Vec::new(),
+        },
+        "LSET" => on_key(Class::LIST, "lset"),
+        "LREM" if changed => on_key(Class::LIST, "lrem"),
+        "LTRIM" => on_key(Class::LIST, "ltrim"),
+        "LINSERT" if matches!(reply, RespValue::Integer(n) if *n > 0) => {
+            on_key(Class::LIST, "linsert")
+        }
+        "LMOVE" if !nil && args.len() == 4 => {
+            let pop = if option(2).as_deref() == Some("LEFT") {
+                "lpop"
+            } else {
+                "rpop"
+            };
+            let push = if option(3).as_deref() == Some("LEFT") {
+                "lpush"
+            } else {
+                "rpush"
+            };
+            vec![
+                KeyEvent::new(Class::LIST, pop, db, key),
+                KeyEvent::new(Class::LIST, push, db, &args[1]),
+            ]
+        }
+        "HSET" | "HMSET" => on_key(Class::HASH, "hset"),
+        "HSETNX" if changed => on_key(Class::HASH, "hset"),
+        "HDEL" if changed => on_key(Class::HASH, "hdel"),
+        "HINCRBY" => on_key(Class::HASH, "hincrby"),
+        "HINCRBYFLOAT" => on_key(Class::HASH, "hincrbyfloat"),
+        "SADD" if changed => on_key(Class::SET, "sadd"),
+        "SREM" if changed => on_key(Class::SET, "srem"),
+        "SPOP" if !nil && *reply != RespValue::array(Vec::new()) => on_key(Class::SET, "spop"),
+        "SMOVE" if changed && args.len() == 3 && args[0] != args[1] => vec![
+            KeyEvent::new(Class::SET, "srem", db, key),
+            KeyEvent::new(Class::SET, "sadd", db, &args[1]),
+        ],
+        "SUNIONSTORE" | "SINTERSTORE" | "SDIFFSTORE" => {
+            if changed {
+                let event = match name {
+                    "SUNIONSTORE" => "sunionstore",
+                    "SINTERSTORE" => "sinterstore",
+                    _ => "sdiffstore",
+                };
+                on_key(Class::SET, event)
+            } else if existed.contains(key) {
+                // An empty result removes the destination
+                on_key(Class::GENERIC, "del")
+            } else {
+                Vec::new()
+            }
+        }
+        "ZADD" if !nil => {
+            let event = if has_option(&["INCR"]) {
+                "zincr"
+            } else {
+                "zadd"
+            };
+            on_key(Class::ZSET, event)
+        }
+        "ZREM" if changed => on_key(Class::ZSET, "zrem"),
+        "ZINCRBY" => on_key(Class::ZSET, "zincr"),
+        "ZMPOP" => match popped_key(reply) {
+            Some(key) => {
+                let event = if option(1 + numkeys(args)).as_deref() == Some("MIN") {
+                    "zpopmin"
+                } else {
+                    "zpopmax"
+                };
+                vec![KeyEvent::new(Class::ZSET, event, db, key)]
+            }
+            None => Vec::new(),
+        },
+        "JSON.SET" if !nil => on_key(Class::MODULE, "json.set"),
+        "JSON.DEL" if changed => on_key(Class::MODULE, "json.del"),
+        _ => Vec::new(),
+    }
+}
+
+/// The key a popping command took its elements from: the first entry of
+/// BLPOP, LMPOP and ZMPOP replies
+fn popped_key(reply: &RespValue) -> Option<&Bytes> {
+    match reply {
+        RespValue::Array(Some(popped)) => match popped.first() {
+            Some(RespValue::BulkString(Some(key))) => Some(key),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The numkeys argument of LMPOP and ZMPOP, which precedes their keys
+fn numkeys(args: &[Bytes]) -> usize {
+    args.first()
+        .and_then(|n| String::from_utf8_lossy(n).parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(name: &str, args: &[&str], reply: RespValue) -> Vec<(&'static str, String)> {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        let existed = args.iter().take(1).cloned().collect();
+        command_events(name, &args, 0, &reply, &existed)
+            .into_iter()
+            .map(|e| (e.event, String::from_utf8_lossy(&e.key).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!(KeyspaceEvents::parse(""), Some(KeyspaceEvents::NONE));
+        assert_eq!(
+            KeyspaceEvents::parse("Kl"),
+            Some(KeyspaceEvents::KEYSPACE | KeyspaceEvents::LIST)
+        );
+        assert_eq!(KeyspaceEvents::parse("KEA").unwrap().to_string(), "AKE");
+        assert_eq!(
+            KeyspaceEvents::parse("g$lshzxetdKE").unwrap().to_string(),
+            "AKE"
+        );
+        assert_eq!(KeyspaceEvents::parse("Elx").unwrap().to_string(), "lxE");
+        assert_eq!(KeyspaceEvents::parse("nmK").unwrap().to_string(), "Kmn");
+        assert_eq!(KeyspaceEvents::parse("Kq"), None);
+
+        // Classes need a channel, and a channel needs a class
+        assert!(!KeyspaceEvents::parse("l")
+            .unwrap()
+            .fires(KeyspaceEvents::LIST));
+        assert!(!KeyspaceEvents::parse("KE").unwrap().fires_any());
+        let list_only = KeyspaceEvents::parse("El").unwrap();
+        assert!(list_only.fires(KeyspaceEvents::LIST));
+        assert!(!list_only.fires(KeyspaceEvents::STRING));
+    }
+
+    #[test]
+    fn test_command_events() {
+        let ok = RespValue::ok;
+        assert_eq!(events("SET", &["k", "v"], ok()), [("set", "k".to_string())]);
+        assert_eq!(
+            events("SET", &["k", "v", "EX", "10"], ok()),
+            [("set", "k".to_string()), ("expire", "k".to_string())]
+        );
+        // NX that didn't write, unless GET reports the old value instead
+        assert!(events("SET", &["k", "v", "NX"], RespValue::null_bulk_string()).is_empty());
+        assert!(events(
+            "SET",
+            &["k", "v", "NX", "GET"],
+            RespValue::bulk_string("old")
+        )
+        .is_empty());
+        assert_eq!(
+            events("SET", &["k", "v", "GET"], RespValue::null_bulk_string()),
+            [("set", "k".to_string())]
+        );
+
+        assert_eq!(
+            events(
+                "LMOVE",
+                &["a", "b", "RIGHT", "LEFT"],
+                RespValue::bulk_string("x")
+            ),
+            [("rpop", "a".to_string()), ("lpush", "b".to_string())]
+        );
+        assert_eq!(
+            events(
+                "LMPOP",
+                &["2", "a", "b", "RIGHT"],
+                RespValue::array(vec![
+                    RespValue::bulk_string("b"),
+                    RespValue::array(vec![RespValue::bulk_string("x")])
+                ])
+            ),
+            [("rpop", "b".to_string())]
+        );
+        assert!(events("ZMPOP", &["1", "z", "MIN"], RespValue::null_array()).is_empty());
+        assert_eq!(
+            events("SMOVE", &["a", "b", "m"], RespValue::integer(1)),
+            [("srem", "a".to_string()), ("sadd", "b".to_string())]
+        );
+        assert!(events("SADD", &["s", "m"], RespValue::integer(0)).is_empty());
+        assert_eq!(
+            events("RENAME", &["a", "b"], ok()),
+            [
+                ("rename_from", "a".to_string()),
+                ("rename_to", "b".to_string())
+            ]
+        );
+
+        // DEL only reports keys that existed
+        assert_eq!(
+            events("DEL", &["k", "missing"], RespValue::integer(1)),
+            [("del", "k".to_string())]
+        );
+        assert!(events("GET", &["k"], RespValue::bulk_string("v")).is_empty());
+    }
+}
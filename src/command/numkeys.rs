@@ -0,0 +1,135 @@
+//! Argument parsing for commands that take a `numkeys` count before their keys
+//!
+//! SINTERCARD and ZINTERCARD take `numkeys key [key ...] [LIMIT limit]`, LMPOP
+//! and ZMPOP `numkeys key [key ...] <end> [COUNT count]`. Each rejects malformed
+//! input with the same messages Redis uses for that command.
+
+use crate::error::{AikvError, Result};
+use bytes::Bytes;
+
+/// Parsed arguments of LMPOP or ZMPOP
+#[derive(Debug)]
+pub struct MpopArgs<'a> {
+    pub keys: &'a [Bytes],
+    /// Whether the first of the two ends (LEFT or MIN) was given
+    pub first_end: bool,
+    pub count: usize,
+}
+
+/// Parse `numkeys key [key ...] [LIMIT limit]`, returning the keys and the
+/// limit (0 meaning no limit)
+///
+/// ZINTERCARD words its numkeys errors differently from SINTERCARD, as in Redis.
+pub fn parse_intercard<'a>(command: &str, args: &'a [Bytes]) -> Result<(&'a [Bytes], usize)> {
+    if args.is_empty() {
+        return Err(AikvError::WrongArgCount(command.to_string()));
+    }
+
+    let numkeys = if command == "ZINTERCARD" {
+        let numkeys = parse_integer(&args[0])?;
+        if numkeys < 1 {
+            return Err(AikvError::InvalidArgument(format!(
+                "ERR at least 1 input key is needed for '{}' command",
+                command.to_lowercase()
+            )));
+        }
+        if numkeys as usize > args.len() - 1 {
+            return Err(syntax_error());
+        }
+        numkeys as usize
+    } else {
+        let numkeys = parse_numkeys(&args[0])?;
+        if numkeys > args.len() - 1 {
+            return Err(AikvError::InvalidArgument(
+                "ERR Number of keys can't be greater than number of args".to_string(),
+            ));
+        }
+        numkeys
+    };
+
+    let keys = &args[1..=numkeys];
+    let mut limit = 0;
+    let mut options = args[1 + numkeys..].iter();
+    while let Some(option) = options.next() {
+        match options.next() {
+            Some(value) if option.eq_ignore_ascii_case(b"LIMIT") => {
+                limit = parse_integer(value)
+                    .ok()
+                    .filter(|limit| *limit >= 0)
+                    .ok_or_else(|| {
+                        AikvError::InvalidArgument("ERR LIMIT can't be negative".to_string())
+                    })? as usize;
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+
+    Ok((keys, limit))
+}
+
+/// Parse `numkeys key [key ...] <end> [COUNT count]`, where `ends` names the
+/// two ends to pop from (LEFT/RIGHT or MIN/MAX)
+pub fn parse_mpop<'a>(command: &str, args: &'a [Bytes], ends: [&str; 2]) -> Result<MpopArgs<'a>> {
+    if args.len() < 3 {
+        return Err(AikvError::WrongArgCount(command.to_string()));
+    }
+
+    let numkeys = parse_numkeys(&args[0])?;
+    let Some(end) = args.get(1 + numkeys) else {
+        return Err(syntax_error());
+    };
+    let first_end = if end.eq_ignore_ascii_case(ends[0].as_bytes()) {
+        true
+    } else if end.eq_ignore_ascii_case(ends[1].as_bytes()) {
+        false
+    } else {
+        return Err(syntax_error());
+    };
+
+    let mut count = None;
+    let mut options = args[2 + numkeys..].iter();
+    while let Some(option) = options.next() {
+        match options.next() {
+            Some(value) if count.is_none() && option.eq_ignore_ascii_case(b"COUNT") => {
+                count = Some(
+                    parse_integer(value)
+                        .ok()
+                        .filter(|count| *count > 0)
+                        .ok_or_else(|| {
+                            AikvError::InvalidArgument(
+                                "ERR count should be greater than 0".to_string(),
+                            )
+                        })? as usize,
+                );
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+
+    Ok(MpopArgs {
+        keys: &args[1..=numkeys],
+        first_end,
+        count: count.unwrap_or(1),
+    })
+}
+
+/// Parse a numkeys argument, which must be a positive integer
+fn parse_numkeys(arg: &Bytes) -> Result<usize> {
+    parse_integer(arg)
+        .ok()
+        .filter(|numkeys| *numkeys > 0)
+        .map(|numkeys| numkeys as usize)
+        .ok_or_else(|| {
+            AikvError::InvalidArgument("ERR numkeys should be greater than 0".to_string())
+        })
+}
+
+fn parse_integer(arg: &Bytes) -> Result<i64> {
+    String::from_utf8_lossy(arg).parse::<i64>().map_err(|_| {
+        AikvError::InvalidArgument("ERR value is not an integer or out of range".to_string())
+    })
+}
+
+fn syntax_error() -> AikvError {
+    AikvError::InvalidArgument("ERR syntax error".to_string())
+}
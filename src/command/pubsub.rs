@@ -0,0 +1,114 @@
+use crate::error::{AikvError, Result};
+use crate::protocol::RespValue;
+use crate::server::pubsub::PubSubBroker;
+use bytes::Bytes;
+use std::sync::Arc;
+
+/// Pub/Sub command handler.
+///
+/// PUBLISH and PUBSUB run through the executor; the subscribe family changes
+/// the connection's state and is handled by the connection itself.
+pub struct PubSubCommands {
+    broker: Arc<PubSubBroker>,
+}
+
+impl PubSubCommands {
+    pub fn new(broker: Arc<PubSubBroker>) -> Self {
+        Self {
+            broker,
+        }
+    }
+
+    /// Get the broker shared with the connection
+    pub fn broker(&self) -> &Arc<PubSubBroker> {
+        &self.broker
+    }
+
+    /// Replace the broker (used by the server to share one broker across connections)
+    pub fn set_broker(&mut self, broker: Arc<PubSubBroker>) {
+        self.broker = broker;
+    }
+
+    /// PUBLISH channel message
+    pub fn publish(&self, args: &[Bytes]) -> Result<RespValue> {
+        if args.len() != 2 {
+            return Err(AikvError::WrongArgCount("PUBLISH".to_string()));
+        }
+
+        let receivers = self.broker.publish(&args[0], &args[1])?;
+        Ok(RespValue::integer(receivers as i64))
+    }
+
+    /// SPUBLISH shardchannel message
+    pub fn spublish(&self, args: &[Bytes]) -> Result<RespValue> {
+        if args.len() != 2 {
+            return Err(AikvError::WrongArgCount("SPUBLISH".to_string()));
+        }
+
+        let receivers = self.broker.spublish(&args[0], &args[1])?;
+        Ok(RespValue::integer(receivers as i64))
+    }
+
+    /// PUBSUB subcommand \[argument \[argument ...\]\]
+    pub fn pubsub(&self, args: &[Bytes]) -> Result<RespValue> {
+        if args.is_empty() {
+            return Err(AikvError::WrongArgCount("PUBSUB".to_string()));
+        }
+
+        let subcommand = String::from_utf8_lossy(&args[0]).to_uppercase();
+        match subcommand.as_str() {
+            "CHANNELS" => {
+                if args.len() > 2 {
+                    return Err(AikvError::WrongArgCount("PUBSUB|CHANNELS".to_string()));
+                }
+                let pattern = args.get(1).map(|p| p.as_ref());
+                let channels = self
+                    .broker
+                    .channels(pattern)?
+                    .into_iter()
+                    .map(RespValue::bulk_string)
+                    .collect();
+                Ok(RespValue::array(channels))
+            }
+            "NUMSUB" => {
+                let mut results = Vec::with_capacity((args.len() - 1) * 2);
+                for channel in &args[1..] {
+                    results.push(RespValue::bulk_string(channel.clone()));
+                    results.push(RespValue::integer(self.broker.numsub(channel)? as i64));
+                }
+                Ok(RespValue::array(results))
+            }
+            "SHARDCHANNELS" => {
+                if args.len() > 2 {
+                    return Err(AikvError::WrongArgCount("PUBSUB|SHARDCHANNELS".to_string()));
+                }
+                let pattern = args.get(1).map(|p| p.as_ref());
+                let channels = self
+                    .broker
+                    .shard_channels(pattern)?
+                    .into_iter()
+                    .map(RespValue::bulk_string)
+                    .collect();
+                Ok(RespValue::array(channels))
+            }
+            "SHARDNUMSUB" => {
+                let mut results = Vec::with_capacity((args.len() - 1) * 2);
+                for channel in &args[1..] {
+                    results.push(RespValue::bulk_string(channel.clone()));
+                    results.push(RespValue::integer(self.broker.shard_numsub(channel)? as i64));
+                }
+                Ok(RespValue::array(results))
+            }
+            "NUMPAT" => {
+                if args.len() != 1 {
+                    return Err(AikvError::WrongArgCount("PUBSUB|NUMPAT".to_string()));
+                }
+                Ok(RespValue::integer(self.broker.numpat()? as i64))
+            }
+            _ => Err(AikvError::InvalidCommand(format!(
+                "Unknown PUBSUB subcommand: {}",
+                subcommand
+            ))),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Randomness source for RANDOMKEY, SPOP and SRANDMEMBER
+//!
+//! Each executor owns one [`CommandRng`] and shares it with the command
+//! handlers that pick random elements. It is seeded from the OS, so replies are
+//! non-deterministic. Debug builds also accept `DEBUG SET-RNG-SEED seed`, which
+//! reseeds it so tests can assert exact replies.
+//!
+//! Handlers sort their candidates before picking from them, so the same seed
+//! always selects the same elements regardless of hash map iteration order.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Seedable random number generator shared by an executor's command handlers
+#[derive(Debug, Clone)]
+pub struct CommandRng {
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl CommandRng {
+    /// Create a generator seeded from the OS
+    pub fn new() -> Self {
+        Self {
+            rng: Arc::new(Mutex::new(StdRng::from_entropy())),
+        }
+    }
+
+    /// Restart the sequence from a fixed seed
+    pub fn seed(&self, seed: u64) {
+        *self.lock() = StdRng::seed_from_u64(seed);
+    }
+
+    /// Pick an index in `0..len` (`len` must be non-zero)
+    pub fn index(&self, len: usize) -> usize {
+        self.lock().gen_range(0..len)
+    }
+
+    /// Pick `amount` distinct indices in `0..len`, in random order
+    pub fn sample(&self, len: usize, amount: usize) -> Vec<usize> {
+        rand::seq::index::sample(&mut *self.lock(), len, amount.min(len)).into_vec()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, StdRng> {
+        // The generator holds no invariants a panicking holder could break
+        self.rng.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for CommandRng {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seed_replays_sequence() {
+        let rng = CommandRng::new();
+        rng.seed(42);
+        let first: Vec<usize> = (0..8).map(|_| rng.index(100)).collect();
+        rng.seed(42);
+        let second: Vec<usize> = (0..8).map(|_| rng.index(100)).collect();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_sample_is_distinct_and_bounded() {
+        let rng = CommandRng::new();
+        let mut picked = rng.sample(5, 10);
+        picked.sort();
+        assert_eq!(picked, vec![0, 1, 2, 3, 4]);
+    }
+}
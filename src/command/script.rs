@@ -1,136 +1,541 @@
+use super::notify::KeyEvent;
+use super::server::{lookup_command, CommandInfo};
+use super::CommandExecutor;
 use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
-use crate::storage::{BatchOp, StorageEngine};
+use crate::server::pubsub::PubSubBroker;
+use crate::storage::{BatchOp, StorageEngine, StoredValue};
 use bytes::Bytes;
-use mlua::{Lua, LuaOptions, StdLib, Value as LuaValue};
+use mlua::{HookTriggers, Lua, LuaOptions, StdLib, Value as LuaValue, VmState};
 use sha1::{Digest, Sha1};
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// Largest script EVAL and SCRIPT LOAD accept by default (4MB)
+const DEFAULT_MAX_SCRIPT_SIZE: usize = 4 * 1024 * 1024;
+
+/// Default cap on the total size of the cached scripts (64MB)
+const DEFAULT_MAX_CACHE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Default run time after which a script is aborted, in milliseconds
+const DEFAULT_TIME_LIMIT_MS: u64 = 5000;
+
+/// Lua instructions between checks for a timeout or SCRIPT KILL
+const HOOK_INSTRUCTIONS: u32 = 1000;
+
+/// Most idle interpreters a connection keeps for later scripts
+const LUA_POOL_SIZE: usize = 4;
+
+/// Deepest nesting cjson.encode accepts, as in Redis' bundled cjson
+const CJSON_MAX_DEPTH: usize = 1000;
+
+/// Registry key of the set of read-only library proxies, checked by rawset
+const READ_ONLY_TABLES: &str = "aikv_read_only_tables";
 
 /// Script cache entry
 #[derive(Clone, Debug)]
 struct CachedScript {
     script: String,
+    /// Cache tick of the last load or run, for LRU eviction
+    last_used: u64,
 }
 
+/// Keyless commands that scripts may call
+const SCRIPT_KEYLESS_COMMANDS: &[&str] = &["PING", "ECHO", "TIME"];
+
+/// Commands that touch keys outside the script's database and cannot be buffered
+const SCRIPT_CROSS_DB_COMMANDS: &[&str] = &["MOVE", "COPY", "MIGRATE"];
+
+/// Commands that change or wait on connection state, which a script never
+/// may: it runs against a fixed database, atomically, without blocking
+const SCRIPT_DENIED_COMMANDS: &[&str] = &[
+    "SELECT",
+    "SWAPDB",
+    "SUBSCRIBE",
+    "PSUBSCRIBE",
+    "SSUBSCRIBE",
+    "UNSUBSCRIBE",
+    "PUNSUBSCRIBE",
+    "SUNSUBSCRIBE",
+    "MULTI",
+    "EXEC",
+    "DISCARD",
+    "WATCH",
+    "UNWATCH",
+    "BLPOP",
+    "BRPOP",
+    "BLMOVE",
+    "BRPOPLPUSH",
+    "BLMPOP",
+    "BZPOPMIN",
+    "BZPOPMAX",
+    "BZMPOP",
+];
+
 /// Transaction context for Lua script execution
 ///
-/// This provides transactional semantics for Lua scripts by buffering all write
-/// operations and only committing them if the script completes successfully.
-/// If the script fails, the buffer is discarded, achieving automatic rollback.
+/// This provides transactional semantics for Lua scripts by running every command
+/// against a scratch storage and only committing the keys the script wrote if it
+/// completes successfully. If the script fails, the scratch storage is discarded,
+/// achieving automatic rollback.
+///
+/// Keys are copied from the real storage into the scratch storage the first time
+/// a command touches them, so commands see the script's own writes ("read your
+/// own writes") as well as the untouched data.
 ///
 /// When using AiDbStorageEngine, this leverages AiDb's WriteBatch for true
 /// atomic batch writes with WAL durability guarantees.
-#[derive(Debug)]
 struct ScriptTransaction {
     /// Database index for this transaction
     db_index: usize,
-    /// Write buffer: key -> operation
-    write_buffer: HashMap<String, BatchOp>,
+    /// Scratch storage holding the keys touched by the script
+    scratch: StorageEngine,
+    /// Executor running script commands against the scratch storage
+    executor: CommandExecutor,
+    /// Keys already copied into the scratch storage
+    loaded: HashSet<String>,
+    /// Keys written by the script
+    dirty: HashSet<String>,
+    /// Keyspace events of the script's writes, published once it commits;
+    /// `None` when keyspace notifications are off
+    events: Option<Vec<KeyEvent>>,
 }
 
 impl ScriptTransaction {
-    /// Create a new transaction context for a database
-    fn new(db_index: usize) -> Self {
+    /// Create a new transaction context for a database, collecting keyspace
+    /// events if `notify` is set
+    fn new(db_index: usize, notify: bool) -> Self {
+        let scratch = StorageEngine::new_memory(db_index + 1);
         Self {
             db_index,
-            write_buffer: HashMap::new(),
+            executor: CommandExecutor::new(scratch.clone()),
+            scratch,
+            loaded: HashSet::new(),
+            dirty: HashSet::new(),
+            events: notify.then(Vec::new),
         }
     }
 
-    /// Read a value, checking buffer first, then storage
-    ///
-    /// This implements "read your own writes" semantics - if a key was set
-    /// or deleted in this transaction, return that state.
-    fn get(&self, storage: &StorageEngine, key: &str) -> Result<Option<Bytes>> {
-        // Check write buffer first
-        if let Some(op) = self.write_buffer.get(key) {
-            match op {
-                BatchOp::Set(value) => return Ok(Some(value.clone())),
-                BatchOp::Delete => return Ok(None),
+    /// Copy a key from storage into the scratch storage, once per transaction
+    fn load(&mut self, storage: &StorageEngine, key: &str) -> Result<()> {
+        if !self.loaded.insert(key.to_string()) {
+            return Ok(());
+        }
+
+        let value = match storage.get_value(self.db_index, key) {
+            Ok(value) => value,
+            // AiDb keeps strings as raw bytes outside the serialized value format
+            Err(_) => storage
+                .get_from_db(self.db_index, key)?
+                .map(StoredValue::new_string),
+        };
+
+        if let Some(mut value) = value {
+            let expire_at = storage.get_expire_time_in_db(self.db_index, key)?;
+            if expire_at > 0 {
+                value.set_expiration(Some(expire_at as u64));
             }
+            self.scratch
+                .set_value(self.db_index, key.to_string(), value)?;
         }
 
-        // Fall back to storage
-        storage.get_from_db(self.db_index, key)
+        Ok(())
     }
 
-    /// Write a value to the buffer
-    fn set(&mut self, key: String, value: Bytes) {
-        self.write_buffer.insert(key, BatchOp::Set(value));
-    }
+    /// Execute a command against the scratch storage
+    fn execute(
+        &mut self,
+        storage: &StorageEngine,
+        info: &CommandInfo,
+        args: &[Bytes],
+    ) -> Result<RespValue> {
+        let keys: Vec<String> = info
+            .extract_keys(args)
+            .into_iter()
+            .map(|key| String::from_utf8_lossy(key).to_string())
+            .collect();
 
-    /// Mark a key for deletion in the buffer
-    fn delete(&mut self, key: String) {
-        self.write_buffer.insert(key, BatchOp::Delete);
-    }
+        for key in &keys {
+            self.load(storage, key)?;
+        }
 
-    /// Check if a key exists, considering the buffer
-    fn exists(&self, storage: &StorageEngine, key: &str) -> Result<bool> {
-        // Check write buffer first
-        if let Some(op) = self.write_buffer.get(key) {
-            match op {
-                BatchOp::Set(_) => return Ok(true),
-                BatchOp::Delete => return Ok(false),
-            }
+        let write = info.has_flag("write");
+        if write {
+            self.dirty.extend(keys);
         }
 
-        // Fall back to storage
-        storage.exists_in_db(self.db_index, key)
+        // Events are worked out against the scratch storage, which holds the
+        // keys as the script left them at this point
+        let existed = match &self.events {
+            Some(_) if write => self.executor.existing_keys(info, args, self.db_index)?,
+            _ => HashSet::new(),
+        };
+
+        let mut db_index = self.db_index;
+        let reply = self.executor.execute(info.name, args, &mut db_index, 0)?;
+        if let Some(events) = self.events.as_mut().filter(|_| write) {
+            events.extend(self.executor.keyspace_events(
+                info.name,
+                args,
+                self.db_index,
+                &reply,
+                &existed,
+            ));
+        }
+        Ok(reply)
     }
 
-    /// Commit the transaction - apply all buffered operations to storage atomically
+    /// Commit the transaction - apply all written keys to storage atomically
     ///
     /// This method uses write_batch() which provides:
     /// - For MemoryAdapter: In-memory atomicity within a single lock
     /// - For AiDbStorageEngine: True atomic batch writes via AiDb's WriteBatch
     ///   with WAL durability guarantees (all operations written to WAL first,
     ///   single fsync, atomic recovery on crash)
-    fn commit(self, storage: &StorageEngine) -> Result<()> {
-        if self.write_buffer.is_empty() {
-            return Ok(());
+    ///
+    /// Returns the keyspace events of the committed writes.
+    fn commit(self, storage: &StorageEngine) -> Result<Vec<KeyEvent>> {
+        if self.dirty.is_empty() {
+            return Ok(Vec::new());
         }
 
-        // Convert HashMap to Vec for write_batch
-        let operations: Vec<(String, BatchOp)> = self.write_buffer.into_iter().collect();
+        let mut operations = Vec::with_capacity(self.dirty.len());
+        for key in self.dirty {
+            let op = match self.scratch.get_value(self.db_index, &key)? {
+                Some(value) => BatchOp::SetValue(value),
+                None => BatchOp::Delete,
+            };
+            operations.push((key, op));
+        }
 
         // Use write_batch for atomic commit
         storage.write_batch(self.db_index, operations)?;
 
-        Ok(())
+        Ok(self.events.unwrap_or_default())
     }
 
     // Note: rollback() is implicit - just drop the transaction without calling commit()
 }
 
+/// Scripts cached by SCRIPT LOAD and EVAL, keyed by SHA1.
+///
+/// A server shares one cache between all of its connections, so a script
+/// loaded on one connection can be run with EVALSHA from any other.
+///
+/// Scripts are limited to `lua-max-script-size` bytes each, and once the
+/// cached scripts exceed `lua-script-cache-size` bytes in total the least
+/// recently used ones are evicted. EVAL caches every script it runs, so
+/// without the cap a client sending ever-different scripts would grow the
+/// cache without bound.
+///
+/// Running scripts are tracked here too, so SCRIPT KILL on any connection can
+/// stop them, and any script running past `lua-time-limit` milliseconds is aborted.
+#[derive(Clone, Debug)]
+pub struct ScriptCache {
+    state: Arc<RwLock<CacheState>>,
+    max_script_size: Arc<AtomicUsize>,
+    max_cache_size: Arc<AtomicUsize>,
+    time_limit_ms: Arc<AtomicU64>,
+    /// Number of scripts running right now
+    running: Arc<AtomicUsize>,
+    /// Set by SCRIPT KILL, cleared once no script is running
+    busy_kill: Arc<AtomicBool>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    scripts: HashMap<String, CachedScript>,
+    /// Total length of the cached scripts
+    size: usize,
+    /// Incremented on every access, ordering the entries by recency
+    tick: u64,
+}
+
+impl Default for ScriptCache {
+    fn default() -> Self {
+        Self {
+            state: Arc::default(),
+            max_script_size: Arc::new(AtomicUsize::new(DEFAULT_MAX_SCRIPT_SIZE)),
+            max_cache_size: Arc::new(AtomicUsize::new(DEFAULT_MAX_CACHE_SIZE)),
+            time_limit_ms: Arc::new(AtomicU64::new(DEFAULT_TIME_LIMIT_MS)),
+            running: Arc::default(),
+            busy_kill: Arc::default(),
+        }
+    }
+}
+
+impl ScriptCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Largest script accepted, in bytes (`lua-max-script-size`)
+    pub fn max_script_size(&self) -> usize {
+        self.max_script_size.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_script_size(&self, size: usize) {
+        self.max_script_size.store(size, Ordering::Relaxed);
+    }
+
+    /// Cap on the total size of the cached scripts (`lua-script-cache-size`)
+    pub fn max_cache_size(&self) -> usize {
+        self.max_cache_size.load(Ordering::Relaxed)
+    }
+
+    /// Change the cache cap, evicting scripts right away if now over it
+    pub fn set_max_cache_size(&self, size: usize) -> Result<()> {
+        self.max_cache_size.store(size, Ordering::Relaxed);
+        let mut state = self.write()?;
+        Self::evict(&mut state, size);
+        Ok(())
+    }
+
+    /// Run time after which a script is aborted, in milliseconds, 0 for none (`lua-time-limit`)
+    pub fn time_limit_ms(&self) -> u64 {
+        self.time_limit_ms.load(Ordering::Relaxed)
+    }
+
+    pub fn set_time_limit_ms(&self, limit: u64) {
+        self.time_limit_ms.store(limit, Ordering::Relaxed);
+    }
+
+    /// Count a script as running until the returned guard is dropped
+    fn start_script(&self) -> RunningScript {
+        if self.running.fetch_add(1, Ordering::SeqCst) == 0 {
+            self.busy_kill.store(false, Ordering::SeqCst);
+        }
+        RunningScript {
+            cache: self.clone(),
+        }
+    }
+
+    /// Ask the running scripts to stop, returning false when there are none
+    fn kill_running(&self) -> bool {
+        if self.running.load(Ordering::SeqCst) == 0 {
+            return false;
+        }
+        self.busy_kill.store(true, Ordering::SeqCst);
+        true
+    }
+
+    /// Reject a script over `lua-max-script-size` before it is compiled
+    fn check_size(&self, script: &[u8]) -> Result<()> {
+        let max = self.max_script_size();
+        if script.len() > max {
+            return Err(AikvError::InvalidArgument(format!(
+                "ERR script too large ({} bytes, lua-max-script-size is {})",
+                script.len(),
+                max
+            )));
+        }
+        Ok(())
+    }
+
+    /// Cache a script, or mark it as just used if already cached
+    fn insert(&self, sha1: String, script: &str) -> Result<()> {
+        let mut state = self.write()?;
+        state.tick += 1;
+        let tick = state.tick;
+        if let Some(cached) = state.scripts.get_mut(&sha1) {
+            cached.last_used = tick;
+            return Ok(());
+        }
+
+        state.size += script.len();
+        state.scripts.insert(
+            sha1,
+            CachedScript {
+                script: script.to_string(),
+                last_used: tick,
+            },
+        );
+        Self::evict(&mut state, self.max_cache_size());
+        Ok(())
+    }
+
+    /// Look up a script by digest, marking it as just used
+    fn get(&self, sha1: &str) -> Result<Option<String>> {
+        let mut state = self.write()?;
+        state.tick += 1;
+        let tick = state.tick;
+        Ok(state.scripts.get_mut(sha1).map(|cached| {
+            cached.last_used = tick;
+            cached.script.clone()
+        }))
+    }
+
+    /// The cached scripts, least recently used first, for saving in a snapshot
+    pub fn scripts(&self) -> Result<Vec<String>> {
+        let state = self.read()?;
+        let mut cached: Vec<&CachedScript> = state.scripts.values().collect();
+        cached.sort_by_key(|cached| cached.last_used);
+        Ok(cached
+            .into_iter()
+            .map(|cached| cached.script.clone())
+            .collect())
+    }
+
+    /// Cache scripts loaded from a snapshot, returning how many were restored
+    ///
+    /// Scripts are given in the order of [`ScriptCache::scripts`], so the most
+    /// recently used ones stay cached if the cap is now smaller. Scripts over
+    /// the current size limit are skipped.
+    pub fn restore(&self, scripts: impl IntoIterator<Item = String>) -> Result<usize> {
+        let mut restored = 0;
+        for script in scripts {
+            if self.check_size(script.as_bytes()).is_err() {
+                continue;
+            }
+            self.insert(ScriptCommands::calculate_sha1(&script), &script)?;
+            restored += 1;
+        }
+        Ok(restored)
+    }
+
+    fn contains(&self, sha1: &str) -> Result<bool> {
+        Ok(self.read()?.scripts.contains_key(sha1))
+    }
+
+    fn clear(&self) -> Result<()> {
+        let mut state = self.write()?;
+        state.scripts.clear();
+        state.size = 0;
+        Ok(())
+    }
+
+    /// Drop least recently used scripts until the cache fits in `max_size`
+    fn evict(state: &mut CacheState, max_size: usize) {
+        while state.size > max_size {
+            let Some(oldest) = state
+                .scripts
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(sha1, _)| sha1.clone())
+            else {
+                break;
+            };
+            if let Some(cached) = state.scripts.remove(&oldest) {
+                state.size -= cached.script.len();
+            }
+        }
+    }
+
+    fn read(&self) -> Result<std::sync::RwLockReadGuard<'_, CacheState>> {
+        self.state
+            .read()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))
+    }
+
+    fn write(&self) -> Result<std::sync::RwLockWriteGuard<'_, CacheState>> {
+        self.state
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))
+    }
+}
+
+/// A script counted as running by its [`ScriptCache`]
+struct RunningScript {
+    cache: ScriptCache,
+}
+
+impl Drop for RunningScript {
+    fn drop(&mut self) {
+        // A kill is meant for the scripts running when it was sent
+        if self.cache.running.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.cache.busy_kill.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// State of the script being run, kept in its interpreter for redis.call
+struct ScriptRun {
+    storage: StorageEngine,
+    transaction: ScriptTransaction,
+    /// Set for EVAL_RO and EVALSHA_RO, which may not write
+    read_only: bool,
+}
+
 /// Script command handler
 pub struct ScriptCommands {
     storage: StorageEngine,
-    script_cache: Arc<RwLock<HashMap<String, CachedScript>>>,
+    script_cache: ScriptCache,
+    /// Idle interpreters, reused instead of creating one per script
+    lua_pool: Mutex<Vec<Lua>>,
+    /// Broker publishing the keyspace events of script writes
+    broker: Arc<PubSubBroker>,
 }
 
 impl ScriptCommands {
     pub fn new(storage: StorageEngine) -> Self {
         Self {
             storage,
-            script_cache: Arc::new(RwLock::new(HashMap::new())),
+            script_cache: ScriptCache::new(),
+            lua_pool: Mutex::new(Vec::new()),
+            broker: Arc::new(PubSubBroker::new()),
         }
     }
 
+    /// Publish keyspace events through the server's Pub/Sub broker
+    pub fn set_broker(&mut self, broker: Arc<PubSubBroker>) {
+        self.broker = broker;
+    }
+
+    /// Use a script cache shared with other connections
+    pub fn set_cache(&mut self, cache: ScriptCache) {
+        self.script_cache = cache;
+    }
+
+    /// Get the script cache, whose limits CONFIG reads and changes
+    pub fn cache(&self) -> &ScriptCache {
+        &self.script_cache
+    }
+
     /// Calculate SHA1 hash of a script
-    fn calculate_sha1(script: &str) -> String {
+    fn calculate_sha1(data: impl AsRef<[u8]>) -> String {
         let mut hasher = Sha1::new();
-        hasher.update(script.as_bytes());
+        hasher.update(data);
         format!("{:x}", hasher.finalize())
     }
 
     /// EVAL script numkeys [key [key ...]] [arg [arg ...]]
     /// Execute a Lua script
     pub fn eval(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        self.run_eval("EVAL", args, db_index, false)
+    }
+
+    /// EVAL_RO script numkeys [key [key ...]] [arg [arg ...]]
+    /// Execute a Lua script that may only call read commands
+    pub fn eval_ro(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        self.run_eval("EVAL_RO", args, db_index, true)
+    }
+
+    /// EVALSHA sha1 numkeys [key [key ...]] [arg [arg ...]]
+    /// Execute a cached script by its SHA1 digest
+    pub fn evalsha(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        self.run_evalsha("EVALSHA", args, db_index, false)
+    }
+
+    /// EVALSHA_RO sha1 numkeys [key [key ...]] [arg [arg ...]]
+    /// Execute a cached script that may only call read commands
+    pub fn evalsha_ro(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        self.run_evalsha("EVALSHA_RO", args, db_index, true)
+    }
+
+    fn run_eval(
+        &self,
+        command: &str,
+        args: &[Bytes],
+        db_index: usize,
+        read_only: bool,
+    ) -> Result<RespValue> {
         if args.len() < 2 {
-            return Err(AikvError::WrongArgCount("EVAL".to_string()));
+            return Err(AikvError::WrongArgCount(command.to_string()));
         }
 
+        self.script_cache.check_size(&args[0])?;
         let script = String::from_utf8_lossy(&args[0]).to_string();
         let numkeys: usize = String::from_utf8_lossy(&args[1])
             .parse()
@@ -142,27 +547,30 @@ impl ScriptCommands {
             ));
         }
 
-        let keys: Vec<String> = args[2..2 + numkeys]
-            .iter()
-            .map(|b| String::from_utf8_lossy(b).to_string())
-            .collect();
+        // Keys and arguments reach the script as raw bytes, like any Redis value
+        let keys = &args[2..2 + numkeys];
+        let argv = &args[2 + numkeys..];
 
-        let argv: Vec<String> = args[2 + numkeys..]
-            .iter()
-            .map(|b| String::from_utf8_lossy(b).to_string())
-            .collect();
+        // As in Redis, EVAL caches the script so EVALSHA can run it later
+        self.script_cache
+            .insert(Self::calculate_sha1(&script), &script)?;
 
-        self.execute_script(&script, &keys, &argv, db_index)
+        self.execute_script(&script, keys, argv, db_index, read_only)
     }
 
-    /// EVALSHA sha1 numkeys [key [key ...]] [arg [arg ...]]
-    /// Execute a cached script by its SHA1 digest
-    pub fn evalsha(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+    fn run_evalsha(
+        &self,
+        command: &str,
+        args: &[Bytes],
+        db_index: usize,
+        read_only: bool,
+    ) -> Result<RespValue> {
         if args.len() < 2 {
-            return Err(AikvError::WrongArgCount("EVALSHA".to_string()));
+            return Err(AikvError::WrongArgCount(command.to_string()));
         }
 
-        let sha1 = String::from_utf8_lossy(&args[0]).to_string();
+        // Digests are stored lowercase, as SCRIPT LOAD returns them
+        let sha1 = String::from_utf8_lossy(&args[0]).to_lowercase();
         let numkeys: usize = String::from_utf8_lossy(&args[1])
             .parse()
             .map_err(|_| AikvError::InvalidArgument("numkeys must be a number".to_string()))?;
@@ -174,29 +582,13 @@ impl ScriptCommands {
         }
 
         // Get script from cache
-        let cache = self
-            .script_cache
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let cached_script = cache.get(&sha1).ok_or_else(|| {
-            AikvError::InvalidArgument("NOSCRIPT No matching script. Use EVAL.".to_string())
-        })?;
-
-        let script = cached_script.script.clone();
-        drop(cache);
-
-        let keys: Vec<String> = args[2..2 + numkeys]
-            .iter()
-            .map(|b| String::from_utf8_lossy(b).to_string())
-            .collect();
+        let script = self.script_cache.get(&sha1)?.ok_or(AikvError::NoScript)?;
 
-        let argv: Vec<String> = args[2 + numkeys..]
-            .iter()
-            .map(|b| String::from_utf8_lossy(b).to_string())
-            .collect();
+        // Keys and arguments reach the script as raw bytes, like any Redis value
+        let keys = &args[2..2 + numkeys];
+        let argv = &args[2 + numkeys..];
 
-        self.execute_script(&script, &keys, &argv, db_index)
+        self.execute_script(&script, keys, argv, db_index, read_only)
     }
 
     /// SCRIPT LOAD script
@@ -206,24 +598,29 @@ impl ScriptCommands {
             return Err(AikvError::WrongArgCount("SCRIPT LOAD".to_string()));
         }
 
+        self.script_cache.check_size(&args[0])?;
         let script = String::from_utf8_lossy(&args[0]).to_string();
         let sha1 = Self::calculate_sha1(&script);
 
-        let mut cache = self
-            .script_cache
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        cache.insert(
-            sha1.clone(),
-            CachedScript {
-                script,
-            },
-        );
+        self.script_cache.insert(sha1.clone(), &script)?;
 
         Ok(RespValue::bulk_string(Bytes::from(sha1)))
     }
 
+    /// Check whether SCRIPT `args` would load a new script or flush a non-empty cache
+    pub fn changes_cache(&self, args: &[Bytes]) -> Result<bool> {
+        match args {
+            [subcommand, script] if subcommand.eq_ignore_ascii_case(b"LOAD") => {
+                let sha1 = Self::calculate_sha1(String::from_utf8_lossy(script).as_bytes());
+                Ok(!self.script_cache.contains(&sha1)?)
+            }
+            [subcommand, ..] if subcommand.eq_ignore_ascii_case(b"FLUSH") => {
+                Ok(!self.script_cache.read()?.scripts.is_empty())
+            }
+            _ => Ok(false),
+        }
+    }
+
     /// SCRIPT EXISTS sha1 [sha1 ...]
     /// Check if scripts exist in the cache
     pub fn script_exists(&self, args: &[Bytes]) -> Result<RespValue> {
@@ -231,19 +628,14 @@ impl ScriptCommands {
             return Err(AikvError::WrongArgCount("SCRIPT EXISTS".to_string()));
         }
 
-        let cache = self
-            .script_cache
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let results: Vec<RespValue> = args
+        let results = args
             .iter()
             .map(|sha1_bytes| {
-                let sha1 = String::from_utf8_lossy(sha1_bytes).to_string();
-                let exists = cache.contains_key(&sha1);
-                RespValue::Integer(if exists { 1 } else { 0 })
+                let sha1 = String::from_utf8_lossy(sha1_bytes).to_lowercase();
+                let exists = self.script_cache.contains(&sha1)?;
+                Ok(RespValue::Integer(if exists { 1 } else { 0 }))
             })
-            .collect();
+            .collect::<Result<Vec<_>>>()?;
 
         Ok(RespValue::Array(Some(results)))
     }
@@ -251,140 +643,432 @@ impl ScriptCommands {
     /// SCRIPT FLUSH [ASYNC|SYNC]
     /// Clear the script cache
     pub fn script_flush(&self, _args: &[Bytes]) -> Result<RespValue> {
-        let mut cache = self
-            .script_cache
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        cache.clear();
+        self.script_cache.clear()?;
         Ok(RespValue::simple_string("OK"))
     }
 
     /// SCRIPT KILL
-    /// Kill the currently executing script (not implemented for now)
+    /// Stop the scripts running on any connection.
+    ///
+    /// Killing is always safe, even after a script wrote: its writes are only
+    /// committed once it finishes, so a killed script leaves nothing behind.
     pub fn script_kill(&self, _args: &[Bytes]) -> Result<RespValue> {
-        // In a single-threaded execution model, this is not really applicable
-        // Return NOTBUSY when no script is running
-        Err(AikvError::InvalidArgument(
-            "NOTBUSY No scripts in execution right now.".to_string(),
-        ))
+        if !self.script_cache.kill_running() {
+            return Err(AikvError::InvalidArgument(
+                "NOTBUSY No scripts in execution right now.".to_string(),
+            ));
+        }
+        Ok(RespValue::ok())
     }
 
-    /// Execute a Lua script with given keys and arguments
+    /// Execute a Lua script with given keys and arguments.
+    ///
+    /// A `read_only` script fails any write command it calls.
     fn execute_script(
         &self,
         script: &str,
-        keys: &[String],
-        argv: &[String],
+        keys: &[Bytes],
+        argv: &[Bytes],
         db_index: usize,
+        read_only: bool,
     ) -> Result<RespValue> {
-        // Create transaction context for this script execution
-        let transaction = Arc::new(RwLock::new(ScriptTransaction::new(db_index)));
-
-        // Execute the script in a scope to ensure Lua is dropped before we commit
-        let resp_result = {
-            // Create a new Lua instance with minimal standard library
-            let lua = Lua::new_with(
-                StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8,
-                LuaOptions::default(),
-            )
-            .map_err(|e| AikvError::Script(format!("Failed to create Lua instance: {}", e)))?;
-
-            // Set up KEYS and ARGV tables
-            lua.globals()
-                .set("KEYS", lua.create_table().unwrap())
-                .map_err(|e| AikvError::Script(format!("Failed to set KEYS: {}", e)))?;
-
-            lua.globals()
-                .set("ARGV", lua.create_table().unwrap())
-                .map_err(|e| AikvError::Script(format!("Failed to set ARGV: {}", e)))?;
-
-            // Populate KEYS (1-indexed in Lua)
-            let keys_table = lua.globals().get::<mlua::Table>("KEYS").unwrap();
-            for (i, key) in keys.iter().enumerate() {
-                keys_table.set(i + 1, key.clone()).map_err(|e| {
-                    AikvError::Script(format!("Failed to set KEYS[{}]: {}", i + 1, e))
-                })?;
-            }
+        let lua = self.take_lua()?;
+
+        // Abort the script once it runs out of time or SCRIPT KILL asks it to stop
+        let running = self.script_cache.start_script();
+        let time_limit = self.script_cache.time_limit_ms();
+        let deadline = (time_limit > 0).then(|| Instant::now() + Duration::from_millis(time_limit));
+        let killed = Arc::clone(&self.script_cache.busy_kill);
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(HOOK_INSTRUCTIONS),
+            move |_, _| {
+                if killed.load(Ordering::Relaxed) {
+                    return Err(mlua::Error::RuntimeError(
+                        "ERR Script killed by user with SCRIPT KILL...".to_string(),
+                    ));
+                }
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "ERR Script killed after running for more than {} milliseconds (lua-time-limit)",
+                        time_limit
+                    )));
+                }
+                Ok(VmState::Continue)
+            },
+        );
 
-            // Populate ARGV (1-indexed in Lua)
-            let argv_table = lua.globals().get::<mlua::Table>("ARGV").unwrap();
-            for (i, arg) in argv.iter().enumerate() {
-                argv_table.set(i + 1, arg.clone()).map_err(|e| {
-                    AikvError::Script(format!("Failed to set ARGV[{}]: {}", i + 1, e))
-                })?;
+        // Create transaction context for this script execution, where redis.call finds it
+        lua.set_app_data(ScriptRun {
+            storage: self.storage.clone(),
+            transaction: ScriptTransaction::new(
+                db_index,
+                self.broker.keyspace_events().fires_any(),
+            ),
+            read_only,
+        });
+
+        let result = Self::run_script(&lua, script, keys, argv);
+        drop(running);
+        lua.remove_hook();
+        let run = lua.remove_app_data::<ScriptRun>();
+        self.return_lua(lua);
+
+        // Convert the result first: a failed script is rolled back by dropping the transaction
+        let resp_result = result?;
+        let run = run.ok_or_else(|| AikvError::Script("Script transaction lost".to_string()))?;
+
+        // Script succeeded - commit the transaction, then announce its writes
+        let events = run.transaction.commit(&self.storage)?;
+        CommandExecutor::publish_keyspace_events(&self.broker, events);
+
+        // Return the converted result
+        Ok(resp_result)
+    }
+
+    /// Run a script in a fresh environment of the interpreter.
+    ///
+    /// KEYS, ARGV and any globals the script sets live in the environment and
+    /// are dropped with it, so nothing carries over to the next script.
+    fn run_script(lua: &Lua, script: &str, keys: &[Bytes], argv: &[Bytes]) -> Result<RespValue> {
+        let env = lua
+            .create_table()
+            .map_err(|e| AikvError::Script(format!("Failed to create environment: {}", e)))?;
+
+        // Populate KEYS and ARGV (1-indexed in Lua)
+        for (name, values) in [("KEYS", keys), ("ARGV", argv)] {
+            let populate = || -> mlua::Result<()> {
+                let table = lua.create_table()?;
+                for (i, value) in values.iter().enumerate() {
+                    table.set(i + 1, lua.create_string(value)?)?;
+                }
+                env.set(name, table)
+            };
+            populate().map_err(|e| AikvError::Script(format!("Failed to set {}: {}", name, e)))?;
+        }
+
+        // Everything else is looked up in the shared globals, which stay untouched
+        let globals = lua.globals();
+        let meta = lua
+            .create_table()
+            .and_then(|meta| {
+                meta.set("__index", globals)?;
+                meta.set("__metatable", false)?;
+                env.set("_G", env.clone())?;
+                Ok(meta)
+            })
+            .map_err(|e| AikvError::Script(format!("Failed to set up environment: {}", e)))?;
+        env.set_metatable(Some(meta));
+
+        // Execute the script
+        let result: LuaValue = lua
+            .load(script)
+            .set_environment(env)
+            .eval()
+            .map_err(|e| AikvError::Script(format!("Script execution error: {}", e)))?;
+
+        // Convert Lua result to RespValue while Lua is still alive
+        Self::lua_to_resp(result)
+    }
+
+    /// Take an idle interpreter from the pool, or create one
+    fn take_lua(&self) -> Result<Lua> {
+        let idle = self
+            .lua_pool
+            .lock()
+            .map_err(|e| AikvError::Script(format!("Lock error: {}", e)))?
+            .pop();
+        match idle {
+            Some(lua) => Ok(lua),
+            None => Self::new_lua(),
+        }
+    }
+
+    /// Keep an interpreter for later scripts, unless enough are idle already
+    fn return_lua(&self, lua: Lua) {
+        if let Ok(mut pool) = self.lua_pool.lock() {
+            if pool.len() < LUA_POOL_SIZE {
+                pool.push(lua);
             }
+        }
+    }
 
-            // Set up redis.call and redis.pcall functions
-            let storage = self.storage.clone();
-
-            lua.globals()
-                .set(
-                    "redis",
-                    lua.create_table().map_err(|e| {
-                        AikvError::Script(format!("Failed to create redis table: {}", e))
-                    })?,
-                )
-                .map_err(|e| AikvError::Script(format!("Failed to set redis table: {}", e)))?;
-
-            let redis_table = lua.globals().get::<mlua::Table>("redis").unwrap();
-
-            // redis.call - Execute Redis command (throws error on failure)
-            let storage_for_call = storage.clone();
-            let txn_for_call = transaction.clone();
-            let call_fn = lua
-                .create_function(move |lua_ctx, args: mlua::MultiValue| {
-                    Self::redis_call(&storage_for_call, &txn_for_call, lua_ctx, args, true)
-                })
-                .map_err(|e| AikvError::Script(format!("Failed to create call function: {}", e)))?;
+    /// Create an interpreter with the libraries and the redis table scripts use.
+    ///
+    /// Scripts get read-only views of the libraries, so one script cannot
+    /// change what the next one sees through a pooled interpreter.
+    fn new_lua() -> Result<Lua> {
+        // Create a new Lua instance with minimal standard library
+        let lua = Lua::new_with(
+            StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8,
+            LuaOptions::default(),
+        )
+        .map_err(|e| AikvError::Script(format!("Failed to create Lua instance: {}", e)))?;
+
+        let redis_table = lua
+            .create_table()
+            .map_err(|e| AikvError::Script(format!("Failed to create redis table: {}", e)))?;
+
+        // redis.call - Execute Redis command (throws error on failure)
+        let call_fn = lua
+            .create_function(|lua_ctx, args: mlua::MultiValue| {
+                Self::redis_call(lua_ctx, args, true)
+            })
+            .map_err(|e| AikvError::Script(format!("Failed to create call function: {}", e)))?;
 
-            redis_table
-                .set("call", call_fn)
-                .map_err(|e| AikvError::Script(format!("Failed to set redis.call: {}", e)))?;
-
-            // redis.pcall - Protected call (returns error as result)
-            let storage_for_pcall = storage.clone();
-            let txn_for_pcall = transaction.clone();
-            let pcall_fn = lua
-                .create_function(move |lua_ctx, args: mlua::MultiValue| {
-                    Self::redis_call(&storage_for_pcall, &txn_for_pcall, lua_ctx, args, false)
+        redis_table
+            .set("call", call_fn)
+            .map_err(|e| AikvError::Script(format!("Failed to set redis.call: {}", e)))?;
+
+        // redis.pcall - Protected call (returns error as result)
+        let pcall_fn = lua
+            .create_function(|lua_ctx, args: mlua::MultiValue| {
+                Self::redis_call(lua_ctx, args, false)
+            })
+            .map_err(|e| AikvError::Script(format!("Failed to create pcall function: {}", e)))?;
+
+        redis_table
+            .set("pcall", pcall_fn)
+            .map_err(|e| AikvError::Script(format!("Failed to set redis.pcall: {}", e)))?;
+
+        // redis.sha1hex - SHA1 of a string, as used for EVALSHA
+        let sha1hex_fn = lua
+            .create_function(|_, data: mlua::String| Ok(Self::calculate_sha1(data.as_bytes())))
+            .map_err(|e| AikvError::Script(format!("Failed to create sha1hex function: {}", e)))?;
+
+        redis_table
+            .set("sha1hex", sha1hex_fn)
+            .map_err(|e| AikvError::Script(format!("Failed to set redis.sha1hex: {}", e)))?;
+
+        // redis.status_reply / redis.error_reply - Tables replied as a status or an error
+        for (name, field) in [("status_reply", "ok"), ("error_reply", "err")] {
+            let reply_fn = lua
+                .create_function(move |lua_ctx, message: mlua::String| {
+                    let table = lua_ctx.create_table()?;
+                    table.set(field, message)?;
+                    Ok(table)
                 })
                 .map_err(|e| {
-                    AikvError::Script(format!("Failed to create pcall function: {}", e))
+                    AikvError::Script(format!("Failed to create {} function: {}", name, e))
                 })?;
 
             redis_table
-                .set("pcall", pcall_fn)
-                .map_err(|e| AikvError::Script(format!("Failed to set redis.pcall: {}", e)))?;
-
-            // Execute the script
-            let result: LuaValue = lua
-                .load(script)
-                .eval()
-                .map_err(|e| AikvError::Script(format!("Script execution error: {}", e)))?;
-
-            // Convert Lua result to RespValue while Lua is still alive
-            Self::lua_to_resp(result)?
-            // Lua is dropped here, releasing the Arc references in the closures
-        };
+                .set(name, reply_fn)
+                .map_err(|e| AikvError::Script(format!("Failed to set redis.{}: {}", name, e)))?;
+        }
+
+        let cjson_table = Self::cjson_table(&lua)
+            .map_err(|e| AikvError::Script(format!("Failed to create cjson table: {}", e)))?;
+
+        let globals = lua.globals();
+        globals
+            .set("redis", redis_table)
+            .map_err(|e| AikvError::Script(format!("Failed to set redis table: {}", e)))?;
+        globals
+            .set("cjson", cjson_table)
+            .map_err(|e| AikvError::Script(format!("Failed to set cjson table: {}", e)))?;
+
+        lua.create_table()
+            .and_then(|tables| lua.set_named_registry_value(READ_ONLY_TABLES, tables))
+            .map_err(|e| AikvError::Script(format!("Failed to create registry: {}", e)))?;
+        for name in ["redis", "cjson", "string", "table", "math", "utf8"] {
+            globals
+                .get::<mlua::Table>(name)
+                .and_then(|library| Self::read_only(&lua, library))
+                .and_then(|library| globals.set(name, library))
+                .map_err(|e| AikvError::Script(format!("Failed to protect {}: {}", name, e)))?;
+        }
+
+        // rawset skips __newindex, so it must refuse the proxies itself
+        lua.create_function(
+            |lua_ctx, (table, key, value): (mlua::Table, LuaValue, LuaValue)| {
+                let read_only: mlua::Table = lua_ctx.named_registry_value(READ_ONLY_TABLES)?;
+                if read_only.contains_key(table.clone())? {
+                    return Err(Self::read_only_error());
+                }
+                table.raw_set(key, value)?;
+                Ok(table)
+            },
+        )
+        .and_then(|rawset| globals.set("rawset", rawset))
+        .map_err(|e| AikvError::Script(format!("Failed to set rawset: {}", e)))?;
+
+        // Strings reach the string library through their shared metatable, so
+        // point it at the proxy and hide it from getmetatable('')
+        lua.load("return getmetatable('')")
+            .eval::<mlua::Table>()
+            .and_then(|meta| {
+                meta.set("__index", globals.get::<mlua::Table>("string")?)?;
+                meta.set("__metatable", false)
+            })
+            .map_err(|e| AikvError::Script(format!("Failed to protect strings: {}", e)))?;
+        drop(globals);
 
-        // Script succeeded - commit the transaction
-        // Now that Lua is dropped, we can unwrap the Arc
-        let txn = Arc::try_unwrap(transaction)
-            .map_err(|_| AikvError::Script("Failed to unwrap transaction".to_string()))?
-            .into_inner()
-            .map_err(|e| AikvError::Script(format!("Lock error on commit: {}", e)))?;
+        Ok(lua)
+    }
+
+    /// Build the cjson library: encode, decode and the null sentinel
+    fn cjson_table(lua: &Lua) -> mlua::Result<mlua::Table> {
+        let cjson = lua.create_table()?;
+        cjson.set(
+            "encode",
+            lua.create_function(|_, value: LuaValue| {
+                let json = Self::lua_to_json(&value, 0)?;
+                serde_json::to_string(&json).map_err(|e| {
+                    mlua::Error::RuntimeError(format!("Cannot serialise value: {}", e))
+                })
+            })?,
+        )?;
+        cjson.set(
+            "decode",
+            lua.create_function(|lua_ctx, text: mlua::String| {
+                let json: serde_json::Value = serde_json::from_slice(&text.as_bytes())
+                    .map_err(|e| mlua::Error::RuntimeError(format!("Cannot decode JSON: {}", e)))?;
+                Self::json_to_lua(lua_ctx, json)
+            })?,
+        )?;
+        cjson.set("null", Self::cjson_null())?;
+        Ok(cjson)
+    }
 
-        txn.commit(&self.storage)?;
+    /// cjson.null, which stands for a JSON null inside tables
+    fn cjson_null() -> LuaValue {
+        LuaValue::LightUserData(mlua::LightUserData(std::ptr::null_mut()))
+    }
 
-        // Return the converted result
-        Ok(resp_result)
+    /// Convert a Lua value to JSON for cjson.encode
+    ///
+    /// Tables whose keys are exactly 1..n become arrays and other tables
+    /// objects; an empty table encodes as an object. Numbers keep the integer
+    /// vs float split used for replies.
+    fn lua_to_json(value: &LuaValue, depth: usize) -> mlua::Result<serde_json::Value> {
+        if depth > CJSON_MAX_DEPTH {
+            return Err(mlua::Error::RuntimeError(
+                "Cannot serialise, excessive nesting".to_string(),
+            ));
+        }
+
+        match value {
+            LuaValue::Nil => Ok(serde_json::Value::Null),
+            LuaValue::LightUserData(ud) if ud.0.is_null() => Ok(serde_json::Value::Null),
+            LuaValue::Boolean(b) => Ok(serde_json::Value::Bool(*b)),
+            LuaValue::Integer(i) => Ok(serde_json::Value::from(*i)),
+            LuaValue::Number(n) => {
+                if n.fract() == 0.0 && *n >= i64::MIN as f64 && *n < i64::MAX as f64 {
+                    Ok(serde_json::Value::from(*n as i64))
+                } else {
+                    serde_json::Number::from_f64(*n)
+                        .map(serde_json::Value::Number)
+                        .ok_or_else(|| {
+                            mlua::Error::RuntimeError(
+                                "Cannot serialise number: must not be NaN or Inf".to_string(),
+                            )
+                        })
+                }
+            }
+            LuaValue::String(s) => Ok(serde_json::Value::String(s.to_string_lossy().to_string())),
+            LuaValue::Table(t) => {
+                let mut entries = Vec::new();
+                t.for_each(|key: LuaValue, value: LuaValue| {
+                    entries.push((key, value));
+                    Ok(())
+                })?;
+
+                let len = entries.len() as i64;
+                let is_array = len > 0
+                    && entries.iter().all(
+                        |(key, _)| matches!(key, LuaValue::Integer(i) if (1..=len).contains(i)),
+                    );
+                if is_array {
+                    entries.sort_by_key(|(key, _)| match key {
+                        LuaValue::Integer(i) => *i,
+                        _ => 0,
+                    });
+                    let items = entries
+                        .iter()
+                        .map(|(_, value)| Self::lua_to_json(value, depth + 1))
+                        .collect::<mlua::Result<Vec<_>>>()?;
+                    return Ok(serde_json::Value::Array(items));
+                }
+
+                let mut object = serde_json::Map::new();
+                for (key, value) in &entries {
+                    let key = match key {
+                        LuaValue::String(s) => s.to_string_lossy().to_string(),
+                        LuaValue::Integer(i) => i.to_string(),
+                        LuaValue::Number(n) => n.to_string(),
+                        _ => {
+                            return Err(mlua::Error::RuntimeError(
+                                "Cannot serialise table: table key must be a number or string"
+                                    .to_string(),
+                            ))
+                        }
+                    };
+                    object.insert(key, Self::lua_to_json(value, depth + 1)?);
+                }
+                Ok(serde_json::Value::Object(object))
+            }
+            other => Err(mlua::Error::RuntimeError(format!(
+                "Cannot serialise {}: type not supported",
+                other.type_name()
+            ))),
+        }
+    }
+
+    /// Convert decoded JSON to a Lua value for cjson.decode
+    ///
+    /// Arrays become 1-based sequences and JSON null becomes cjson.null, so
+    /// nulls inside arrays keep their position.
+    fn json_to_lua(lua: &Lua, json: serde_json::Value) -> mlua::Result<LuaValue> {
+        match json {
+            serde_json::Value::Null => Ok(Self::cjson_null()),
+            serde_json::Value::Bool(b) => Ok(LuaValue::Boolean(b)),
+            serde_json::Value::Number(n) => Ok(match n.as_i64() {
+                Some(i) => LuaValue::Integer(i),
+                None => LuaValue::Number(n.as_f64().unwrap_or(f64::NAN)),
+            }),
+            serde_json::Value::String(s) => Ok(LuaValue::String(lua.create_string(&s)?)),
+            serde_json::Value::Array(items) => {
+                let table = lua.create_table_with_capacity(items.len(), 0)?;
+                for (i, item) in items.into_iter().enumerate() {
+                    table.raw_set(i + 1, Self::json_to_lua(lua, item)?)?;
+                }
+                Ok(LuaValue::Table(table))
+            }
+            serde_json::Value::Object(object) => {
+                let table = lua.create_table_with_capacity(0, object.len())?;
+                for (key, value) in object {
+                    table.raw_set(key, Self::json_to_lua(lua, value)?)?;
+                }
+                Ok(LuaValue::Table(table))
+            }
+        }
+    }
+
+    /// Wrap a table in a proxy that can be read but not changed
+    fn read_only(lua: &Lua, table: mlua::Table) -> mlua::Result<mlua::Table> {
+        let meta = lua.create_table()?;
+        meta.set("__index", table)?;
+        meta.set(
+            "__newindex",
+            lua.create_function(|_, _: mlua::MultiValue| -> mlua::Result<()> {
+                Err(Self::read_only_error())
+            })?,
+        )?;
+        meta.set("__metatable", false)?;
+
+        let proxy = lua.create_table()?;
+        proxy.set_metatable(Some(meta));
+        lua.named_registry_value::<mlua::Table>(READ_ONLY_TABLES)?
+            .raw_set(proxy.clone(), true)?;
+        Ok(proxy)
+    }
+
+    fn read_only_error() -> mlua::Error {
+        mlua::Error::RuntimeError("Attempt to modify a readonly table".to_string())
     }
 
     /// Execute a Redis command from Lua
     fn redis_call(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
         lua: &mlua::Lua,
         args: mlua::MultiValue,
         throw_error: bool,
@@ -407,25 +1091,21 @@ impl ScriptCommands {
                     cmd_args.push(Bytes::from(if b { "1" } else { "0" }));
                 }
                 _ => {
-                    if throw_error {
-                        return Err(mlua::Error::RuntimeError(
-                            "Invalid argument type".to_string(),
-                        ));
-                    } else {
-                        return Ok(LuaValue::Nil);
-                    }
+                    return Self::call_error(
+                        lua,
+                        "ERR Lua redis lib command arguments must be strings or integers",
+                        throw_error,
+                    );
                 }
             }
         }
 
         if cmd_args.is_empty() {
-            if throw_error {
-                return Err(mlua::Error::RuntimeError(
-                    "No command specified".to_string(),
-                ));
-            } else {
-                return Ok(LuaValue::Nil);
-            }
+            return Self::call_error(
+                lua,
+                "ERR Please specify at least one argument for this redis lib call",
+                throw_error,
+            );
         }
 
         // Extract command and arguments
@@ -434,135 +1114,84 @@ impl ScriptCommands {
             .to_string();
         let command_args = &cmd_args[1..];
 
-        // Execute simple string commands
-        let result = match command.as_str() {
-            "GET" => Self::execute_get(storage, transaction, command_args),
-            "SET" => Self::execute_set(storage, transaction, command_args),
-            "DEL" => Self::execute_del(storage, transaction, command_args),
-            "EXISTS" => Self::execute_exists(storage, transaction, command_args),
+        // Only commands whose keys can be buffered in the transaction are allowed
+        let info = match lookup_command(&command) {
+            Some(info) if Self::allowed_in_scripts(&info) => info,
+            None if !SCRIPT_DENIED_COMMANDS.contains(&command.as_str()) => {
+                return Self::call_error(
+                    lua,
+                    "ERR Unknown Redis command called from script",
+                    throw_error,
+                );
+            }
             _ => {
-                if throw_error {
-                    return Err(mlua::Error::RuntimeError(format!(
-                        "Command not supported in scripts: {}",
-                        command
-                    )));
-                } else {
-                    return Ok(LuaValue::Nil);
-                }
+                return Self::call_error(
+                    lua,
+                    "ERR This Redis command is not allowed from script",
+                    throw_error,
+                );
             }
         };
 
-        match result {
-            Ok(resp_value) => Self::resp_to_lua(lua, resp_value),
-            Err(e) => {
-                if throw_error {
-                    Err(mlua::Error::RuntimeError(format!(
-                        "Command execution error: {}",
-                        e
-                    )))
-                } else {
-                    Ok(LuaValue::Nil)
-                }
-            }
-        }
-    }
+        let Some(mut run) = lua.app_data_mut::<ScriptRun>() else {
+            return Self::call_error(lua, "ERR redis.call used outside a script", throw_error);
+        };
 
-    /// Execute GET command
-    fn execute_get(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 1 {
-            return Err(AikvError::WrongArgCount("GET".to_string()));
+        if run.read_only && info.has_flag("write") {
+            drop(run);
+            return Self::call_error(
+                lua,
+                "ERR Write commands are not allowed from read-only scripts.",
+                throw_error,
+            );
         }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
 
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        match txn.get(storage, &key)? {
-            Some(value) => Ok(RespValue::bulk_string(value)),
-            None => Ok(RespValue::Null),
-        }
-    }
+        let ScriptRun {
+            storage,
+            transaction,
+            ..
+        } = &mut *run;
+        let result = transaction.execute(storage, &info, command_args);
+        drop(run);
 
-    /// Execute SET command
-    fn execute_set(
-        _storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() < 2 {
-            return Err(AikvError::WrongArgCount("SET".to_string()));
+        match result {
+            Ok(resp_value) => Self::resp_to_lua(lua, resp_value),
+            Err(e) => Self::call_error(lua, &e.reply_message(), throw_error),
         }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let value = args[1].clone();
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        txn.set(key, value);
-        Ok(RespValue::simple_string("OK"))
     }
 
-    /// Execute DEL command
-    fn execute_del(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.is_empty() {
-            return Err(AikvError::WrongArgCount("DEL".to_string()));
+    /// Report a failed redis.call / redis.pcall.
+    ///
+    /// redis.call raises a Lua error carrying the message; redis.pcall returns
+    /// it as an error table `{err = message}` the script can inspect.
+    fn call_error(lua: &mlua::Lua, message: &str, throw_error: bool) -> mlua::Result<LuaValue> {
+        if throw_error {
+            return Err(mlua::Error::RuntimeError(message.to_string()));
         }
 
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let mut count = 0;
-        for arg in args {
-            let key = String::from_utf8_lossy(arg).to_string();
-            // Check if key exists (in buffer or storage)
-            if txn.exists(storage, &key)? {
-                txn.delete(key);
-                count += 1;
-            }
-        }
-        Ok(RespValue::Integer(count))
+        let table = lua.create_table()?;
+        table.set("err", message)?;
+        Ok(LuaValue::Table(table))
     }
 
-    /// Execute EXISTS command
-    fn execute_exists(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.is_empty() {
-            return Err(AikvError::WrongArgCount("EXISTS".to_string()));
+    /// Check whether a command may be called from a script
+    fn allowed_in_scripts(info: &CommandInfo) -> bool {
+        if info.has_flag("noscript") || SCRIPT_DENIED_COMMANDS.contains(&info.name) {
+            return false;
         }
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let mut count = 0;
-        for arg in args {
-            let key = String::from_utf8_lossy(arg).to_string();
-            if txn.exists(storage, &key)? {
-                count += 1;
-            }
+        if info.first_key == 0 && !info.has_flag("movablekeys") {
+            return SCRIPT_KEYLESS_COMMANDS.contains(&info.name);
         }
-        Ok(RespValue::Integer(count))
+        !SCRIPT_CROSS_DB_COMMANDS.contains(&info.name)
     }
 
     /// Convert Lua value to RESP value
     fn lua_to_resp(value: LuaValue) -> Result<RespValue> {
         match value {
             LuaValue::Nil => Ok(RespValue::Null),
-            LuaValue::Boolean(b) => Ok(RespValue::Integer(if b { 1 } else { 0 })),
+            // Lua false is how scripts see a nil reply, so it converts back to nil
+            LuaValue::Boolean(false) => Ok(RespValue::Null),
+            LuaValue::Boolean(true) => Ok(RespValue::Integer(1)),
             LuaValue::Integer(i) => Ok(RespValue::Integer(i)),
             LuaValue::Number(n) => {
                 // Convert float to integer if possible, otherwise to string
@@ -574,6 +1203,15 @@ impl ScriptCommands {
             }
             LuaValue::String(s) => Ok(RespValue::bulk_string(Bytes::from(s.as_bytes().to_vec()))),
             LuaValue::Table(t) => {
+                // An error table (as returned by redis.pcall) becomes an error reply
+                if let Ok(LuaValue::String(err)) = t.get::<LuaValue>("err") {
+                    return Ok(RespValue::Error(err.to_string_lossy().to_string()));
+                }
+                // and a status table (as returned by redis.status_reply) a status reply
+                if let Ok(LuaValue::String(ok)) = t.get::<LuaValue>("ok") {
+                    return Ok(RespValue::SimpleString(ok.to_string_lossy().to_string()));
+                }
+
                 // Convert table to array
                 let mut results = Vec::new();
                 for i in 1..=t.len().unwrap_or(0) {
@@ -625,7 +1263,8 @@ impl ScriptCommands {
                 }
                 None => Ok(LuaValue::Boolean(false)),
             },
-            _ => Ok(LuaValue::Nil),
+            // Scripts see replies in their RESP2 form (maps as flat arrays, ...)
+            other => Self::resp_to_lua(lua, other.into_resp2()),
         }
     }
 }
@@ -806,7 +1445,33 @@ mod tests {
         let args = vec![Bytes::from(sha1), Bytes::from("0")];
 
         let result = script_commands.evalsha(&args, 0);
-        assert!(result.is_err());
+        assert!(matches!(result, Err(AikvError::NoScript)));
+        assert_eq!(
+            result.unwrap_err().to_resp(),
+            RespValue::error("NOSCRIPT No matching script. Use EVAL.")
+        );
+    }
+
+    #[test]
+    fn test_evalsha_uppercase_sha() {
+        let script_commands = setup();
+        let load_args = vec![Bytes::from("return 'upper'")];
+        let sha1 = match script_commands.script_load(&load_args).unwrap() {
+            RespValue::BulkString(Some(sha)) => String::from_utf8_lossy(&sha).to_uppercase(),
+            other => panic!("Expected BulkString, got {:?}", other),
+        };
+
+        let args = vec![Bytes::from(sha1.clone()), Bytes::from("0")];
+        assert_eq!(
+            script_commands.evalsha(&args, 0).unwrap(),
+            RespValue::bulk_string("upper")
+        );
+
+        let args = vec![Bytes::from(sha1)];
+        assert_eq!(
+            script_commands.script_exists(&args).unwrap(),
+            RespValue::array(vec![RespValue::Integer(1)])
+        );
     }
 
     #[test]
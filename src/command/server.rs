@@ -1,12 +1,21 @@
+use crate::command::docs;
+use crate::command::list::BlockedClients;
+use crate::command::notify::KeyspaceEvents;
+use crate::command::script::ScriptCache;
 use crate::error::{AikvError, Result};
-use crate::observability::{LogConfig, SlowQueryLog};
+use crate::observability::{LogConfig, Metrics, ProcessStats, SlowQueryLog};
+use crate::persistence::rdb;
 use crate::protocol::RespValue;
+use crate::server::pubsub::PubSubBroker;
+use crate::storage::StorageEngine;
 use bytes::Bytes;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::{Arc, RwLock};
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
-use tracing::Level;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+use tracing::{error, Level};
 
 /// AiKv version - the actual version of this server
 const AIKV_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -15,12 +24,392 @@ const AIKV_VERSION: &str = env!("CARGO_PKG_VERSION");
 /// We report a modern Redis version to ensure clients like StackExchange.Redis work correctly
 const REDIS_COMPAT_VERSION: &str = "7.2.4";
 
+/// Process-wide server facts reported by INFO server.
+///
+/// The server creates one identity at startup and shares it with every
+/// connection, so `run_id` and uptime are the same whichever client asks.
+#[derive(Clone, Debug)]
+pub struct ServerIdentity {
+    /// Random 40-character hex id of this server run
+    pub run_id: String,
+    /// Time the server started
+    pub start_time: Instant,
+    /// TCP port clients connect to
+    pub tcp_port: u16,
+    /// Absolute path of the configuration file, if one was loaded
+    pub config_file: Option<String>,
+}
+
+impl ServerIdentity {
+    /// Create the identity of a server run starting now
+    pub fn new(tcp_port: u16) -> Self {
+        Self {
+            run_id: generate_run_id(),
+            start_time: Instant::now(),
+            tcp_port,
+            config_file: None,
+        }
+    }
+}
+
+/// Save bookkeeping reported by LASTSAVE and INFO persistence.
+///
+/// Like [`ServerIdentity`], the server shares one with every connection so
+/// writes and saves on any connection are counted together.
+#[derive(Clone, Debug)]
+pub struct SaveState {
+    /// Unix time in seconds of the last SAVE or BGSAVE (or server start)
+    last_save_time: Arc<AtomicU64>,
+    /// Writes since the last SAVE or BGSAVE
+    changes_since_last_save: Arc<AtomicU64>,
+    /// `save` config: autosave after `seconds` once there are `changes` writes
+    save_points: Arc<RwLock<Vec<SavePoint>>>,
+    /// `dir` and `dbfilename` config: where SAVE and BGSAVE write the snapshot
+    snapshot_path: Arc<RwLock<(PathBuf, String)>>,
+    /// Whether a BGSAVE is writing a snapshot
+    bgsave_in_progress: Arc<AtomicBool>,
+    /// Whether the last BGSAVE succeeded
+    last_bgsave_ok: Arc<AtomicBool>,
+}
+
+/// One `seconds changes` pair of the `save` config
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SavePoint {
+    pub seconds: u64,
+    pub changes: u64,
+}
+
+impl SaveState {
+    /// Create the state of a server that has just started.
+    ///
+    /// There are no save points until `save` is configured: the AiDb backend
+    /// persists every write itself, and snapshotting it would copy the whole
+    /// keyspace into memory.
+    pub fn new() -> Self {
+        Self {
+            last_save_time: Arc::new(AtomicU64::new(unix_time_secs())),
+            changes_since_last_save: Arc::new(AtomicU64::new(0)),
+            save_points: Arc::new(RwLock::new(Vec::new())),
+            snapshot_path: Arc::new(RwLock::new((PathBuf::from("."), "dump.rdb".to_string()))),
+            bgsave_in_progress: Arc::new(AtomicBool::new(false)),
+            last_bgsave_ok: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Count a successful write
+    pub fn record_change(&self) {
+        self.changes_since_last_save.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a save at the current time, clearing the change counter
+    pub fn record_save(&self) {
+        self.last_save_time
+            .store(unix_time_secs(), Ordering::SeqCst);
+        self.changes_since_last_save.store(0, Ordering::SeqCst);
+    }
+
+    /// Record a finished BGSAVE, which snapshotted the data once `changes`
+    /// writes had been made since the last save
+    pub fn record_bgsave(&self, changes: u64, ok: bool) {
+        if ok {
+            self.last_save_time
+                .store(unix_time_secs(), Ordering::SeqCst);
+            self.changes_since_last_save
+                .fetch_sub(changes, Ordering::SeqCst);
+        }
+        self.last_bgsave_ok.store(ok, Ordering::SeqCst);
+        self.bgsave_in_progress.store(false, Ordering::SeqCst);
+    }
+
+    /// Mark a BGSAVE as started, unless one is already running
+    pub fn start_bgsave(&self) -> bool {
+        self.bgsave_in_progress
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    /// Whether a BGSAVE is writing a snapshot
+    pub fn bgsave_in_progress(&self) -> bool {
+        self.bgsave_in_progress.load(Ordering::SeqCst)
+    }
+
+    /// Whether the last BGSAVE succeeded
+    pub fn last_bgsave_ok(&self) -> bool {
+        self.last_bgsave_ok.load(Ordering::SeqCst)
+    }
+
+    /// The `dir` config
+    pub fn dir(&self) -> PathBuf {
+        self.snapshot_path
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .0
+            .clone()
+    }
+
+    /// The `dbfilename` config
+    pub fn dbfilename(&self) -> String {
+        self.snapshot_path
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .1
+            .clone()
+    }
+
+    /// The file SAVE and BGSAVE write, `dbfilename` within `dir`
+    pub fn snapshot_path(&self) -> PathBuf {
+        let path = self.snapshot_path.read().unwrap_or_else(|e| e.into_inner());
+        path.0.join(&path.1)
+    }
+
+    /// Change the directory snapshots are written to, which must exist
+    pub fn set_dir(&self, dir: &str) -> Result<()> {
+        if !Path::new(dir).is_dir() {
+            return Err(AikvError::InvalidArgument(
+                "ERR CONFIG SET failed (possibly related to argument 'dir') - No such file or directory".to_string(),
+            ));
+        }
+        self.snapshot_path
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .0 = PathBuf::from(dir);
+        Ok(())
+    }
+
+    /// Change the file name snapshots are written to within `dir`
+    pub fn set_dbfilename(&self, name: &str) -> Result<()> {
+        if name.is_empty() || Path::new(name).file_name() != Some(name.as_ref()) {
+            return Err(AikvError::InvalidArgument(
+                "ERR CONFIG SET failed (possibly related to argument 'dbfilename') - dbfilename can't be a path, just a filename".to_string(),
+            ));
+        }
+        self.snapshot_path
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .1 = name.to_string();
+        Ok(())
+    }
+
+    /// Unix time in seconds of the last save
+    pub fn last_save_time(&self) -> u64 {
+        self.last_save_time.load(Ordering::SeqCst)
+    }
+
+    /// Writes since the last save
+    pub fn changes_since_last_save(&self) -> u64 {
+        self.changes_since_last_save.load(Ordering::Relaxed)
+    }
+
+    /// The `save` config, formatted as CONFIG GET reports it
+    pub fn save_points(&self) -> String {
+        self.save_points
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|point| format!("{} {}", point.seconds, point.changes))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Replace the save points from a `save` config value; an empty one disables autosave
+    pub fn set_save_points(&self, value: &str) -> Result<()> {
+        let numbers = value
+            .split_whitespace()
+            .map(|n| n.parse::<u64>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .ok()
+            .filter(|numbers| numbers.chunks_exact(2).remainder().is_empty())
+            .ok_or_else(|| {
+                AikvError::InvalidArgument(
+                    "ERR CONFIG SET failed (possibly related to argument 'save') - Invalid save parameters".to_string(),
+                )
+            })?;
+        let points = numbers
+            .chunks_exact(2)
+            .map(|pair| SavePoint {
+                seconds: pair[0],
+                changes: pair[1],
+            })
+            .collect();
+        *self.save_points.write().unwrap_or_else(|e| e.into_inner()) = points;
+        Ok(())
+    }
+
+    /// Whether enough writes have piled up since the last save to autosave now
+    pub fn save_point_reached(&self) -> bool {
+        let changes = self.changes_since_last_save();
+        if changes == 0 {
+            return false;
+        }
+        let elapsed = unix_time_secs().saturating_sub(self.last_save_time());
+        self.save_points
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .any(|point| changes >= point.changes && elapsed >= point.seconds)
+    }
+}
+
+impl Default for SaveState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn unix_time_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
 /// Client info structure
 #[derive(Clone, Debug)]
 pub struct ClientInfo {
     pub id: usize,
     pub name: Option<String>,
     pub addr: String,
+    /// Local address the client connected to
+    pub laddr: String,
+    /// When the connection was accepted
+    pub created_at: Instant,
+    /// Last command run, as CLIENT LIST reports it (`NULL` before the first)
+    pub last_command: String,
+    /// Commands run on the connection
+    pub total_commands: u64,
+    /// Wakes the connection when CLIENT KILL closes it
+    pub kill: Arc<Notify>,
+}
+
+/// Connected clients by id.
+///
+/// The server shares one registry with every connection, so CLIENT LIST,
+/// CLIENT KILL and INFO clients see all of them.
+pub type ClientRegistry = Arc<RwLock<HashMap<usize, ClientInfo>>>;
+
+/// Client type as used by CLIENT KILL TYPE
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ClientType {
+    Normal,
+    Pubsub,
+    Master,
+    Replica,
+}
+
+/// Filters of CLIENT KILL; a client is killed when it matches all of them
+#[derive(Debug)]
+struct ClientKillFilter {
+    id: Option<usize>,
+    addr: Option<String>,
+    laddr: Option<String>,
+    client_type: Option<ClientType>,
+    skip_me: bool,
+    max_age: Option<Duration>,
+}
+
+impl ClientKillFilter {
+    /// Parse the `filter value` pairs of the new CLIENT KILL form
+    fn parse(args: &[Bytes]) -> Result<Self> {
+        let pairs = args.chunks_exact(2);
+        if !pairs.remainder().is_empty() {
+            return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
+        }
+
+        let mut filter = Self {
+            id: None,
+            addr: None,
+            laddr: None,
+            client_type: None,
+            skip_me: true,
+            max_age: None,
+        };
+        for pair in pairs {
+            let option = String::from_utf8_lossy(&pair[0]).to_uppercase();
+            let value = String::from_utf8_lossy(&pair[1]).to_string();
+            match option.as_str() {
+                "ID" => {
+                    let id = value.parse::<usize>().ok().filter(|&id| id > 0);
+                    filter.id = Some(id.ok_or_else(|| {
+                        AikvError::InvalidArgument(
+                            "ERR client-id should be greater than 0".to_string(),
+                        )
+                    })?);
+                }
+                "ADDR" => filter.addr = Some(value),
+                "LADDR" => filter.laddr = Some(value),
+                "TYPE" => {
+                    filter.client_type = Some(match value.to_lowercase().as_str() {
+                        "normal" => ClientType::Normal,
+                        "pubsub" => ClientType::Pubsub,
+                        "master" => ClientType::Master,
+                        "replica" | "slave" => ClientType::Replica,
+                        _ => {
+                            return Err(AikvError::InvalidArgument(format!(
+                                "ERR Unknown client type '{}'",
+                                value
+                            )))
+                        }
+                    });
+                }
+                // Every connection is the default user, the only one there is
+                "USER" => {
+                    if value != "default" {
+                        return Err(AikvError::InvalidArgument(format!(
+                            "ERR No such user '{}'",
+                            value
+                        )));
+                    }
+                }
+                "SKIPME" => {
+                    filter.skip_me = match value.to_lowercase().as_str() {
+                        "yes" => true,
+                        "no" => false,
+                        _ => {
+                            return Err(AikvError::InvalidArgument("ERR syntax error".to_string()))
+                        }
+                    };
+                }
+                "MAXAGE" => {
+                    let seconds = value.parse::<u64>().map_err(|_| {
+                        AikvError::InvalidArgument(
+                            "ERR value is not an integer or out of range".to_string(),
+                        )
+                    })?;
+                    filter.max_age = Some(Duration::from_secs(seconds));
+                }
+                _ => return Err(AikvError::InvalidArgument("ERR syntax error".to_string())),
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Whether `client` matches; `client_type` is only computed when filtered on
+    fn matches(
+        &self,
+        client: &ClientInfo,
+        caller_id: usize,
+        client_type: impl FnOnce() -> Result<ClientType>,
+    ) -> Result<bool> {
+        if self.skip_me && client.id == caller_id {
+            return Ok(false);
+        }
+        if self.id.is_some_and(|id| id != client.id)
+            || self.addr.as_ref().is_some_and(|addr| *addr != client.addr)
+            || self
+                .laddr
+                .as_ref()
+                .is_some_and(|laddr| *laddr != client.laddr)
+            || self
+                .max_age
+                .is_some_and(|max_age| client.created_at.elapsed() < max_age)
+        {
+            return Ok(false);
+        }
+        match self.client_type {
+            Some(wanted) => Ok(client_type()? == wanted),
+            None => Ok(true),
+        }
+    }
 }
 
 /// Command information structure for COMMAND command
@@ -38,26 +427,171 @@ pub struct CommandInfo {
     pub last_key: i64,
     /// Key step
     pub step: i64,
+    /// Command group reported by COMMAND DOCS
+    pub group: &'static str,
+    /// Redis version that introduced the command
+    pub since: &'static str,
+    /// One-line summary reported by COMMAND DOCS
+    pub summary: &'static str,
+    /// Arguments in documentation syntax (see [`super::docs`])
+    pub syntax: &'static str,
+}
+
+impl CommandInfo {
+    /// Whether the command carries the given flag
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    /// Extract the keys from a command's arguments (excluding the command name)
+    pub fn extract_keys<'a>(&self, args: &'a [Bytes]) -> Vec<&'a Bytes> {
+        if self.has_flag("movablekeys") {
+            return self.movable_keys(args);
+        }
+
+        let mut keys = Vec::new();
+        if self.first_key == 0 {
+            return keys;
+        }
+
+        let start = (self.first_key - 1) as usize;
+        if self.last_key == -1 {
+            // All remaining args from first_key are keys
+            for (i, arg) in args.iter().enumerate().skip(start) {
+                if self.step == 1 || (i - start) % self.step as usize == 0 {
+                    keys.push(arg);
+                }
+            }
+        } else if self.last_key < 0 {
+            // Keys up to that many arguments from the end, like BLPOP's timeout
+            let end = (args.len() as i64 + self.last_key + 1).max(0) as usize;
+            keys.extend(
+                args.iter()
+                    .take(end)
+                    .skip(start)
+                    .step_by(self.step.max(1) as usize),
+            );
+        } else if self.last_key >= self.first_key {
+            let end = (self.last_key - 1) as usize;
+            for i in
+                (start..=end.min(args.len().saturating_sub(1))).step_by(self.step.max(1) as usize)
+            {
+                if i < args.len() {
+                    keys.push(&args[i]);
+                }
+            }
+        }
+
+        keys
+    }
+
+    /// Extract the keys of a command whose key positions depend on its
+    /// arguments, which first_key/last_key/step cannot describe
+    fn movable_keys<'a>(&self, args: &'a [Bytes]) -> Vec<&'a Bytes> {
+        match self.name {
+            // EVAL script numkeys [key ...] [arg ...]
+            "EVAL" | "EVALSHA" | "EVAL_RO" | "EVALSHA_RO" => {
+                let numkeys = args
+                    .get(1)
+                    .and_then(|n| String::from_utf8_lossy(n).parse::<usize>().ok())
+                    .unwrap_or(0);
+                args.iter().skip(2).take(numkeys).collect()
+            }
+            // SINTERCARD/ZINTERCARD/LMPOP/ZMPOP numkeys key [key ...] ...
+            "SINTERCARD" | "ZINTERCARD" | "LMPOP" | "ZMPOP" => {
+                let numkeys = args
+                    .first()
+                    .and_then(|n| String::from_utf8_lossy(n).parse::<usize>().ok())
+                    .unwrap_or(0);
+                args.iter().skip(1).take(numkeys).collect()
+            }
+            // MIGRATE host port key|"" db timeout [COPY] [REPLACE]
+            //   [AUTH password | AUTH2 username password] [KEYS key ...]
+            "MIGRATE" => {
+                let mut i = 5;
+                while i < args.len() {
+                    match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+                        "AUTH" => i += 1,
+                        "AUTH2" => i += 2,
+                        "KEYS" => return args[i + 1..].iter().collect(),
+                        _ => {}
+                    }
+                    i += 1;
+                }
+                args.get(2).into_iter().collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Parse the arguments of WAIT numreplicas timeout.
+///
+/// Returns the number of replicas to wait for and the timeout, where a
+/// timeout of 0 (block forever) is returned as `None`.
+pub fn parse_wait_args(args: &[Bytes]) -> Result<(usize, Option<Duration>)> {
+    if args.len() != 2 {
+        return Err(AikvError::WrongArgCount("WAIT".to_string()));
+    }
+
+    let numreplicas = String::from_utf8_lossy(&args[0])
+        .parse::<i64>()
+        .map_err(|_| {
+            AikvError::InvalidArgument("ERR value is not an integer or out of range".to_string())
+        })?
+        .max(0) as usize;
+    let timeout = String::from_utf8_lossy(&args[1])
+        .parse::<i64>()
+        .map_err(|_| {
+            AikvError::InvalidArgument("ERR timeout is not an integer or out of range".to_string())
+        })?;
+    if timeout < 0 {
+        return Err(AikvError::InvalidArgument(
+            "ERR timeout is negative".to_string(),
+        ));
+    }
+
+    let timeout = (timeout > 0).then(|| Duration::from_millis(timeout as u64));
+    Ok((numreplicas, timeout))
+}
+
+/// Look up a command's metadata by its uppercase name
+pub fn lookup_command(name: &str) -> Option<CommandInfo> {
+    // Every write command is looked up, so the table is only built once
+    static COMMANDS: OnceLock<HashMap<&'static str, CommandInfo>> = OnceLock::new();
+    COMMANDS
+        .get_or_init(|| {
+            get_command_table()
+                .into_iter()
+                .map(|c| (c.name, c))
+                .collect()
+        })
+        .get(name)
+        .cloned()
 }
 
 /// Server command handler
 pub struct ServerCommands {
-    clients: Arc<RwLock<HashMap<usize, ClientInfo>>>,
+    storage: StorageEngine,
+    clients: ClientRegistry,
     config: Arc<RwLock<HashMap<String, String>>>,
-    start_time: Instant,
-    run_id: String,
-    tcp_port: u16,
+    identity: ServerIdentity,
     current_log_level: Arc<RwLock<Level>>,
     slow_query_log: Arc<SlowQueryLog>,
-    /// Last save timestamp (Unix epoch in seconds)
-    last_save_time: Arc<AtomicU64>,
+    /// Last save time and writes since then
+    save_state: SaveState,
     /// Shutdown flag
     shutdown_requested: Arc<AtomicBool>,
+    /// Server-wide metrics, when running inside a server
+    metrics: Option<Arc<Metrics>>,
+    /// Clients blocked in BLPOP/BRPOP, counted by INFO clients
+    blocked: BlockedClients,
 }
 
 /// All supported commands with their metadata
 fn get_command_table() -> Vec<CommandInfo> {
-    vec![
+    #[allow(unused_mut)]
+    let mut commands = vec![
         // String commands
         CommandInfo {
             name: "GET",
@@ -66,6 +600,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "string",
+            since: "1.0.0",
+            summary: "Returns the string value of a key.",
+            syntax: "key",
         },
         CommandInfo {
             name: "SET",
@@ -74,6 +612,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "string",
+            since: "1.0.0",
+            summary: "Sets the string value of a key, ignoring its type. The key is created if it doesn't exist.",
+            syntax: "key value [NX|XX] [GET] [EX seconds|PX milliseconds|EXAT unix-time-seconds|PXAT unix-time-milliseconds|KEEPTTL]",
         },
         CommandInfo {
             name: "DEL",
@@ -82,6 +624,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: -1,
             step: 1,
+            group: "generic",
+            since: "1.0.0",
+            summary: "Deletes one or more keys.",
+            syntax: "key...",
         },
         CommandInfo {
             name: "EXISTS",
@@ -90,6 +636,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: -1,
             step: 1,
+            group: "generic",
+            since: "1.0.0",
+            summary: "Determines whether one or more keys exist.",
+            syntax: "key...",
         },
         CommandInfo {
             name: "MGET",
@@ -98,6 +648,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: -1,
             step: 1,
+            group: "string",
+            since: "1.0.0",
+            summary: "Atomically returns the string values of one or more keys.",
+            syntax: "key...",
         },
         CommandInfo {
             name: "MSET",
@@ -106,6 +660,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: -1,
             step: 2,
+            group: "string",
+            since: "1.0.1",
+            summary: "Atomically creates or modifies the string values of one or more keys.",
+            syntax: "[key value]...",
         },
         CommandInfo {
             name: "STRLEN",
@@ -114,6 +672,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "string",
+            since: "2.2.0",
+            summary: "Returns the length of a string value.",
+            syntax: "key",
         },
         CommandInfo {
             name: "APPEND",
@@ -122,6 +684,94 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "string",
+            since: "2.0.0",
+            summary: "Appends a string to the value of a key. Creates the key if it doesn't exist.",
+            syntax: "key value",
+        },
+        CommandInfo {
+            name: "SETNX",
+            arity: 3,
+            flags: &["write", "denyoom", "fast"],
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            group: "string",
+            since: "1.0.0",
+            summary: "Set the string value of a key only when the key doesn't exist.",
+            syntax: "key value",
+        },
+        CommandInfo {
+            name: "GETDEL",
+            arity: 2,
+            flags: &["write", "fast"],
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            group: "string",
+            since: "6.2.0",
+            summary: "Returns the string value of a key after deleting the key.",
+            syntax: "key",
+        },
+        CommandInfo {
+            name: "GETEX",
+            arity: -2,
+            flags: &["write", "fast"],
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            group: "string",
+            since: "6.2.0",
+            summary: "Returns the string value of a key after setting its expiration time.",
+            syntax: "key [EX seconds|PX milliseconds|EXAT unix-time-seconds|PXAT unix-time-milliseconds|PERSIST]",
+        },
+        CommandInfo {
+            name: "INCR",
+            arity: 2,
+            flags: &["write", "denyoom", "fast"],
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            group: "string",
+            since: "1.0.0",
+            summary: "Increments the integer value of a key by one. Uses 0 as initial value if the key doesn't exist.",
+            syntax: "key",
+        },
+        CommandInfo {
+            name: "DECR",
+            arity: 2,
+            flags: &["write", "denyoom", "fast"],
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            group: "string",
+            since: "1.0.0",
+            summary: "Decrements the integer value of a key by one. Uses 0 as initial value if the key doesn't exist.",
+            syntax: "key",
+        },
+        CommandInfo {
+            name: "INCRBY",
+            arity: 3,
+            flags: &["write", "denyoom", "fast"],
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            group: "string",
+            since: "1.0.0",
+            summary: "Increments the integer value of a key by a number. Uses 0 as initial value if the key doesn't exist.",
+            syntax: "key increment",
+        },
+        CommandInfo {
+            name: "DECRBY",
+            arity: 3,
+            flags: &["write", "denyoom", "fast"],
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            group: "string",
+            since: "1.0.0",
+            summary: "Decrements a number from the integer value of a key. Uses 0 as initial value if the key doesn't exist.",
+            syntax: "key decrement",
         },
         // JSON commands
         CommandInfo {
@@ -131,6 +781,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "module",
+            since: "1.0.0",
+            summary: "Gets the value at one or more paths in JSON serialized form.",
+            syntax: "key [path]",
         },
         CommandInfo {
             name: "JSON.SET",
@@ -139,6 +793,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "module",
+            since: "1.0.0",
+            summary: "Sets or updates the JSON value at a path.",
+            syntax: "key path value [NX|XX]",
         },
         CommandInfo {
             name: "JSON.DEL",
@@ -147,6 +805,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "module",
+            since: "1.0.0",
+            summary: "Deletes a value.",
+            syntax: "key [path]",
         },
         CommandInfo {
             name: "JSON.TYPE",
@@ -155,6 +817,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "module",
+            since: "1.0.0",
+            summary: "Returns the type of the JSON value at path.",
+            syntax: "key [path]",
         },
         CommandInfo {
             name: "JSON.STRLEN",
@@ -163,6 +829,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "module",
+            since: "1.0.0",
+            summary: "Returns the length of the JSON String at path in key.",
+            syntax: "key [path]",
         },
         CommandInfo {
             name: "JSON.ARRLEN",
@@ -171,6 +841,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "module",
+            since: "1.0.0",
+            summary: "Returns the length of the array at path.",
+            syntax: "key [path]",
         },
         CommandInfo {
             name: "JSON.OBJLEN",
@@ -179,6 +853,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "module",
+            since: "1.0.0",
+            summary: "Returns the number of keys of the object at path.",
+            syntax: "key [path]",
         },
         // List commands
         CommandInfo {
@@ -188,6 +866,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "list",
+            since: "1.0.0",
+            summary: "Prepends one or more elements to a list. Creates the key if it doesn't exist.",
+            syntax: "key element...",
         },
         CommandInfo {
             name: "RPUSH",
@@ -196,6 +878,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "list",
+            since: "1.0.0",
+            summary: "Appends one or more elements to a list. Creates the key if it doesn't exist.",
+            syntax: "key element...",
         },
         CommandInfo {
             name: "LPOP",
@@ -204,6 +890,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "list",
+            since: "1.0.0",
+            summary: "Returns the first elements in a list after removing it. Deletes the list if the last element was popped.",
+            syntax: "key [count]",
         },
         CommandInfo {
             name: "RPOP",
@@ -212,6 +902,34 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "list",
+            since: "1.0.0",
+            summary: "Returns and removes the last elements of a list. Deletes the list if the last element was popped.",
+            syntax: "key [count]",
+        },
+        CommandInfo {
+            name: "BLPOP",
+            arity: -3,
+            flags: &["write", "blocking"],
+            first_key: 1,
+            last_key: -2,
+            step: 1,
+            group: "list",
+            since: "2.0.0",
+            summary: "Removes and returns the first element in a list. Blocks until an element is available otherwise. Deletes the list if the last element was popped.",
+            syntax: "key [key ...] timeout",
+        },
+        CommandInfo {
+            name: "BRPOP",
+            arity: -3,
+            flags: &["write", "blocking"],
+            first_key: 1,
+            last_key: -2,
+            step: 1,
+            group: "list",
+            since: "2.0.0",
+            summary: "Removes and returns the last element in a list. Blocks until an element is available otherwise. Deletes the list if the last element was popped.",
+            syntax: "key [key ...] timeout",
         },
         CommandInfo {
             name: "LLEN",
@@ -220,6 +938,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "list",
+            since: "1.0.0",
+            summary: "Returns the length of a list.",
+            syntax: "key",
         },
         CommandInfo {
             name: "LRANGE",
@@ -228,6 +950,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "list",
+            since: "1.0.0",
+            summary: "Returns a range of elements from a list.",
+            syntax: "key start stop",
         },
         CommandInfo {
             name: "LINDEX",
@@ -236,6 +962,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "list",
+            since: "1.0.0",
+            summary: "Returns an element from a list by its index.",
+            syntax: "key index",
         },
         CommandInfo {
             name: "LSET",
@@ -244,6 +974,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "list",
+            since: "1.0.0",
+            summary: "Sets the value of an element in a list by its index.",
+            syntax: "key index element",
         },
         CommandInfo {
             name: "LREM",
@@ -252,6 +986,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "list",
+            since: "1.0.0",
+            summary: "Removes elements from a list. Deletes the list if the last element was removed.",
+            syntax: "key count element",
         },
         CommandInfo {
             name: "LTRIM",
@@ -260,6 +998,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "list",
+            since: "1.0.0",
+            summary: "Removes elements from both ends a list. Deletes the list if all elements were trimmed.",
+            syntax: "key start stop",
         },
         CommandInfo {
             name: "LINSERT",
@@ -268,6 +1010,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "list",
+            since: "2.2.0",
+            summary: "Inserts an element before or after another element in a list.",
+            syntax: "key BEFORE|AFTER pivot element",
         },
         CommandInfo {
             name: "LMOVE",
@@ -276,6 +1022,22 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 2,
             step: 1,
+            group: "list",
+            since: "6.2.0",
+            summary: "Returns an element after popping it from one list and pushing it to another. Deletes the list if the last element was moved.",
+            syntax: "source destination LEFT|RIGHT LEFT|RIGHT",
+        },
+        CommandInfo {
+            name: "LMPOP",
+            arity: -4,
+            flags: &["write", "movablekeys"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "list",
+            since: "7.0.0",
+            summary: "Returns multiple elements from a list after removing them. Deletes the list if the last element was popped.",
+            syntax: "numkeys key... LEFT|RIGHT [COUNT count]",
         },
         // Hash commands
         CommandInfo {
@@ -285,6 +1047,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "hash",
+            since: "2.0.0",
+            summary: "Creates or modifies the value of a field in a hash.",
+            syntax: "key [field value]...",
         },
         CommandInfo {
             name: "HSETNX",
@@ -293,6 +1059,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "hash",
+            since: "2.0.0",
+            summary: "Sets the value of a field in a hash only when the field doesn't exist.",
+            syntax: "key field value",
         },
         CommandInfo {
             name: "HGET",
@@ -301,6 +1071,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "hash",
+            since: "2.0.0",
+            summary: "Returns the value of a field in a hash.",
+            syntax: "key field",
         },
         CommandInfo {
             name: "HMGET",
@@ -309,6 +1083,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "hash",
+            since: "2.0.0",
+            summary: "Returns the values of all fields in a hash.",
+            syntax: "key field...",
         },
         CommandInfo {
             name: "HMSET",
@@ -317,6 +1095,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "hash",
+            since: "2.0.0",
+            summary: "Sets the values of multiple fields.",
+            syntax: "key [field value]...",
         },
         CommandInfo {
             name: "HDEL",
@@ -325,6 +1107,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "hash",
+            since: "2.0.0",
+            summary: "Deletes one or more fields and their values from a hash. Deletes the hash if no fields remain.",
+            syntax: "key field...",
         },
         CommandInfo {
             name: "HEXISTS",
@@ -333,6 +1119,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "hash",
+            since: "2.0.0",
+            summary: "Determines whether a field exists in a hash.",
+            syntax: "key field",
         },
         CommandInfo {
             name: "HLEN",
@@ -341,6 +1131,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "hash",
+            since: "2.0.0",
+            summary: "Returns the number of fields in a hash.",
+            syntax: "key",
         },
         CommandInfo {
             name: "HKEYS",
@@ -349,6 +1143,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "hash",
+            since: "2.0.0",
+            summary: "Returns all fields in a hash.",
+            syntax: "key",
         },
         CommandInfo {
             name: "HVALS",
@@ -357,6 +1155,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "hash",
+            since: "2.0.0",
+            summary: "Returns all values in a hash.",
+            syntax: "key",
         },
         CommandInfo {
             name: "HGETALL",
@@ -365,6 +1167,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "hash",
+            since: "2.0.0",
+            summary: "Returns all fields and values in a hash.",
+            syntax: "key",
         },
         CommandInfo {
             name: "HINCRBY",
@@ -373,6 +1179,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "hash",
+            since: "2.0.0",
+            summary: "Increments the integer value of a field in a hash by a number. Uses 0 as initial value if the field doesn't exist.",
+            syntax: "key field increment",
         },
         CommandInfo {
             name: "HINCRBYFLOAT",
@@ -381,6 +1191,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "hash",
+            since: "2.6.0",
+            summary: "Increments the floating point value of a field by a number. Uses 0 as initial value if the field doesn't exist.",
+            syntax: "key field increment:double",
         },
         CommandInfo {
             name: "HSCAN",
@@ -389,6 +1203,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "hash",
+            since: "2.8.0",
+            summary: "Iterates over fields and values of a hash.",
+            syntax: "key cursor [MATCH pattern] [COUNT count]",
         },
         // Set commands
         CommandInfo {
@@ -398,6 +1216,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "set",
+            since: "1.0.0",
+            summary: "Adds one or more members to a set. Creates the key if it doesn't exist.",
+            syntax: "key member...",
         },
         CommandInfo {
             name: "SREM",
@@ -406,6 +1228,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "set",
+            since: "1.0.0",
+            summary: "Removes one or more members from a set. Deletes the set if the last member was removed.",
+            syntax: "key member...",
         },
         CommandInfo {
             name: "SISMEMBER",
@@ -414,6 +1240,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "set",
+            since: "1.0.0",
+            summary: "Determines whether a member belongs to a set.",
+            syntax: "key member",
         },
         CommandInfo {
             name: "SMEMBERS",
@@ -422,6 +1252,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "set",
+            since: "1.0.0",
+            summary: "Returns all members of a set.",
+            syntax: "key",
         },
         CommandInfo {
             name: "SCARD",
@@ -430,6 +1264,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "set",
+            since: "1.0.0",
+            summary: "Returns the number of members in a set.",
+            syntax: "key",
         },
         CommandInfo {
             name: "SPOP",
@@ -438,6 +1276,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "set",
+            since: "1.0.0",
+            summary: "Returns one or more random members from a set after removing them. Deletes the set if the last member was popped.",
+            syntax: "key [count]",
         },
         CommandInfo {
             name: "SRANDMEMBER",
@@ -446,6 +1288,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "set",
+            since: "1.0.0",
+            summary: "Get one or multiple random members from a set",
+            syntax: "key [count]",
         },
         CommandInfo {
             name: "SUNION",
@@ -454,6 +1300,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: -1,
             step: 1,
+            group: "set",
+            since: "1.0.0",
+            summary: "Returns the union of multiple sets.",
+            syntax: "key...",
         },
         CommandInfo {
             name: "SINTER",
@@ -462,6 +1312,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: -1,
             step: 1,
+            group: "set",
+            since: "1.0.0",
+            summary: "Returns the intersect of multiple sets.",
+            syntax: "key...",
         },
         CommandInfo {
             name: "SDIFF",
@@ -470,6 +1324,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: -1,
             step: 1,
+            group: "set",
+            since: "1.0.0",
+            summary: "Returns the difference of multiple sets.",
+            syntax: "key...",
         },
         CommandInfo {
             name: "SUNIONSTORE",
@@ -478,6 +1336,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: -1,
             step: 1,
+            group: "set",
+            since: "1.0.0",
+            summary: "Stores the union of multiple sets in a key.",
+            syntax: "destination key...",
         },
         CommandInfo {
             name: "SINTERSTORE",
@@ -486,6 +1348,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: -1,
             step: 1,
+            group: "set",
+            since: "1.0.0",
+            summary: "Stores the intersect of multiple sets in a key.",
+            syntax: "destination key...",
         },
         CommandInfo {
             name: "SDIFFSTORE",
@@ -494,6 +1360,34 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: -1,
             step: 1,
+            group: "set",
+            since: "1.0.0",
+            summary: "Stores the difference of multiple sets in a key.",
+            syntax: "destination key...",
+        },
+        CommandInfo {
+            name: "SINTERCARD",
+            arity: -3,
+            flags: &["readonly", "movablekeys"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "set",
+            since: "7.0.0",
+            summary: "Returns the number of members of the intersect of multiple sets.",
+            syntax: "numkeys key... [LIMIT limit]",
+        },
+        CommandInfo {
+            name: "SMOVE",
+            arity: 4,
+            flags: &["write", "fast"],
+            first_key: 1,
+            last_key: 2,
+            step: 1,
+            group: "set",
+            since: "1.0.0",
+            summary: "Moves a member from one set to another.",
+            syntax: "source destination member",
         },
         // Sorted Set commands
         CommandInfo {
@@ -503,6 +1397,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "sorted-set",
+            since: "1.2.0",
+            summary: "Adds one or more members to a sorted set, or updates their scores. Creates the key if it doesn't exist.",
+            syntax: "key [NX|XX] [GT|LT] [CH] [INCR] [score member]...",
         },
         CommandInfo {
             name: "ZREM",
@@ -511,6 +1409,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "sorted-set",
+            since: "1.2.0",
+            summary: "Removes one or more members from a sorted set. Deletes the sorted set if all members were removed.",
+            syntax: "key member...",
         },
         CommandInfo {
             name: "ZSCORE",
@@ -519,6 +1421,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "sorted-set",
+            since: "1.2.0",
+            summary: "Returns the score of a member in a sorted set.",
+            syntax: "key member",
         },
         CommandInfo {
             name: "ZRANK",
@@ -527,6 +1433,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "sorted-set",
+            since: "2.0.0",
+            summary: "Returns the index of a member in a sorted set ordered by ascending scores.",
+            syntax: "key member",
         },
         CommandInfo {
             name: "ZREVRANK",
@@ -535,6 +1445,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "sorted-set",
+            since: "2.0.0",
+            summary: "Returns the index of a member in a sorted set ordered by descending scores.",
+            syntax: "key member",
         },
         CommandInfo {
             name: "ZRANGE",
@@ -543,6 +1457,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "sorted-set",
+            since: "1.2.0",
+            summary: "Returns members in a sorted set within a range of indexes.",
+            syntax: "key start stop [WITHSCORES]",
         },
         CommandInfo {
             name: "ZREVRANGE",
@@ -551,6 +1469,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "sorted-set",
+            since: "1.2.0",
+            summary: "Returns members in a sorted set within a range of indexes in reverse order.",
+            syntax: "key start stop [WITHSCORES]",
         },
         CommandInfo {
             name: "ZRANGEBYSCORE",
@@ -559,6 +1481,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "sorted-set",
+            since: "1.0.5",
+            summary: "Returns members in a sorted set within a range of scores.",
+            syntax: "key min max [WITHSCORES] [LIMIT offset count]",
         },
         CommandInfo {
             name: "ZREVRANGEBYSCORE",
@@ -567,6 +1493,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "sorted-set",
+            since: "2.2.0",
+            summary: "Returns members in a sorted set within a range of scores in reverse order.",
+            syntax: "key max min [WITHSCORES] [LIMIT offset count]",
         },
         CommandInfo {
             name: "ZCARD",
@@ -575,6 +1505,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "sorted-set",
+            since: "1.2.0",
+            summary: "Returns the number of members in a sorted set.",
+            syntax: "key",
         },
         CommandInfo {
             name: "ZCOUNT",
@@ -583,6 +1517,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "sorted-set",
+            since: "2.0.0",
+            summary: "Returns the count of members in a sorted set that have scores within a range.",
+            syntax: "key min max",
         },
         CommandInfo {
             name: "ZINCRBY",
@@ -591,6 +1529,71 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "sorted-set",
+            since: "1.2.0",
+            summary: "Increments the score of a member in a sorted set.",
+            syntax: "key increment:double member",
+        },
+        CommandInfo {
+            name: "ZINTERCARD",
+            arity: -3,
+            flags: &["readonly", "movablekeys"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "sorted-set",
+            since: "7.0.0",
+            summary: "Returns the number of members of the intersect of multiple sorted sets.",
+            syntax: "numkeys key... [LIMIT limit]",
+        },
+        CommandInfo {
+            name: "ZMPOP",
+            arity: -4,
+            flags: &["write", "movablekeys"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "sorted-set",
+            since: "7.0.0",
+            summary: "Returns the highest- or lowest-scoring members from one or more sorted sets after removing them. Deletes the sorted set if the last member was popped.",
+            syntax: "numkeys key... MIN|MAX [COUNT count]",
+        },
+        // Transaction commands
+        CommandInfo {
+            name: "MULTI",
+            arity: 1,
+            flags: &["noscript", "loading", "stale", "fast"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "transactions",
+            since: "1.2.0",
+            summary: "Starts a transaction.",
+            syntax: "",
+        },
+        CommandInfo {
+            name: "EXEC",
+            arity: 1,
+            flags: &["noscript", "loading", "stale", "skip_slowlog"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "transactions",
+            since: "1.2.0",
+            summary: "Executes all commands in a transaction.",
+            syntax: "",
+        },
+        CommandInfo {
+            name: "DISCARD",
+            arity: 1,
+            flags: &["noscript", "loading", "stale", "fast"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "transactions",
+            since: "2.0.0",
+            summary: "Discards a transaction.",
+            syntax: "",
         },
         // Database commands
         CommandInfo {
@@ -600,6 +1603,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "connection",
+            since: "1.0.0",
+            summary: "Changes the selected database.",
+            syntax: "index",
         },
         CommandInfo {
             name: "DBSIZE",
@@ -608,6 +1615,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "server",
+            since: "1.0.0",
+            summary: "Returns the number of keys in the database.",
+            syntax: "",
         },
         CommandInfo {
             name: "FLUSHDB",
@@ -616,6 +1627,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "server",
+            since: "1.0.0",
+            summary: "Remove all keys from the current database.",
+            syntax: "[ASYNC|SYNC]",
         },
         CommandInfo {
             name: "FLUSHALL",
@@ -624,6 +1639,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "server",
+            since: "1.0.0",
+            summary: "Removes all keys from all databases.",
+            syntax: "[ASYNC|SYNC]",
         },
         CommandInfo {
             name: "SWAPDB",
@@ -632,6 +1651,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "server",
+            since: "4.0.0",
+            summary: "Swaps two Redis databases.",
+            syntax: "index1 index2",
         },
         CommandInfo {
             name: "MOVE",
@@ -640,6 +1663,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "generic",
+            since: "1.0.0",
+            summary: "Moves a key to another database.",
+            syntax: "key db",
         },
         // Key commands
         CommandInfo {
@@ -649,6 +1676,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "generic",
+            since: "1.0.0",
+            summary: "Returns all key names that match a pattern.",
+            syntax: "pattern",
         },
         CommandInfo {
             name: "SCAN",
@@ -657,6 +1688,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "generic",
+            since: "2.8.0",
+            summary: "Iterates over the key names in the database.",
+            syntax: "cursor [MATCH pattern] [COUNT count] [TYPE type]",
         },
         CommandInfo {
             name: "RANDOMKEY",
@@ -665,6 +1700,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "generic",
+            since: "1.0.0",
+            summary: "Returns a random key name from the database.",
+            syntax: "",
         },
         CommandInfo {
             name: "RENAME",
@@ -673,6 +1712,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 2,
             step: 1,
+            group: "generic",
+            since: "1.0.0",
+            summary: "Renames a key and overwrites the destination.",
+            syntax: "key newkey",
         },
         CommandInfo {
             name: "RENAMENX",
@@ -681,6 +1724,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 2,
             step: 1,
+            group: "generic",
+            since: "1.0.0",
+            summary: "Renames a key only when the target key name doesn't exist.",
+            syntax: "key newkey",
         },
         CommandInfo {
             name: "TYPE",
@@ -689,6 +1736,34 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "generic",
+            since: "1.0.0",
+            summary: "Determines the type of value stored at a key.",
+            syntax: "key",
+        },
+        CommandInfo {
+            name: "OBJECT",
+            arity: -2,
+            flags: &["readonly"],
+            first_key: 2,
+            last_key: 2,
+            step: 1,
+            group: "generic",
+            since: "2.2.3",
+            summary: "A container for object introspection commands.",
+            syntax: "subcommand [arg]...",
+        },
+        CommandInfo {
+            name: "MEMORY",
+            arity: -2,
+            flags: &["readonly"],
+            first_key: 2,
+            last_key: 2,
+            step: 1,
+            group: "server",
+            since: "4.0.0",
+            summary: "A container for memory diagnostics commands.",
+            syntax: "subcommand [arg]...",
         },
         CommandInfo {
             name: "COPY",
@@ -697,6 +1772,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 2,
             step: 1,
+            group: "generic",
+            since: "6.2.0",
+            summary: "Copies the value of a key to a new key.",
+            syntax: "source destination [DB destination-db] [REPLACE]",
         },
         CommandInfo {
             name: "DUMP",
@@ -705,6 +1784,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "generic",
+            since: "2.6.0",
+            summary: "Returns a serialized representation of the value stored at a key.",
+            syntax: "key",
         },
         CommandInfo {
             name: "RESTORE",
@@ -713,14 +1796,22 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "generic",
+            since: "2.6.0",
+            summary: "Creates a key from the serialized representation of a value.",
+            syntax: "key ttl serialized-value [REPLACE] [ABSTTL] [IDLETIME seconds] [FREQ frequency]",
         },
         CommandInfo {
             name: "MIGRATE",
             arity: -6,
-            flags: &["write"],
+            flags: &["write", "movablekeys"],
             first_key: 3,
             last_key: 3,
             step: 1,
+            group: "generic",
+            since: "2.6.0",
+            summary: "Atomically transfers a key from one Redis instance to another.",
+            syntax: "host port key destination-db timeout [COPY] [REPLACE] [AUTH password] [AUTH2 username password] [KEYS key...]",
         },
         CommandInfo {
             name: "EXPIRE",
@@ -729,6 +1820,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "generic",
+            since: "1.0.0",
+            summary: "Sets the expiration time of a key in seconds.",
+            syntax: "key seconds [NX|XX|GT|LT]",
         },
         CommandInfo {
             name: "EXPIREAT",
@@ -737,6 +1832,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "generic",
+            since: "1.2.0",
+            summary: "Sets the expiration time of a key to a Unix timestamp.",
+            syntax: "key unix-time-seconds [NX|XX|GT|LT]",
         },
         CommandInfo {
             name: "PEXPIRE",
@@ -745,6 +1844,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "generic",
+            since: "2.6.0",
+            summary: "Sets the expiration time of a key in milliseconds.",
+            syntax: "key milliseconds [NX|XX|GT|LT]",
         },
         CommandInfo {
             name: "PEXPIREAT",
@@ -753,6 +1856,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "generic",
+            since: "2.6.0",
+            summary: "Sets the expiration time of a key to a Unix milliseconds timestamp.",
+            syntax: "key unix-time-milliseconds [NX|XX|GT|LT]",
         },
         CommandInfo {
             name: "TTL",
@@ -761,6 +1868,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "generic",
+            since: "1.0.0",
+            summary: "Returns the expiration time in seconds of a key.",
+            syntax: "key",
         },
         CommandInfo {
             name: "PTTL",
@@ -769,6 +1880,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "generic",
+            since: "2.6.0",
+            summary: "Returns the expiration time in milliseconds of a key.",
+            syntax: "key",
         },
         CommandInfo {
             name: "PERSIST",
@@ -777,6 +1892,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "generic",
+            since: "2.2.0",
+            summary: "Removes the expiration time of a key.",
+            syntax: "key",
         },
         CommandInfo {
             name: "EXPIRETIME",
@@ -785,6 +1904,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "generic",
+            since: "7.0.0",
+            summary: "Returns the expiration time of a key as a Unix timestamp.",
+            syntax: "key",
         },
         CommandInfo {
             name: "PEXPIRETIME",
@@ -793,6 +1916,119 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 1,
             last_key: 1,
             step: 1,
+            group: "generic",
+            since: "7.0.0",
+            summary: "Returns the expiration time of a key as a Unix milliseconds timestamp.",
+            syntax: "key",
+        },
+        // Pub/Sub commands
+        CommandInfo {
+            name: "PUBLISH",
+            arity: 3,
+            flags: &["pubsub", "loading", "stale", "fast"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "pubsub",
+            since: "2.0.0",
+            summary: "Posts a message to a channel.",
+            syntax: "channel message",
+        },
+        CommandInfo {
+            name: "PUBSUB",
+            arity: -2,
+            flags: &["pubsub", "loading", "stale"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "pubsub",
+            since: "2.8.0",
+            summary: "A container for Pub/Sub commands.",
+            syntax: "subcommand [arg]...",
+        },
+        CommandInfo {
+            name: "SUBSCRIBE",
+            arity: -2,
+            flags: &["pubsub", "noscript", "loading", "stale"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "pubsub",
+            since: "2.0.0",
+            summary: "Listens for messages published to channels.",
+            syntax: "channel...",
+        },
+        CommandInfo {
+            name: "UNSUBSCRIBE",
+            arity: -1,
+            flags: &["pubsub", "noscript", "loading", "stale"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "pubsub",
+            since: "2.0.0",
+            summary: "Stops listening to messages posted to channels.",
+            syntax: "[channel]...",
+        },
+        CommandInfo {
+            name: "PSUBSCRIBE",
+            arity: -2,
+            flags: &["pubsub", "noscript", "loading", "stale"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "pubsub",
+            since: "2.0.0",
+            summary: "Listens for messages published to channels that match one or more patterns.",
+            syntax: "pattern...",
+        },
+        CommandInfo {
+            name: "PUNSUBSCRIBE",
+            arity: -1,
+            flags: &["pubsub", "noscript", "loading", "stale"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "pubsub",
+            since: "2.0.0",
+            summary: "Stops listening to messages published to channels that match one or more patterns.",
+            syntax: "[pattern]...",
+        },
+        CommandInfo {
+            name: "SPUBLISH",
+            arity: 3,
+            flags: &["pubsub", "loading", "stale", "fast"],
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            group: "pubsub",
+            since: "7.0.0",
+            summary: "Post a message to a shard channel",
+            syntax: "shardchannel message",
+        },
+        CommandInfo {
+            name: "SSUBSCRIBE",
+            arity: -2,
+            flags: &["pubsub", "noscript", "loading", "stale"],
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            group: "pubsub",
+            since: "7.0.0",
+            summary: "Listens for messages published to shard channels.",
+            syntax: "shardchannel...",
+        },
+        CommandInfo {
+            name: "SUNSUBSCRIBE",
+            arity: -1,
+            flags: &["pubsub", "noscript", "loading", "stale"],
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            group: "pubsub",
+            since: "7.0.0",
+            summary: "Stops listening to messages posted to shard channels.",
+            syntax: "[shardchannel]...",
         },
         // Server commands
         CommandInfo {
@@ -802,6 +2038,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "connection",
+            since: "1.0.0",
+            summary: "Returns the server's liveliness response.",
+            syntax: "[message]",
         },
         CommandInfo {
             name: "ECHO",
@@ -810,6 +2050,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "connection",
+            since: "1.0.0",
+            summary: "Returns the given string.",
+            syntax: "message",
         },
         CommandInfo {
             name: "INFO",
@@ -818,6 +2062,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "server",
+            since: "1.0.0",
+            summary: "Returns information and statistics about the server.",
+            syntax: "[section]...",
         },
         CommandInfo {
             name: "CONFIG",
@@ -826,6 +2074,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "server",
+            since: "2.0.0",
+            summary: "A container for server configuration commands.",
+            syntax: "subcommand [arg]...",
         },
         CommandInfo {
             name: "SLOWLOG",
@@ -834,6 +2086,10 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "server",
+            since: "2.2.12",
+            summary: "A container for slow log commands.",
+            syntax: "subcommand [arg]...",
         },
         CommandInfo {
             name: "TIME",
@@ -842,98 +2098,292 @@ fn get_command_table() -> Vec<CommandInfo> {
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "server",
+            since: "2.6.0",
+            summary: "Returns the server time.",
+            syntax: "",
+        },
+        CommandInfo {
+            name: "CLIENT",
+            arity: -2,
+            flags: &["admin", "stale"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "connection",
+            since: "2.4.0",
+            summary: "A container for client connection commands.",
+            syntax: "subcommand [arg]...",
+        },
+        CommandInfo {
+            name: "COMMAND",
+            arity: -1,
+            flags: &["stale"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "server",
+            since: "2.8.13",
+            summary: "Returns detailed information about all commands.",
+            syntax: "[subcommand] [arg]...",
+        },
+        CommandInfo {
+            name: "SAVE",
+            arity: 1,
+            flags: &["admin"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "server",
+            since: "1.0.0",
+            summary: "Synchronously saves the database(s) to disk.",
+            syntax: "",
+        },
+        CommandInfo {
+            name: "BGSAVE",
+            arity: -1,
+            flags: &["admin"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "server",
+            since: "1.0.0",
+            summary: "Asynchronously saves the database(s) to disk.",
+            syntax: "[SCHEDULE]",
+        },
+        CommandInfo {
+            name: "LASTSAVE",
+            arity: 1,
+            flags: &["fast", "stale"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "server",
+            since: "1.0.0",
+            summary: "Returns the Unix timestamp of the last successful save to disk.",
+            syntax: "",
+        },
+        CommandInfo {
+            name: "SHUTDOWN",
+            arity: -1,
+            flags: &["admin"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "server",
+            since: "1.0.0",
+            summary: "Synchronously saves the database(s) to disk and shuts down the Redis server.",
+            syntax: "[NOSAVE|SAVE] [NOW] [FORCE] [ABORT]",
+        },
+        CommandInfo {
+            name: "WAIT",
+            arity: 3,
+            flags: &["noscript"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "generic",
+            since: "3.0.0",
+            summary: "Blocks until the asynchronous replication of all preceding write commands sent by the connection is completed.",
+            syntax: "numreplicas timeout",
+        },
+        CommandInfo {
+            name: "MONITOR",
+            arity: 1,
+            flags: &["noscript", "admin"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "server",
+            since: "1.0.0",
+            summary: "Listens for all requests received by the server in real-time.",
+            syntax: "",
+        },
+        // Script commands
+        CommandInfo {
+            name: "EVAL",
+            arity: -3,
+            flags: &["noscript", "write", "denyoom", "movablekeys"],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            group: "scripting",
+            since: "2.6.0",
+            summary: "Executes a server-side Lua script.",
+            syntax: "script numkeys [key]... [arg]...",
         },
         CommandInfo {
-            name: "CLIENT",
-            arity: -2,
-            flags: &["admin", "stale"],
+            name: "EVALSHA",
+            arity: -3,
+            flags: &["noscript", "write", "denyoom", "movablekeys"],
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "scripting",
+            since: "2.6.0",
+            summary: "Executes a server-side Lua script by SHA1 digest.",
+            syntax: "sha1 numkeys [key]... [arg]...",
         },
         CommandInfo {
-            name: "COMMAND",
-            arity: -1,
-            flags: &["stale"],
+            name: "EVAL_RO",
+            arity: -3,
+            flags: &["noscript", "readonly", "movablekeys"],
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "scripting",
+            since: "7.0.0",
+            summary: "Executes a read-only server-side Lua script.",
+            syntax: "script numkeys [key]... [arg]...",
         },
         CommandInfo {
-            name: "SAVE",
-            arity: 1,
-            flags: &["admin"],
+            name: "EVALSHA_RO",
+            arity: -3,
+            flags: &["noscript", "readonly", "movablekeys"],
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "scripting",
+            since: "7.0.0",
+            summary: "Executes a read-only server-side Lua script by SHA1 digest.",
+            syntax: "sha1 numkeys [key]... [arg]...",
         },
         CommandInfo {
-            name: "BGSAVE",
-            arity: -1,
-            flags: &["admin"],
+            name: "SCRIPT",
+            arity: -2,
+            flags: &["noscript", "admin"],
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "scripting",
+            since: "2.6.0",
+            summary: "A container for Lua scripts management commands.",
+            syntax: "subcommand [arg]...",
         },
+        // Connection commands
         CommandInfo {
-            name: "LASTSAVE",
-            arity: 1,
-            flags: &["fast", "stale"],
+            name: "AUTH",
+            arity: -2,
+            flags: &["noscript", "loading", "stale", "fast", "no-auth"],
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "connection",
+            since: "1.0.0",
+            summary: "Authenticates the connection.",
+            syntax: "[username] password",
         },
         CommandInfo {
-            name: "SHUTDOWN",
+            name: "HELLO",
             arity: -1,
-            flags: &["admin"],
+            flags: &["fast", "stale"],
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "connection",
+            since: "6.0.0",
+            summary: "Handshakes with the Redis server.",
+            syntax: "[protover] [AUTH username password] [SETNAME clientname]",
         },
         CommandInfo {
-            name: "MONITOR",
+            name: "RESET",
             arity: 1,
-            flags: &["admin"],
+            flags: &["noscript", "loading", "stale", "fast"],
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "connection",
+            since: "6.2.0",
+            summary: "Resets the connection.",
+            syntax: "",
         },
-        // Script commands
         CommandInfo {
-            name: "EVAL",
-            arity: -3,
-            flags: &["write", "denyoom"],
+            name: "QUIT",
+            arity: -1,
+            flags: &["noscript", "loading", "stale", "fast"],
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "connection",
+            since: "1.0.0",
+            summary: "Closes the connection.",
+            syntax: "",
         },
+    ];
+
+    // Cluster commands are only dispatched when the cluster feature is enabled
+    #[cfg(feature = "cluster")]
+    commands.extend([
         CommandInfo {
-            name: "EVALSHA",
-            arity: -3,
-            flags: &["write", "denyoom"],
+            name: "CLUSTER",
+            arity: -2,
+            flags: &["admin"],
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "cluster",
+            since: "3.0.0",
+            summary: "A container for Redis Cluster commands.",
+            syntax: "subcommand [arg]...",
         },
         CommandInfo {
-            name: "SCRIPT",
-            arity: -2,
-            flags: &["admin"],
+            name: "READONLY",
+            arity: 1,
+            flags: &["fast"],
             first_key: 0,
             last_key: 0,
             step: 0,
+            group: "cluster",
+            since: "3.0.0",
+            summary: "Enables read-only queries for a connection to a Redis Cluster replica node.",
+            syntax: "",
         },
-        // Connection commands
         CommandInfo {
-            name: "HELLO",
-            arity: -1,
-            flags: &["fast", "stale"],
+            name: "READWRITE",
+            arity: 1,
+            flags: &["fast"],
             first_key: 0,
             last_key: 0,
             step: 0,
-        },
-    ]
+            group: "cluster",
+            since: "3.0.0",
+            summary: "Enables read-write queries for a connection to a Redis Cluster replica node.",
+            syntax: "",
+        },
+    ]);
+
+    // DEBUG is only compiled into debug builds
+    #[cfg(debug_assertions)]
+    commands.push(CommandInfo {
+        name: "DEBUG",
+        arity: -2,
+        flags: &["admin", "noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "server",
+        since: "1.0.0",
+        summary: "A container for debugging commands.",
+        syntax: "subcommand [arg]...",
+    });
+
+    commands
+}
+
+/// Format a byte count the way INFO does (e.g. `1000.00K`)
+fn bytes_to_human(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["K", "M", "G", "T", "P"];
+
+    if bytes < 1024 {
+        return format!("{}B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2}{}", value, UNITS[unit])
 }
 
 /// Generate a random 40-character hex string for run_id (similar to Redis)
@@ -961,11 +2411,11 @@ fn generate_run_id() -> String {
 }
 
 impl ServerCommands {
-    pub fn new() -> Self {
-        Self::with_port(6379)
+    pub fn new(storage: StorageEngine) -> Self {
+        Self::with_port(storage, 6379)
     }
 
-    pub fn with_port(port: u16) -> Self {
+    pub fn with_port(storage: StorageEngine, port: u16) -> Self {
         let mut default_config = HashMap::new();
         default_config.insert("server".to_string(), "aikv".to_string());
         default_config.insert("version".to_string(), AIKV_VERSION.to_string());
@@ -975,25 +2425,50 @@ impl ServerCommands {
         default_config.insert("slowlog-log-slower-than".to_string(), "10000".to_string());
         default_config.insert("slowlog-max-len".to_string(), "128".to_string());
 
-        // Initialize last_save_time to current time
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-
         Self {
+            storage,
             clients: Arc::new(RwLock::new(HashMap::new())),
             config: Arc::new(RwLock::new(default_config)),
-            start_time: Instant::now(),
-            run_id: generate_run_id(),
-            tcp_port: port,
+            identity: ServerIdentity::new(port),
             current_log_level: Arc::new(RwLock::new(Level::INFO)),
             slow_query_log: Arc::new(SlowQueryLog::new()),
-            last_save_time: Arc::new(AtomicU64::new(now)),
+            save_state: SaveState::new(),
             shutdown_requested: Arc::new(AtomicBool::new(false)),
+            metrics: None,
+            blocked: BlockedClients::new(),
         }
     }
 
+    /// Attach the server metrics reported by INFO stats
+    pub fn set_metrics(&mut self, metrics: Arc<Metrics>) {
+        self.metrics = Some(metrics);
+    }
+
+    /// Share the server's identity so INFO server reports the same run everywhere
+    pub fn set_identity(&mut self, identity: ServerIdentity) {
+        self.identity = identity;
+    }
+
+    /// Share the server's client registry so client commands see every connection
+    pub fn set_client_registry(&mut self, clients: ClientRegistry) {
+        self.clients = clients;
+    }
+
+    /// Share the server's save bookkeeping so every connection counts writes
+    pub fn set_save_state(&mut self, save_state: SaveState) {
+        self.save_state = save_state;
+    }
+
+    /// Share the server's blocked clients so INFO clients counts them all
+    pub fn set_blocked_clients(&mut self, blocked: BlockedClients) {
+        self.blocked = blocked;
+    }
+
+    /// Count a successful write towards rdb_changes_since_last_save
+    pub fn record_change(&self) {
+        self.save_state.record_change();
+    }
+
     /// Get the slow query log
     pub fn slow_query_log(&self) -> Arc<SlowQueryLog> {
         Arc::clone(&self.slow_query_log)
@@ -1009,14 +2484,26 @@ impl ServerCommands {
 
     /// Get server uptime in seconds
     fn uptime_seconds(&self) -> u64 {
-        self.start_time.elapsed().as_secs()
+        self.identity.start_time.elapsed().as_secs()
     }
 
     /// Build the Server section info lines
     fn build_server_info(&self) -> Vec<String> {
         let uptime_secs = self.uptime_seconds();
         let uptime_days = uptime_secs / 86400;
+        let server_time_usec = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros();
         let pid = std::process::id();
+        let executable = std::env::current_exe()
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|_| "aikv".to_string());
+        let mode = if cfg!(feature = "cluster") {
+            "cluster"
+        } else {
+            "standalone"
+        };
 
         vec![
             "# Server".to_string(),
@@ -1024,7 +2511,7 @@ impl ServerCommands {
             "redis_git_sha1:00000000".to_string(),
             "redis_git_dirty:0".to_string(),
             format!("redis_build_id:aikv{}", AIKV_VERSION.replace('.', "")),
-            "redis_mode:standalone".to_string(),
+            format!("redis_mode:{}", mode),
             format!(
                 "os:{} {} {}",
                 std::env::consts::OS,
@@ -1041,15 +2528,19 @@ impl ServerCommands {
             ),
             "multiplexing_api:tokio".to_string(),
             format!("process_id:{}", pid),
-            format!("run_id:{}", self.run_id),
-            format!("tcp_port:{}", self.tcp_port),
+            format!("run_id:{}", self.identity.run_id),
+            format!("tcp_port:{}", self.identity.tcp_port),
+            format!("server_time_usec:{}", server_time_usec),
             format!("uptime_in_seconds:{}", uptime_secs),
             format!("uptime_in_days:{}", uptime_days),
             "hz:10".to_string(),
             "configured_hz:10".to_string(),
             "lru_clock:0".to_string(),
-            "executable:aikv".to_string(),
-            "config_file:".to_string(),
+            format!("executable:{}", executable),
+            format!(
+                "config_file:{}",
+                self.identity.config_file.as_deref().unwrap_or_default()
+            ),
             "io_threads_active:0".to_string(),
             format!("aikv_version:{}", AIKV_VERSION),
         ]
@@ -1069,30 +2560,61 @@ impl ServerCommands {
             "maxclients:10000".to_string(),
             "client_recent_max_input_buffer:0".to_string(),
             "client_recent_max_output_buffer:0".to_string(),
-            "blocked_clients:0".to_string(),
+            format!("blocked_clients:{}", self.blocked.count()),
             "tracking_clients:0".to_string(),
             "clients_in_timeout_table:0".to_string(),
         ])
     }
 
-    /// Build the Memory section info lines
-    fn build_memory_info(&self) -> Vec<String> {
-        vec![
+    /// Build the Memory section info lines.
+    ///
+    /// `used_memory` is the estimated size of the dataset (the sum of MEMORY
+    /// USAGE over every key); `used_memory_rss` comes from the operating system.
+    fn build_memory_info(&self) -> Result<Vec<String>> {
+        let mut used_memory = 0;
+        for db_index in 0..self.storage.db_count()? {
+            used_memory += self.storage.used_memory_in_db(db_index)? as u64;
+        }
+        let rss = ProcessStats::current().used_memory_rss;
+
+        // The peak is tracked server-wide when metrics are attached
+        let peak = match &self.metrics {
+            Some(metrics) => {
+                metrics.memory.set_used_memory(used_memory);
+                metrics.memory.used_memory_peak()
+            }
+            None => used_memory,
+        };
+        let peak_perc = if peak > 0 {
+            used_memory as f64 * 100.0 / peak as f64
+        } else {
+            0.0
+        };
+        let fragmentation_ratio = if used_memory > 0 {
+            rss as f64 / used_memory as f64
+        } else {
+            0.0
+        };
+
+        Ok(vec![
             "# Memory".to_string(),
-            "used_memory:1024000".to_string(),
-            "used_memory_human:1000.00K".to_string(),
-            "used_memory_rss:2048000".to_string(),
-            "used_memory_rss_human:2.00M".to_string(),
-            "used_memory_peak:1024000".to_string(),
-            "used_memory_peak_human:1000.00K".to_string(),
-            "used_memory_peak_perc:100.00%".to_string(),
-            "used_memory_overhead:1000000".to_string(),
-            "used_memory_startup:1000000".to_string(),
-            "used_memory_dataset:24000".to_string(),
-            "used_memory_dataset_perc:0.00%".to_string(),
-            "allocator_allocated:1024000".to_string(),
-            "allocator_active:2048000".to_string(),
-            "allocator_resident:2048000".to_string(),
+            format!("used_memory:{}", used_memory),
+            format!("used_memory_human:{}", bytes_to_human(used_memory)),
+            format!("used_memory_rss:{}", rss),
+            format!("used_memory_rss_human:{}", bytes_to_human(rss)),
+            format!("used_memory_peak:{}", peak),
+            format!("used_memory_peak_human:{}", bytes_to_human(peak)),
+            format!("used_memory_peak_perc:{:.2}%", peak_perc),
+            "used_memory_overhead:0".to_string(),
+            "used_memory_startup:0".to_string(),
+            format!("used_memory_dataset:{}", used_memory),
+            format!(
+                "used_memory_dataset_perc:{}",
+                if used_memory > 0 { "100.00%" } else { "0.00%" }
+            ),
+            format!("allocator_allocated:{}", used_memory),
+            format!("allocator_active:{}", rss),
+            format!("allocator_resident:{}", rss),
             "total_system_memory:8589934592".to_string(),
             "total_system_memory_human:8.00G".to_string(),
             "used_memory_lua:31744".to_string(),
@@ -1108,8 +2630,11 @@ impl ServerCommands {
             "allocator_rss_bytes:0".to_string(),
             "rss_overhead_ratio:1.00".to_string(),
             "rss_overhead_bytes:0".to_string(),
-            "mem_fragmentation_ratio:2.00".to_string(),
-            "mem_fragmentation_bytes:1024000".to_string(),
+            format!("mem_fragmentation_ratio:{:.2}", fragmentation_ratio),
+            format!(
+                "mem_fragmentation_bytes:{}",
+                rss.saturating_sub(used_memory)
+            ),
             "mem_not_counted_for_evict:0".to_string(),
             "mem_replication_backlog:0".to_string(),
             "mem_clients_slaves:0".to_string(),
@@ -1119,12 +2644,12 @@ impl ServerCommands {
             "active_defrag_running:0".to_string(),
             "lazyfree_pending_objects:0".to_string(),
             "lazyfreed_objects:0".to_string(),
-        ]
+        ])
     }
 
     /// Build the Stats section info lines
     fn build_stats_info(&self) -> Vec<String> {
-        vec![
+        let mut lines = vec![
             "# Stats".to_string(),
             "total_connections_received:1".to_string(),
             "total_commands_processed:1".to_string(),
@@ -1146,6 +2671,7 @@ impl ServerCommands {
             "keyspace_misses:0".to_string(),
             "pubsub_channels:0".to_string(),
             "pubsub_patterns:0".to_string(),
+            "total_messages_published:0".to_string(),
             "latest_fork_usec:0".to_string(),
             "total_forks:0".to_string(),
             "migrate_cached_sockets:0".to_string(),
@@ -1164,14 +2690,28 @@ impl ServerCommands {
             "total_writes_processed:1".to_string(),
             "io_threaded_reads_processed:0".to_string(),
             "io_threaded_writes_processed:0".to_string(),
-        ]
+        ];
+
+        // Replace the placeholders with live values when metrics are attached
+        if let Some(metrics) = &self.metrics {
+            let stats: HashMap<String, String> = metrics.get_stats_info().into_iter().collect();
+            for line in lines.iter_mut().skip(1) {
+                let field = line.split(':').next().unwrap_or_default();
+                if let Some(value) = stats.get(field) {
+                    *line = format!("{}:{}", field, value);
+                }
+            }
+        }
+
+        lines
     }
 
     /// Build the Replication section info lines
-    fn build_replication_info(&self) -> Vec<String> {
+    fn build_replication_info(&self, replica: bool) -> Vec<String> {
+        let role = if replica { "slave" } else { "master" };
         vec![
             "# Replication".to_string(),
-            "role:master".to_string(),
+            format!("role:{}", role),
             "connected_slaves:0".to_string(),
             "master_failover_state:no-failover".to_string(),
             "master_replid:0000000000000000000000000000000000000000".to_string(),
@@ -1187,12 +2727,13 @@ impl ServerCommands {
 
     /// Build the CPU section info lines
     fn build_cpu_info(&self) -> Vec<String> {
+        let stats = ProcessStats::current();
         vec![
             "# CPU".to_string(),
-            "used_cpu_sys:0.000000".to_string(),
-            "used_cpu_user:0.000000".to_string(),
-            "used_cpu_sys_children:0.000000".to_string(),
-            "used_cpu_user_children:0.000000".to_string(),
+            format!("used_cpu_sys:{:.6}", stats.used_cpu_sys),
+            format!("used_cpu_user:{:.6}", stats.used_cpu_user),
+            format!("used_cpu_sys_children:{:.6}", stats.used_cpu_sys_children),
+            format!("used_cpu_user_children:{:.6}", stats.used_cpu_user_children),
             "used_cpu_sys_main_thread:0.000000".to_string(),
             "used_cpu_user_main_thread:0.000000".to_string(),
         ]
@@ -1203,9 +2744,23 @@ impl ServerCommands {
         vec!["# Modules".to_string()]
     }
 
-    /// Build the Errorstats section info lines
+    /// Build the Errorstats section info lines, one per error code replied
     fn build_errorstats_info(&self) -> Vec<String> {
-        vec!["# Errorstats".to_string()]
+        let mut lines = vec!["# Errorstats".to_string()];
+        if let Some(metrics) = &self.metrics {
+            let mut counts: Vec<(String, u64)> = metrics
+                .commands
+                .error_replies_by_code()
+                .into_iter()
+                .collect();
+            counts.sort();
+            lines.extend(
+                counts
+                    .into_iter()
+                    .map(|(code, count)| format!("errorstat_{}:count={}", code, count)),
+            );
+        }
+        lines
     }
 
     /// Build the Cluster section info lines
@@ -1221,8 +2776,23 @@ impl ServerCommands {
     }
 
     /// Build the Keyspace section info lines
-    fn build_keyspace_info(&self) -> Vec<String> {
-        vec!["# Keyspace".to_string()]
+    fn build_keyspace_info(&self) -> Result<Vec<String>> {
+        let mut lines = vec!["# Keyspace".to_string()];
+
+        // Only databases holding keys are listed, like Redis
+        for db_index in 0..self.storage.db_count()? {
+            let keys = self.storage.dbsize_in_db(db_index)?;
+            if keys == 0 {
+                continue;
+            }
+            let expires = self.storage.expires_count_in_db(db_index)?;
+            lines.push(format!(
+                "db{}:keys={},expires={},avg_ttl=0",
+                db_index, keys, expires
+            ));
+        }
+
+        Ok(lines)
     }
 
     /// Build the Persistence section info lines
@@ -1235,10 +2805,23 @@ impl ServerCommands {
             "current_fork_perc:0.00".to_string(),
             "current_save_keys_processed:0".to_string(),
             "current_save_keys_total:0".to_string(),
-            "rdb_changes_since_last_save:0".to_string(),
-            "rdb_bgsave_in_progress:0".to_string(),
-            "rdb_last_save_time:0".to_string(),
-            "rdb_last_bgsave_status:ok".to_string(),
+            format!(
+                "rdb_changes_since_last_save:{}",
+                self.save_state.changes_since_last_save()
+            ),
+            format!(
+                "rdb_bgsave_in_progress:{}",
+                u8::from(self.save_state.bgsave_in_progress())
+            ),
+            format!("rdb_last_save_time:{}", self.save_state.last_save_time()),
+            format!(
+                "rdb_last_bgsave_status:{}",
+                if self.save_state.last_bgsave_ok() {
+                    "ok"
+                } else {
+                    "err"
+                }
+            ),
             "rdb_last_bgsave_time_sec:-1".to_string(),
             "rdb_current_bgsave_time_sec:-1".to_string(),
             "rdb_last_cow_size:0".to_string(),
@@ -1256,7 +2839,9 @@ impl ServerCommands {
     }
 
     /// INFO \[section\] - Get server information
-    pub fn info(&self, args: &[Bytes]) -> Result<RespValue> {
+    ///
+    /// `replica` selects the role reported in the Replication section.
+    pub fn info(&self, args: &[Bytes], replica: bool) -> Result<RespValue> {
         let section = if args.is_empty() {
             "default"
         } else {
@@ -1274,19 +2859,19 @@ impl ServerCommands {
                 info_lines.push(String::new());
                 info_lines.extend(self.build_clients_info()?);
                 info_lines.push(String::new());
-                info_lines.extend(self.build_memory_info());
+                info_lines.extend(self.build_memory_info()?);
                 info_lines.push(String::new());
                 info_lines.extend(self.build_persistence_info());
                 info_lines.push(String::new());
                 info_lines.extend(self.build_stats_info());
                 info_lines.push(String::new());
-                info_lines.extend(self.build_replication_info());
+                info_lines.extend(self.build_replication_info(replica));
                 info_lines.push(String::new());
                 info_lines.extend(self.build_cpu_info());
                 info_lines.push(String::new());
                 info_lines.extend(self.build_cluster_info());
                 info_lines.push(String::new());
-                info_lines.extend(self.build_keyspace_info());
+                info_lines.extend(self.build_keyspace_info()?);
             }
             "server" => {
                 info_lines.extend(self.build_server_info());
@@ -1295,13 +2880,13 @@ impl ServerCommands {
                 info_lines.extend(self.build_clients_info()?);
             }
             "memory" => {
-                info_lines.extend(self.build_memory_info());
+                info_lines.extend(self.build_memory_info()?);
             }
             "stats" => {
                 info_lines.extend(self.build_stats_info());
             }
             "replication" => {
-                info_lines.extend(self.build_replication_info());
+                info_lines.extend(self.build_replication_info(replica));
             }
             "cpu" => {
                 info_lines.extend(self.build_cpu_info());
@@ -1316,7 +2901,7 @@ impl ServerCommands {
                 info_lines.extend(self.build_cluster_info());
             }
             "keyspace" => {
-                info_lines.extend(self.build_keyspace_info());
+                info_lines.extend(self.build_keyspace_info()?);
             }
             "persistence" => {
                 info_lines.extend(self.build_persistence_info());
@@ -1327,13 +2912,13 @@ impl ServerCommands {
                 info_lines.push(String::new());
                 info_lines.extend(self.build_clients_info()?);
                 info_lines.push(String::new());
-                info_lines.extend(self.build_memory_info());
+                info_lines.extend(self.build_memory_info()?);
                 info_lines.push(String::new());
                 info_lines.extend(self.build_persistence_info());
                 info_lines.push(String::new());
                 info_lines.extend(self.build_stats_info());
                 info_lines.push(String::new());
-                info_lines.extend(self.build_replication_info());
+                info_lines.extend(self.build_replication_info(replica));
                 info_lines.push(String::new());
                 info_lines.extend(self.build_cpu_info());
                 info_lines.push(String::new());
@@ -1343,7 +2928,7 @@ impl ServerCommands {
                 info_lines.push(String::new());
                 info_lines.extend(self.build_cluster_info());
                 info_lines.push(String::new());
-                info_lines.extend(self.build_keyspace_info());
+                info_lines.extend(self.build_keyspace_info()?);
             }
             // For unknown sections, return empty result (Redis behavior)
             // This is important for compatibility - Redis returns empty for unknown sections
@@ -1357,35 +2942,80 @@ impl ServerCommands {
     }
 
     /// CONFIG GET parameter - Get configuration value
-    pub fn config_get(&self, args: &[Bytes]) -> Result<RespValue> {
+    ///
+    /// `notify-keyspace-events` lives in the server-wide Pub/Sub broker, the
+    /// Lua script limits in the server-wide script cache, `save` in the
+    /// server-wide save state, and the encoding thresholds in the storage.
+    pub fn config_get(
+        &self,
+        args: &[Bytes],
+        broker: &PubSubBroker,
+        scripts: &ScriptCache,
+    ) -> Result<RespValue> {
         if args.len() != 1 {
             return Err(AikvError::WrongArgCount("CONFIG GET".to_string()));
         }
 
         let parameter = String::from_utf8_lossy(&args[0]).to_string();
-        let config = self
+        let mut config = self
             .config
             .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?
+            .clone();
+        config.insert(
+            "notify-keyspace-events".to_string(),
+            broker.keyspace_events().to_string(),
+        );
+        config.insert(
+            "lua-max-script-size".to_string(),
+            scripts.max_script_size().to_string(),
+        );
+        config.insert(
+            "lua-script-cache-size".to_string(),
+            scripts.max_cache_size().to_string(),
+        );
+        config.insert(
+            "lua-time-limit".to_string(),
+            scripts.time_limit_ms().to_string(),
+        );
+        config.insert("save".to_string(), self.save_state.save_points());
+        config.insert(
+            "dir".to_string(),
+            self.save_state.dir().to_string_lossy().into_owned(),
+        );
+        config.insert("dbfilename".to_string(), self.save_state.dbfilename());
+        for (name, value) in self.storage.encoding_thresholds()?.parameters() {
+            config.insert(name.to_string(), value);
+        }
 
         let mut results = Vec::new();
 
         // Support wildcard matching
         if parameter == "*" {
             for (key, value) in config.iter() {
-                results.push(RespValue::bulk_string(key.clone()));
-                results.push(RespValue::bulk_string(value.clone()));
+                results.push((
+                    RespValue::bulk_string(key.clone()),
+                    RespValue::bulk_string(value.clone()),
+                ));
             }
         } else if let Some(value) = config.get(&parameter) {
-            results.push(RespValue::bulk_string(parameter.clone()));
-            results.push(RespValue::bulk_string(value.clone()));
+            results.push((
+                RespValue::bulk_string(parameter.clone()),
+                RespValue::bulk_string(value.clone()),
+            ));
         }
 
-        Ok(RespValue::array(results))
+        // A map for RESP3 clients; RESP2 connections flatten it to an array
+        Ok(RespValue::map(results))
     }
 
     /// CONFIG SET parameter value - Set configuration value
-    pub fn config_set(&self, args: &[Bytes]) -> Result<RespValue> {
+    pub fn config_set(
+        &self,
+        args: &[Bytes],
+        broker: &PubSubBroker,
+        scripts: &ScriptCache,
+    ) -> Result<RespValue> {
         if args.len() != 2 {
             return Err(AikvError::WrongArgCount("CONFIG SET".to_string()));
         }
@@ -1423,6 +3053,52 @@ impl ServerCommands {
                     ));
                 }
             }
+        } else if param_lower == "notify-keyspace-events" {
+            // Shared by every connection, so kept by the broker rather than in config
+            let events = KeyspaceEvents::parse(&value).ok_or_else(|| {
+                AikvError::InvalidArgument(
+                    "ERR CONFIG SET failed (possibly related to argument 'notify-keyspace-events') - Invalid event class character. Use 'Ag$lshzxeKEtmdn'.".to_string(),
+                )
+            })?;
+            broker.set_keyspace_events(events);
+            return Ok(RespValue::ok());
+        } else if param_lower == "lua-max-script-size" || param_lower == "lua-script-cache-size" {
+            // Shared by every connection, so kept by the script cache
+            let size = value.parse::<usize>().map_err(|_| {
+                AikvError::InvalidArgument(format!(
+                    "ERR CONFIG SET failed (possibly related to argument '{}') - argument couldn't be parsed into an integer",
+                    param_lower
+                ))
+            })?;
+            if param_lower == "lua-max-script-size" {
+                scripts.set_max_script_size(size);
+            } else {
+                scripts.set_max_cache_size(size)?;
+            }
+            return Ok(RespValue::ok());
+        } else if param_lower == "lua-time-limit" {
+            // Shared by every connection, so kept by the script cache
+            let limit = value.parse::<u64>().map_err(|_| {
+                AikvError::InvalidArgument(
+                    "ERR CONFIG SET failed (possibly related to argument 'lua-time-limit') - argument couldn't be parsed into an integer".to_string(),
+                )
+            })?;
+            scripts.set_time_limit_ms(limit);
+            return Ok(RespValue::ok());
+        } else if param_lower == "save" {
+            // Shared by every connection and the autosave task, so kept in the save state
+            self.save_state.set_save_points(&value)?;
+            return Ok(RespValue::ok());
+        } else if param_lower == "dir" {
+            // Shared with BGSAVE and the autosave task, so kept in the save state
+            self.save_state.set_dir(&value)?;
+            return Ok(RespValue::ok());
+        } else if param_lower == "dbfilename" {
+            self.save_state.set_dbfilename(&value)?;
+            return Ok(RespValue::ok());
+        } else if self.storage.set_encoding_threshold(&param_lower, &value)? {
+            // Kept by the storage, which classifies encodings as values are written
+            return Ok(RespValue::ok());
         } else if param_lower == "slowlog-max-len" {
             // Update slow query max length
             match value.parse::<usize>() {
@@ -1533,6 +3209,19 @@ impl ServerCommands {
         ]))
     }
 
+    /// Format a client as a line of CLIENT LIST
+    fn format_client(client: &ClientInfo) -> String {
+        let name = client
+            .name
+            .as_ref()
+            .map(|n| format!(" name={}", n))
+            .unwrap_or_default();
+        format!(
+            "id={} addr={}{} tot-cmds={} cmd={}",
+            client.id, client.addr, name, client.total_commands, client.last_command
+        )
+    }
+
     /// CLIENT LIST - List all client connections
     pub fn client_list(&self, _args: &[Bytes]) -> Result<RespValue> {
         let clients = self
@@ -1540,26 +3229,80 @@ impl ServerCommands {
             .read()
             .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
 
-        let mut client_lines = Vec::new();
-        for (id, client) in clients.iter() {
-            let name = client
-                .name
-                .as_ref()
-                .map(|n| format!(" name={}", n))
-                .unwrap_or_default();
-            client_lines.push(format!("id={} addr={}{}", id, client.addr, name));
-        }
+        let client_lines: Vec<String> = clients.values().map(Self::format_client).collect();
 
         let client_str = client_lines.join("\n");
         Ok(RespValue::bulk_string(client_str))
     }
 
+    /// CLIENT INFO - Describe the calling connection, as a CLIENT LIST line
+    pub fn client_info(&self, args: &[Bytes], client_id: usize) -> Result<RespValue> {
+        if !args.is_empty() {
+            return Err(AikvError::WrongArgCount("CLIENT INFO".to_string()));
+        }
+
+        let clients = self
+            .clients
+            .read()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+
+        let line = clients
+            .get(&client_id)
+            .map(Self::format_client)
+            .unwrap_or_default();
+        Ok(RespValue::bulk_string(line))
+    }
+
+    /// Count a command against a client and remember it as its last one.
+    ///
+    /// Container commands are named with their subcommand, as in Redis
+    /// (`client|list`); names are lowercase.
+    pub fn record_client_command(
+        &self,
+        client_id: usize,
+        command: &str,
+        subcommand: Option<&[u8]>,
+    ) -> Result<()> {
+        let command = command.to_lowercase();
+        let is_container = lookup_command(&command.to_uppercase())
+            .is_some_and(|info| info.syntax.starts_with("subcommand"));
+        let name = match subcommand {
+            Some(subcommand) if is_container => {
+                format!(
+                    "{}|{}",
+                    command,
+                    String::from_utf8_lossy(subcommand).to_lowercase()
+                )
+            }
+            _ => command,
+        };
+
+        let mut clients = self
+            .clients
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+
+        if let Some(client) = clients.get_mut(&client_id) {
+            client.last_command = name;
+            client.total_commands += 1;
+        }
+        Ok(())
+    }
+
     /// CLIENT SETNAME name - Set client name
     pub fn client_setname(&self, args: &[Bytes], client_id: usize) -> Result<RespValue> {
         if args.len() != 1 {
             return Err(AikvError::WrongArgCount("CLIENT SETNAME".to_string()));
         }
 
+        // Names are restricted to printable ASCII without spaces, like Redis
+        if args[0].iter().any(|&b| !(b'!'..=b'~').contains(&b)) {
+            return Err(AikvError::InvalidArgument(
+                "ERR Client names cannot contain spaces, newlines or special characters."
+                    .to_string(),
+            ));
+        }
+
         let name = String::from_utf8_lossy(&args[0]).to_string();
 
         let mut clients = self
@@ -1568,7 +3311,8 @@ impl ServerCommands {
             .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
 
         if let Some(client) = clients.get_mut(&client_id) {
-            client.name = Some(name);
+            // An empty name clears the connection name
+            client.name = if name.is_empty() { None } else { Some(name) };
         }
 
         Ok(RespValue::ok())
@@ -1587,25 +3331,105 @@ impl ServerCommands {
             }
         }
 
-        Ok(RespValue::null_bulk_string())
+        // A connection without a name reports an empty name
+        Ok(RespValue::bulk_string(""))
+    }
+
+    /// CLIENT KILL ip:port
+    /// CLIENT KILL \[ID id\] \[ADDR ip:port\] \[LADDR ip:port\] \[TYPE normal|pubsub|master|replica\]
+    /// \[USER name\] \[SKIPME yes|no\] \[MAXAGE seconds\]
+    ///
+    /// Killed clients leave the registry at once and their connections close.
+    pub fn client_kill(
+        &self,
+        args: &[Bytes],
+        client_id: usize,
+        broker: &PubSubBroker,
+    ) -> Result<RespValue> {
+        if args.is_empty() {
+            return Err(AikvError::WrongArgCount("CLIENT KILL".to_string()));
+        }
+
+        // The old form kills one address, the caller included, and replies OK
+        if args.len() == 1 {
+            let filter = ClientKillFilter {
+                id: None,
+                addr: Some(String::from_utf8_lossy(&args[0]).to_string()),
+                laddr: None,
+                client_type: None,
+                skip_me: false,
+                max_age: None,
+            };
+            return match self.kill_clients(&filter, client_id, broker)? {
+                0 => Err(AikvError::InvalidArgument("ERR No such client".to_string())),
+                _ => Ok(RespValue::ok()),
+            };
+        }
+
+        let filter = ClientKillFilter::parse(args)?;
+        let killed = self.kill_clients(&filter, client_id, broker)?;
+        Ok(RespValue::integer(killed as i64))
+    }
+
+    /// Remove the clients matching `filter` and wake their connections to close
+    fn kill_clients(
+        &self,
+        filter: &ClientKillFilter,
+        client_id: usize,
+        broker: &PubSubBroker,
+    ) -> Result<usize> {
+        let mut clients = self
+            .clients
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+
+        let mut matched = Vec::new();
+        for client in clients.values() {
+            // AiKv has no replication, so every client is normal or pubsub
+            let client_type = || {
+                let subscriptions = broker.subscription_count(client.id)?
+                    + broker.shard_subscription_count(client.id)?;
+                Ok(if subscriptions > 0 {
+                    ClientType::Pubsub
+                } else {
+                    ClientType::Normal
+                })
+            };
+            if filter.matches(client, client_id, client_type)? {
+                matched.push(client.id);
+            }
+        }
+
+        for id in &matched {
+            if let Some(client) = clients.remove(id) {
+                client.kill.notify_one();
+            }
+        }
+        Ok(matched.len())
     }
 
-    /// Register a client
-    pub fn register_client(&self, id: usize, addr: String) -> Result<()> {
+    /// Register a client, returning the signal CLIENT KILL wakes it with
+    pub fn register_client(&self, id: usize, addr: String, laddr: String) -> Result<Arc<Notify>> {
         let mut clients = self
             .clients
             .write()
             .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
 
+        let kill = Arc::new(Notify::new());
         clients.insert(
             id,
             ClientInfo {
                 id,
                 name: None,
                 addr,
+                laddr,
+                created_at: Instant::now(),
+                last_command: "NULL".to_string(),
+                total_commands: 0,
+                kill: Arc::clone(&kill),
             },
         );
-        Ok(())
+        Ok(kill)
     }
 
     /// Unregister a client
@@ -1693,64 +3517,62 @@ impl ServerCommands {
         Ok(RespValue::array(result))
     }
 
-    /// COMMAND DOCS - Get command documentation (simplified)
+    /// Format a single command's documentation for COMMAND DOCS
+    fn format_command_docs(&self, cmd: &CommandInfo) -> (RespValue, RespValue) {
+        let mut docs = vec![
+            (
+                RespValue::bulk_string("summary"),
+                RespValue::bulk_string(cmd.summary),
+            ),
+            (
+                RespValue::bulk_string("since"),
+                RespValue::bulk_string(cmd.since),
+            ),
+            (
+                RespValue::bulk_string("group"),
+                RespValue::bulk_string(cmd.group),
+            ),
+            (
+                RespValue::bulk_string("arity"),
+                RespValue::integer(cmd.arity),
+            ),
+        ];
+        let arguments = docs::arguments(cmd.syntax);
+        if !arguments.is_empty() {
+            docs.push((
+                RespValue::bulk_string("arguments"),
+                RespValue::array(arguments),
+            ));
+        }
+
+        (
+            RespValue::bulk_string(cmd.name.to_lowercase()),
+            RespValue::map(docs),
+        )
+    }
+
+    /// COMMAND DOCS [command ...] - Get command documentation
     fn command_docs(&self, args: &[Bytes]) -> Result<RespValue> {
+        let commands = get_command_table();
         if args.is_empty() {
-            // Return docs for all commands (simplified)
-            let commands = get_command_table();
-            let result: Vec<(RespValue, RespValue)> = commands
-                .iter()
-                .map(|cmd| {
-                    (
-                        RespValue::bulk_string(cmd.name.to_lowercase()),
-                        RespValue::map(vec![
-                            (
-                                RespValue::bulk_string("summary"),
-                                RespValue::bulk_string(format!("{} command", cmd.name)),
-                            ),
-                            (
-                                RespValue::bulk_string("since"),
-                                RespValue::bulk_string("1.0.0"),
-                            ),
-                            (
-                                RespValue::bulk_string("group"),
-                                RespValue::bulk_string("generic"),
-                            ),
-                        ]),
-                    )
-                })
-                .collect();
-            return Ok(RespValue::map(result));
+            return Ok(RespValue::map(
+                commands
+                    .iter()
+                    .map(|cmd| self.format_command_docs(cmd))
+                    .collect(),
+            ));
         }
 
-        // Return docs for specific commands
-        let commands = get_command_table();
+        // Unknown commands are left out of the reply
         let command_map: HashMap<&str, &CommandInfo> =
             commands.iter().map(|c| (c.name, c)).collect();
-
         let result: Vec<(RespValue, RespValue)> = args
             .iter()
             .filter_map(|arg| {
                 let name = String::from_utf8_lossy(arg).to_uppercase();
-                command_map.get(name.as_str()).map(|cmd| {
-                    (
-                        RespValue::bulk_string(cmd.name.to_lowercase()),
-                        RespValue::map(vec![
-                            (
-                                RespValue::bulk_string("summary"),
-                                RespValue::bulk_string(format!("{} command", cmd.name)),
-                            ),
-                            (
-                                RespValue::bulk_string("since"),
-                                RespValue::bulk_string("1.0.0"),
-                            ),
-                            (
-                                RespValue::bulk_string("group"),
-                                RespValue::bulk_string("generic"),
-                            ),
-                        ]),
-                    )
-                })
+                command_map
+                    .get(name.as_str())
+                    .map(|cmd| self.format_command_docs(cmd))
             })
             .collect();
 
@@ -1764,39 +3586,28 @@ impl ServerCommands {
         }
 
         let cmd_name = String::from_utf8_lossy(&args[0]).to_uppercase();
-        let commands = get_command_table();
 
-        let cmd = commands.iter().find(|c| c.name == cmd_name.as_str());
-
-        match cmd {
+        match lookup_command(&cmd_name) {
             Some(cmd_info) => {
-                if cmd_info.first_key == 0 {
-                    return Ok(RespValue::array(vec![]));
+                let argc = args.len() as i64;
+                if (cmd_info.arity > 0 && argc != cmd_info.arity)
+                    || (cmd_info.arity < 0 && argc < -cmd_info.arity)
+                {
+                    return Err(AikvError::InvalidArgument(
+                        "ERR Invalid number of arguments specified for command".to_string(),
+                    ));
                 }
 
-                let cmd_args = &args[1..];
-                let mut keys = Vec::new();
-
-                if cmd_info.last_key == -1 {
-                    // All remaining args from first_key are keys
-                    let start = (cmd_info.first_key - 1) as usize;
-                    for (i, arg) in cmd_args.iter().enumerate().skip(start) {
-                        if cmd_info.step == 1 || (i - start) % cmd_info.step as usize == 0 {
-                            keys.push(RespValue::bulk_string(arg.clone()));
-                        }
-                    }
-                } else if cmd_info.last_key >= cmd_info.first_key {
-                    let start = (cmd_info.first_key - 1) as usize;
-                    let end = (cmd_info.last_key - 1) as usize;
-                    for i in (start..=end.min(cmd_args.len().saturating_sub(1)))
-                        .step_by(cmd_info.step.max(1) as usize)
-                    {
-                        if i < cmd_args.len() {
-                            keys.push(RespValue::bulk_string(cmd_args[i].clone()));
-                        }
-                    }
+                let keys: Vec<RespValue> = cmd_info
+                    .extract_keys(&args[1..])
+                    .into_iter()
+                    .map(|key| RespValue::bulk_string(key.clone()))
+                    .collect();
+                if keys.is_empty() {
+                    return Err(AikvError::InvalidArgument(
+                        "ERR The command has no key arguments".to_string(),
+                    ));
                 }
-
                 Ok(RespValue::array(keys))
             }
             None => Err(AikvError::InvalidCommand(format!(
@@ -1832,40 +3643,85 @@ impl ServerCommands {
         Ok(RespValue::ok())
     }
 
+    /// CONFIG RESETSTAT - Reset the statistics reported by INFO
+    pub fn config_resetstat(&self, args: &[Bytes]) -> Result<RespValue> {
+        if !args.is_empty() {
+            return Err(AikvError::WrongArgCount("CONFIG RESETSTAT".to_string()));
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.reset_stats();
+        }
+        Ok(RespValue::ok())
+    }
+
     /// SAVE - Synchronously save the dataset to disk
-    /// Note: This is a stub implementation. Actual persistence is handled by the storage engine.
-    pub fn save(&self, _args: &[Bytes]) -> Result<RespValue> {
-        // Update last save time
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        self.last_save_time.store(now, Ordering::SeqCst);
+    ///
+    /// Writes an RDB snapshot of every database and the cached scripts to
+    /// `dbfilename` in `dir`.
+    pub fn save(&self, _args: &[Bytes], scripts: &ScriptCache) -> Result<RespValue> {
+        if self.save_state.bgsave_in_progress() {
+            return Err(AikvError::InvalidArgument(
+                "ERR Background save already in progress".to_string(),
+            ));
+        }
 
-        // In AiKv with memory storage, there's no actual persistence
-        // With AiDb storage, persistence is automatic via LSM-Tree
+        let databases = rdb::snapshot_storage(&self.storage)?;
+        rdb::save_snapshot(
+            self.save_state.snapshot_path(),
+            &databases,
+            &scripts.scripts()?,
+        )?;
+        self.save_state.record_save();
         Ok(RespValue::ok())
     }
 
     /// BGSAVE - Asynchronously save the dataset to disk
-    /// Note: This is a stub implementation. Actual persistence is handled by the storage engine.
-    pub fn bgsave(&self, _args: &[Bytes]) -> Result<RespValue> {
-        // Update last save time
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        self.last_save_time.store(now, Ordering::SeqCst);
+    ///
+    /// The values are copied here, so the snapshot holds the data as it was
+    /// when BGSAVE ran, and written to the RDB file on a separate thread.
+    pub fn bgsave(&self, _args: &[Bytes], scripts: &ScriptCache) -> Result<RespValue> {
+        if !self.save_state.start_bgsave() {
+            return Err(AikvError::InvalidArgument(
+                "ERR Background save already in progress".to_string(),
+            ));
+        }
+
+        let changes = self.save_state.changes_since_last_save();
+        let snapshot = rdb::snapshot_storage(&self.storage)
+            .and_then(|databases| Ok((databases, scripts.scripts()?)));
+        let (databases, scripts) = match snapshot {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                self.save_state.record_bgsave(changes, false);
+                return Err(e);
+            }
+        };
+        let path = self.save_state.snapshot_path();
+        let save_state = self.save_state.clone();
+        std::thread::spawn(move || {
+            let result = rdb::save_snapshot(&path, &databases, &scripts);
+            if let Err(e) = &result {
+                error!("Background save to {} failed: {}", path.display(), e);
+            }
+            save_state.record_bgsave(changes, result.is_ok());
+        });
 
-        // In AiKv, background save is simulated
-        // With AiDb storage, persistence is automatic via LSM-Tree
         Ok(RespValue::simple_string("Background saving started"))
     }
 
     /// LASTSAVE - Get the Unix timestamp of the last successful save
     pub fn lastsave(&self, _args: &[Bytes]) -> Result<RespValue> {
-        let last_save = self.last_save_time.load(Ordering::SeqCst);
-        Ok(RespValue::integer(last_save as i64))
+        Ok(RespValue::integer(self.save_state.last_save_time() as i64))
+    }
+
+    /// WAIT numreplicas timeout - Wait for replicas to acknowledge earlier writes
+    ///
+    /// A standalone server has no replicas, so this validates the arguments and
+    /// returns 0 at once. In cluster mode the connection answers WAIT from the
+    /// Raft replication state instead.
+    pub fn wait(&self, args: &[Bytes]) -> Result<RespValue> {
+        parse_wait_args(args)?;
+        Ok(RespValue::integer(0))
     }
 
     /// SHUTDOWN - Shut down the server
@@ -1916,9 +3772,3 @@ impl ServerCommands {
         self.shutdown_requested.load(Ordering::SeqCst)
     }
 }
-
-impl Default for ServerCommands {
-    fn default() -> Self {
-        Self::new()
-    }
-}
@@ -1,3 +1,5 @@
+use crate::command::numkeys;
+use crate::command::random::CommandRng;
 use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
 use crate::storage::{StorageEngine, StoredValue};
@@ -7,12 +9,14 @@ use std::collections::HashSet;
 /// Set command handler
 pub struct SetCommands {
     storage: StorageEngine,
+    rng: CommandRng,
 }
 
 impl SetCommands {
-    pub fn new(storage: StorageEngine) -> Self {
+    pub fn new(storage: StorageEngine, rng: CommandRng) -> Self {
         Self {
             storage,
+            rng,
         }
     }
 
@@ -106,6 +110,49 @@ impl SetCommands {
         Ok(RespValue::Integer(if is_member { 1 } else { 0 }))
     }
 
+    /// SMOVE source destination member
+    /// Move member from the set at source to the set at destination
+    pub fn smove(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        if args.len() != 3 {
+            return Err(AikvError::WrongArgCount("SMOVE".to_string()));
+        }
+
+        let source = String::from_utf8_lossy(&args[0]).to_string();
+        let dest = String::from_utf8_lossy(&args[1]).to_string();
+        let member = args[2].to_vec();
+
+        let Some(source_stored) = self.storage.get_value(db_index, &source)? else {
+            return Ok(RespValue::Integer(0));
+        };
+        let mut source_set = source_stored.as_set()?.clone();
+        // Both keys must hold sets, even when the member is missing
+        let dest_set = match self.storage.get_value(db_index, &dest)? {
+            Some(stored) => Some(stored.as_set()?.clone()),
+            None => None,
+        };
+
+        if source == dest {
+            return Ok(RespValue::Integer(source_set.contains(&member) as i64));
+        }
+        if !source_set.remove(&member) {
+            return Ok(RespValue::Integer(0));
+        }
+
+        if source_set.is_empty() {
+            self.storage.delete_from_db(db_index, &source)?;
+        } else {
+            self.storage
+                .set_value(db_index, source, StoredValue::new_set(source_set))?;
+        }
+
+        let mut dest_set = dest_set.unwrap_or_default();
+        dest_set.insert(member);
+        self.storage
+            .set_value(db_index, dest, StoredValue::new_set(dest_set))?;
+
+        Ok(RespValue::Integer(1))
+    }
+
     /// SMEMBERS key
     /// Returns all the members of the set value stored at key
     pub fn smembers(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
@@ -168,7 +215,13 @@ impl SetCommands {
         if let Some(stored) = self.storage.get_value(db_index, &key)? {
             let mut set = stored.as_set()?.clone();
 
-            let to_remove: Vec<Vec<u8>> = set.iter().take(count).cloned().collect();
+            let candidates = Self::sorted_members(&set);
+            let to_remove: Vec<Vec<u8>> = self
+                .rng
+                .sample(candidates.len(), count)
+                .into_iter()
+                .map(|i| candidates[i].clone())
+                .collect();
             for member in to_remove {
                 set.remove(&member);
                 members.push(Bytes::from(member));
@@ -210,11 +263,20 @@ impl SetCommands {
             1
         };
 
-        let members = if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let set = stored.as_set()?;
-            set.iter()
-                .take(count.unsigned_abs() as usize)
-                .map(|v| Bytes::from(v.clone()))
+        let members: Vec<Bytes> = if let Some(stored) = self.storage.get_value(db_index, &key)? {
+            let candidates = Self::sorted_members(stored.as_set()?);
+            let amount = count.unsigned_abs() as usize;
+            let picked = if count < 0 && !candidates.is_empty() {
+                // A negative count may return the same member more than once
+                (0..amount)
+                    .map(|_| self.rng.index(candidates.len()))
+                    .collect()
+            } else {
+                self.rng.sample(candidates.len(), amount)
+            };
+            picked
+                .into_iter()
+                .map(|i| Bytes::from(candidates[i].clone()))
                 .collect()
         } else {
             Vec::new()
@@ -231,6 +293,13 @@ impl SetCommands {
         }
     }
 
+    /// Members in a stable order, so a seeded RNG picks the same ones every run
+    fn sorted_members(set: &HashSet<Vec<u8>>) -> Vec<&Vec<u8>> {
+        let mut members: Vec<&Vec<u8>> = set.iter().collect();
+        members.sort();
+        members
+    }
+
     /// SUNION key [key ...]
     /// Returns the members of the set resulting from the union of all the given sets
     pub fn sunion(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
@@ -296,6 +365,42 @@ impl SetCommands {
         )))
     }
 
+    /// SINTERCARD numkeys key [key ...] \[LIMIT limit\]
+    /// Returns the number of members in the intersection of the given sets,
+    /// counting no further than limit when it is not 0
+    pub fn sintercard(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        let (keys, limit) = numkeys::parse_intercard("SINTERCARD", args)?;
+
+        // Every key is type checked before a missing one empties the result
+        let mut sets = Vec::with_capacity(keys.len());
+        for key in keys {
+            let key = String::from_utf8_lossy(key).to_string();
+            match self.storage.get_value(db_index, &key)? {
+                Some(stored) => sets.push(Some(stored.as_set()?.clone())),
+                None => sets.push(None),
+            }
+        }
+        let Some(mut sets) = sets.into_iter().collect::<Option<Vec<_>>>() else {
+            return Ok(RespValue::Integer(0));
+        };
+
+        sets.sort_by_key(|set| set.len());
+        let Some((smallest, others)) = sets.split_first() else {
+            return Ok(RespValue::Integer(0));
+        };
+        let mut count = 0;
+        for member in smallest {
+            if others.iter().all(|set| set.contains(member)) {
+                count += 1;
+                if count == limit {
+                    break;
+                }
+            }
+        }
+
+        Ok(RespValue::Integer(count as i64))
+    }
+
     /// SDIFF key [key ...]
     /// Returns the members of the set resulting from the difference between the first set and all the successive sets
     pub fn sdiff(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
@@ -332,6 +437,19 @@ impl SetCommands {
         )))
     }
 
+    /// Store the result of a *STORE command, deleting the destination if it
+    /// is empty as Redis never keeps empty sets
+    fn store(&self, db_index: usize, dest: String, set: HashSet<Vec<u8>>) -> Result<usize> {
+        let count = set.len();
+        if set.is_empty() {
+            self.storage.delete_from_db(db_index, &dest)?;
+        } else {
+            self.storage
+                .replace_value(db_index, dest, StoredValue::new_set(set))?;
+        }
+        Ok(count)
+    }
+
     /// SUNIONSTORE destination key [key ...]
     /// Store the members of the set resulting from the union of all the given sets
     pub fn sunionstore(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
@@ -353,9 +471,7 @@ impl SetCommands {
             }
         }
 
-        let count = result.len();
-        self.storage
-            .set_value(db_index, dest, StoredValue::new_set(result))?;
+        let count = self.store(db_index, dest, result)?;
 
         Ok(RespValue::Integer(count as i64))
     }
@@ -390,10 +506,7 @@ impl SetCommands {
             }
         }
 
-        let final_set = result.unwrap_or_default();
-        let count = final_set.len();
-        self.storage
-            .set_value(db_index, dest, StoredValue::new_set(final_set))?;
+        let count = self.store(db_index, dest, result.unwrap_or_default())?;
 
         Ok(RespValue::Integer(count as i64))
     }
@@ -427,9 +540,7 @@ impl SetCommands {
             }
         }
 
-        let count = result.len();
-        self.storage
-            .set_value(db_index, dest, StoredValue::new_set(result))?;
+        let count = self.store(db_index, dest, result)?;
 
         Ok(RespValue::Integer(count as i64))
     }
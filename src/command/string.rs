@@ -1,7 +1,8 @@
 use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
-use crate::storage::StorageEngine;
+use crate::storage::{StorageEngine, StoredValue, ValueType};
 use bytes::Bytes;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// String command handler
 pub struct StringCommands {
@@ -29,7 +30,11 @@ impl StringCommands {
         }
     }
 
-    /// SET key value \[EX seconds\] \[PX milliseconds\] \[NX|XX\]
+    /// SET key value \[NX|XX\] \[GET\] \[EX seconds|PX milliseconds|EXAT timestamp|PXAT ms-timestamp|KEEPTTL\]
+    ///
+    /// Without an expiration option the key loses any TTL it had; KEEPTTL keeps it.
+    /// With GET the old value (or nil) is returned instead of OK, even when NX or
+    /// XX prevented the write.
     pub fn set(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
         if args.len() < 2 {
             return Err(AikvError::WrongArgCount("SET".to_string()));
@@ -42,66 +47,99 @@ impl StringCommands {
         let mut i = 2;
         let mut nx = false;
         let mut xx = false;
-        let mut expire_ms: Option<u64> = None;
+        let mut keep_ttl = false;
+        let mut get = false;
+        // Absolute expiration time in milliseconds
+        let mut expire_at: Option<u64> = None;
 
         while i < args.len() {
             let option = String::from_utf8_lossy(&args[i]).to_uppercase();
             match option.as_str() {
                 "NX" => nx = true,
                 "XX" => xx = true,
-                "EX" => {
-                    // Set expiration in seconds
-                    if i + 1 >= args.len() {
+                "GET" => get = true,
+                "KEEPTTL" => {
+                    if expire_at.is_some() {
                         return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
                     }
-                    i += 1;
-                    let seconds_str = String::from_utf8_lossy(&args[i]);
-                    let seconds = seconds_str.parse::<u64>().map_err(|_| {
-                        AikvError::InvalidArgument("ERR value is not an integer".to_string())
-                    })?;
-                    expire_ms = Some(seconds * 1000);
+                    keep_ttl = true;
                 }
-                "PX" => {
-                    // Set expiration in milliseconds
-                    if i + 1 >= args.len() {
+                "EX" | "PX" | "EXAT" | "PXAT" => {
+                    // Only one expiration option may be given
+                    if expire_at.is_some() || keep_ttl || i + 1 >= args.len() {
                         return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
                     }
                     i += 1;
-                    let ms_str = String::from_utf8_lossy(&args[i]);
-                    let ms = ms_str.parse::<u64>().map_err(|_| {
-                        AikvError::InvalidArgument("ERR value is not an integer".to_string())
-                    })?;
-                    expire_ms = Some(ms);
+                    let amount =
+                        String::from_utf8_lossy(&args[i])
+                            .parse::<i64>()
+                            .map_err(|_| {
+                                AikvError::InvalidArgument(
+                                    "ERR value is not an integer or out of range".to_string(),
+                                )
+                            })?;
+                    if amount <= 0 {
+                        return Err(AikvError::InvalidArgument(
+                            "ERR invalid expire time in 'set' command".to_string(),
+                        ));
+                    }
+
+                    let amount = amount as u64;
+                    let now_ms = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis() as u64;
+                    expire_at = Some(match option.as_str() {
+                        "EX" => now_ms.saturating_add(amount.saturating_mul(1000)),
+                        "PX" => now_ms.saturating_add(amount),
+                        "EXAT" => amount.saturating_mul(1000),
+                        _ => amount,
+                    });
                 }
-                _ => {}
+                _ => return Err(AikvError::InvalidArgument("ERR syntax error".to_string())),
             }
             i += 1;
         }
 
+        if nx && xx {
+            return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
+        }
+
+        // Fetching the old value fails with WRONGTYPE for non-string keys
+        let old_value = if get {
+            self.storage.get_from_db(current_db, &key)?
+        } else {
+            None
+        };
+        let reply = || match &old_value {
+            Some(old) => RespValue::bulk_string(old.clone()),
+            None if get => RespValue::null_bulk_string(),
+            None => RespValue::ok(),
+        };
+
         // Check conditions
         if nx && self.storage.exists_in_db(current_db, &key)? {
-            return Ok(RespValue::null_bulk_string());
+            return Ok(if get {
+                reply()
+            } else {
+                RespValue::null_bulk_string()
+            });
         }
 
         if xx && !self.storage.exists_in_db(current_db, &key)? {
             return Ok(RespValue::null_bulk_string());
         }
 
-        // Set with or without expiration
-        if let Some(ms) = expire_ms {
-            use std::time::{SystemTime, UNIX_EPOCH};
-            let now_ms = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as u64;
-            let expire_at = now_ms + ms;
+        if let Some(expire_at) = expire_at {
             self.storage
                 .set_with_expiration_in_db(current_db, key, value, expire_at)?;
+        } else if keep_ttl {
+            self.set_keeping_ttl(current_db, key, value)?;
         } else {
             self.storage.set_in_db(current_db, key, value)?;
         }
 
-        Ok(RespValue::ok())
+        Ok(reply())
     }
 
     /// DEL key \[key ...\]
@@ -206,10 +244,204 @@ impl StringCommands {
         };
 
         let len = new_value.len() as i64;
-        self.storage.set_in_db(current_db, key, new_value)?;
+        self.set_keeping_ttl(current_db, key, new_value)?;
 
         Ok(RespValue::integer(len))
     }
+
+    /// SETNX key value
+    pub fn setnx(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.len() != 2 {
+            return Err(AikvError::WrongArgCount("SETNX".to_string()));
+        }
+
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+
+        if self.storage.exists_in_db(current_db, &key)? {
+            return Ok(RespValue::integer(0));
+        }
+
+        self.storage.set_in_db(current_db, key, args[1].clone())?;
+        Ok(RespValue::integer(1))
+    }
+
+    /// GETDEL key
+    pub fn getdel(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.len() != 1 {
+            return Err(AikvError::WrongArgCount("GETDEL".to_string()));
+        }
+
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+
+        match self.storage.get_from_db(current_db, &key)? {
+            Some(value) => {
+                self.storage.delete_from_db(current_db, &key)?;
+                Ok(RespValue::bulk_string(value))
+            }
+            None => Ok(RespValue::null_bulk_string()),
+        }
+    }
+
+    /// GETEX key \[EX seconds|PX milliseconds|EXAT timestamp|PXAT ms-timestamp|PERSIST\]
+    pub fn getex(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.is_empty() {
+            return Err(AikvError::WrongArgCount("GETEX".to_string()));
+        }
+
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+
+        // Parse the expiration option: (option, value)
+        let option = match args.len() {
+            1 => None,
+            2 => {
+                let option = String::from_utf8_lossy(&args[1]).to_uppercase();
+                if option != "PERSIST" {
+                    return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
+                }
+                Some((option, 0))
+            }
+            3 => {
+                let option = String::from_utf8_lossy(&args[1]).to_uppercase();
+                if !matches!(option.as_str(), "EX" | "PX" | "EXAT" | "PXAT") {
+                    return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
+                }
+                let value = String::from_utf8_lossy(&args[2])
+                    .parse::<i64>()
+                    .map_err(|_| {
+                        AikvError::InvalidArgument(
+                            "ERR value is not an integer or out of range".to_string(),
+                        )
+                    })?;
+                if value <= 0 {
+                    return Err(AikvError::InvalidArgument(
+                        "ERR invalid expire time in 'getex' command".to_string(),
+                    ));
+                }
+                Some((option, value as u64))
+            }
+            _ => return Err(AikvError::InvalidArgument("ERR syntax error".to_string())),
+        };
+
+        let value = match self.storage.get_from_db(current_db, &key)? {
+            Some(value) => value,
+            None => return Ok(RespValue::null_bulk_string()),
+        };
+
+        if let Some((option, amount)) = option {
+            match option.as_str() {
+                "EX" => {
+                    self.storage
+                        .set_expire_in_db(current_db, &key, amount.saturating_mul(1000))?;
+                }
+                "PX" => {
+                    self.storage.set_expire_in_db(current_db, &key, amount)?;
+                }
+                "EXAT" => {
+                    self.storage.set_expire_at_in_db(
+                        current_db,
+                        &key,
+                        amount.saturating_mul(1000),
+                    )?;
+                }
+                "PXAT" => {
+                    self.storage.set_expire_at_in_db(current_db, &key, amount)?;
+                }
+                _ => {
+                    self.storage.persist_in_db(current_db, &key)?;
+                }
+            }
+        }
+
+        Ok(RespValue::bulk_string(value))
+    }
+
+    /// INCR key
+    pub fn incr(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.len() != 1 {
+            return Err(AikvError::WrongArgCount("INCR".to_string()));
+        }
+
+        self.incr_by(&args[0], 1, current_db)
+    }
+
+    /// DECR key
+    pub fn decr(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.len() != 1 {
+            return Err(AikvError::WrongArgCount("DECR".to_string()));
+        }
+
+        self.incr_by(&args[0], -1, current_db)
+    }
+
+    /// INCRBY key increment
+    pub fn incrby(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.len() != 2 {
+            return Err(AikvError::WrongArgCount("INCRBY".to_string()));
+        }
+
+        let increment = Self::parse_integer(&args[1])?;
+        self.incr_by(&args[0], increment, current_db)
+    }
+
+    /// DECRBY key decrement
+    pub fn decrby(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.len() != 2 {
+            return Err(AikvError::WrongArgCount("DECRBY".to_string()));
+        }
+
+        let decrement = Self::parse_integer(&args[1])?;
+        let increment = decrement.checked_neg().ok_or_else(|| {
+            AikvError::InvalidArgument("ERR decrement would overflow".to_string())
+        })?;
+        self.incr_by(&args[0], increment, current_db)
+    }
+
+    /// Add `increment` to the integer stored at key, keeping its TTL
+    fn incr_by(&self, key: &Bytes, increment: i64, current_db: usize) -> Result<RespValue> {
+        let key = String::from_utf8_lossy(key).to_string();
+        let mut new_value = 0;
+
+        // Read and write under one storage lock so concurrent INCRs never lose an update
+        self.storage.upsert_value(
+            current_db,
+            &key,
+            StoredValue::new_string(Bytes::from_static(b"0")),
+            |stored| {
+                let current = Self::parse_integer(stored.as_string()?)?;
+                new_value = current.checked_add(increment).ok_or_else(|| {
+                    AikvError::InvalidArgument(
+                        "ERR increment or decrement would overflow".to_string(),
+                    )
+                })?;
+                *stored.value_mut() = ValueType::String(Bytes::from(new_value.to_string()));
+                Ok(())
+            },
+        )?;
+        Ok(RespValue::integer(new_value))
+    }
+
+    /// Overwrite a string value without touching the key's TTL
+    fn set_keeping_ttl(&self, current_db: usize, key: String, value: Bytes) -> Result<()> {
+        let expire_at = self.storage.get_expire_time_in_db(current_db, &key)?;
+        if expire_at > 0 {
+            self.storage
+                .set_with_expiration_in_db(current_db, key, value, expire_at as u64)
+        } else {
+            self.storage.set_in_db(current_db, key, value)
+        }
+    }
+
+    /// Parse a 64-bit signed integer argument or stored value
+    fn parse_integer(value: &[u8]) -> Result<i64> {
+        std::str::from_utf8(value)
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or_else(|| {
+                AikvError::InvalidArgument(
+                    "ERR value is not an integer or out of range".to_string(),
+                )
+            })
+    }
 }
 
 #[cfg(test)]
@@ -335,4 +567,94 @@ mod tests {
         let result = cmd.get(&[Bytes::from("key1")], 0).unwrap();
         assert_eq!(result, RespValue::bulk_string("Hello World"));
     }
+
+    #[test]
+    fn test_incr_decr() {
+        let cmd = setup();
+
+        let result = cmd.incr(&[Bytes::from("counter")], 0).unwrap();
+        assert_eq!(result, RespValue::integer(1));
+
+        let result = cmd
+            .incrby(&[Bytes::from("counter"), Bytes::from("10")], 0)
+            .unwrap();
+        assert_eq!(result, RespValue::integer(11));
+
+        let result = cmd
+            .decrby(&[Bytes::from("counter"), Bytes::from("3")], 0)
+            .unwrap();
+        assert_eq!(result, RespValue::integer(8));
+
+        let result = cmd.decr(&[Bytes::from("counter")], 0).unwrap();
+        assert_eq!(result, RespValue::integer(7));
+
+        cmd.set(&[Bytes::from("text"), Bytes::from("abc")], 0)
+            .unwrap();
+        assert!(cmd.incr(&[Bytes::from("text")], 0).is_err());
+
+        cmd.set(&[Bytes::from("max"), Bytes::from(i64::MAX.to_string())], 0)
+            .unwrap();
+        assert!(cmd.incr(&[Bytes::from("max")], 0).is_err());
+    }
+
+    #[test]
+    fn test_incr_after_append() {
+        let cmd = setup();
+
+        cmd.incr(&[Bytes::from("counter")], 0).unwrap();
+        cmd.incrby(&[Bytes::from("counter"), Bytes::from("2")], 0)
+            .unwrap();
+
+        // APPEND works on the decimal form, which INCR parses again
+        let result = cmd
+            .append(&[Bytes::from("counter"), Bytes::from("5")], 0)
+            .unwrap();
+        assert_eq!(result, RespValue::integer(2));
+        let result = cmd.get(&[Bytes::from("counter")], 0).unwrap();
+        assert_eq!(result, RespValue::bulk_string("35"));
+        let result = cmd.incr(&[Bytes::from("counter")], 0).unwrap();
+        assert_eq!(result, RespValue::integer(36));
+        let result = cmd.strlen(&[Bytes::from("counter")], 0).unwrap();
+        assert_eq!(result, RespValue::integer(2));
+
+        cmd.append(&[Bytes::from("counter"), Bytes::from("x")], 0)
+            .unwrap();
+        let err = cmd.incr(&[Bytes::from("counter")], 0).unwrap_err();
+        assert!(err.to_string().contains("not an integer"));
+        let result = cmd.get(&[Bytes::from("counter")], 0).unwrap();
+        assert_eq!(result, RespValue::bulk_string("36x"));
+    }
+
+    #[test]
+    fn test_getdel_getex() {
+        let cmd = setup();
+
+        cmd.set(&[Bytes::from("key1"), Bytes::from("value1")], 0)
+            .unwrap();
+
+        let result = cmd
+            .getex(
+                &[
+                    Bytes::from("key1"),
+                    Bytes::from("PX"),
+                    Bytes::from("100000"),
+                ],
+                0,
+            )
+            .unwrap();
+        assert_eq!(result, RespValue::bulk_string("value1"));
+        assert!(cmd.storage.get_ttl_in_db(0, "key1").unwrap() > 0);
+
+        let result = cmd.getex(
+            &[Bytes::from("key1"), Bytes::from("EX"), Bytes::from("0")],
+            0,
+        );
+        assert!(result.is_err());
+
+        let result = cmd.getdel(&[Bytes::from("key1")], 0).unwrap();
+        assert_eq!(result, RespValue::bulk_string("value1"));
+
+        let result = cmd.getdel(&[Bytes::from("key1")], 0).unwrap();
+        assert_eq!(result, RespValue::null_bulk_string());
+    }
 }
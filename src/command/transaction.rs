@@ -0,0 +1,218 @@
+//! MULTI/EXEC transactions
+//!
+//! A connection enters a transaction with MULTI. Every following command is
+//! validated and queued instead of executed, and EXEC runs the queue in order
+//! against the connection's state, so a queued SELECT switches the database for
+//! the commands queued after it. DISCARD drops the queue.
+//!
+//! A command that is unknown or has the wrong arity flags the transaction
+//! when queued, and EXEC then discards it with EXECABORT without running
+//! anything. Errors raised while EXEC runs a queued
+//! command only become that command's reply.
+//!
+//! EXEC is atomic: it holds the server-wide [`CommandGate`] exclusively, so no
+//! other connection's command runs between two queued commands. Scripts hold
+//! it exclusively as well, while every other command shares it; each of those
+//! is atomic on its own in storage, as INCR is.
+
+use super::server::lookup_command;
+use crate::error::{AikvError, Result};
+use crate::protocol::RespValue;
+use bytes::Bytes;
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+use tokio::runtime::RuntimeFlavor;
+
+/// Commands that change the connection's subscriptions and are rejected inside
+/// MULTI instead of being queued
+const NOT_ALLOWED_IN_TRANSACTION: &[&str] = &[
+    "SUBSCRIBE",
+    "UNSUBSCRIBE",
+    "PSUBSCRIBE",
+    "PUNSUBSCRIBE",
+    "SSUBSCRIBE",
+    "SUNSUBSCRIBE",
+];
+
+/// A command queued inside MULTI
+#[derive(Debug, Clone)]
+pub struct QueuedCommand {
+    /// Uppercase command name
+    pub name: String,
+    /// Command arguments (excluding the name)
+    pub args: Vec<Bytes>,
+}
+
+/// Server-wide lock that serializes EXEC and scripts against every other command
+///
+/// The lock guards no data, so a poisoned gate is simply taken over.
+#[derive(Debug, Clone, Default)]
+pub struct CommandGate(Arc<RwLock<()>>);
+
+impl CommandGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold the gate while running a command
+    pub fn shared(&self) -> RwLockReadGuard<'_, ()> {
+        match self.0.try_read() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                run_blocking(|| self.0.read().unwrap_or_else(PoisonError::into_inner))
+            }
+        }
+    }
+
+    /// Hold the gate for a whole EXEC or script
+    pub fn exclusive(&self) -> RwLockWriteGuard<'_, ()> {
+        match self.0.try_write() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                run_blocking(|| self.0.write().unwrap_or_else(PoisonError::into_inner))
+            }
+        }
+    }
+}
+
+/// Run work that blocks the thread, such as waiting on the gate or a script.
+///
+/// On the multi-threaded runtime the worker hands its other tasks to another
+/// thread first, so the connections queued behind the gate, including one
+/// sending SCRIPT KILL, keep being served. Commands that get the gate at once
+/// don't pay for the hand-over.
+pub(crate) fn run_blocking<T>(f: impl FnOnce() -> T) -> T {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+/// An open transaction's queue
+#[derive(Debug, Default)]
+struct Queue {
+    commands: Vec<QueuedCommand>,
+    /// Set when a command was rejected while queuing
+    dirty: bool,
+}
+
+/// Per-connection transaction state
+#[derive(Debug, Default)]
+pub struct Transaction {
+    /// `None` when no transaction is open
+    queue: Mutex<Option<Queue>>,
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// MULTI - Start a transaction
+    pub fn begin(&self, args: &[Bytes]) -> Result<RespValue> {
+        if !args.is_empty() {
+            return Err(AikvError::WrongArgCount("MULTI".to_string()));
+        }
+
+        let mut queue = self.lock()?;
+        if queue.is_some() {
+            return Err(AikvError::InvalidArgument(
+                "ERR MULTI calls can not be nested".to_string(),
+            ));
+        }
+
+        *queue = Some(Queue::default());
+        Ok(RespValue::ok())
+    }
+
+    /// Check whether a transaction is open
+    pub fn is_active(&self) -> Result<bool> {
+        Ok(self.lock()?.is_some())
+    }
+
+    /// Queue a command, validating its name and arity first.
+    ///
+    /// A rejected command is not queued, but the transaction stays open. An
+    /// unknown command or a wrong arity also flags it, so EXEC aborts it.
+    pub fn queue(&self, name: &str, args: &[Bytes]) -> Result<RespValue> {
+        if NOT_ALLOWED_IN_TRANSACTION.contains(&name) {
+            return Err(AikvError::InvalidArgument(format!(
+                "ERR {} is not allowed in transactions",
+                name
+            )));
+        }
+
+        let mut queue = self.lock()?;
+        let queue = queue.as_mut().ok_or_else(|| {
+            AikvError::Internal("No transaction to queue the command in".to_string())
+        })?;
+
+        if let Err(e) = Self::validate(name, args) {
+            queue.dirty = true;
+            return Err(e);
+        }
+
+        queue.commands.push(QueuedCommand {
+            name: name.to_string(),
+            args: args.to_vec(),
+        });
+
+        Ok(RespValue::simple_string("QUEUED"))
+    }
+
+    /// Check that a command exists and gets the right number of arguments
+    fn validate(name: &str, args: &[Bytes]) -> Result<()> {
+        let info = lookup_command(name)
+            .ok_or_else(|| AikvError::InvalidCommand(format!("Unknown command: {}", name)))?;
+
+        let argc = args.len() as i64 + 1;
+        if (info.arity > 0 && argc != info.arity) || (info.arity < 0 && argc < -info.arity) {
+            return Err(AikvError::WrongArgCount(name.to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Close the transaction and take its queued commands (EXEC)
+    ///
+    /// A transaction flagged while queuing is closed without returning its
+    /// commands, failing with EXECABORT.
+    pub fn take(&self, args: &[Bytes]) -> Result<Vec<QueuedCommand>> {
+        if !args.is_empty() {
+            return Err(AikvError::WrongArgCount("EXEC".to_string()));
+        }
+
+        let queue = self
+            .lock()?
+            .take()
+            .ok_or_else(|| AikvError::InvalidArgument("ERR EXEC without MULTI".to_string()))?;
+        if queue.dirty {
+            return Err(AikvError::ExecAbort);
+        }
+
+        Ok(queue.commands)
+    }
+
+    /// DISCARD - Drop the queued commands and close the transaction
+    pub fn discard(&self, args: &[Bytes]) -> Result<RespValue> {
+        if !args.is_empty() {
+            return Err(AikvError::WrongArgCount("DISCARD".to_string()));
+        }
+
+        match self.lock()?.take() {
+            Some(_) => Ok(RespValue::ok()),
+            None => Err(AikvError::InvalidArgument(
+                "ERR DISCARD without MULTI".to_string(),
+            )),
+        }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Option<Queue>>> {
+        self.queue
+            .lock()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))
+    }
+}
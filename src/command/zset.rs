@@ -1,3 +1,4 @@
+use crate::command::numkeys;
 use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
 use crate::storage::{StorageEngine, StoredValue};
@@ -16,48 +17,108 @@ impl ZSetCommands {
         }
     }
 
-    /// ZADD key score member [score member ...]
+    /// ZADD key \[NX|XX\] \[GT|LT\] \[CH\] \[INCR\] score member \[score member ...\]
     /// Adds all the specified members with the specified scores to the sorted set stored at key
     pub fn zadd(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
-        if args.len() < 3 || args.len() % 2 == 0 {
+        if args.len() < 3 {
             return Err(AikvError::WrongArgCount("ZADD".to_string()));
         }
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
-        let mut members = Vec::new();
+        let (mut nx, mut xx, mut gt, mut lt, mut ch, mut incr) =
+            (false, false, false, false, false, false);
+        let mut i = 1;
+        while i < args.len() {
+            match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+                "NX" => nx = true,
+                "XX" => xx = true,
+                "GT" => gt = true,
+                "LT" => lt = true,
+                "CH" => ch = true,
+                "INCR" => incr = true,
+                _ => break,
+            }
+            i += 1;
+        }
 
-        for i in (1..args.len()).step_by(2) {
-            let score = String::from_utf8_lossy(&args[i])
-                .parse::<f64>()
-                .map_err(|_| AikvError::InvalidArgument("invalid score".to_string()))?;
-            let member = args[i + 1].clone();
-            members.push((score, member));
+        let pairs = &args[i..];
+        if pairs.is_empty() || !pairs.chunks_exact(2).remainder().is_empty() {
+            return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
+        }
+        if nx && xx {
+            return Err(AikvError::InvalidArgument(
+                "ERR XX and NX options at the same time are not compatible".to_string(),
+            ));
+        }
+        if (gt && lt) || (nx && (gt || lt)) {
+            return Err(AikvError::InvalidArgument(
+                "ERR GT, LT, and/or NX options at the same time are not compatible".to_string(),
+            ));
+        }
+        if incr && pairs.len() > 2 {
+            return Err(AikvError::InvalidArgument(
+                "ERR INCR option supports a single increment-element pair".to_string(),
+            ));
         }
 
-        // Migrated: Logic moved from storage layer to command layer
-        let zset = if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let mut zset = stored.as_zset()?.clone();
-            let mut count = 0;
-            for (score, member) in &members {
-                if zset.insert(member.to_vec(), *score).is_none() {
-                    count += 1;
+        // Parse every score before touching the set so a bad one changes nothing
+        let members = pairs
+            .chunks_exact(2)
+            .map(|pair| Ok((parse_score(&pair[0])?, pair[1].to_vec())))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut zset = match self.storage.get_value(db_index, &key)? {
+            Some(stored) => stored.as_zset()?.clone(),
+            None => BTreeMap::new(),
+        };
+
+        let mut added = 0;
+        let mut updated = 0;
+        let mut incr_score = None;
+        for (score, member) in members {
+            match zset.get(&member).copied() {
+                Some(current) => {
+                    if nx {
+                        continue;
+                    }
+                    let new_score = if incr { current + score } else { score };
+                    if new_score.is_nan() {
+                        return Err(AikvError::InvalidArgument(
+                            "ERR resulting score is not a number (NaN)".to_string(),
+                        ));
+                    }
+                    // GT and LT only hold back updates; new members are still added
+                    if (gt && new_score <= current) || (lt && new_score >= current) {
+                        continue;
+                    }
+                    if new_score != current {
+                        zset.insert(member, new_score);
+                        updated += 1;
+                    }
+                    incr_score = Some(new_score);
                 }
-            }
-            (count, zset)
-        } else {
-            let mut zset = BTreeMap::new();
-            let mut count = 0;
-            for (score, member) in &members {
-                if zset.insert(member.to_vec(), *score).is_none() {
-                    count += 1;
+                None => {
+                    if xx {
+                        continue;
+                    }
+                    zset.insert(member, score);
+                    added += 1;
+                    incr_score = Some(score);
                 }
             }
-            (count, zset)
-        };
+        }
 
-        self.storage
-            .set_value(db_index, key, StoredValue::new_zset(zset.1))?;
-        Ok(RespValue::Integer(zset.0 as i64))
+        if added + updated > 0 {
+            self.storage
+                .set_value(db_index, key, StoredValue::new_zset(zset))?;
+        }
+
+        if incr {
+            // An increment held back by NX, XX, GT or LT replies nil
+            return Ok(incr_score.map_or(RespValue::Null, RespValue::double));
+        }
+        let count = if ch { added + updated } else { added };
+        Ok(RespValue::Integer(count as i64))
     }
 
     /// ZREM key member [member ...]
@@ -113,7 +174,7 @@ impl ZSetCommands {
         };
 
         match score {
-            Some(score) => Ok(RespValue::bulk_string(Bytes::from(score.to_string()))),
+            Some(score) => Ok(RespValue::double(score)),
             None => Ok(RespValue::Null),
         }
     }
@@ -240,7 +301,7 @@ impl ZSetCommands {
         for (member, score) in members {
             result.push(RespValue::bulk_string(member));
             if with_scores {
-                result.push(RespValue::bulk_string(Bytes::from(score.to_string())));
+                result.push(RespValue::double(score));
             }
         }
 
@@ -301,7 +362,7 @@ impl ZSetCommands {
         for (member, score) in members {
             result.push(RespValue::bulk_string(member));
             if with_scores {
-                result.push(RespValue::bulk_string(Bytes::from(score.to_string())));
+                result.push(RespValue::double(score));
             }
         }
 
@@ -345,7 +406,7 @@ impl ZSetCommands {
         for (member, score) in members {
             result.push(RespValue::bulk_string(member));
             if with_scores {
-                result.push(RespValue::bulk_string(Bytes::from(score.to_string())));
+                result.push(RespValue::double(score));
             }
         }
 
@@ -389,7 +450,7 @@ impl ZSetCommands {
         for (member, score) in members {
             result.push(RespValue::bulk_string(member));
             if with_scores {
-                result.push(RespValue::bulk_string(Bytes::from(score.to_string())));
+                result.push(RespValue::double(score));
             }
         }
 
@@ -442,6 +503,96 @@ impl ZSetCommands {
         Ok(RespValue::Integer(count as i64))
     }
 
+    /// ZINTERCARD numkeys key \[key ...\] \[LIMIT limit\]
+    /// Returns the number of members in the intersection of the given sorted
+    /// sets, counting no further than limit when it is not 0
+    pub fn zintercard(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        let (keys, limit) = numkeys::parse_intercard("ZINTERCARD", args)?;
+
+        // Every key is type checked before a missing one empties the result
+        let mut zsets = Vec::with_capacity(keys.len());
+        for key in keys {
+            let key = String::from_utf8_lossy(key).to_string();
+            match self.storage.get_value(db_index, &key)? {
+                Some(stored) => zsets.push(Some(stored.as_zset()?.clone())),
+                None => zsets.push(None),
+            }
+        }
+        let Some(mut zsets) = zsets.into_iter().collect::<Option<Vec<_>>>() else {
+            return Ok(RespValue::Integer(0));
+        };
+
+        zsets.sort_by_key(|zset| zset.len());
+        let Some((smallest, others)) = zsets.split_first() else {
+            return Ok(RespValue::Integer(0));
+        };
+        let mut count = 0;
+        for member in smallest.keys() {
+            if others.iter().all(|zset| zset.contains_key(member)) {
+                count += 1;
+                if count == limit {
+                    break;
+                }
+            }
+        }
+
+        Ok(RespValue::Integer(count as i64))
+    }
+
+    /// ZMPOP numkeys key \[key ...\] MIN|MAX \[COUNT count\]
+    /// Pops up to count members with the lowest (MIN) or highest (MAX) scores
+    /// from the first non-empty sorted set, replying with its key and the
+    /// members with their scores, or nil when every sorted set is empty
+    pub fn zmpop(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        let parsed = numkeys::parse_mpop("ZMPOP", args, ["MIN", "MAX"])?;
+
+        for key in parsed.keys {
+            let name = String::from_utf8_lossy(key).to_string();
+            let Some(stored) = self.storage.get_value(db_index, &name)? else {
+                continue;
+            };
+            let mut zset = stored.as_zset()?.clone();
+            if zset.is_empty() {
+                continue;
+            }
+
+            // Ties are ordered by member, and MAX walks the order backwards
+            let mut sorted: Vec<(Vec<u8>, f64)> =
+                zset.iter().map(|(m, s)| (m.clone(), *s)).collect();
+            sorted.sort_by(|a, b| a.1.total_cmp(&b.1));
+            if !parsed.first_end {
+                sorted.reverse();
+            }
+            sorted.truncate(parsed.count);
+
+            for (member, _) in &sorted {
+                zset.remove(member);
+            }
+            if zset.is_empty() {
+                self.storage.delete_from_db(db_index, &name)?;
+            } else {
+                self.storage
+                    .set_value(db_index, name, StoredValue::new_zset(zset))?;
+            }
+
+            let popped = sorted
+                .into_iter()
+                .map(|(member, score)| {
+                    RespValue::array(vec![
+                        RespValue::bulk_string(Bytes::from(member)),
+                        RespValue::double(score),
+                    ])
+                })
+                .collect();
+            return Ok(RespValue::array(vec![
+                RespValue::bulk_string(key.clone()),
+                RespValue::array(popped),
+            ]));
+        }
+
+        Ok(RespValue::null_array())
+    }
+
     /// ZINCRBY key increment member
     /// Increments the score of member in the sorted set stored at key by increment
     pub fn zincrby(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
@@ -450,9 +601,7 @@ impl ZSetCommands {
         }
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
-        let increment = String::from_utf8_lossy(&args[1])
-            .parse::<f64>()
-            .map_err(|_| AikvError::InvalidArgument("invalid increment".to_string()))?;
+        let increment = parse_score(&args[1])?;
         let member = args[2].clone();
 
         // Migrated: Logic moved from storage layer to command layer
@@ -461,6 +610,11 @@ impl ZSetCommands {
             let member_vec = member.to_vec();
             let current = zset.get(&member_vec).copied().unwrap_or(0.0);
             let new_score = current + increment;
+            if new_score.is_nan() {
+                return Err(AikvError::InvalidArgument(
+                    "ERR resulting score is not a number (NaN)".to_string(),
+                ));
+            }
             zset.insert(member_vec, new_score);
             (new_score, zset)
         } else {
@@ -472,6 +626,15 @@ impl ZSetCommands {
 
         self.storage
             .set_value(db_index, key, StoredValue::new_zset(zset.1))?;
-        Ok(RespValue::bulk_string(Bytes::from(zset.0.to_string())))
+        Ok(RespValue::double(zset.0))
     }
 }
+
+/// Parse a score like Redis: `inf`, `-inf` and exponents are accepted, NaN is not
+fn parse_score(arg: &[u8]) -> Result<f64> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|score| !score.is_nan())
+        .ok_or_else(|| AikvError::InvalidArgument("ERR value is not a valid float".to_string()))
+}
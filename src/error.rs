@@ -1,3 +1,4 @@
+use crate::protocol::RespValue;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -35,6 +36,12 @@ pub enum AikvError {
     #[error("Script error: {0}")]
     Script(String),
 
+    #[error("NOSCRIPT No matching script. Use EVAL.")]
+    NoScript,
+
+    #[error("EXECABORT Transaction discarded because of previous errors.")]
+    ExecAbort,
+
     #[error("Internal error: {0}")]
     Internal(String),
 
@@ -47,6 +54,9 @@ pub enum AikvError {
     #[error("ASK {0} {1}")]
     Ask(u16, String),
 
+    #[error("TRYAGAIN Hash slot {0} is being migrated")]
+    TryAgain(u16),
+
     #[error("Cluster support is not enabled")]
     ClusterDisabled,
 
@@ -54,4 +64,30 @@ pub enum AikvError {
     Unknown(String),
 }
 
+impl AikvError {
+    /// Format the error as a RESP error reply.
+    pub fn to_resp(&self) -> RespValue {
+        RespValue::error(self.reply_message())
+    }
+
+    /// The text of the error reply sent for this error.
+    ///
+    /// Errors that carry their own Redis error code (cluster redirections,
+    /// TRYAGAIN, NOSCRIPT, EXECABORT and WRONGTYPE) are sent with it so clients
+    /// can react to them, as are invalid arguments already worded as a Redis
+    /// `ERR` reply; everything else gets the generic ERR prefix.
+    pub fn reply_message(&self) -> String {
+        match self {
+            AikvError::Moved(..)
+            | AikvError::Ask(..)
+            | AikvError::TryAgain(..)
+            | AikvError::NoScript
+            | AikvError::ExecAbort => self.to_string(),
+            AikvError::InvalidArgument(message) if message.starts_with("ERR ") => message.clone(),
+            AikvError::WrongType(message) => format!("WRONGTYPE {}", message),
+            _ => format!("ERR {}", self),
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, AikvError>;
@@ -20,6 +20,18 @@ struct ServerConfig {
     host: String,
     #[serde(default = "default_port")]
     port: u16,
+    /// Unix socket path to accept clients on, in addition to the TCP port
+    #[serde(default)]
+    unixsocket: Option<String>,
+    /// Password clients must AUTH with before running other commands
+    #[serde(default)]
+    requirepass: Option<String>,
+    /// Further hosts to listen on with the same port, e.g. ["::1"]
+    #[serde(default)]
+    bind: Vec<String>,
+    /// Refuse non-loopback clients when listening beyond loopback without requirepass
+    #[serde(default)]
+    protected_mode: bool,
 }
 
 fn default_host() -> String {
@@ -30,6 +42,15 @@ fn default_port() -> u16 {
     6379
 }
 
+/// Join a host and port into a listen address, bracketing IPv6 hosts
+fn listen_addr(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
 /// Storage section of the configuration file
 #[derive(Deserialize, Default)]
 struct StorageConfig {
@@ -155,6 +176,10 @@ fn print_help() {
     println!("    [server]");
     println!("    host = \"127.0.0.1\"");
     println!("    port = 6379");
+    println!("    unixsocket = \"/tmp/aikv.sock\"  # optional, alongside the TCP port");
+    println!("    requirepass = \"secret\"         # optional, clients must AUTH first");
+    println!("    bind = [\"::1\"]                 # optional, more hosts on the same port");
+    println!("    protected_mode = true            # optional, loopback-only without a password");
     println!();
     println!("    [storage]");
     println!("    engine = \"memory\"    # or \"aidb\"");
@@ -271,7 +296,9 @@ fn parse_args() -> CliArgs {
 
 /// Load configuration from file and merge with CLI arguments
 #[cfg(feature = "cluster")]
-fn load_config(cli: &CliArgs) -> (String, u16, StorageConfig, LoggingConfig, ClusterConfigSection) {
+fn load_config(
+    cli: &CliArgs,
+) -> (ServerConfig, StorageConfig, LoggingConfig, ClusterConfigSection) {
     let mut config = Config::default();
 
     // Load from config file if specified
@@ -292,15 +319,19 @@ fn load_config(cli: &CliArgs) -> (String, u16, StorageConfig, LoggingConfig, Clu
     }
 
     // CLI arguments override config file
-    let host = cli.host.clone().unwrap_or(config.server.host);
-    let port = cli.port.unwrap_or(config.server.port);
+    if let Some(ref host) = cli.host {
+        config.server.host = host.clone();
+    }
+    if let Some(port) = cli.port {
+        config.server.port = port;
+    }
 
-    (host, port, config.storage, config.logging, config.cluster)
+    (config.server, config.storage, config.logging, config.cluster)
 }
 
 /// Load configuration from file and merge with CLI arguments
 #[cfg(not(feature = "cluster"))]
-fn load_config(cli: &CliArgs) -> (String, u16, StorageConfig, LoggingConfig) {
+fn load_config(cli: &CliArgs) -> (ServerConfig, StorageConfig, LoggingConfig) {
     let mut config = Config::default();
 
     // Load from config file if specified
@@ -321,10 +352,14 @@ fn load_config(cli: &CliArgs) -> (String, u16, StorageConfig, LoggingConfig) {
     }
 
     // CLI arguments override config file
-    let host = cli.host.clone().unwrap_or(config.server.host);
-    let port = cli.port.unwrap_or(config.server.port);
+    if let Some(ref host) = cli.host {
+        config.server.host = host.clone();
+    }
+    if let Some(port) = cli.port {
+        config.server.port = port;
+    }
 
-    (host, port, config.storage, config.logging)
+    (config.server, config.storage, config.logging)
 }
 
 /// Create storage engine based on configuration
@@ -374,9 +409,9 @@ async fn main() {
 
     // Load configuration
     #[cfg(feature = "cluster")]
-    let (host, port, storage_config, logging_config, cluster_config) = load_config(&cli);
+    let (server_config, storage_config, logging_config, cluster_config) = load_config(&cli);
     #[cfg(not(feature = "cluster"))]
-    let (host, port, storage_config, logging_config) = load_config(&cli);
+    let (server_config, storage_config, logging_config) = load_config(&cli);
 
     // Initialize logging with configured level
     let log_level = logging_config.level.to_lowercase();
@@ -396,7 +431,7 @@ async fn main() {
         .with_env_filter(filter)
         .init();
 
-    let addr = format!("{}:{}", host, port);
+    let addr = listen_addr(&server_config.host, server_config.port);
 
     // Print startup banner
     println!("{}", LOGO);
@@ -411,6 +446,19 @@ async fn main() {
 
     // Create and run server
     let mut server = Server::new(addr, storage);
+    if let Some(ref path) = cli.config_path {
+        server.set_config_file(path);
+    }
+    if let Some(path) = server_config.unixsocket {
+        server.set_unix_socket(path);
+    }
+    if let Some(password) = server_config.requirepass {
+        server.set_requirepass(password);
+    }
+    for host in &server_config.bind {
+        server.add_bind_address(listen_addr(host, server_config.port));
+    }
+    server.set_protected_mode(server_config.protected_mode);
 
     // Initialize cluster if enabled
     #[cfg(feature = "cluster")]
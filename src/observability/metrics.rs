@@ -5,6 +5,7 @@
 //! - Command execution statistics
 //! - Connection statistics
 //! - Memory usage statistics
+//! - Pub/Sub statistics
 
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
@@ -92,6 +93,8 @@ pub struct CommandMetrics {
     pub total_errors: Counter,
     /// Errors per command type
     pub errors_by_type: RwLock<HashMap<String, Counter>>,
+    /// Error replies per error code (`WRONGTYPE`, `ERR`, ...), for INFO errorstats
+    pub error_replies_by_code: RwLock<HashMap<String, Counter>>,
     /// Total command execution time in microseconds
     pub total_duration_us: AtomicU64,
     /// Commands per second (calculated)
@@ -116,6 +119,7 @@ impl CommandMetrics {
             commands_by_type: RwLock::new(HashMap::new()),
             total_errors: Counter::new(),
             errors_by_type: RwLock::new(HashMap::new()),
+            error_replies_by_code: RwLock::new(HashMap::new()),
             total_duration_us: AtomicU64::new(0),
             ops_per_sec: RwLock::new(0.0),
             last_ops_calc: RwLock::new(Instant::now()),
@@ -151,6 +155,37 @@ impl CommandMetrics {
         }
     }
 
+    /// Record an error reply sent to a client, counted by its error code.
+    ///
+    /// As in Redis, the code is the first word of the message, or `ERR` when
+    /// the message does not start with a short code followed by a space.
+    pub fn record_error_reply(&self, message: &str) {
+        let code = match message.find(' ') {
+            Some(end) if end > 0 && end <= 32 => &message[..end],
+            _ => "ERR",
+        };
+        if let Ok(mut replies) = self.error_replies_by_code.write() {
+            replies
+                .entry(code.to_string())
+                .or_insert_with(Counter::new)
+                .inc();
+        }
+    }
+
+    /// Get error reply counts by error code
+    pub fn error_replies_by_code(&self) -> HashMap<String, u64> {
+        if let Ok(replies) = self.error_replies_by_code.read() {
+            replies.iter().map(|(k, v)| (k.clone(), v.get())).collect()
+        } else {
+            HashMap::new()
+        }
+    }
+
+    /// Get total error replies sent
+    pub fn total_error_replies(&self) -> u64 {
+        self.error_replies_by_code().values().sum()
+    }
+
     /// Get total commands processed
     pub fn total_commands(&self) -> u64 {
         self.total_commands.get()
@@ -211,11 +246,14 @@ impl CommandMetrics {
         if let Ok(mut errors) = self.errors_by_type.write() {
             errors.clear();
         }
+        if let Ok(mut replies) = self.error_replies_by_code.write() {
+            replies.clear();
+        }
     }
 }
 
 /// Connection metrics
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct ConnectionMetrics {
     /// Total connections received
     pub total_connections: Counter,
@@ -227,6 +265,20 @@ pub struct ConnectionMetrics {
     pub bytes_received: Counter,
     /// Total bytes sent
     pub bytes_sent: Counter,
+    /// Input/output kbps over the last sampling window (calculated)
+    net_kbps: RwLock<(f64, f64)>,
+    /// Last sampling time
+    last_net_calc: RwLock<Instant>,
+    /// Bytes received at last sampling
+    last_bytes_received: AtomicU64,
+    /// Bytes sent at last sampling
+    last_bytes_sent: AtomicU64,
+}
+
+impl Default for ConnectionMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ConnectionMetrics {
@@ -238,6 +290,10 @@ impl ConnectionMetrics {
             rejected_connections: Counter::new(),
             bytes_received: Counter::new(),
             bytes_sent: Counter::new(),
+            net_kbps: RwLock::new((0.0, 0.0)),
+            last_net_calc: RwLock::new(Instant::now()),
+            last_bytes_received: AtomicU64::new(0),
+            last_bytes_sent: AtomicU64::new(0),
         }
     }
 
@@ -299,6 +355,35 @@ impl ConnectionMetrics {
     pub fn output_kbps(&self, bytes_in_period: u64, period_secs: f64) -> f64 {
         Self::calculate_kbps(bytes_in_period, period_secs)
     }
+
+    /// Calculate and get the instantaneous (input, output) kbps.
+    ///
+    /// Rates are sampled over windows of at least one second; between samples
+    /// the last calculated rates are returned.
+    pub fn instantaneous_kbps(&self) -> (f64, f64) {
+        let now = Instant::now();
+        let received = self.bytes_received.get();
+        let sent = self.bytes_sent.get();
+
+        if let (Ok(mut kbps), Ok(mut last_calc)) =
+            (self.net_kbps.write(), self.last_net_calc.write())
+        {
+            let elapsed = now.duration_since(*last_calc);
+            if elapsed >= Duration::from_secs(1) {
+                let period = elapsed.as_secs_f64();
+                let last_received = self.last_bytes_received.swap(received, Ordering::Relaxed);
+                let last_sent = self.last_bytes_sent.swap(sent, Ordering::Relaxed);
+                *kbps = (
+                    self.input_kbps(received.saturating_sub(last_received), period),
+                    self.output_kbps(sent.saturating_sub(last_sent), period),
+                );
+                *last_calc = now;
+            }
+            *kbps
+        } else {
+            (0.0, 0.0)
+        }
+    }
 }
 
 /// Memory usage metrics
@@ -414,6 +499,29 @@ impl MemoryMetrics {
     }
 }
 
+/// Pub/Sub metrics, kept up to date by the server's Pub/Sub broker
+#[derive(Debug, Default)]
+pub struct PubSubMetrics {
+    /// Channels with at least one subscriber
+    pub channels: Gauge,
+    /// Patterns with at least one subscriber
+    pub patterns: Gauge,
+    /// Messages published, whether or not anyone received them
+    pub messages_published: Counter,
+}
+
+impl PubSubMetrics {
+    /// Create new Pub/Sub metrics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a published message
+    pub fn record_publish(&self) {
+        self.messages_published.inc();
+    }
+}
+
 /// Combined metrics for the entire server
 #[derive(Debug)]
 pub struct Metrics {
@@ -423,6 +531,8 @@ pub struct Metrics {
     pub connections: Arc<ConnectionMetrics>,
     /// Memory metrics
     pub memory: Arc<MemoryMetrics>,
+    /// Pub/Sub metrics
+    pub pubsub: Arc<PubSubMetrics>,
     /// Server start time
     pub start_time: Instant,
 }
@@ -440,10 +550,27 @@ impl Metrics {
             commands: Arc::new(CommandMetrics::new()),
             connections: Arc::new(ConnectionMetrics::new()),
             memory: Arc::new(MemoryMetrics::new()),
+            pubsub: Arc::new(PubSubMetrics::new()),
             start_time: Instant::now(),
         }
     }
 
+    /// Reset the counters CONFIG RESETSTAT clears: command and error stats,
+    /// connection and network totals, keyspace hits and misses, and published
+    /// messages. Gauges such as connected clients are left alone.
+    pub fn reset_stats(&self) {
+        self.commands.reset();
+        self.connections.total_connections.reset();
+        self.connections.rejected_connections.reset();
+        self.connections.bytes_received.reset();
+        self.connections.bytes_sent.reset();
+        self.memory.expired_keys.reset();
+        self.memory.evicted_keys.reset();
+        self.memory.keyspace_hits.reset();
+        self.memory.keyspace_misses.reset();
+        self.pubsub.messages_published.reset();
+    }
+
     /// Get uptime in seconds
     pub fn uptime_seconds(&self) -> u64 {
         self.start_time.elapsed().as_secs()
@@ -547,6 +674,28 @@ impl Metrics {
             self.memory.expired_keys.get()
         ));
 
+        // Pub/Sub metrics
+        output.push_str("# HELP aikv_pubsub_channels Channels with at least one subscriber\n");
+        output.push_str("# TYPE aikv_pubsub_channels gauge\n");
+        output.push_str(&format!(
+            "aikv_pubsub_channels {}\n",
+            self.pubsub.channels.get()
+        ));
+
+        output.push_str("# HELP aikv_pubsub_patterns Patterns with at least one subscriber\n");
+        output.push_str("# TYPE aikv_pubsub_patterns gauge\n");
+        output.push_str(&format!(
+            "aikv_pubsub_patterns {}\n",
+            self.pubsub.patterns.get()
+        ));
+
+        output.push_str("# HELP aikv_messages_published_total Total Pub/Sub messages published\n");
+        output.push_str("# TYPE aikv_messages_published_total counter\n");
+        output.push_str(&format!(
+            "aikv_messages_published_total {}\n",
+            self.pubsub.messages_published.get()
+        ));
+
         // Commands by type
         output.push_str("# HELP aikv_commands_by_type Commands processed by type\n");
         output.push_str("# TYPE aikv_commands_by_type counter\n");
@@ -562,6 +711,7 @@ impl Metrics {
 
     /// Get metrics summary for INFO command
     pub fn get_stats_info(&self) -> Vec<(String, String)> {
+        let (input_kbps, output_kbps) = self.connections.instantaneous_kbps();
         vec![
             (
                 "total_connections_received".to_string(),
@@ -583,6 +733,14 @@ impl Metrics {
                 "total_net_output_bytes".to_string(),
                 self.connections.bytes_sent.get().to_string(),
             ),
+            (
+                "instantaneous_input_kbps".to_string(),
+                format!("{:.2}", input_kbps),
+            ),
+            (
+                "instantaneous_output_kbps".to_string(),
+                format!("{:.2}", output_kbps),
+            ),
             (
                 "rejected_connections".to_string(),
                 self.connections.rejected_connections().to_string(),
@@ -603,6 +761,22 @@ impl Metrics {
                 "keyspace_misses".to_string(),
                 self.memory.keyspace_misses.get().to_string(),
             ),
+            (
+                "pubsub_channels".to_string(),
+                self.pubsub.channels.get().to_string(),
+            ),
+            (
+                "pubsub_patterns".to_string(),
+                self.pubsub.patterns.get().to_string(),
+            ),
+            (
+                "total_messages_published".to_string(),
+                self.pubsub.messages_published.get().to_string(),
+            ),
+            (
+                "total_error_replies".to_string(),
+                self.commands.total_error_replies().to_string(),
+            ),
         ]
     }
 }
@@ -658,6 +832,26 @@ mod tests {
         assert_eq!(by_type.get("SET"), Some(&1));
     }
 
+    #[test]
+    fn test_error_replies_by_code() {
+        let metrics = CommandMetrics::new();
+
+        metrics.record_error_reply("WRONGTYPE Operation against a key");
+        metrics.record_error_reply("WRONGTYPE Operation against a key");
+        metrics.record_error_reply("ERR syntax error");
+        metrics.record_error_reply("no code here");
+        metrics.record_error_reply("NOSPACE");
+
+        let by_code = metrics.error_replies_by_code();
+        assert_eq!(by_code.get("WRONGTYPE"), Some(&2));
+        assert_eq!(by_code.get("ERR"), Some(&2));
+        assert_eq!(by_code.get("no"), Some(&1));
+        assert_eq!(metrics.total_error_replies(), 5);
+
+        metrics.reset();
+        assert!(metrics.error_replies_by_code().is_empty());
+    }
+
     #[test]
     fn test_connection_metrics() {
         let metrics = ConnectionMetrics::new();
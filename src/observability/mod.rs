@@ -6,11 +6,14 @@
 //! - Slow query logging
 //! - Prometheus metrics
 //! - OpenTelemetry tracing integration
+//! - Process CPU and memory statistics
 
 pub mod logging;
 pub mod metrics;
+pub mod process;
 pub mod tracing_setup;
 
 pub use logging::{LogConfig, LogFormat, LoggingManager, SlowQueryLog};
-pub use metrics::{CommandMetrics, ConnectionMetrics, MemoryMetrics, Metrics};
+pub use metrics::{CommandMetrics, ConnectionMetrics, MemoryMetrics, Metrics, PubSubMetrics};
+pub use process::ProcessStats;
 pub use tracing_setup::TracingConfig;
@@ -0,0 +1,105 @@
+//! Process resource statistics
+//!
+//! INFO cpu and INFO memory report what the operating system knows about the
+//! server process. CPU times come from `getrusage(2)` on Unix and the resident
+//! set size from `/proc/self/statm` on Linux. Anything a platform cannot
+//! provide is reported as 0.
+
+/// CPU time and memory used by the server process
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProcessStats {
+    /// System CPU seconds used by the process
+    pub used_cpu_sys: f64,
+    /// User CPU seconds used by the process
+    pub used_cpu_user: f64,
+    /// System CPU seconds used by waited-for child processes
+    pub used_cpu_sys_children: f64,
+    /// User CPU seconds used by waited-for child processes
+    pub used_cpu_user_children: f64,
+    /// Resident set size in bytes
+    pub used_memory_rss: u64,
+}
+
+impl ProcessStats {
+    /// Read the current statistics of this process
+    pub fn current() -> Self {
+        let (used_cpu_sys, used_cpu_user) = cpu_times(Usage::Process);
+        let (used_cpu_sys_children, used_cpu_user_children) = cpu_times(Usage::Children);
+        Self {
+            used_cpu_sys,
+            used_cpu_user,
+            used_cpu_sys_children,
+            used_cpu_user_children,
+            used_memory_rss: resident_set_size(),
+        }
+    }
+}
+
+/// Whose resource usage to read
+#[derive(Debug, Clone, Copy)]
+enum Usage {
+    Process,
+    Children,
+}
+
+/// (system, user) CPU seconds
+#[cfg(unix)]
+fn cpu_times(usage: Usage) -> (f64, f64) {
+    let who = match usage {
+        Usage::Process => libc::RUSAGE_SELF,
+        Usage::Children => libc::RUSAGE_CHILDREN,
+    };
+
+    // SAFETY: getrusage only writes into the zeroed struct we hand it
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(who, &mut rusage) } != 0 {
+        return (0.0, 0.0);
+    }
+
+    let seconds = |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 / 1_000_000.0;
+    (seconds(rusage.ru_stime), seconds(rusage.ru_utime))
+}
+
+#[cfg(not(unix))]
+fn cpu_times(_usage: Usage) -> (f64, f64) {
+    (0.0, 0.0)
+}
+
+/// Resident set size in bytes
+#[cfg(target_os = "linux")]
+fn resident_set_size() -> u64 {
+    // statm reports sizes in pages: "size resident shared ..."
+    let pages = std::fs::read_to_string("/proc/self/statm")
+        .ok()
+        .and_then(|statm| statm.split_whitespace().nth(1)?.parse::<u64>().ok())
+        .unwrap_or(0);
+
+    // SAFETY: sysconf has no preconditions
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    pages * page_size.max(0) as u64
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident_set_size() -> u64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_current_process_stats() {
+        // Burn a little CPU so the process has measurable user time
+        let mut sum = 0u64;
+        for i in 0..5_000_000u64 {
+            sum = std::hint::black_box(sum.wrapping_add(i * i));
+        }
+        assert!(sum > 0);
+
+        let stats = ProcessStats::current();
+        assert!(stats.used_memory_rss > 0);
+        assert!(stats.used_cpu_user + stats.used_cpu_sys > 0.0);
+    }
+}
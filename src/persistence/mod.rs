@@ -4,4 +4,8 @@ pub mod rdb;
 
 pub use aof::{load_aof, AofReader, AofWriter};
 pub use config::{AofSyncPolicy, PersistenceConfig};
-pub use rdb::{load_rdb, save_rdb, DatabaseData, RdbReader, RdbWriter};
+pub use rdb::{
+    load_rdb, load_rdb_with_scripts, load_snapshot, restore_storage, save_rdb,
+    save_rdb_with_scripts, save_snapshot, snapshot_storage, DatabaseData, DatabaseValues,
+    RdbReader, RdbWriter,
+};
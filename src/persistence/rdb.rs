@@ -1,4 +1,5 @@
 use crate::error::{AikvError, Result};
+use crate::storage::{SerializableStoredValue, StorageEngine, StoredValue, ValueType};
 use bytes::Bytes;
 use std::collections::HashMap;
 use std::fs::File;
@@ -12,12 +13,25 @@ const RDB_MAGIC: &[u8] = b"REDIS";
 /// Type alias for database data structure
 pub type DatabaseData = HashMap<String, (Bytes, Option<u64>)>;
 
+/// Every value of one database, as a snapshot of a running server holds them
+pub type DatabaseValues = HashMap<String, StoredValue>;
+
 /// Opcodes for RDB file format
 const OPCODE_EOF: u8 = 0xFF;
 const OPCODE_SELECTDB: u8 = 0xFE;
 const OPCODE_EXPIRETIME_MS: u8 = 0xFC;
 const OPCODE_AUX: u8 = 0xFA;
 
+/// Value type of strings
+const TYPE_STRING: u8 = 0;
+/// Value type of lists, hashes, sets and sorted sets, stored in the encoding
+/// DUMP payloads use. Redis assigns no value type this high.
+const TYPE_AIKV_VALUE: u8 = 0x7F;
+
+/// Auxiliary field holding the source of one cached Lua script, as older
+/// Redis versions stored them
+const AUX_LUA: &str = "lua";
+
 /// RDB writer for creating database snapshots
 pub struct RdbWriter<W: Write> {
     writer: BufWriter<W>,
@@ -94,8 +108,14 @@ impl<W: Write> RdbWriter<W> {
         Ok(())
     }
 
-    /// Write a key-value pair
-    fn write_key_value(&mut self, key: &str, value: &[u8], expire_ms: Option<u64>) -> Result<()> {
+    /// Write a key-value pair holding a value of type `value_type`
+    fn write_key_value(
+        &mut self,
+        value_type: u8,
+        key: &str,
+        value: &[u8],
+        expire_ms: Option<u64>,
+    ) -> Result<()> {
         // Write expiration if present
         if let Some(expire_at) = expire_ms {
             self.writer
@@ -108,9 +128,9 @@ impl<W: Write> RdbWriter<W> {
                 })?;
         }
 
-        // Write value type (0 = string)
+        // Write value type
         self.writer
-            .write_all(&[0])
+            .write_all(&[value_type])
             .map_err(|e| AikvError::Persistence(format!("Failed to write type: {}", e)))?;
 
         // Write key
@@ -125,6 +145,14 @@ impl<W: Write> RdbWriter<W> {
         Ok(())
     }
 
+    /// Write cached Lua scripts, one auxiliary field each
+    pub fn write_scripts(&mut self, scripts: &[String]) -> Result<()> {
+        for script in scripts {
+            self.write_aux(AUX_LUA, script)?;
+        }
+        Ok(())
+    }
+
     /// Write database snapshot
     pub fn write_database(&mut self, db_index: usize, data: &DatabaseData) -> Result<()> {
         if data.is_empty() {
@@ -134,7 +162,32 @@ impl<W: Write> RdbWriter<W> {
         self.write_select_db(db_index)?;
 
         for (key, (value, expire_ms)) in data {
-            self.write_key_value(key, value, *expire_ms)?;
+            self.write_key_value(TYPE_STRING, key, value, *expire_ms)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write database snapshot holding values of every type
+    pub fn write_values(&mut self, db_index: usize, data: &DatabaseValues) -> Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        self.write_select_db(db_index)?;
+
+        for (key, value) in data {
+            match value.value() {
+                ValueType::String(bytes) => {
+                    self.write_key_value(TYPE_STRING, key, bytes, value.expires_at())?
+                }
+                _ => {
+                    let encoded = bincode::serialize(&value.to_serializable()).map_err(|e| {
+                        AikvError::Persistence(format!("Failed to encode value: {}", e))
+                    })?;
+                    self.write_key_value(TYPE_AIKV_VALUE, key, &encoded, value.expires_at())?
+                }
+            }
         }
 
         Ok(())
@@ -162,15 +215,23 @@ impl<W: Write> RdbWriter<W> {
 /// RDB reader for loading database snapshots
 pub struct RdbReader<R: Read> {
     reader: BufReader<R>,
+    /// Lua scripts found by [`RdbReader::load`]
+    scripts: Vec<String>,
 }
 
 impl<R: Read> RdbReader<R> {
     pub fn new(reader: R) -> Self {
         Self {
             reader: BufReader::new(reader),
+            scripts: Vec::new(),
         }
     }
 
+    /// Lua scripts stored in the snapshot, in the order they were written
+    pub fn scripts(&self) -> &[String] {
+        &self.scripts
+    }
+
     /// Read and verify RDB header
     fn read_header(&mut self) -> Result<u16> {
         let mut magic = [0u8; 5];
@@ -249,10 +310,32 @@ impl<R: Read> RdbReader<R> {
     }
 
     /// Load database from RDB file
+    ///
+    /// Only string values are returned; [`RdbReader::load_values`] loads
+    /// values of every type.
     pub fn load(&mut self) -> Result<Vec<DatabaseData>> {
+        Ok(self
+            .load_values()?
+            .into_iter()
+            .map(|values| {
+                values
+                    .into_iter()
+                    .filter_map(|(key, value)| match value.value() {
+                        ValueType::String(bytes) => {
+                            Some((key, (bytes.clone(), value.expires_at())))
+                        }
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Load every value from RDB file
+    pub fn load_values(&mut self) -> Result<Vec<DatabaseValues>> {
         let _version = self.read_header()?;
 
-        let mut databases: Vec<DatabaseData> = vec![HashMap::new(); 16];
+        let mut databases: Vec<DatabaseValues> = vec![HashMap::new(); 16];
         let mut current_db = 0;
         let mut expire_ms: Option<u64> = None;
 
@@ -283,17 +366,35 @@ impl<R: Read> RdbReader<R> {
                     expire_ms = Some(u64::from_le_bytes(time_bytes));
                 }
                 OPCODE_AUX => {
-                    // Skip auxiliary fields
-                    let _key = self.read_string()?;
-                    let _value = self.read_string()?;
+                    // Keep cached scripts and skip other auxiliary fields
+                    let key = self.read_string()?;
+                    let value = self.read_string()?;
+                    if key == AUX_LUA {
+                        self.scripts.push(value);
+                    }
                 }
-                // Value type (0 = string)
-                0 => {
+                TYPE_STRING => {
                     let key = self.read_string()?;
                     let value_len = self.read_length()?;
                     let value = self.read_bytes(value_len)?;
 
-                    databases[current_db].insert(key, (Bytes::from(value), expire_ms));
+                    let mut value = StoredValue::new_string(Bytes::from(value));
+                    value.set_expiration(expire_ms);
+                    databases[current_db].insert(key, value);
+                    expire_ms = None;
+                }
+                TYPE_AIKV_VALUE => {
+                    let key = self.read_string()?;
+                    let value_len = self.read_length()?;
+                    let encoded = self.read_bytes(value_len)?;
+
+                    let value: SerializableStoredValue =
+                        bincode::deserialize(&encoded).map_err(|e| {
+                            AikvError::Persistence(format!("Failed to decode value: {}", e))
+                        })?;
+                    let mut value = StoredValue::from_serializable(value);
+                    value.set_expiration(expire_ms);
+                    databases[current_db].insert(key, value);
                     expire_ms = None;
                 }
                 _ => {
@@ -311,6 +412,67 @@ impl<R: Read> RdbReader<R> {
 
 /// Save database to RDB file
 pub fn save_rdb<P: AsRef<Path>>(path: P, databases: &[DatabaseData]) -> Result<()> {
+    save_rdb_with_scripts(path, databases, &[])
+}
+
+/// Save database to RDB file together with cached Lua scripts, so EVALSHA
+/// keeps resolving them once the snapshot is loaded again
+pub fn save_rdb_with_scripts<P: AsRef<Path>>(
+    path: P,
+    databases: &[DatabaseData],
+    scripts: &[String],
+) -> Result<()> {
+    write_rdb(path, scripts, |writer| {
+        for (db_index, db_data) in databases.iter().enumerate() {
+            writer.write_database(db_index, db_data)?;
+        }
+        Ok(())
+    })
+}
+
+/// Save a snapshot of values of every type to RDB file together with cached
+/// Lua scripts
+///
+/// The snapshot is written to a temporary file next to `path` and then renamed
+/// over it, so a failed save leaves the previous snapshot in place.
+pub fn save_snapshot<P: AsRef<Path>>(
+    path: P,
+    databases: &[DatabaseValues],
+    scripts: &[String],
+) -> Result<()> {
+    let path = path.as_ref();
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| AikvError::Persistence("RDB path has no file name".to_string()))?;
+    let temp_path = path.with_file_name(format!(
+        "temp-{}-{}",
+        std::process::id(),
+        file_name.to_string_lossy()
+    ));
+
+    let written = write_rdb(&temp_path, scripts, |writer| {
+        for (db_index, db_data) in databases.iter().enumerate() {
+            writer.write_values(db_index, db_data)?;
+        }
+        Ok(())
+    })
+    .and_then(|()| {
+        std::fs::rename(&temp_path, path)
+            .map_err(|e| AikvError::Persistence(format!("Failed to rename RDB file: {}", e)))
+    });
+    if written.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    written
+}
+
+/// Create an RDB file with its header and metadata, letting `write_databases`
+/// write the database sections
+fn write_rdb<P: AsRef<Path>>(
+    path: P,
+    scripts: &[String],
+    write_databases: impl FnOnce(&mut RdbWriter<File>) -> Result<()>,
+) -> Result<()> {
     let file = File::create(path)
         .map_err(|e| AikvError::Persistence(format!("Failed to create RDB file: {}", e)))?;
 
@@ -324,23 +486,67 @@ pub fn save_rdb<P: AsRef<Path>>(path: P, databases: &[DatabaseData]) -> Result<(
         .as_secs();
     writer.write_aux("ctime", &now.to_string())?;
     writer.write_aux("aikv-ver", env!("CARGO_PKG_VERSION"))?;
+    writer.write_scripts(scripts)?;
 
     // Write each database
-    for (db_index, db_data) in databases.iter().enumerate() {
-        writer.write_database(db_index, db_data)?;
-    }
+    write_databases(&mut writer)?;
 
     writer.finish()?;
     Ok(())
 }
 
+/// Collect every value held by `storage`, one map per database
+pub fn snapshot_storage(storage: &StorageEngine) -> Result<Vec<DatabaseValues>> {
+    let mut databases = Vec::new();
+    for db_index in 0..storage.db_count()? {
+        let mut values = HashMap::new();
+        for key in storage.get_all_keys_in_db(db_index)? {
+            if let Some(value) = storage.get_value(db_index, &key)? {
+                values.insert(key, value);
+            }
+        }
+        databases.push(values);
+    }
+    Ok(databases)
+}
+
+/// Put the values of a loaded snapshot into `storage`, skipping any that
+/// expired while the server was down
+pub fn restore_storage(storage: &StorageEngine, databases: Vec<DatabaseValues>) -> Result<()> {
+    for (db_index, values) in databases.into_iter().enumerate() {
+        for (key, value) in values {
+            if !value.is_expired() {
+                storage.replace_value(db_index, key, value)?;
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Load database from RDB file
 pub fn load_rdb<P: AsRef<Path>>(path: P) -> Result<Vec<DatabaseData>> {
+    Ok(load_rdb_with_scripts(path)?.0)
+}
+
+/// Load a snapshot of values of every type from RDB file along with the Lua
+/// scripts saved in it
+pub fn load_snapshot<P: AsRef<Path>>(path: P) -> Result<(Vec<DatabaseValues>, Vec<String>)> {
     let file = File::open(path)
         .map_err(|e| AikvError::Persistence(format!("Failed to open RDB file: {}", e)))?;
 
     let mut reader = RdbReader::new(file);
-    reader.load()
+    let databases = reader.load_values()?;
+    Ok((databases, reader.scripts))
+}
+
+/// Load database from RDB file along with the Lua scripts saved in it
+pub fn load_rdb_with_scripts<P: AsRef<Path>>(path: P) -> Result<(Vec<DatabaseData>, Vec<String>)> {
+    let file = File::open(path)
+        .map_err(|e| AikvError::Persistence(format!("Failed to open RDB file: {}", e)))?;
+
+    let mut reader = RdbReader::new(file);
+    let databases = reader.load()?;
+    Ok((databases, reader.scripts))
 }
 
 #[cfg(test)]
@@ -383,6 +589,44 @@ mod tests {
         assert_eq!(loaded[1].get("key3").unwrap().0, Bytes::from("value3"));
     }
 
+    #[test]
+    fn test_snapshot_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("dump.rdb");
+
+        let storage = StorageEngine::new_memory(16);
+        storage
+            .set_in_db(0, "temp".to_string(), Bytes::from("value"))
+            .unwrap();
+        storage.set_expire_in_db(0, "temp", 100_000).unwrap();
+        let list = ["a", "b"].into_iter().map(Bytes::from).collect();
+        storage
+            .set_value(1, "list".to_string(), StoredValue::new_list(list))
+            .unwrap();
+
+        let databases = snapshot_storage(&storage).unwrap();
+        save_snapshot(&path, &databases, &["return 1".to_string()]).unwrap();
+
+        let (loaded, scripts) = load_snapshot(&path).unwrap();
+        assert_eq!(scripts, vec!["return 1".to_string()]);
+        let restored = StorageEngine::new_memory(16);
+        restore_storage(&restored, loaded).unwrap();
+        assert_eq!(
+            restored.get_from_db(0, "temp").unwrap(),
+            Some(Bytes::from("value"))
+        );
+        assert!(restored.get_ttl_in_db(0, "temp").unwrap() > 90_000);
+        match restored.get_value(1, "list").unwrap().unwrap().value() {
+            ValueType::List(list) => assert_eq!(list, &["a", "b"]),
+            _ => panic!("Expected a list"),
+        }
+
+        // Plain loads keep only the strings
+        let loaded = load_rdb(&path).unwrap();
+        assert_eq!(loaded[0].len(), 1);
+        assert!(loaded[1].is_empty());
+    }
+
     #[test]
     fn test_rdb_length_encoding() {
         let mut cursor = Cursor::new(Vec::new());
@@ -1,4 +1,4 @@
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 
 /// RESP (REdis Serialization Protocol) value types
 /// Supports both RESP2 and RESP3 protocol versions
@@ -164,111 +164,195 @@ impl RespValue {
         RespValue::StreamedString(chunks)
     }
 
+    /// Downgrade RESP3-only types to their RESP2 equivalents, recursively.
+    ///
+    /// Commands build protocol-agnostic replies (e.g. a `Map` for field/value
+    /// pairs); RESP2 connections send them through this conversion so maps
+    /// become flat arrays, nulls become null bulk strings, and so on.
+    pub fn into_resp2(self) -> RespValue {
+        match self {
+            RespValue::Array(Some(arr)) => {
+                RespValue::Array(Some(arr.into_iter().map(Self::into_resp2).collect()))
+            }
+            RespValue::Null => RespValue::BulkString(None),
+            RespValue::Boolean(b) => RespValue::Integer(b as i64),
+            RespValue::Double(d) => RespValue::bulk_string(Self::format_double(d)),
+            RespValue::BigNumber(s) => RespValue::bulk_string(s),
+            RespValue::BulkError(e) => RespValue::Error(e),
+            RespValue::VerbatimString {
+                data,
+                ..
+            } => RespValue::BulkString(Some(data)),
+            RespValue::Map(pairs) => RespValue::array(
+                pairs
+                    .into_iter()
+                    .flat_map(|(key, value)| [key.into_resp2(), value.into_resp2()])
+                    .collect(),
+            ),
+            RespValue::Set(items) | RespValue::Push(items) => {
+                RespValue::array(items.into_iter().map(Self::into_resp2).collect())
+            }
+            RespValue::Attribute {
+                data,
+                ..
+            } => data.into_resp2(),
+            RespValue::StreamedString(chunks) => RespValue::bulk_string(chunks.concat()),
+            other => other,
+        }
+    }
+
+    /// Replace RESP2 nulls with the RESP3 null, recursively.
+    ///
+    /// Commands reply with the null RESP2 expects (a null bulk string for a
+    /// missing value, a null array for a missing list of values); RESP3 has a
+    /// single null, so RESP3 connections send their replies through this.
+    pub fn into_resp3(self) -> RespValue {
+        match self {
+            RespValue::BulkString(None) | RespValue::Array(None) => RespValue::Null,
+            RespValue::Array(Some(arr)) => {
+                RespValue::Array(Some(arr.into_iter().map(Self::into_resp3).collect()))
+            }
+            RespValue::Map(pairs) => RespValue::Map(
+                pairs
+                    .into_iter()
+                    .map(|(key, value)| (key.into_resp3(), value.into_resp3()))
+                    .collect(),
+            ),
+            RespValue::Set(items) => {
+                RespValue::Set(items.into_iter().map(Self::into_resp3).collect())
+            }
+            RespValue::Push(items) => {
+                RespValue::Push(items.into_iter().map(Self::into_resp3).collect())
+            }
+            RespValue::Attribute {
+                attributes,
+                data,
+            } => RespValue::Attribute {
+                attributes: attributes
+                    .into_iter()
+                    .map(|(key, value)| (key.into_resp3(), value.into_resp3()))
+                    .collect(),
+                data: Box::new(data.into_resp3()),
+            },
+            other => other,
+        }
+    }
+
+    /// Format a double the way both protocols print it (`inf`, `-inf`, `nan`,
+    /// `1.5`, and integral values without a decimal point)
+    fn format_double(d: f64) -> String {
+        if d.is_nan() {
+            "nan".to_string()
+        } else if d.is_infinite() {
+            if d.is_sign_positive() {
+                "inf".to_string()
+            } else {
+                "-inf".to_string()
+            }
+        } else {
+            d.to_string()
+        }
+    }
+
     /// Serialize to RESP format bytes
     /// Supports both RESP2 and RESP3 formats
     pub fn serialize(&self) -> Bytes {
+        let mut out = BytesMut::new();
+        self.write_to(&mut out);
+        out.freeze()
+    }
+
+    /// Append the RESP encoding of this value to `out`.
+    ///
+    /// Bulk data is copied byte for byte, so binary values such as DUMP
+    /// payloads survive the trip.
+    fn write_to(&self, out: &mut BytesMut) {
         match self {
             // RESP2 types
-            RespValue::SimpleString(s) => Bytes::from(format!("+{}\r\n", s)),
-            RespValue::Error(e) => Bytes::from(format!("-{}\r\n", e)),
-            RespValue::Integer(i) => Bytes::from(format!(":{}\r\n", i)),
-            RespValue::BulkString(None) => Bytes::from("$-1\r\n"),
+            RespValue::SimpleString(s) => out.extend_from_slice(format!("+{}\r\n", s).as_bytes()),
+            RespValue::Error(e) => out.extend_from_slice(format!("-{}\r\n", e).as_bytes()),
+            RespValue::Integer(i) => out.extend_from_slice(format!(":{}\r\n", i).as_bytes()),
+            RespValue::BulkString(None) => out.extend_from_slice(b"$-1\r\n"),
             RespValue::BulkString(Some(s)) => {
-                let mut result = format!("${}\r\n", s.len());
-                result.push_str(&String::from_utf8_lossy(s));
-                result.push_str("\r\n");
-                Bytes::from(result)
+                out.extend_from_slice(format!("${}\r\n", s.len()).as_bytes());
+                out.extend_from_slice(s);
+                out.extend_from_slice(b"\r\n");
             }
-            RespValue::Array(None) => Bytes::from("*-1\r\n"),
+            RespValue::Array(None) => out.extend_from_slice(b"*-1\r\n"),
             RespValue::Array(Some(arr)) => {
-                let mut result = format!("*{}\r\n", arr.len());
+                out.extend_from_slice(format!("*{}\r\n", arr.len()).as_bytes());
                 for item in arr {
-                    result.push_str(&String::from_utf8_lossy(&item.serialize()));
+                    item.write_to(out);
                 }
-                Bytes::from(result)
             }
             // RESP3 types
-            RespValue::Null => Bytes::from("_\r\n"),
+            RespValue::Null => out.extend_from_slice(b"_\r\n"),
             RespValue::Boolean(b) => {
                 if *b {
-                    Bytes::from("#t\r\n")
+                    out.extend_from_slice(b"#t\r\n")
                 } else {
-                    Bytes::from("#f\r\n")
+                    out.extend_from_slice(b"#f\r\n")
                 }
             }
             RespValue::Double(d) => {
-                if d.is_infinite() {
-                    if d.is_sign_positive() {
-                        Bytes::from(",inf\r\n")
-                    } else {
-                        Bytes::from(",-inf\r\n")
-                    }
-                } else {
-                    Bytes::from(format!(",{}\r\n", d))
-                }
+                out.extend_from_slice(format!(",{}\r\n", Self::format_double(*d)).as_bytes())
             }
-            RespValue::BigNumber(s) => Bytes::from(format!("({}\r\n", s)),
+            RespValue::BigNumber(s) => out.extend_from_slice(format!("({}\r\n", s).as_bytes()),
             RespValue::BulkError(e) => {
                 let bytes = e.as_bytes();
-                Bytes::from(format!("!{}\r\n{}\r\n", bytes.len(), e))
+                out.extend_from_slice(format!("!{}\r\n{}\r\n", bytes.len(), e).as_bytes())
             }
             RespValue::VerbatimString {
                 format,
                 data,
             } => {
                 let total_len = format.len() + 1 + data.len(); // format + ':' + data
-                let mut result = format!("={}\r\n{}:", total_len, format);
-                result.push_str(&String::from_utf8_lossy(data));
-                result.push_str("\r\n");
-                Bytes::from(result)
+                out.extend_from_slice(format!("={}\r\n{}:", total_len, format).as_bytes());
+                out.extend_from_slice(data);
+                out.extend_from_slice(b"\r\n");
             }
             RespValue::Map(pairs) => {
-                let mut result = format!("%{}\r\n", pairs.len());
+                out.extend_from_slice(format!("%{}\r\n", pairs.len()).as_bytes());
                 for (key, value) in pairs {
-                    result.push_str(&String::from_utf8_lossy(&key.serialize()));
-                    result.push_str(&String::from_utf8_lossy(&value.serialize()));
+                    key.write_to(out);
+                    value.write_to(out);
                 }
-                Bytes::from(result)
             }
             RespValue::Set(items) => {
-                let mut result = format!("~{}\r\n", items.len());
+                out.extend_from_slice(format!("~{}\r\n", items.len()).as_bytes());
                 for item in items {
-                    result.push_str(&String::from_utf8_lossy(&item.serialize()));
+                    item.write_to(out);
                 }
-                Bytes::from(result)
             }
             RespValue::Push(items) => {
-                let mut result = format!(">{}\r\n", items.len());
+                out.extend_from_slice(format!(">{}\r\n", items.len()).as_bytes());
                 for item in items {
-                    result.push_str(&String::from_utf8_lossy(&item.serialize()));
+                    item.write_to(out);
                 }
-                Bytes::from(result)
             }
             RespValue::Attribute {
                 attributes,
                 data,
             } => {
                 // Serialize attributes map followed by the actual data
-                let mut result = format!("|{}\r\n", attributes.len());
+                out.extend_from_slice(format!("|{}\r\n", attributes.len()).as_bytes());
                 for (key, value) in attributes {
-                    result.push_str(&String::from_utf8_lossy(&key.serialize()));
-                    result.push_str(&String::from_utf8_lossy(&value.serialize()));
+                    key.write_to(out);
+                    value.write_to(out);
                 }
                 // Append the actual data
-                result.push_str(&String::from_utf8_lossy(&data.serialize()));
-                Bytes::from(result)
+                data.write_to(out);
             }
             RespValue::StreamedString(chunks) => {
                 // Streamed string format: $?\r\n;len\r\ndata\r\n...;0\r\n
-                let mut result = String::from("$?\r\n");
+                out.extend_from_slice(b"$?\r\n");
                 for chunk in chunks {
-                    result.push_str(&format!(";{}\r\n", chunk.len()));
-                    result.push_str(&String::from_utf8_lossy(chunk));
-                    result.push_str("\r\n");
+                    out.extend_from_slice(format!(";{}\r\n", chunk.len()).as_bytes());
+                    out.extend_from_slice(chunk);
+                    out.extend_from_slice(b"\r\n");
                 }
                 // Terminator
-                result.push_str(";0\r\n");
-                Bytes::from(result)
+                out.extend_from_slice(b";0\r\n");
             }
         }
     }
@@ -302,6 +386,15 @@ mod tests {
         assert_eq!(val.serialize(), Bytes::from("$6\r\nfoobar\r\n"));
     }
 
+    #[test]
+    fn test_binary_bulk_string() {
+        let val = RespValue::array(vec![RespValue::bulk_string(vec![0xff, 0x00, 0xfe])]);
+        assert_eq!(
+            val.serialize(),
+            Bytes::from_static(b"*1\r\n$3\r\n\xff\x00\xfe\r\n")
+        );
+    }
+
     #[test]
     fn test_null_bulk_string() {
         let val = RespValue::null_bulk_string();
@@ -363,6 +456,15 @@ mod tests {
         assert_eq!(val.serialize(), Bytes::from(",-inf\r\n"));
     }
 
+    #[test]
+    fn test_double_integral_and_nan() {
+        assert_eq!(RespValue::double(3.0).serialize(), Bytes::from(",3\r\n"));
+        assert_eq!(
+            RespValue::double(f64::NAN).serialize(),
+            Bytes::from(",nan\r\n")
+        );
+    }
+
     #[test]
     fn test_big_number() {
         let val = RespValue::big_number("3492890328409238509324850943850943825024385");
@@ -467,4 +569,75 @@ mod tests {
             Bytes::from("|2\r\n+server\r\n+aikv\r\n+version\r\n,1\r\n*2\r\n$6\r\nvalue1\r\n$6\r\nvalue2\r\n")
         );
     }
+
+    #[test]
+    fn test_into_resp2() {
+        let val = RespValue::map(vec![
+            (
+                RespValue::bulk_string("name"),
+                RespValue::bulk_string("aikv"),
+            ),
+            (RespValue::bulk_string("missing"), RespValue::null()),
+            (
+                RespValue::bulk_string("flags"),
+                RespValue::set(vec![RespValue::boolean(true)]),
+            ),
+            (
+                RespValue::bulk_string("score"),
+                RespValue::double(f64::INFINITY),
+            ),
+        ]);
+        assert_eq!(
+            val.into_resp2(),
+            RespValue::array(vec![
+                RespValue::bulk_string("name"),
+                RespValue::bulk_string("aikv"),
+                RespValue::bulk_string("missing"),
+                RespValue::null_bulk_string(),
+                RespValue::bulk_string("flags"),
+                RespValue::array(vec![RespValue::integer(1)]),
+                RespValue::bulk_string("score"),
+                RespValue::bulk_string("inf"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_nulls_per_protocol() {
+        // RESP2 keeps the null bulk string and the null array apart
+        assert_eq!(
+            RespValue::null_bulk_string().into_resp2().serialize(),
+            Bytes::from("$-1\r\n")
+        );
+        assert_eq!(
+            RespValue::null_array().into_resp2().serialize(),
+            Bytes::from("*-1\r\n")
+        );
+        assert_eq!(
+            RespValue::null().into_resp2().serialize(),
+            Bytes::from("$-1\r\n")
+        );
+
+        // RESP3 has one null for all of them, nested ones included
+        for null in [
+            RespValue::null_bulk_string(),
+            RespValue::null_array(),
+            RespValue::null(),
+        ] {
+            assert_eq!(null.into_resp3().serialize(), Bytes::from("_\r\n"));
+        }
+        assert_eq!(
+            RespValue::array(vec![
+                RespValue::bulk_string("a"),
+                RespValue::null_bulk_string()
+            ])
+            .into_resp3(),
+            RespValue::array(vec![RespValue::bulk_string("a"), RespValue::null()])
+        );
+        assert_eq!(
+            RespValue::map(vec![(RespValue::bulk_string("k"), RespValue::null_array())])
+                .into_resp3(),
+            RespValue::map(vec![(RespValue::bulk_string("k"), RespValue::null())])
+        );
+    }
 }
@@ -1,23 +1,43 @@
+use crate::command::list::BlockingPop;
 use crate::command::CommandExecutor;
 use crate::error::Result;
 use crate::observability::Metrics;
 use crate::protocol::{RespParser, RespValue};
 use crate::server::monitor::MonitorBroadcaster;
+use crate::server::pubsub::{self, PubSubReceiver, PubSubSender};
 use bytes::Bytes;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
 use tokio::select;
+use tokio::sync::Notify;
 use tracing::{debug, warn};
 
-static CLIENT_ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
+/// Client ids start at 1 like in Redis, where CLIENT KILL ID rejects 0
+static CLIENT_ID_COUNTER: AtomicUsize = AtomicUsize::new(1);
 
 /// Commands that should not be broadcast to MONITOR clients.
 /// These are typically internal, debugging, or replication commands.
 const MONITOR_EXCLUDED_COMMANDS: &[&str] = &["MONITOR", "DEBUG", "SYNC", "PSYNC"];
 
+/// Commands that change the connection's Pub/Sub subscriptions.
+/// Each replies once per channel or pattern rather than once per command.
+const SUBSCRIBE_COMMANDS: &[&str] = &[
+    "SUBSCRIBE",
+    "UNSUBSCRIBE",
+    "PSUBSCRIBE",
+    "PUNSUBSCRIBE",
+    "SSUBSCRIBE",
+    "SUNSUBSCRIBE",
+];
+
+/// Commands a connection may run before it has authenticated
+const PRE_AUTH_COMMANDS: &[&str] = &["AUTH", "HELLO", "QUIT", "RESET"];
+
 /// Protocol version
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ProtocolVersion {
@@ -32,9 +52,14 @@ enum ConnectionMode {
     Monitor,
 }
 
+/// A client stream the server accepts connections on: TCP or a Unix socket
+pub trait ClientStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ClientStream for T {}
+
 /// Connection handler for a single client
 pub struct Connection {
-    stream: TcpStream,
+    stream: Box<dyn ClientStream>,
     parser: RespParser,
     executor: CommandExecutor,
     protocol_version: ProtocolVersion,
@@ -44,6 +69,14 @@ pub struct Connection {
     client_addr: String,
     monitor_broadcaster: Option<Arc<MonitorBroadcaster>>,
     mode: ConnectionMode,
+    pubsub_sender: PubSubSender,
+    pubsub_receiver: PubSubReceiver,
+    /// Woken when CLIENT KILL closes this connection
+    kill: Arc<Notify>,
+    closing: bool,
+    /// Password clients must AUTH with, if the server requires one
+    requirepass: Option<String>,
+    authenticated: bool,
 }
 
 impl Connection {
@@ -62,19 +95,74 @@ impl Connection {
         metrics: Option<Arc<Metrics>>,
         monitor_broadcaster: Option<Arc<MonitorBroadcaster>>,
     ) -> Self {
-        let client_id = CLIENT_ID_COUNTER.fetch_add(1, Ordering::SeqCst);
         let peer_addr = stream
             .peer_addr()
             .map(|addr| addr.to_string())
             .unwrap_or_else(|_| "unknown".to_string());
 
+        let local_addr = stream
+            .local_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_else(|_| "unknown".to_string());
+
+        Self::with_stream(
+            Box::new(stream),
+            peer_addr,
+            local_addr,
+            executor,
+            metrics,
+            monitor_broadcaster,
+        )
+    }
+
+    /// Create a connection handler for a client of the Unix socket.
+    ///
+    /// Unix socket clients have no address of their own, so like Redis both
+    /// addresses report the socket path with port 0.
+    #[cfg(unix)]
+    pub fn new_unix(
+        stream: UnixStream,
+        executor: CommandExecutor,
+        metrics: Option<Arc<Metrics>>,
+        monitor_broadcaster: Option<Arc<MonitorBroadcaster>>,
+    ) -> Self {
+        let path = stream
+            .local_addr()
+            .ok()
+            .and_then(|addr| addr.as_pathname().map(|p| p.display().to_string()))
+            .unwrap_or_else(|| "unknown".to_string());
+        let addr = format!("{}:0", path);
+
+        Self::with_stream(
+            Box::new(stream),
+            addr.clone(),
+            addr,
+            executor,
+            metrics,
+            monitor_broadcaster,
+        )
+    }
+
+    fn with_stream(
+        stream: Box<dyn ClientStream>,
+        peer_addr: String,
+        local_addr: String,
+        executor: CommandExecutor,
+        metrics: Option<Arc<Metrics>>,
+        monitor_broadcaster: Option<Arc<MonitorBroadcaster>>,
+    ) -> Self {
+        let client_id = CLIENT_ID_COUNTER.fetch_add(1, Ordering::SeqCst);
+
         // Register client
-        if let Err(e) = executor
+        let kill = executor
             .server_commands()
-            .register_client(client_id, peer_addr.clone())
-        {
-            warn!("Failed to register client: {}", e);
-        }
+            .register_client(client_id, peer_addr.clone(), local_addr)
+            .unwrap_or_else(|e| {
+                warn!("Failed to register client: {}", e);
+                Arc::new(Notify::new())
+            });
+
+        let (pubsub_sender, pubsub_receiver) = pubsub::message_queue();
 
         Self {
             stream,
@@ -87,11 +175,32 @@ impl Connection {
             client_addr: peer_addr,
             monitor_broadcaster,
             mode: ConnectionMode::Normal,
+            pubsub_sender,
+            pubsub_receiver,
+            kill,
+            closing: false,
+            requirepass: None,
+            authenticated: true,
         }
     }
 
-    /// Handle the connection using a state machine
+    /// Require the client to AUTH with `password` before running other commands
+    pub fn set_requirepass(&mut self, password: Option<String>) {
+        self.authenticated = password.is_none();
+        self.requirepass = password;
+    }
+
+    /// Handle the connection until it closes.
+    ///
+    /// Cleanup runs however the connection ends, including on I/O errors.
     pub async fn handle(&mut self) -> Result<()> {
+        let result = self.run().await;
+        self.cleanup().await;
+        result
+    }
+
+    /// Run the connection state machine
+    async fn run(&mut self) -> Result<()> {
         loop {
             match self.mode {
                 ConnectionMode::Normal => {
@@ -107,14 +216,42 @@ impl Connection {
             }
         }
 
-        self.cleanup().await;
         Ok(())
     }
 
     /// Handle normal command mode. Returns false if connection should close.
     async fn handle_normal_mode(&mut self) -> Result<bool> {
-        // Read data from the client
-        let n = self.stream.read_buf(self.parser.buffer_mut()).await?;
+        // Read data from the client, or forward a published message
+        let n = select! {
+            result = self.stream.read_buf(self.parser.buffer_mut()) => result?,
+            _ = self.kill.notified() => {
+                debug!("Client {} killed", self.client_id);
+                return Ok(false);
+            }
+            message = self.pubsub_receiver.recv() => {
+                // No message means the queue overflowed. The write is abandoned
+                // on overflow too, so a subscriber that stopped reading can't
+                // keep the connection blocked.
+                let overflowed = self.pubsub_receiver.overflowed();
+                let delivered = match message {
+                    Some(message) => select! {
+                        result = self.write_response(message.to_resp()) => {
+                            result?;
+                            true
+                        }
+                        _ = overflowed => false,
+                    },
+                    None => false,
+                };
+                if !delivered {
+                    warn!(
+                        "Client {} closed for overcoming of Pub/Sub output buffer limits",
+                        self.client_id
+                    );
+                }
+                return Ok(delivered);
+            }
+        };
 
         if n == 0 {
             // Connection closed
@@ -128,8 +265,17 @@ impl Connection {
 
         // Parse and process commands
         while let Some(value) = self.parser.parse()? {
-            let response = self.process_command(value).await;
-            self.write_response(response).await?;
+            for response in self.dispatch(value).await {
+                if let (RespValue::Error(message), Some(metrics)) = (&response, &self.metrics) {
+                    metrics.commands.record_error_reply(message);
+                }
+                self.write_response(response).await?;
+            }
+
+            // QUIT closes the connection once its reply is written
+            if self.closing {
+                return Ok(false);
+            }
 
             // Check if mode changed to monitor
             if self.mode == ConnectionMode::Monitor {
@@ -178,6 +324,10 @@ impl Connection {
                         }
                     }
                 }
+                _ = self.kill.notified() => {
+                    broadcaster.unregister_monitor(self.client_id).await;
+                    return Ok(false);
+                }
                 // Check for client input (QUIT, RESET, or disconnect)
                 result = self.stream.read_buf(self.parser.buffer_mut()) => {
                     match result {
@@ -186,7 +336,11 @@ impl Connection {
                             broadcaster.unregister_monitor(self.client_id).await;
                             return Ok(false);
                         }
-                        Ok(_) => {
+                        Ok(n) => {
+                            if let Some(ref metrics) = self.metrics {
+                                metrics.connections.record_bytes_received(n as u64);
+                            }
+
                             // Client sent data - check for QUIT or RESET
                             while let Some(value) = self.parser.parse()? {
                                 if let RespValue::Array(Some(arr)) = &value {
@@ -236,6 +390,160 @@ impl Connection {
                 broadcaster.unregister_monitor(self.client_id).await;
             }
         }
+
+        // Drop subscriptions so PUBSUB doesn't report a gone subscriber
+        if let Err(e) = self
+            .executor
+            .pubsub_broker()
+            .unsubscribe_all(self.client_id)
+        {
+            warn!("Failed to remove client subscriptions: {}", e);
+        }
+    }
+
+    /// Process one command, returning its replies in order.
+    ///
+    /// The subscribe family replies once per channel or pattern; every other
+    /// command replies exactly once.
+    async fn dispatch(&mut self, value: RespValue) -> Vec<RespValue> {
+        if let RespValue::Array(Some(arr)) = &value {
+            if let Some(RespValue::BulkString(Some(cmd))) = arr.first() {
+                let command = String::from_utf8_lossy(cmd).to_uppercase();
+                if !self.authenticated && !PRE_AUTH_COMMANDS.contains(&command.as_str()) {
+                    return vec![RespValue::error("NOAUTH Authentication required.")];
+                }
+
+                // Inside MULTI these go to the executor, which rejects them
+                let is_subscription = SUBSCRIBE_COMMANDS.contains(&command.as_str());
+                if is_subscription && !self.executor.in_transaction() {
+                    let args: Vec<Bytes> = arr[1..]
+                        .iter()
+                        .filter_map(|v| match v {
+                            RespValue::BulkString(Some(b)) => Some(b.clone()),
+                            _ => None,
+                        })
+                        .collect();
+                    self.broadcast_to_monitors(&command, &args);
+                    return self.handle_subscription(&command, &args);
+                }
+            }
+        }
+
+        vec![self.process_command(value).await]
+    }
+
+    /// Handle the subscribe family: (P|S)SUBSCRIBE and (P|S)UNSUBSCRIBE
+    fn handle_subscription(&mut self, command: &str, args: &[Bytes]) -> Vec<RespValue> {
+        let broker = Arc::clone(self.executor.pubsub_broker());
+        let kind = command.to_lowercase();
+
+        // Shard channels must hash to a slot this node serves
+        #[cfg(feature = "cluster")]
+        if command == "SSUBSCRIBE" {
+            if let Some(cluster_cmds) = self.executor.cluster_commands() {
+                for channel in args {
+                    if let Err(e) = cluster_cmds.check_key_slot(channel) {
+                        return vec![e.to_resp()];
+                    }
+                }
+            }
+        }
+
+        let targets = match command {
+            "SUBSCRIBE" | "PSUBSCRIBE" | "SSUBSCRIBE" if args.is_empty() => {
+                return vec![RespValue::error(format!(
+                    "ERR wrong number of arguments for '{}' command",
+                    kind
+                ))];
+            }
+            "UNSUBSCRIBE" | "PUNSUBSCRIBE" if args.is_empty() => {
+                // Without arguments, drop every channel (or pattern)
+                match broker.subscriptions_of(self.client_id) {
+                    Ok((channels, _)) if command == "UNSUBSCRIBE" => channels,
+                    Ok((_, patterns)) => patterns,
+                    Err(e) => return vec![RespValue::error(format!("ERR {}", e))],
+                }
+            }
+            "SUNSUBSCRIBE" if args.is_empty() => {
+                match broker.shard_subscriptions_of(self.client_id) {
+                    Ok(channels) => channels,
+                    Err(e) => return vec![RespValue::error(format!("ERR {}", e))],
+                }
+            }
+            _ => args.to_vec(),
+        };
+
+        if targets.is_empty() {
+            let count = if command == "SUNSUBSCRIBE" {
+                broker.shard_subscription_count(self.client_id)
+            } else {
+                broker.subscription_count(self.client_id)
+            }
+            .unwrap_or(0);
+            return vec![RespValue::push(vec![
+                RespValue::bulk_string(kind),
+                RespValue::null_bulk_string(),
+                RespValue::integer(count as i64),
+            ])];
+        }
+
+        targets
+            .into_iter()
+            .map(|target| {
+                let count = match command {
+                    "SUBSCRIBE" => {
+                        broker.subscribe(self.client_id, &self.pubsub_sender, target.clone())
+                    }
+                    "PSUBSCRIBE" => {
+                        broker.psubscribe(self.client_id, &self.pubsub_sender, target.clone())
+                    }
+                    "SSUBSCRIBE" => {
+                        broker.ssubscribe(self.client_id, &self.pubsub_sender, target.clone())
+                    }
+                    "UNSUBSCRIBE" => broker.unsubscribe(self.client_id, &target),
+                    "SUNSUBSCRIBE" => broker.sunsubscribe(self.client_id, &target),
+                    _ => broker.punsubscribe(self.client_id, &target),
+                };
+                match count {
+                    Ok(count) => RespValue::push(vec![
+                        RespValue::bulk_string(kind.clone()),
+                        RespValue::bulk_string(target),
+                        RespValue::integer(count as i64),
+                    ]),
+                    Err(e) => RespValue::error(format!("ERR {}", e)),
+                }
+            })
+            .collect()
+    }
+
+    /// Handle RESET - drop subscriptions and transaction state and restore defaults
+    fn handle_reset(&mut self) -> RespValue {
+        if let Err(e) = self
+            .executor
+            .pubsub_broker()
+            .unsubscribe_all(self.client_id)
+        {
+            return RespValue::error(format!("ERR {}", e));
+        }
+        if let Err(e) = self.executor.reset() {
+            return RespValue::error(format!("ERR {}", e));
+        }
+
+        self.current_db = 0;
+        self.protocol_version = ProtocolVersion::Resp2;
+        self.authenticated = self.requirepass.is_none();
+        RespValue::simple_string("RESET")
+    }
+
+    /// Check whether the connection has any channel, pattern or shard channel subscriptions
+    fn is_subscribed(&self) -> bool {
+        let broker = self.executor.pubsub_broker();
+        broker
+            .subscription_count(self.client_id)
+            .is_ok_and(|count| count > 0)
+            || broker
+                .shard_subscription_count(self.client_id)
+                .is_ok_and(|count| count > 0)
     }
 
     async fn process_command(&mut self, value: RespValue) -> RespValue {
@@ -252,6 +560,11 @@ impl Connection {
                 };
 
                 let command_upper = command.to_uppercase();
+                self.record_client_command(&command_upper, arr.get(1));
+
+                if command_upper == "AUTH" {
+                    return self.handle_auth(&arr[1..]);
+                }
 
                 // Handle HELLO command for protocol version negotiation
                 if command_upper == "HELLO" {
@@ -263,6 +576,15 @@ impl Connection {
                     return self.handle_monitor().await;
                 }
 
+                if command_upper == "RESET" {
+                    return self.handle_reset();
+                }
+
+                if command_upper == "QUIT" {
+                    self.closing = true;
+                    return RespValue::ok();
+                }
+
                 let args: Vec<Bytes> = arr[1..]
                     .iter()
                     .filter_map(|v| match v {
@@ -271,6 +593,21 @@ impl Connection {
                     })
                     .collect();
 
+                // A subscribed RESP2 connection only accepts the subscribe family, PING, QUIT and RESET
+                if self.protocol_version == ProtocolVersion::Resp2 && self.is_subscribed() {
+                    if command_upper == "PING" {
+                        let payload = args.first().cloned().unwrap_or_default();
+                        return RespValue::array(vec![
+                            RespValue::bulk_string("pong"),
+                            RespValue::bulk_string(payload),
+                        ]);
+                    }
+                    return RespValue::error(format!(
+                        "ERR Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                        command.to_lowercase()
+                    ));
+                }
+
                 // Broadcast to monitors (except excluded internal/debugging commands)
                 if !MONITOR_EXCLUDED_COMMANDS.contains(&command_upper.as_str()) {
                     self.broadcast_to_monitors(&command_upper, &args);
@@ -281,7 +618,7 @@ impl Connection {
                 if command_upper == "CLUSTER" && !args.is_empty() {
                     let subcommand = String::from_utf8_lossy(&args[0]).to_uppercase();
                     // These are async cluster management commands
-                    if matches!(subcommand.as_str(), "MEET" | "FORGET" | "ADDSLOTS" | "DELSLOTS" | "REPLICATE") {
+                    if matches!(subcommand.as_str(), "MEET" | "FORGET" | "ADDSLOTS" | "DELSLOTS" | "ADDSLOTSRANGE" | "DELSLOTSRANGE" | "SETSLOT" | "MIGRATE-SLOT" | "REPLICATE") {
                         if let Some(cluster_cmds) = self.executor.cluster_commands() {
                             let result = self.handle_async_cluster_command(cluster_cmds, &subcommand, &args[1..]).await;
                             
@@ -307,7 +644,7 @@ impl Connection {
                             
                             return match result {
                                 Ok(resp) => resp,
-                                Err(e) => RespValue::error(format!("ERR {}", e)),
+                                Err(e) => e.to_resp(),
                             };
                         } else {
                             return RespValue::error("ERR Cluster not initialized. Please initialize cluster node first.");
@@ -315,9 +652,52 @@ impl Connection {
                     }
                 }
 
-                let result =
+                // WAIT blocks on Raft replication progress in cluster mode
+                #[cfg(feature = "cluster")]
+                if command_upper == "WAIT" {
+                    if let Some(cluster_cmds) = self.executor.cluster_commands() {
+                        let result = match crate::command::server::parse_wait_args(&args) {
+                            Ok((numreplicas, timeout)) => {
+                                cluster_cmds.wait_replicas(numreplicas, timeout).await
+                            }
+                            Err(e) => Err(e),
+                        };
+                        return match result {
+                            Ok(resp) => resp,
+                            Err(e) => e.to_resp(),
+                        };
+                    }
+                }
+
+                // SCRIPT LOAD and SCRIPT FLUSH are handed on to the other nodes
+                // when they change this node's cache
+                #[cfg(feature = "cluster")]
+                let propagate_script = command_upper == "SCRIPT"
+                    && self.executor.cluster_commands().is_some()
+                    && !self.executor.in_transaction()
+                    && self.executor.script_changes_cache(&args).unwrap_or(false);
+
+                // Inside MULTI the executor runs these without blocking
+                let blocking = matches!(command_upper.as_str(), "BLPOP" | "BRPOP");
+                let result = if blocking && !self.executor.in_transaction() {
+                    self.blocking_pop(&command_upper, &args).await
+                } else {
                     self.executor
-                        .execute(&command, &args, &mut self.current_db, self.client_id);
+                        .execute(&command, &args, &mut self.current_db, self.client_id)
+                };
+
+                #[cfg(feature = "cluster")]
+                if propagate_script && result.is_ok() {
+                    if let Some(cluster_cmds) = self.executor.cluster_commands() {
+                        if let Err(e) = cluster_cmds.propagate_script(&args).await {
+                            warn!(
+                                "Failed to propagate SCRIPT {}: {}",
+                                String::from_utf8_lossy(&args[0]),
+                                e
+                            );
+                        }
+                    }
+                }
 
                 // Record metrics
                 if let Some(ref metrics) = self.metrics {
@@ -341,13 +721,96 @@ impl Connection {
 
                 match result {
                     Ok(resp) => resp,
-                    Err(e) => RespValue::error(format!("ERR {}", e)),
+                    Err(e) => e.to_resp(),
                 }
             }
             _ => RespValue::error("ERR invalid command format"),
         }
     }
 
+    /// Run BLPOP or BRPOP, waiting for a push while every list is empty.
+    ///
+    /// Clients blocked on the same key are served in the order they blocked.
+    /// Input is still read while waiting, so a disconnect or CLIENT KILL ends
+    /// the wait and pipelined commands run once it is over.
+    async fn blocking_pop(&mut self, command: &str, args: &[Bytes]) -> Result<RespValue> {
+        let (id, mut receiver, timeout) =
+            match self
+                .executor
+                .block_pop(command, args, &mut self.current_db, self.client_id)?
+            {
+                BlockingPop::Reply(reply) => return Ok(reply),
+                BlockingPop::Blocked {
+                    id,
+                    receiver,
+                    timeout,
+                } => (id, receiver, timeout),
+            };
+
+        let timed_out = async {
+            match timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(timed_out);
+
+        loop {
+            select! {
+                served = &mut receiver => {
+                    if let Ok((key, value)) = served {
+                        return Ok(RespValue::array(vec![
+                            RespValue::bulk_string(key),
+                            RespValue::bulk_string(value),
+                        ]));
+                    }
+                    break;
+                }
+                _ = &mut timed_out => break,
+                _ = self.kill.notified() => {
+                    self.closing = true;
+                    break;
+                }
+                result = self.stream.read_buf(self.parser.buffer_mut()) => match result {
+                    Ok(n) if n > 0 => {
+                        if let Some(ref metrics) = self.metrics {
+                            metrics.connections.record_bytes_received(n as u64);
+                        }
+                    }
+                    _ => {
+                        self.closing = true;
+                        break;
+                    }
+                },
+            }
+        }
+
+        // An element may have been handed over just as the wait ended
+        self.executor.unblock(id);
+        Ok(match receiver.try_recv() {
+            Ok((key, value)) => RespValue::array(vec![
+                RespValue::bulk_string(key),
+                RespValue::bulk_string(value),
+            ]),
+            Err(_) => RespValue::null_array(),
+        })
+    }
+
+    /// Count a command against this connection for CLIENT LIST and CLIENT INFO
+    fn record_client_command(&self, command: &str, first_arg: Option<&RespValue>) {
+        let subcommand = match first_arg {
+            Some(RespValue::BulkString(Some(arg))) => Some(arg.as_ref()),
+            _ => None,
+        };
+        if let Err(e) = self.executor.server_commands().record_client_command(
+            self.client_id,
+            command,
+            subcommand,
+        ) {
+            warn!("Failed to record client command: {}", e);
+        }
+    }
+
     /// Broadcast command to all monitoring clients
     fn broadcast_to_monitors(&self, command: &str, args: &[Bytes]) {
         if let Some(ref broadcaster) = self.monitor_broadcaster {
@@ -375,7 +838,7 @@ impl Connection {
         args: &[Bytes],
     ) -> Result<RespValue> {
         use crate::error::AikvError;
-        
+
         match subcommand {
             "MEET" => {
                 // CLUSTER MEET ip port [node-id]
@@ -450,6 +913,79 @@ impl Connection {
                 
                 cluster_cmds.cluster_delslots(slots).await
             }
+            "ADDSLOTSRANGE" | "DELSLOTSRANGE" => {
+                // CLUSTER ADDSLOTSRANGE|DELSLOTSRANGE start end [start end ...]
+                let pairs = args.chunks_exact(2);
+                if args.is_empty() || !pairs.remainder().is_empty() {
+                    return Err(AikvError::WrongArgCount(format!("CLUSTER {}", subcommand)));
+                }
+
+                let mut ranges = Vec::new();
+                for pair in pairs {
+                    let mut bounds = [0u16; 2];
+                    for (bound, arg) in bounds.iter_mut().zip(pair) {
+                        *bound = String::from_utf8_lossy(arg)
+                            .parse::<u16>()
+                            .map_err(|_| AikvError::Invalid("Invalid slot".to_string()))?;
+                    }
+                    ranges.push((bounds[0], bounds[1]));
+                }
+
+                if subcommand == "ADDSLOTSRANGE" {
+                    cluster_cmds.cluster_addslotsrange(ranges).await
+                } else {
+                    cluster_cmds.cluster_delslotsrange(ranges).await
+                }
+            }
+            "SETSLOT" => {
+                // CLUSTER SETSLOT slot NODE node-id
+                if args.len() < 2 {
+                    return Err(AikvError::WrongArgCount("CLUSTER SETSLOT".to_string()));
+                }
+
+                let slot = String::from_utf8_lossy(&args[0])
+                    .parse::<u16>()
+                    .map_err(|_| AikvError::Invalid("Invalid slot".to_string()))?;
+                let action = String::from_utf8_lossy(&args[1]).to_uppercase();
+
+                match (action.as_str(), args.len()) {
+                    ("NODE", 3) => {
+                        let id_str = String::from_utf8_lossy(&args[2]);
+                        let node_id = u64::from_str_radix(&id_str, 16)
+                            .map_err(|_| AikvError::Invalid("Invalid node ID".to_string()))?;
+
+                        cluster_cmds.cluster_setslot_node(slot, node_id).await
+                    }
+                    ("IMPORTING" | "MIGRATING", 3) => {
+                        let id_str = String::from_utf8_lossy(&args[2]);
+                        let node_id = u64::from_str_radix(&id_str, 16)
+                            .map_err(|_| AikvError::Invalid("Invalid node ID".to_string()))?;
+
+                        cluster_cmds.cluster_setslot_migration(slot, &action, node_id)
+                    }
+                    ("STABLE", 2) => Err(AikvError::InvalidArgument(
+                        format!("ERR CLUSTER SETSLOT {} is not supported", action),
+                    )),
+                    _ => Err(AikvError::InvalidArgument(
+                        "ERR Invalid CLUSTER SETSLOT action or number of arguments".to_string(),
+                    )),
+                }
+            }
+            "MIGRATE-SLOT" => {
+                // CLUSTER MIGRATE-SLOT slot node-id
+                if args.len() != 2 {
+                    return Err(AikvError::WrongArgCount("CLUSTER MIGRATE-SLOT".to_string()));
+                }
+
+                let slot = String::from_utf8_lossy(&args[0])
+                    .parse::<u16>()
+                    .map_err(|_| AikvError::Invalid("Invalid slot".to_string()))?;
+                let id_str = String::from_utf8_lossy(&args[1]);
+                let node_id = u64::from_str_radix(&id_str, 16)
+                    .map_err(|_| AikvError::Invalid("Invalid node ID".to_string()))?;
+
+                cluster_cmds.cluster_migrate_slot(slot, node_id).await
+            }
             "REPLICATE" => {
                 // CLUSTER REPLICATE node-id
                 if args.len() != 1 {
@@ -482,56 +1018,181 @@ impl Connection {
         }
     }
 
+    /// Handle AUTH \[username\] password
+    fn handle_auth(&mut self, args: &[RespValue]) -> RespValue {
+        let args: Vec<Bytes> = args
+            .iter()
+            .filter_map(|v| match v {
+                RespValue::BulkString(Some(b)) => Some(b.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let result = match args.as_slice() {
+            [password] => self.authenticate(None, password),
+            [username, password] => self.authenticate(Some(username), password),
+            _ => return RespValue::error("ERR wrong number of arguments for 'auth' command"),
+        };
+        match result {
+            Ok(()) => RespValue::ok(),
+            Err(e) => e,
+        }
+    }
+
+    /// Check credentials against `requirepass`, authenticating the connection on success.
+    ///
+    /// The only user is `default`. Without a password it accepts any credentials
+    /// naming it, but AUTH with a bare password is a configuration mistake.
+    fn authenticate(
+        &mut self,
+        username: Option<&Bytes>,
+        password: &Bytes,
+    ) -> std::result::Result<(), RespValue> {
+        let wrongpass =
+            || RespValue::error("WRONGPASS invalid username-password pair or user is disabled.");
+        if username.is_some_and(|u| u.as_ref() != b"default") {
+            return Err(wrongpass());
+        }
+
+        match &self.requirepass {
+            None if username.is_none() => Err(RespValue::error(
+                "ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?",
+            )),
+            Some(requirepass) if requirepass.as_bytes() != password.as_ref() => Err(wrongpass()),
+            _ => {
+                self.authenticated = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// Handle HELLO \[protover\]: switch protocol and reply with the connection context.
+    ///
+    /// Without a protocol version the current one is kept, so clients can
+    /// query their state without changing it.
     fn handle_hello(&mut self, args: &[RespValue]) -> RespValue {
-        if args.is_empty() {
-            return RespValue::error("ERR wrong number of arguments for 'hello' command");
+        let args: Vec<Bytes> = args
+            .iter()
+            .filter_map(|v| match v {
+                RespValue::BulkString(Some(b)) => Some(b.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let mut protocol_version = self.protocol_version;
+        if let Some(version) = args.first() {
+            protocol_version = match std::str::from_utf8(version)
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+            {
+                Some(2) => ProtocolVersion::Resp2,
+                Some(3) => ProtocolVersion::Resp3,
+                Some(_) => return RespValue::error("NOPROTO unsupported protocol version"),
+                None => {
+                    return RespValue::error(
+                        "ERR Protocol version is not an integer or out of range",
+                    )
+                }
+            };
         }
 
-        // Parse protocol version
-        let version_str = match &args[0] {
-            RespValue::BulkString(Some(v)) => String::from_utf8_lossy(v).to_string(),
-            _ => return RespValue::error("ERR invalid protocol version"),
-        };
+        // [AUTH username password] [SETNAME clientname]
+        let mut credentials = None;
+        let mut name = None;
+        let mut i = 1;
+        while i < args.len() {
+            let option = String::from_utf8_lossy(&args[i]).to_uppercase();
+            match option.as_str() {
+                "AUTH" if i + 2 < args.len() => {
+                    credentials = Some((args[i + 1].clone(), args[i + 2].clone()));
+                    i += 3;
+                }
+                "SETNAME" if i + 1 < args.len() => {
+                    name = Some(args[i + 1].clone());
+                    i += 2;
+                }
+                _ => {
+                    return RespValue::error(format!(
+                        "ERR Syntax error in HELLO option '{}'",
+                        String::from_utf8_lossy(&args[i])
+                    ))
+                }
+            }
+        }
 
-        let version = match version_str.as_str() {
-            "2" => ProtocolVersion::Resp2,
-            "3" => ProtocolVersion::Resp3,
-            _ => return RespValue::error("NOPROTO unsupported protocol version"),
-        };
+        // Credentials must check out before anything else changes
+        match credentials {
+            Some((username, password)) => {
+                if let Err(e) = self.authenticate(Some(&username), &password) {
+                    return e;
+                }
+            }
+            None if !self.authenticated => {
+                return RespValue::error(
+                    "NOAUTH HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time",
+                );
+            }
+            None => {}
+        }
 
-        self.protocol_version = version;
-
-        // Build response based on protocol version
-        match self.protocol_version {
-            ProtocolVersion::Resp2 => {
-                // RESP2 response: array
-                RespValue::array(vec![
-                    RespValue::bulk_string("server"),
-                    RespValue::bulk_string("aikv"),
-                    RespValue::bulk_string("version"),
-                    RespValue::bulk_string("0.1.0"),
-                    RespValue::bulk_string("proto"),
-                    RespValue::integer(2),
-                ])
-            }
-            ProtocolVersion::Resp3 => {
-                // RESP3 response: map
-                RespValue::map(vec![
-                    (
-                        RespValue::simple_string("server"),
-                        RespValue::simple_string("aikv"),
-                    ),
-                    (
-                        RespValue::simple_string("version"),
-                        RespValue::simple_string("0.1.0"),
-                    ),
-                    (RespValue::simple_string("proto"), RespValue::integer(3)),
-                ])
+        // The name is checked like CLIENT SETNAME; a bad one leaves the protocol as it was
+        if let Some(name) = name {
+            if let Err(e) = self
+                .executor
+                .server_commands()
+                .client_setname(&[name], self.client_id)
+            {
+                return e.to_resp();
             }
         }
+        self.protocol_version = protocol_version;
+
+        let proto = match self.protocol_version {
+            ProtocolVersion::Resp2 => 2,
+            ProtocolVersion::Resp3 => 3,
+        };
+
+        #[cfg(feature = "cluster")]
+        let mode = if self.executor.cluster_commands().is_some() {
+            "cluster"
+        } else {
+            "standalone"
+        };
+        #[cfg(not(feature = "cluster"))]
+        let mode = "standalone";
+        let role = if self.executor.is_replica() {
+            "replica"
+        } else {
+            "master"
+        };
+
+        // A map for RESP3 clients; RESP2 connections flatten it to an array
+        RespValue::map(vec![
+            (
+                RespValue::bulk_string("server"),
+                RespValue::bulk_string("aikv"),
+            ),
+            (
+                RespValue::bulk_string("version"),
+                RespValue::bulk_string(env!("CARGO_PKG_VERSION")),
+            ),
+            (RespValue::bulk_string("proto"), RespValue::integer(proto)),
+            (
+                RespValue::bulk_string("id"),
+                RespValue::integer(self.client_id as i64),
+            ),
+            (RespValue::bulk_string("mode"), RespValue::bulk_string(mode)),
+            (RespValue::bulk_string("role"), RespValue::bulk_string(role)),
+            (RespValue::bulk_string("modules"), RespValue::array(vec![])),
+        ])
     }
 
     async fn write_response(&mut self, response: RespValue) -> Result<()> {
+        // RESP2 clients can't parse RESP3-only types, and RESP3 has a single null
+        let response = match self.protocol_version {
+            ProtocolVersion::Resp2 => response.into_resp2(),
+            ProtocolVersion::Resp3 => response.into_resp3(),
+        };
         let data = response.serialize();
 
         // Record bytes sent
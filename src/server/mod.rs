@@ -1,29 +1,62 @@
 pub mod connection;
 pub mod monitor;
+pub mod pubsub;
 
 pub use monitor::{MonitorBroadcaster, MonitorMessage};
+pub use pubsub::{PubSubBroker, PubSubMessage};
 
 use self::connection::Connection;
+use crate::command::list::BlockedClients;
+use crate::command::notify::KeyspaceEvents;
+use crate::command::script::ScriptCache;
+use crate::command::server::{ClientRegistry, SaveState, ServerIdentity};
+use crate::command::transaction::CommandGate;
 use crate::command::CommandExecutor;
 use crate::error::Result;
 use crate::observability::Metrics;
-use crate::storage::StorageEngine;
-use tracing::warn;
+use crate::persistence;
+use crate::storage::{ExpiryListener, StorageEngine};
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::net::TcpListener;
+use std::task::Poll;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::net::{TcpListener, TcpStream};
+use tracing::warn;
 use tracing::{error, info};
 
 #[cfg(feature = "cluster")]
-use crate::cluster::{ClusterCommands, MetaRaftNode, MultiRaftNode, Router};
+use crate::cluster::{
+    ClusterCommands, ForgetBlacklist, MetaRaftNode, MigratingSlots, MultiRaftNode, NodeLiveness,
+    Router,
+};
+
+/// Reply sent to clients refused by protected mode, before closing the connection
+const PROTECTED_MODE_ERROR: &str = "DENIED AiKv is running in protected mode because protected mode is enabled and no password is set for the default user. In this mode connections are only accepted from the loopback interface. If you want to connect from external computers to AiKv you may adopt one of the following solutions: 1) Disable protected mode by setting protected_mode = false in the [server] section of the configuration file, and restart the server. MAKE SURE AiKv is not publicly accessible from internet if you do so. 2) Set up a password for the default user with requirepass. NOTE: You only need to do one of the above things in order for the server to start accepting connections from the outside.";
 
 /// AiKv server
 pub struct Server {
     addr: String,
+    /// Further TCP addresses to accept clients on, next to `addr`
+    bind_addrs: Vec<String>,
     port: u16,
+    unix_socket: Option<PathBuf>,
+    requirepass: Option<String>,
+    /// Refuse non-loopback clients while listening beyond loopback without a password
+    protected_mode: bool,
     storage: StorageEngine,
     metrics: Arc<Metrics>,
     monitor_broadcaster: Arc<MonitorBroadcaster>,
+    pubsub_broker: Arc<PubSubBroker>,
+    script_cache: ScriptCache,
+    blocked_clients: BlockedClients,
+    command_gate: CommandGate,
+    save_state: SaveState,
+    clients: ClientRegistry,
+    identity: ServerIdentity,
     #[cfg(feature = "cluster")]
     node_id: u64,
     #[cfg(feature = "cluster")]
@@ -32,6 +65,12 @@ pub struct Server {
     multi_raft: Option<Arc<MultiRaftNode>>,
     #[cfg(feature = "cluster")]
     router: Option<Arc<Router>>,
+    #[cfg(feature = "cluster")]
+    forget_blacklist: Arc<ForgetBlacklist>,
+    #[cfg(feature = "cluster")]
+    node_liveness: Arc<NodeLiveness>,
+    #[cfg(feature = "cluster")]
+    migrating_slots: Arc<MigratingSlots>,
 }
 
 impl Server {
@@ -53,8 +92,9 @@ impl Server {
 
         #[cfg(feature = "cluster")]
         let node_id = {
-            // Generate a unique node ID for this cluster node
-            let node_id = ClusterCommands::generate_node_id();
+            // Derive the node ID from the listen address so that peers meeting
+            // this node by address agree on its identity
+            let node_id = ClusterCommands::resolve_node_id(&addr);
 
             info!(
                 "Cluster mode enabled: node_id={:040x}, port={}",
@@ -64,12 +104,38 @@ impl Server {
             node_id
         };
 
+        // Keys reclaimed by expiry fire `expired` keyspace events
+        let metrics = Arc::new(Metrics::new());
+        let pubsub_broker = Arc::new(PubSubBroker::with_metrics(Arc::clone(&metrics.pubsub)));
+        let broker = Arc::clone(&pubsub_broker);
+        let listener: ExpiryListener = Arc::new(move |db, key| {
+            if let Err(e) =
+                broker.notify_keyspace_event(KeyspaceEvents::EXPIRED, "expired", db, key.as_bytes())
+            {
+                warn!("Failed to publish expired event for {}: {}", key, e);
+            }
+        });
+        if let Err(e) = storage.set_expiry_listener(listener) {
+            warn!("Expired keyspace events are unavailable: {}", e);
+        }
+
         Self {
             addr,
+            bind_addrs: Vec::new(),
             port,
+            unix_socket: None,
+            requirepass: None,
+            protected_mode: false,
             storage,
-            metrics: Arc::new(Metrics::new()),
+            metrics,
             monitor_broadcaster: Arc::new(MonitorBroadcaster::new()),
+            pubsub_broker,
+            script_cache: ScriptCache::new(),
+            blocked_clients: BlockedClients::new(),
+            command_gate: CommandGate::new(),
+            save_state: SaveState::new(),
+            clients: ClientRegistry::default(),
+            identity: ServerIdentity::new(port),
             #[cfg(feature = "cluster")]
             node_id,
             #[cfg(feature = "cluster")]
@@ -78,6 +144,12 @@ impl Server {
             multi_raft: None,
             #[cfg(feature = "cluster")]
             router: None,
+            #[cfg(feature = "cluster")]
+            forget_blacklist: Arc::new(ForgetBlacklist::new()),
+            #[cfg(feature = "cluster")]
+            node_liveness: Arc::new(NodeLiveness::new()),
+            #[cfg(feature = "cluster")]
+            migrating_slots: Arc::new(MigratingSlots::new()),
         }
     }
 
@@ -156,6 +228,9 @@ impl Server {
         // Initialize Router with cluster metadata
         let router = Arc::new(Router::new(cluster_meta));
 
+        // COUNTKEYSINSLOT, GETKEYSINSLOT and MIGRATE-SLOT look keys up by slot
+        self.storage.enable_slot_index()?;
+
         self.meta_raft = Some(meta_raft.clone());
         self.multi_raft = Some(multi_raft);
         self.router = Some(router);
@@ -175,46 +250,154 @@ impl Server {
         Arc::clone(&self.monitor_broadcaster)
     }
 
+    /// Get the Pub/Sub broker
+    pub fn pubsub_broker(&self) -> Arc<PubSubBroker> {
+        Arc::clone(&self.pubsub_broker)
+    }
+
+    /// Record the configuration file the server was started with (reported by INFO)
+    pub fn set_config_file(&mut self, path: &str) {
+        let path = std::fs::canonicalize(path)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| path.to_string());
+        self.identity.config_file = Some(path);
+    }
+
+    /// Also accept clients on a Unix socket at `path`, next to the TCP port
+    pub fn set_unix_socket(&mut self, path: impl Into<PathBuf>) {
+        self.unix_socket = Some(path.into());
+    }
+
+    /// Require clients to AUTH with `password` before running other commands
+    pub fn set_requirepass(&mut self, password: impl Into<String>) {
+        self.requirepass = Some(password.into());
+    }
+
+    /// Also accept TCP clients on `addr`, e.g. the IPv6 loopback next to the IPv4 one
+    pub fn add_bind_address(&mut self, addr: impl Into<String>) {
+        self.bind_addrs.push(addr.into());
+    }
+
+    /// Refuse clients from outside the loopback interface while the server
+    /// listens beyond it without a password, as Redis `protected-mode` does
+    pub fn set_protected_mode(&mut self, enabled: bool) {
+        self.protected_mode = enabled;
+    }
+
+    /// Whether protected mode refuses a TCP client connecting from `peer`
+    fn refuses(&self, peer: &SocketAddr) -> bool {
+        if !self.protected_mode || self.requirepass.is_some() || peer.ip().is_loopback() {
+            return false;
+        }
+        // Only a listener reachable from outside can let such a client in
+        std::iter::once(&self.addr)
+            .chain(&self.bind_addrs)
+            .any(|addr| match addr.parse::<SocketAddr>() {
+                Ok(addr) => !addr.ip().is_loopback(),
+                // Host names are not resolved here, so assume they reach outside
+                Err(_) => true,
+            })
+    }
+
+    /// Set the directory SAVE and BGSAVE write the snapshot to, and the server
+    /// loads it from when it starts, as the `dir` config does
+    pub fn set_dir(&mut self, dir: &str) -> Result<()> {
+        self.save_state.set_dir(dir)
+    }
+
+    /// Load the snapshot left by SAVE or BGSAVE, if there is one
+    ///
+    /// The cached scripts are always restored so EVALSHA keeps working. The
+    /// data only is with the memory engine, as AiDb keeps its own on disk.
+    fn load_snapshot(&self) -> Result<()> {
+        let path = self.save_state.snapshot_path();
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let (databases, scripts) = persistence::load_snapshot(&path)?;
+        if matches!(self.storage, StorageEngine::Memory(_)) {
+            persistence::restore_storage(&self.storage, databases)?;
+        }
+        let restored = self.script_cache.restore(scripts)?;
+        info!(
+            "Loaded snapshot {} with {} cached scripts",
+            path.display(),
+            restored
+        );
+        Ok(())
+    }
+
     /// Run the server
     pub async fn run(&self) -> Result<()> {
-        let listener = TcpListener::bind(&self.addr).await?;
-        info!("AiKv server listening on {}", self.addr);
+        self.load_snapshot()?;
+
+        let mut listeners = Vec::new();
+        for addr in std::iter::once(&self.addr).chain(&self.bind_addrs) {
+            listeners.push(TcpListener::bind(addr).await?);
+            info!("AiKv server listening on {}", addr);
+        }
+
+        tokio::spawn(autosave(
+            self.new_executor(),
+            self.save_state.clone(),
+            self.script_cache.clone(),
+        ));
+
+        #[cfg(unix)]
+        let unix_listener = match &self.unix_socket {
+            Some(path) => {
+                // A socket file left behind by a previous run would fail the bind
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+                let unix_listener = UnixListener::bind(path)?;
+                info!("AiKv server listening on {}", path.display());
+                Some(unix_listener)
+            }
+            None => None,
+        };
+        #[cfg(not(unix))]
+        let unix_listener = self.unix_socket.as_ref().and_then(|path| {
+            warn!(
+                "Unix sockets are not supported on this platform, ignoring {}",
+                path.display()
+            );
+            None
+        });
 
         loop {
-            match listener.accept().await {
-                Ok((stream, addr)) => {
+            let accepted = tokio::select! {
+                accepted = accept_tcp(&listeners) => match accepted {
+                    Ok((stream, addr)) if self.refuses(&addr) => {
+                        warn!("Protected mode refused connection from: {}", addr);
+                        tokio::spawn(refuse(stream));
+                        continue;
+                    }
+                    accepted => accepted.map(|(stream, addr)| {
+                        let conn = Connection::new(
+                            stream,
+                            self.new_executor(),
+                            Some(Arc::clone(&self.metrics)),
+                            Some(Arc::clone(&self.monitor_broadcaster)),
+                        );
+                        (conn, addr.to_string())
+                    }),
+                },
+                accepted = self.accept_unix(unix_listener.as_ref()) => accepted,
+            };
+
+            match accepted {
+                Ok((mut conn, addr)) => {
                     info!("New connection from: {}", addr);
+                    conn.set_requirepass(self.requirepass.clone());
 
                     // Record connection metrics
                     self.metrics.connections.record_connection();
 
-                    // Create executor with or without cluster commands
-                    let mut executor = CommandExecutor::with_port(self.storage.clone(), self.port);
-
-                    #[cfg(feature = "cluster")]
-                    if let (Some(meta_raft), Some(multi_raft), Some(router)) = 
-                        (&self.meta_raft, &self.multi_raft, &self.router) {
-                        // Create ClusterCommands for this connection
-                        let cluster_commands = ClusterCommands::new(
-                            self.node_id,
-                            Arc::clone(meta_raft),
-                            Arc::clone(multi_raft),
-                            Arc::clone(router),
-                        );
-                        executor.set_cluster_commands(cluster_commands);
-                    }
-
                     let metrics = Arc::clone(&self.metrics);
-                    let monitor_broadcaster = Arc::clone(&self.monitor_broadcaster);
 
                     tokio::spawn(async move {
-                        let mut conn = Connection::new(
-                            stream,
-                            executor,
-                            Some(metrics.clone()),
-                            Some(monitor_broadcaster),
-                        );
-
                         if let Err(e) = conn.handle().await {
                             error!("Connection error: {}", e);
                         }
@@ -230,4 +413,150 @@ impl Server {
             }
         }
     }
+
+    /// Accept the next Unix socket client, or wait forever without a socket
+    #[cfg(unix)]
+    async fn accept_unix(
+        &self,
+        listener: Option<&UnixListener>,
+    ) -> std::io::Result<(Connection, String)> {
+        let Some(listener) = listener else {
+            return std::future::pending().await;
+        };
+        let (stream, _) = listener.accept().await?;
+        let conn = Connection::new_unix(
+            stream,
+            self.new_executor(),
+            Some(Arc::clone(&self.metrics)),
+            Some(Arc::clone(&self.monitor_broadcaster)),
+        );
+        let addr = self
+            .unix_socket
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+        Ok((conn, addr))
+    }
+
+    #[cfg(not(unix))]
+    async fn accept_unix(&self, _listener: Option<&()>) -> std::io::Result<(Connection, String)> {
+        std::future::pending().await
+    }
+
+    /// Create the command executor for a new connection, sharing the server's state
+    fn new_executor(&self) -> CommandExecutor {
+        // Create executor with or without cluster commands
+        let mut executor = CommandExecutor::with_port(self.storage.clone(), self.port);
+        executor.set_metrics(Arc::clone(&self.metrics));
+        executor.set_pubsub_broker(Arc::clone(&self.pubsub_broker));
+        executor.set_script_cache(self.script_cache.clone());
+        executor.set_blocked_clients(self.blocked_clients.clone());
+        executor.set_command_gate(self.command_gate.clone());
+        executor.set_save_state(self.save_state.clone());
+        executor.set_client_registry(Arc::clone(&self.clients));
+        executor.set_server_identity(self.identity.clone());
+
+        #[cfg(feature = "cluster")]
+        if let (Some(meta_raft), Some(multi_raft), Some(router)) = 
+            (&self.meta_raft, &self.multi_raft, &self.router) {
+            // Create ClusterCommands for this connection
+            let mut cluster_commands = ClusterCommands::new(
+                self.node_id,
+                Arc::clone(meta_raft),
+                Arc::clone(multi_raft),
+                Arc::clone(router),
+            );
+            cluster_commands.set_forget_blacklist(Arc::clone(&self.forget_blacklist));
+            cluster_commands.set_node_liveness(Arc::clone(&self.node_liveness));
+            cluster_commands.set_migrating_slots(Arc::clone(&self.migrating_slots));
+            cluster_commands.set_command_gate(self.command_gate.clone());
+            cluster_commands.set_storage(self.storage.clone());
+            executor.set_cluster_commands(cluster_commands);
+        }
+
+        executor
+    }
+}
+
+/// Accept the next TCP client from whichever listener has one
+async fn accept_tcp(listeners: &[TcpListener]) -> std::io::Result<(TcpStream, SocketAddr)> {
+    std::future::poll_fn(|cx| {
+        for listener in listeners {
+            if let Poll::Ready(accepted) = listener.poll_accept(cx) {
+                return Poll::Ready(accepted);
+            }
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// Tell a client refused by protected mode why, then close the connection
+async fn refuse(mut stream: TcpStream) {
+    let reply = format!("-{}\r\n", PROTECTED_MODE_ERROR);
+    let _ = stream.write_all(reply.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Run BGSAVE whenever a `save` point is reached, checking ten times a second
+async fn autosave(executor: CommandExecutor, save_state: SaveState, scripts: ScriptCache) {
+    let executor = Arc::new(executor);
+    let mut interval = tokio::time::interval(Duration::from_millis(100));
+    loop {
+        interval.tick().await;
+        if save_state.save_point_reached() && !save_state.bgsave_in_progress() {
+            info!("Save point reached, saving in the background");
+            // BGSAVE copies the whole keyspace before returning, so it runs
+            // off the async workers
+            let (executor, scripts) = (Arc::clone(&executor), scripts.clone());
+            let result = tokio::task::spawn_blocking(move || {
+                executor.server_commands().bgsave(&[], &scripts).map(|_| ())
+            })
+            .await
+            .unwrap_or_else(|e| Err(crate::error::AikvError::Internal(e.to_string())));
+            if let Err(e) = result {
+                error!("Background save failed: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(addr: &str) -> Server {
+        let mut server = Server::new(addr.to_string(), StorageEngine::new_memory(16));
+        server.set_protected_mode(true);
+        server
+    }
+
+    #[test]
+    fn test_protected_mode_refuses_external_clients() {
+        let external: SocketAddr = "192.168.1.10:50000".parse().unwrap();
+        let loopback: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let loopback6: SocketAddr = "[::1]:50000".parse().unwrap();
+
+        // Listening on every interface without a password
+        let mut exposed = server("0.0.0.0:6379");
+        assert!(exposed.refuses(&external));
+        assert!(!exposed.refuses(&loopback));
+        assert!(!exposed.refuses(&loopback6));
+
+        // A password lifts the restriction
+        exposed.set_requirepass("secret");
+        assert!(!exposed.refuses(&external));
+
+        // as does turning protected mode off
+        let mut exposed = server("0.0.0.0:6379");
+        exposed.set_protected_mode(false);
+        assert!(!exposed.refuses(&external));
+
+        // Any address beyond loopback exposes the server
+        let mut local = server("127.0.0.1:6379");
+        local.add_bind_address("[::1]:6379");
+        assert!(!local.refuses(&external));
+        local.add_bind_address("10.0.0.1:6379");
+        assert!(local.refuses(&external));
+    }
 }
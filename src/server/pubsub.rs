@@ -0,0 +1,767 @@
+//! Pub/Sub message broker
+//!
+//! The broker keeps the server-wide registry of channel and pattern
+//! subscriptions. Each subscribed connection registers a message sender the
+//! first time it subscribes; PUBLISH fans messages out to every matching
+//! subscriber's sender, and the connection writes them to its client.
+//!
+//! Shard channels (SSUBSCRIBE/SPUBLISH) are kept in a registry of their own.
+//! In cluster mode the connection checks that a shard channel's slot is served
+//! by this node before it reaches the broker.
+//!
+//! Subscriptions are owned by the connection that made them: RESET and
+//! connection teardown call [`PubSubBroker::unsubscribe_all`] so PUBSUB
+//! introspection never reports subscribers that are gone.
+//!
+//! Publishers never wait for subscribers. Each subscriber's queue is bounded
+//! by the broker's buffer limit, like Redis' `client-output-buffer-limit
+//! pubsub` hard limit: a message that would take a queue past it is not
+//! queued, and the queue is marked as overflowed so the subscriber's
+//! connection closes instead of falling further behind.
+//!
+//! The broker also publishes keyspace notifications for the event classes
+//! enabled by `notify-keyspace-events` (see [`crate::command::notify`]).
+//!
+//! Subscription counts and published messages are recorded in the broker's
+//! [`PubSubMetrics`], which the server shares with INFO stats.
+
+use crate::command::glob::glob_match;
+use crate::command::notify::KeyspaceEvents;
+use crate::error::{AikvError, Result};
+use crate::observability::PubSubMetrics;
+use crate::protocol::RespValue;
+use bytes::Bytes;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::select;
+use tokio::sync::{mpsc, Notify};
+
+/// Default limit on the bytes queued for one subscriber (Redis' 32mb pubsub hard limit)
+pub const DEFAULT_SUBSCRIBER_BUFFER_LIMIT: usize = 32 * 1024 * 1024;
+
+/// A message delivered to a subscriber
+#[derive(Debug, Clone, PartialEq)]
+pub struct PubSubMessage {
+    /// Pattern that matched, for pattern subscriptions
+    pub pattern: Option<Bytes>,
+    /// Channel the message was published to
+    pub channel: Bytes,
+    /// Whether the channel is a shard channel (SPUBLISH)
+    pub sharded: bool,
+    /// Message payload
+    pub payload: Bytes,
+}
+
+impl PubSubMessage {
+    /// Format the message as pushed to the client.
+    ///
+    /// RESP3 clients receive a push; RESP2 connections downgrade it to an array.
+    pub fn to_resp(&self) -> RespValue {
+        match &self.pattern {
+            None if self.sharded => RespValue::push(vec![
+                RespValue::bulk_string("smessage"),
+                RespValue::bulk_string(self.channel.clone()),
+                RespValue::bulk_string(self.payload.clone()),
+            ]),
+            Some(pattern) => RespValue::push(vec![
+                RespValue::bulk_string("pmessage"),
+                RespValue::bulk_string(pattern.clone()),
+                RespValue::bulk_string(self.channel.clone()),
+                RespValue::bulk_string(self.payload.clone()),
+            ]),
+            None => RespValue::push(vec![
+                RespValue::bulk_string("message"),
+                RespValue::bulk_string(self.channel.clone()),
+                RespValue::bulk_string(self.payload.clone()),
+            ]),
+        }
+    }
+
+    /// Bytes the message takes up in a subscriber's queue
+    fn size(&self) -> usize {
+        self.pattern.as_ref().map_or(0, |p| p.len()) + self.channel.len() + self.payload.len()
+    }
+}
+
+/// Create a subscriber's message queue
+pub fn message_queue() -> (PubSubSender, PubSubReceiver) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let state = Arc::new(QueueState::default());
+    (
+        PubSubSender {
+            messages: sender,
+            state: Arc::clone(&state),
+        },
+        PubSubReceiver {
+            messages: receiver,
+            state,
+        },
+    )
+}
+
+/// Bookkeeping shared by both halves of a message queue
+#[derive(Debug, Default)]
+struct QueueState {
+    /// Bytes of messages queued but not yet received
+    queued_bytes: AtomicUsize,
+    /// Set once a message would have overrun the buffer limit
+    overflowed: AtomicBool,
+    /// Wakes the receiver when the queue overflows
+    overflow: Notify,
+}
+
+impl QueueState {
+    async fn wait_for_overflow(&self) {
+        // notify_one keeps a permit, so an overflow between the check and the
+        // wait still wakes us
+        while !self.overflowed.load(Ordering::Acquire) {
+            self.overflow.notified().await;
+        }
+    }
+}
+
+/// Sending half of a subscriber's message queue
+#[derive(Debug, Clone)]
+pub struct PubSubSender {
+    messages: mpsc::UnboundedSender<PubSubMessage>,
+    state: Arc<QueueState>,
+}
+
+impl PubSubSender {
+    /// Queue a message unless that would take the queue past `limit` bytes
+    /// (0 means unlimited), returning whether it was queued.
+    ///
+    /// Overrunning the limit marks the queue as overflowed instead of waiting.
+    fn send(&self, message: PubSubMessage, limit: usize) -> bool {
+        if self.state.overflowed.load(Ordering::Acquire) {
+            return false;
+        }
+
+        let size = message.size();
+        let queued = self.state.queued_bytes.fetch_add(size, Ordering::AcqRel) + size;
+        if limit != 0 && queued > limit {
+            self.state.queued_bytes.fetch_sub(size, Ordering::AcqRel);
+            self.state.overflowed.store(true, Ordering::Release);
+            self.state.overflow.notify_one();
+            return false;
+        }
+
+        if self.messages.send(message).is_err() {
+            self.state.queued_bytes.fetch_sub(size, Ordering::AcqRel);
+            return false;
+        }
+        true
+    }
+}
+
+/// Receiving half of a subscriber's message queue
+#[derive(Debug)]
+pub struct PubSubReceiver {
+    messages: mpsc::UnboundedReceiver<PubSubMessage>,
+    state: Arc<QueueState>,
+}
+
+impl PubSubReceiver {
+    /// Receive the next message, or `None` once the queue has overflowed
+    pub async fn recv(&mut self) -> Option<PubSubMessage> {
+        let overflowed = self.overflowed();
+        let message = select! {
+            biased;
+            _ = overflowed => None,
+            message = self.messages.recv() => message,
+        };
+        message.inspect(|m| self.received(m))
+    }
+
+    /// Receive a queued message without waiting
+    pub fn try_recv(&mut self) -> std::result::Result<PubSubMessage, mpsc::error::TryRecvError> {
+        let message = self.messages.try_recv()?;
+        self.received(&message);
+        Ok(message)
+    }
+
+    /// Future that completes once the queue has overflowed.
+    ///
+    /// It doesn't borrow the receiver, so a connection can stop writing to a
+    /// client that no longer reads.
+    pub fn overflowed(&self) -> impl Future<Output = ()> + Send + 'static {
+        let state = Arc::clone(&self.state);
+        async move { state.wait_for_overflow().await }
+    }
+
+    fn received(&self, message: &PubSubMessage) {
+        self.state
+            .queued_bytes
+            .fetch_sub(message.size(), Ordering::AcqRel);
+    }
+}
+
+/// Subscription registry
+#[derive(Debug, Default)]
+struct Registry {
+    /// Message senders of subscribed clients
+    senders: HashMap<usize, PubSubSender>,
+    /// Channel -> subscribed client ids
+    channels: BTreeMap<Bytes, HashSet<usize>>,
+    /// Pattern -> subscribed client ids
+    patterns: BTreeMap<Bytes, HashSet<usize>>,
+    /// Shard channel -> subscribed client ids
+    shard_channels: BTreeMap<Bytes, HashSet<usize>>,
+    /// Client id -> what it is subscribed to
+    clients: HashMap<usize, ClientSubscriptions>,
+}
+
+/// Channels, patterns and shard channels a single client is subscribed to
+#[derive(Debug, Default)]
+struct ClientSubscriptions {
+    channels: HashSet<Bytes>,
+    patterns: HashSet<Bytes>,
+    shard_channels: HashSet<Bytes>,
+}
+
+impl Registry {
+    /// Total channel and pattern subscriptions of a client
+    fn subscription_count(&self, client_id: usize) -> usize {
+        self.clients
+            .get(&client_id)
+            .map(|subs| subs.channels.len() + subs.patterns.len())
+            .unwrap_or(0)
+    }
+
+    /// Shard channel subscriptions of a client
+    fn shard_subscription_count(&self, client_id: usize) -> usize {
+        self.clients
+            .get(&client_id)
+            .map(|subs| subs.shard_channels.len())
+            .unwrap_or(0)
+    }
+
+    /// Drop a client's sender once it has no subscriptions left
+    fn release_if_idle(&mut self, client_id: usize) {
+        if self.subscription_count(client_id) + self.shard_subscription_count(client_id) == 0 {
+            self.clients.remove(&client_id);
+            self.senders.remove(&client_id);
+        }
+    }
+}
+
+/// Server-wide Pub/Sub broker
+#[derive(Debug)]
+pub struct PubSubBroker {
+    registry: RwLock<Registry>,
+    /// Bytes a subscriber may have queued before it is disconnected (0 = unlimited)
+    buffer_limit: AtomicUsize,
+    /// Keyspace event flags (notify-keyspace-events)
+    keyspace_events: AtomicU32,
+    metrics: Arc<PubSubMetrics>,
+}
+
+impl Default for PubSubBroker {
+    fn default() -> Self {
+        Self::with_metrics(Arc::new(PubSubMetrics::new()))
+    }
+}
+
+impl PubSubBroker {
+    /// Create a new broker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new broker that records its activity in `metrics`
+    pub fn with_metrics(metrics: Arc<PubSubMetrics>) -> Self {
+        Self {
+            registry: RwLock::default(),
+            buffer_limit: AtomicUsize::new(DEFAULT_SUBSCRIBER_BUFFER_LIMIT),
+            keyspace_events: AtomicU32::new(0),
+            metrics,
+        }
+    }
+
+    /// Get the per-subscriber buffer limit in bytes
+    pub fn buffer_limit(&self) -> usize {
+        self.buffer_limit.load(Ordering::Relaxed)
+    }
+
+    /// Set the per-subscriber buffer limit in bytes (0 disables it)
+    pub fn set_buffer_limit(&self, bytes: usize) {
+        self.buffer_limit.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Get the keyspace events that are published
+    pub fn keyspace_events(&self) -> KeyspaceEvents {
+        KeyspaceEvents::from_bits(self.keyspace_events.load(Ordering::Relaxed))
+    }
+
+    /// Set the keyspace events that are published
+    pub fn set_keyspace_events(&self, events: KeyspaceEvents) {
+        self.keyspace_events.store(events.bits(), Ordering::Relaxed);
+    }
+
+    /// Publish a keyspace notification for `key`, if events of its class are enabled
+    pub fn notify_keyspace_event(
+        &self,
+        class: KeyspaceEvents,
+        event: &str,
+        db: usize,
+        key: &[u8],
+    ) -> Result<()> {
+        let events = self.keyspace_events();
+        if !events.fires(class) {
+            return Ok(());
+        }
+
+        if events.contains(KeyspaceEvents::KEYSPACE) {
+            let mut channel = format!("__keyspace@{}__:", db).into_bytes();
+            channel.extend_from_slice(key);
+            self.publish(
+                &Bytes::from(channel),
+                &Bytes::copy_from_slice(event.as_bytes()),
+            )?;
+        }
+        if events.contains(KeyspaceEvents::KEYEVENT) {
+            let channel = format!("__keyevent@{}__:{}", db, event);
+            self.publish(&Bytes::from(channel), &Bytes::copy_from_slice(key))?;
+        }
+        Ok(())
+    }
+
+    /// Subscribe a client to a channel, returning its total subscription count
+    pub fn subscribe(
+        &self,
+        client_id: usize,
+        sender: &PubSubSender,
+        channel: Bytes,
+    ) -> Result<usize> {
+        let mut registry = self.write()?;
+        registry
+            .senders
+            .entry(client_id)
+            .or_insert_with(|| sender.clone());
+        registry
+            .channels
+            .entry(channel.clone())
+            .or_default()
+            .insert(client_id);
+        registry
+            .clients
+            .entry(client_id)
+            .or_default()
+            .channels
+            .insert(channel);
+        self.record_counts(&registry);
+        Ok(registry.subscription_count(client_id))
+    }
+
+    /// Subscribe a client to a pattern, returning its total subscription count
+    pub fn psubscribe(
+        &self,
+        client_id: usize,
+        sender: &PubSubSender,
+        pattern: Bytes,
+    ) -> Result<usize> {
+        let mut registry = self.write()?;
+        registry
+            .senders
+            .entry(client_id)
+            .or_insert_with(|| sender.clone());
+        registry
+            .patterns
+            .entry(pattern.clone())
+            .or_default()
+            .insert(client_id);
+        registry
+            .clients
+            .entry(client_id)
+            .or_default()
+            .patterns
+            .insert(pattern);
+        self.record_counts(&registry);
+        Ok(registry.subscription_count(client_id))
+    }
+
+    /// Subscribe a client to a shard channel, returning its shard subscription count
+    pub fn ssubscribe(
+        &self,
+        client_id: usize,
+        sender: &PubSubSender,
+        channel: Bytes,
+    ) -> Result<usize> {
+        let mut registry = self.write()?;
+        registry
+            .senders
+            .entry(client_id)
+            .or_insert_with(|| sender.clone());
+        registry
+            .shard_channels
+            .entry(channel.clone())
+            .or_default()
+            .insert(client_id);
+        registry
+            .clients
+            .entry(client_id)
+            .or_default()
+            .shard_channels
+            .insert(channel);
+        Ok(registry.shard_subscription_count(client_id))
+    }
+
+    /// Unsubscribe a client from a channel, returning its remaining subscription count
+    pub fn unsubscribe(&self, client_id: usize, channel: &Bytes) -> Result<usize> {
+        let mut registry = self.write()?;
+        if let Some(subscribers) = registry.channels.get_mut(channel) {
+            subscribers.remove(&client_id);
+            if subscribers.is_empty() {
+                registry.channels.remove(channel);
+            }
+        }
+        if let Some(subs) = registry.clients.get_mut(&client_id) {
+            subs.channels.remove(channel);
+        }
+        self.record_counts(&registry);
+        let count = registry.subscription_count(client_id);
+        registry.release_if_idle(client_id);
+        Ok(count)
+    }
+
+    /// Unsubscribe a client from a pattern, returning its remaining subscription count
+    pub fn punsubscribe(&self, client_id: usize, pattern: &Bytes) -> Result<usize> {
+        let mut registry = self.write()?;
+        if let Some(subscribers) = registry.patterns.get_mut(pattern) {
+            subscribers.remove(&client_id);
+            if subscribers.is_empty() {
+                registry.patterns.remove(pattern);
+            }
+        }
+        if let Some(subs) = registry.clients.get_mut(&client_id) {
+            subs.patterns.remove(pattern);
+        }
+        self.record_counts(&registry);
+        let count = registry.subscription_count(client_id);
+        registry.release_if_idle(client_id);
+        Ok(count)
+    }
+
+    /// Unsubscribe a client from a shard channel, returning its remaining shard subscription count
+    pub fn sunsubscribe(&self, client_id: usize, channel: &Bytes) -> Result<usize> {
+        let mut registry = self.write()?;
+        if let Some(subscribers) = registry.shard_channels.get_mut(channel) {
+            subscribers.remove(&client_id);
+            if subscribers.is_empty() {
+                registry.shard_channels.remove(channel);
+            }
+        }
+        if let Some(subs) = registry.clients.get_mut(&client_id) {
+            subs.shard_channels.remove(channel);
+        }
+        let count = registry.shard_subscription_count(client_id);
+        registry.release_if_idle(client_id);
+        Ok(count)
+    }
+
+    /// Remove every subscription of a client (RESET and connection teardown)
+    pub fn unsubscribe_all(&self, client_id: usize) -> Result<()> {
+        let (channels, patterns) = self.subscriptions_of(client_id)?;
+        for channel in &channels {
+            self.unsubscribe(client_id, channel)?;
+        }
+        for pattern in &patterns {
+            self.punsubscribe(client_id, pattern)?;
+        }
+        for channel in &self.shard_subscriptions_of(client_id)? {
+            self.sunsubscribe(client_id, channel)?;
+        }
+        Ok(())
+    }
+
+    /// Get the channels and patterns a client is subscribed to, each sorted
+    pub fn subscriptions_of(&self, client_id: usize) -> Result<(Vec<Bytes>, Vec<Bytes>)> {
+        let registry = self.read()?;
+        Ok(match registry.clients.get(&client_id) {
+            Some(subs) => {
+                let mut channels: Vec<Bytes> = subs.channels.iter().cloned().collect();
+                let mut patterns: Vec<Bytes> = subs.patterns.iter().cloned().collect();
+                channels.sort();
+                patterns.sort();
+                (channels, patterns)
+            }
+            None => (Vec::new(), Vec::new()),
+        })
+    }
+
+    /// Get the shard channels a client is subscribed to, sorted
+    pub fn shard_subscriptions_of(&self, client_id: usize) -> Result<Vec<Bytes>> {
+        let registry = self.read()?;
+        let mut channels: Vec<Bytes> = registry
+            .clients
+            .get(&client_id)
+            .map(|subs| subs.shard_channels.iter().cloned().collect())
+            .unwrap_or_default();
+        channels.sort();
+        Ok(channels)
+    }
+
+    /// Get the total channel and pattern subscriptions of a client
+    pub fn subscription_count(&self, client_id: usize) -> Result<usize> {
+        Ok(self.read()?.subscription_count(client_id))
+    }
+
+    /// Get the shard channel subscriptions of a client
+    pub fn shard_subscription_count(&self, client_id: usize) -> Result<usize> {
+        Ok(self.read()?.shard_subscription_count(client_id))
+    }
+
+    /// Publish a message, returning the number of clients that received it
+    pub fn publish(&self, channel: &Bytes, payload: &Bytes) -> Result<usize> {
+        self.metrics.record_publish();
+        let registry = self.read()?;
+        let limit = self.buffer_limit();
+        let mut receivers = 0;
+
+        if let Some(subscribers) = registry.channels.get(channel) {
+            for client_id in subscribers {
+                let message = PubSubMessage {
+                    pattern: None,
+                    channel: channel.clone(),
+                    sharded: false,
+                    payload: payload.clone(),
+                };
+                if Self::deliver(&registry, *client_id, message, limit) {
+                    receivers += 1;
+                }
+            }
+        }
+
+        for (pattern, subscribers) in &registry.patterns {
+            if !glob_match(pattern, channel) {
+                continue;
+            }
+            for client_id in subscribers {
+                let message = PubSubMessage {
+                    pattern: Some(pattern.clone()),
+                    channel: channel.clone(),
+                    sharded: false,
+                    payload: payload.clone(),
+                };
+                if Self::deliver(&registry, *client_id, message, limit) {
+                    receivers += 1;
+                }
+            }
+        }
+
+        Ok(receivers)
+    }
+
+    /// Publish a message to a shard channel, returning the number of clients that received it.
+    ///
+    /// Shard channels are not matched against patterns.
+    pub fn spublish(&self, channel: &Bytes, payload: &Bytes) -> Result<usize> {
+        self.metrics.record_publish();
+        let registry = self.read()?;
+        let limit = self.buffer_limit();
+        let mut receivers = 0;
+
+        if let Some(subscribers) = registry.shard_channels.get(channel) {
+            for client_id in subscribers {
+                let message = PubSubMessage {
+                    pattern: None,
+                    channel: channel.clone(),
+                    sharded: true,
+                    payload: payload.clone(),
+                };
+                if Self::deliver(&registry, *client_id, message, limit) {
+                    receivers += 1;
+                }
+            }
+        }
+
+        Ok(receivers)
+    }
+
+    /// PUBSUB CHANNELS - Active channels, optionally filtered by a pattern
+    pub fn channels(&self, pattern: Option<&[u8]>) -> Result<Vec<Bytes>> {
+        let registry = self.read()?;
+        Ok(registry
+            .channels
+            .keys()
+            .filter(|channel| pattern.is_none_or(|p| glob_match(p, channel)))
+            .cloned()
+            .collect())
+    }
+
+    /// PUBSUB NUMSUB - Number of subscribers of a channel
+    pub fn numsub(&self, channel: &Bytes) -> Result<usize> {
+        Ok(self.read()?.channels.get(channel).map_or(0, |s| s.len()))
+    }
+
+    /// PUBSUB SHARDCHANNELS - Active shard channels, optionally filtered by a pattern
+    pub fn shard_channels(&self, pattern: Option<&[u8]>) -> Result<Vec<Bytes>> {
+        let registry = self.read()?;
+        Ok(registry
+            .shard_channels
+            .keys()
+            .filter(|channel| pattern.is_none_or(|p| glob_match(p, channel)))
+            .cloned()
+            .collect())
+    }
+
+    /// PUBSUB SHARDNUMSUB - Number of subscribers of a shard channel
+    pub fn shard_numsub(&self, channel: &Bytes) -> Result<usize> {
+        Ok(self
+            .read()?
+            .shard_channels
+            .get(channel)
+            .map_or(0, |s| s.len()))
+    }
+
+    /// PUBSUB NUMPAT - Number of pattern subscriptions across all clients
+    pub fn numpat(&self) -> Result<usize> {
+        Ok(self.read()?.patterns.values().map(|s| s.len()).sum())
+    }
+
+    /// Update the channel and pattern gauges after a subscription change
+    fn record_counts(&self, registry: &Registry) {
+        self.metrics.channels.set(registry.channels.len() as u64);
+        self.metrics.patterns.set(registry.patterns.len() as u64);
+    }
+
+    /// Queue a message for a subscriber; subscribers over the limit miss it
+    fn deliver(
+        registry: &Registry,
+        client_id: usize,
+        message: PubSubMessage,
+        limit: usize,
+    ) -> bool {
+        registry
+            .senders
+            .get(&client_id)
+            .is_some_and(|sender| sender.send(message, limit))
+    }
+
+    fn read(&self) -> Result<std::sync::RwLockReadGuard<'_, Registry>> {
+        self.registry
+            .read()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))
+    }
+
+    fn write(&self) -> Result<std::sync::RwLockWriteGuard<'_, Registry>> {
+        self.registry
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_to_channel_and_pattern() {
+        let broker = PubSubBroker::new();
+        let (sender, mut receiver) = message_queue();
+
+        assert_eq!(
+            broker.subscribe(1, &sender, Bytes::from("news")).unwrap(),
+            1
+        );
+        assert_eq!(broker.psubscribe(1, &sender, Bytes::from("n*")).unwrap(), 2);
+
+        let receivers = broker
+            .publish(&Bytes::from("news"), &Bytes::from("hello"))
+            .unwrap();
+        assert_eq!(receivers, 2);
+
+        let message = receiver.try_recv().unwrap();
+        assert_eq!(message.pattern, None);
+        assert_eq!(message.payload, Bytes::from("hello"));
+        let message = receiver.try_recv().unwrap();
+        assert_eq!(message.pattern, Some(Bytes::from("n*")));
+    }
+
+    #[test]
+    fn test_unsubscribe_all_clears_registry() {
+        let broker = PubSubBroker::new();
+        let (sender, _receiver) = message_queue();
+
+        broker.subscribe(1, &sender, Bytes::from("a")).unwrap();
+        broker.subscribe(1, &sender, Bytes::from("b")).unwrap();
+        broker.psubscribe(1, &sender, Bytes::from("c*")).unwrap();
+        assert_eq!(broker.channels(None).unwrap().len(), 2);
+        assert_eq!(broker.numpat().unwrap(), 1);
+
+        broker.unsubscribe_all(1).unwrap();
+        assert!(broker.channels(None).unwrap().is_empty());
+        assert_eq!(broker.numsub(&Bytes::from("a")).unwrap(), 0);
+        assert_eq!(broker.numpat().unwrap(), 0);
+        assert_eq!(broker.subscription_count(1).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_shard_channels_are_separate() {
+        let broker = PubSubBroker::new();
+        let (sender, mut receiver) = message_queue();
+
+        assert_eq!(broker.subscribe(1, &sender, Bytes::from("a")).unwrap(), 1);
+        assert_eq!(broker.ssubscribe(1, &sender, Bytes::from("a")).unwrap(), 1);
+        assert_eq!(broker.psubscribe(1, &sender, Bytes::from("*")).unwrap(), 2);
+
+        // SPUBLISH only reaches shard subscribers, and patterns never match it
+        let receivers = broker
+            .spublish(&Bytes::from("a"), &Bytes::from("hi"))
+            .unwrap();
+        assert_eq!(receivers, 1);
+        let message = receiver.try_recv().unwrap();
+        assert!(message.sharded);
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(broker.channels(None).unwrap(), vec![Bytes::from("a")]);
+
+        // Shard channels are counted on their own
+        broker.ssubscribe(1, &sender, Bytes::from("b")).unwrap();
+        assert_eq!(
+            broker.shard_channels(None).unwrap(),
+            vec![Bytes::from("a"), Bytes::from("b")]
+        );
+        assert_eq!(
+            broker.shard_channels(Some(b"b*")).unwrap(),
+            vec![Bytes::from("b")]
+        );
+        assert_eq!(broker.shard_numsub(&Bytes::from("b")).unwrap(), 1);
+        assert_eq!(broker.numsub(&Bytes::from("b")).unwrap(), 0);
+
+        broker.unsubscribe_all(1).unwrap();
+        assert_eq!(broker.shard_subscription_count(1).unwrap(), 0);
+        assert_eq!(
+            broker
+                .spublish(&Bytes::from("a"), &Bytes::from("hi"))
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_slow_subscriber_overflows() {
+        let broker = PubSubBroker::new();
+        broker.set_buffer_limit(10);
+        let (slow, mut slow_receiver) = message_queue();
+        let (fast, mut fast_receiver) = message_queue();
+        broker.subscribe(1, &slow, Bytes::from("ch")).unwrap();
+        broker.subscribe(2, &fast, Bytes::from("ch")).unwrap();
+
+        // "ch" + "1234" is 6 bytes, so a second unread message overruns 10
+        let payload = Bytes::from("1234");
+        assert_eq!(broker.publish(&Bytes::from("ch"), &payload).unwrap(), 2);
+        fast_receiver.try_recv().unwrap();
+        assert_eq!(broker.publish(&Bytes::from("ch"), &payload).unwrap(), 1);
+        fast_receiver.try_recv().unwrap();
+
+        // The overflowed subscriber receives nothing more, even once drained
+        slow_receiver.try_recv().unwrap();
+        assert_eq!(broker.publish(&Bytes::from("ch"), &payload).unwrap(), 1);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        assert_eq!(runtime.block_on(slow_receiver.recv()), None);
+    }
+}
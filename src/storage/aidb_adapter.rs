@@ -39,11 +39,14 @@
 //! ```
 
 use crate::error::{AikvError, Result};
-use crate::storage::{SerializableStoredValue, StoredValue};
+use crate::storage::encoding::EncodingThresholds;
+use crate::storage::slots::{key_slot, SlotIndex};
+use crate::storage::{ExpiryListener, SerializableStoredValue, StoredValue, ValueType};
 use aidb::{Options, WriteBatch, DB};
 use bytes::Bytes;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 // Re-export BatchOp from memory_adapter for consistency
@@ -67,6 +70,19 @@ pub struct AiDbStorageAdapter {
     /// Multiple databases (default: 16 databases like Redis)
     /// Each database is a separate AiDb instance with its own directory
     databases: Arc<Vec<Arc<DB>>>,
+    /// Thresholds used to classify object encodings.
+    /// Encodings are not persisted; values are classified from their contents on read.
+    encoding_thresholds: Arc<RwLock<EncodingThresholds>>,
+    /// Told about every key removed because its TTL passed
+    expiry_listener: Arc<RwLock<Option<ExpiryListener>>>,
+    /// Keys of each database bucketed by cluster hash slot, once cluster mode
+    /// has enabled the index. Built from a scan then and kept up to date on
+    /// every write.
+    slots: Arc<RwLock<Option<Vec<SlotIndex>>>>,
+    /// Serializes read-modify-write updates, which AiDb cannot do in one call
+    update_lock: Arc<Mutex<()>>,
+    /// How many times each database has been flushed
+    generations: Arc<Vec<AtomicU64>>,
 }
 
 impl AiDbStorageAdapter {
@@ -105,9 +121,77 @@ impl AiDbStorageAdapter {
 
         Ok(Self {
             databases: Arc::new(databases),
+            encoding_thresholds: Arc::new(RwLock::new(EncodingThresholds::default())),
+            expiry_listener: Arc::new(RwLock::new(None)),
+            slots: Arc::new(RwLock::new(None)),
+            update_lock: Arc::new(Mutex::new(())),
+            generations: Arc::new((0..db_count).map(|_| AtomicU64::new(0)).collect()),
         })
     }
 
+    /// Bucket every key by cluster hash slot, as cluster mode needs for
+    /// COUNTKEYSINSLOT and GETKEYSINSLOT.
+    ///
+    /// Scans each database once; the index is then kept up to date on every
+    /// write. Writes wait for the scan, so none is missed while it runs.
+    #[cfg(feature = "cluster")]
+    pub fn enable_slot_index(&self) -> Result<()> {
+        let mut slots = self
+            .slots
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        if slots.is_some() {
+            return Ok(());
+        }
+
+        let mut indexes = vec![SlotIndex::default(); self.databases.len()];
+        for (i, index) in indexes.iter_mut().enumerate() {
+            for key in self.get_all_keys_in_db(i)? {
+                index.insert(&key);
+            }
+        }
+        *slots = Some(indexes);
+        Ok(())
+    }
+
+    /// Record that `key` holds a value in a database's slot index
+    fn index_key(&self, db_index: usize, key: &str) {
+        if let Ok(mut slots) = self.slots.write() {
+            if let Some(index) = slots.as_mut().and_then(|slots| slots.get_mut(db_index)) {
+                index.insert(key);
+            }
+        }
+    }
+
+    /// Drop `key` from a database's slot index
+    fn unindex_key(&self, db_index: usize, key: &str) {
+        if let Ok(mut slots) = self.slots.write() {
+            if let Some(index) = slots.as_mut().and_then(|slots| slots.get_mut(db_index)) {
+                index.remove(key);
+            }
+        }
+    }
+
+    /// Get the thresholds used to classify object encodings
+    pub fn encoding_thresholds(&self) -> Result<EncodingThresholds> {
+        let thresholds = self
+            .encoding_thresholds
+            .read()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        Ok(*thresholds)
+    }
+
+    /// Set the threshold behind a CONFIG parameter, returning `Ok(false)` if there is none.
+    ///
+    /// Only later writes are classified with the new value; recorded encodings stay as they are.
+    pub fn set_encoding_threshold(&self, parameter: &str, value: &str) -> Result<bool> {
+        let mut thresholds = self
+            .encoding_thresholds
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        thresholds.set_parameter(parameter, value)
+    }
+
     /// Get current time in milliseconds
     fn current_time_ms() -> u64 {
         SystemTime::now()
@@ -149,6 +233,52 @@ impl AiDbStorageAdapter {
         expire_key
     }
 
+    /// Register the listener told about every key removed by expiry
+    pub fn set_expiry_listener(&self, listener: ExpiryListener) -> Result<()> {
+        let mut current = self
+            .expiry_listener
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        *current = Some(listener);
+        Ok(())
+    }
+
+    /// Tell the expiry listener, if any, that `key` expired
+    fn notify_expired(&self, db_index: usize, key: &str) {
+        let listener = match self.expiry_listener.read() {
+            Ok(listener) => listener.clone(),
+            Err(_) => None,
+        };
+        if let Some(listener) = listener {
+            listener(db_index, key);
+        }
+    }
+
+    /// Delete a key if it has logically expired, returning whether it was removed.
+    ///
+    /// Every read path calls this before looking at a key, so an expired key is
+    /// reclaimed on first access and never reported by TYPE, EXISTS, TTL, OBJECT
+    /// or any other read.
+    pub fn expire_if_due(&self, db_index: usize, key: &str) -> Result<bool> {
+        let Some(db) = self.databases.get(db_index) else {
+            return Ok(false);
+        };
+        let key_bytes = key.as_bytes();
+
+        if !self.is_expired(db, key_bytes)? {
+            return Ok(false);
+        }
+
+        db.delete(key_bytes)
+            .map_err(|e| AikvError::Storage(format!("Failed to delete expired key: {}", e)))?;
+        let expire_key = Self::expiration_key(key_bytes);
+        db.delete(&expire_key)
+            .map_err(|e| AikvError::Storage(format!("Failed to delete expiration: {}", e)))?;
+        self.unindex_key(db_index, key);
+        self.notify_expired(db_index, key);
+        Ok(true)
+    }
+
     // ========================================================================
     // CORE STORAGE METHODS (Minimal Interface Post-Refactoring)
     // ========================================================================
@@ -193,14 +323,7 @@ impl AiDbStorageAdapter {
         let db = &self.databases[db_index];
         let key_bytes = key.as_bytes();
 
-        // Check if key is expired
-        if self.is_expired(db, key_bytes)? {
-            // Clean up expired key
-            db.delete(key_bytes)
-                .map_err(|e| AikvError::Storage(format!("Failed to delete expired key: {}", e)))?;
-            let expire_key = Self::expiration_key(key_bytes);
-            db.delete(&expire_key)
-                .map_err(|e| AikvError::Storage(format!("Failed to delete expiration: {}", e)))?;
+        if self.expire_if_due(db_index, key)? {
             return Ok(None);
         }
 
@@ -260,6 +383,7 @@ impl AiDbStorageAdapter {
         // Store the serialized value
         db.put(key_bytes, &serialized)
             .map_err(|e| AikvError::Storage(format!("Failed to put value: {}", e)))?;
+        self.index_key(db_index, &key);
 
         // Handle expiration if set
         if let Some(expires_at) = value.expires_at() {
@@ -306,6 +430,11 @@ impl AiDbStorageAdapter {
             )));
         }
 
+        let _guard = self
+            .update_lock
+            .lock()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+
         // Get the current value
         let mut value = match self.get_value(db_index, key)? {
             Some(v) => v,
@@ -315,12 +444,54 @@ impl AiDbStorageAdapter {
         // Apply the update function
         f(&mut value)?;
 
-        // Store the updated value
-        self.set_value(db_index, key.to_string(), value)?;
+        // Store the updated value, or drop a container the update emptied
+        if value.is_empty_container() {
+            self.delete_from_db(db_index, key)?;
+        } else {
+            self.set_value(db_index, key.to_string(), value)?;
+        }
 
         Ok(true)
     }
 
+    /// Atomically update a value, starting from `default` if the key is missing.
+    ///
+    /// Runs under the same lock as `update_value`, so concurrent callers such as
+    /// INCR never lose an update. If the closure fails, nothing is written.
+    pub fn upsert_value<F>(
+        &self,
+        db_index: usize,
+        key: &str,
+        default: StoredValue,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut StoredValue) -> Result<()>,
+    {
+        if db_index >= self.databases.len() {
+            return Err(AikvError::Storage(format!(
+                "Invalid database index: {}",
+                db_index
+            )));
+        }
+
+        let _guard = self
+            .update_lock
+            .lock()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+
+        let mut value = self.get_value(db_index, key)?.unwrap_or(default);
+        f(&mut value)?;
+
+        if value.is_empty_container() {
+            self.delete_from_db(db_index, key)?;
+        } else {
+            self.set_value(db_index, key.to_string(), value)?;
+        }
+
+        Ok(())
+    }
+
     /// Atomically delete a key and return its value.
     ///
     /// This method provides atomic delete-and-get semantics, useful for implementing
@@ -360,6 +531,7 @@ impl AiDbStorageAdapter {
             // Delete the key
             db.delete(key_bytes)
                 .map_err(|e| AikvError::Storage(format!("Failed to delete key: {}", e)))?;
+            self.unindex_key(db_index, key);
 
             // Delete expiration metadata if exists
             let expire_key = Self::expiration_key(key_bytes);
@@ -410,13 +582,33 @@ impl AiDbStorageAdapter {
 
         let db = &self.databases[db_index];
         let mut batch = WriteBatch::new();
+        let mut written = Vec::with_capacity(operations.len());
 
         for (key, op) in operations {
             let key_bytes = key.as_bytes();
+            let deleted = matches!(op, BatchOp::Delete);
             match op {
                 BatchOp::Set(value) => {
                     batch.put(key_bytes, &value);
                 }
+                BatchOp::SetValue(value) => {
+                    // Strings stay raw so the legacy string accessors can read them
+                    match value.value() {
+                        ValueType::String(data) => batch.put(key_bytes, data),
+                        _ => {
+                            let serialized =
+                                bincode::serialize(&value.to_serializable()).map_err(|e| {
+                                    AikvError::Storage(format!("Failed to serialize value: {}", e))
+                                })?;
+                            batch.put(key_bytes, &serialized);
+                        }
+                    }
+                    let expire_key = Self::expiration_key(key_bytes);
+                    match value.expires_at() {
+                        Some(expires_at) => batch.put(&expire_key, &expires_at.to_le_bytes()),
+                        None => batch.delete(&expire_key),
+                    }
+                }
                 BatchOp::Delete => {
                     batch.delete(key_bytes);
                     // Also delete expiration metadata
@@ -424,12 +616,21 @@ impl AiDbStorageAdapter {
                     batch.delete(&expire_key);
                 }
             }
+            written.push((key, deleted));
         }
 
         // Write the batch atomically
         db.write(batch)
             .map_err(|e| AikvError::Storage(format!("Failed to write batch: {}", e)))?;
 
+        for (key, deleted) in written {
+            if deleted {
+                self.unindex_key(db_index, &key);
+            } else {
+                self.index_key(db_index, &key);
+            }
+        }
+
         Ok(())
     }
 
@@ -449,14 +650,7 @@ impl AiDbStorageAdapter {
         let db = &self.databases[db_index];
         let key_bytes = key.as_bytes();
 
-        // Check if key is expired
-        if self.is_expired(db, key_bytes)? {
-            // Clean up expired key
-            db.delete(key_bytes)
-                .map_err(|e| AikvError::Storage(format!("Failed to delete expired key: {}", e)))?;
-            let expire_key = Self::expiration_key(key_bytes);
-            db.delete(&expire_key)
-                .map_err(|e| AikvError::Storage(format!("Failed to delete expiration: {}", e)))?;
+        if self.expire_if_due(db_index, key)? {
             return Ok(None);
         }
 
@@ -487,6 +681,11 @@ impl AiDbStorageAdapter {
         let db = &self.databases[db_index];
         db.put(key.as_bytes(), &value)
             .map_err(|e| AikvError::Storage(format!("Failed to put value: {}", e)))?;
+        self.index_key(db_index, &key);
+
+        // A new value starts without a TTL, as with the memory adapter
+        db.delete(&Self::expiration_key(key.as_bytes()))
+            .map_err(|e| AikvError::Storage(format!("Failed to delete expiration: {}", e)))?;
         Ok(())
     }
 
@@ -516,6 +715,7 @@ impl AiDbStorageAdapter {
         // Set the value
         db.put(key_bytes, &value)
             .map_err(|e| AikvError::Storage(format!("Failed to put value: {}", e)))?;
+        self.index_key(db_index, &key);
 
         // Set the expiration
         let expire_key = Self::expiration_key(key_bytes);
@@ -559,7 +759,9 @@ impl AiDbStorageAdapter {
         Ok(true)
     }
 
-    /// Set expiration at absolute timestamp in milliseconds
+    /// Set expiration at absolute timestamp in milliseconds.
+    ///
+    /// A timestamp that has already passed expires the key at once.
     pub fn set_expire_at_in_db(
         &self,
         db_index: usize,
@@ -593,6 +795,7 @@ impl AiDbStorageAdapter {
         let expire_key = Self::expiration_key(key_bytes);
         db.put(&expire_key, &timestamp_ms.to_le_bytes())
             .map_err(|e| AikvError::Storage(format!("Failed to set expiration: {}", e)))?;
+        self.expire_if_due(db_index, key)?;
 
         Ok(true)
     }
@@ -609,6 +812,10 @@ impl AiDbStorageAdapter {
         let db = &self.databases[db_index];
         let key_bytes = key.as_bytes();
 
+        if self.expire_if_due(db_index, key)? {
+            return Ok(-2);
+        }
+
         // Check if key exists
         if db
             .get(key_bytes)
@@ -618,11 +825,6 @@ impl AiDbStorageAdapter {
             return Ok(-2); // Key doesn't exist
         }
 
-        // Check if expired
-        if self.is_expired(db, key_bytes)? {
-            return Ok(-2);
-        }
-
         // Get expiration
         let expire_key = Self::expiration_key(key_bytes);
         if let Some(expire_bytes) = db
@@ -664,6 +866,10 @@ impl AiDbStorageAdapter {
         let db = &self.databases[db_index];
         let key_bytes = key.as_bytes();
 
+        if self.expire_if_due(db_index, key)? {
+            return Ok(-2);
+        }
+
         // Check if key exists
         if db
             .get(key_bytes)
@@ -673,11 +879,6 @@ impl AiDbStorageAdapter {
             return Ok(-2); // Key doesn't exist
         }
 
-        // Check if expired
-        if self.is_expired(db, key_bytes)? {
-            return Ok(-2);
-        }
-
         // Get expiration
         let expire_key = Self::expiration_key(key_bytes);
         if let Some(expire_bytes) = db
@@ -766,6 +967,7 @@ impl AiDbStorageAdapter {
             // Delete the key
             db.delete(key_bytes)
                 .map_err(|e| AikvError::Storage(format!("Failed to delete key: {}", e)))?;
+            self.unindex_key(db_index, key);
 
             // Delete expiration metadata if exists
             let expire_key = Self::expiration_key(key_bytes);
@@ -794,8 +996,7 @@ impl AiDbStorageAdapter {
         let db = &self.databases[db_index];
         let key_bytes = key.as_bytes();
 
-        // Check if expired
-        if self.is_expired(db, key_bytes)? {
+        if self.expire_if_due(db_index, key)? {
             return Ok(false);
         }
 
@@ -857,6 +1058,81 @@ impl AiDbStorageAdapter {
         Ok(self.get_all_keys_in_db(db_index)?.len())
     }
 
+    /// Count the keys with an expiration set in a database
+    pub fn expires_count_in_db(&self, db_index: usize) -> Result<usize> {
+        let mut count = 0;
+        for key in self.get_all_keys_in_db(db_index)? {
+            if self.get_expire_time_in_db(db_index, &key)? > 0 {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Count the keys in a cluster hash slot.
+    ///
+    /// Once cluster mode has enabled the slot index, only the keys bucketed in
+    /// `slot` are looked at; before that every key of the database is hashed.
+    pub fn count_keys_in_slot(&self, db_index: usize, slot: u16) -> Result<usize> {
+        Ok(self.get_keys_in_slot(db_index, slot, usize::MAX)?.len())
+    }
+
+    /// Get up to `count` keys in a cluster hash slot
+    pub fn get_keys_in_slot(
+        &self,
+        db_index: usize,
+        slot: u16,
+        count: usize,
+    ) -> Result<Vec<String>> {
+        let Some(db) = self.databases.get(db_index) else {
+            return Ok(Vec::new());
+        };
+        let indexed: Option<Vec<String>> = {
+            let slots = self
+                .slots
+                .read()
+                .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+            slots.as_ref().map(|slots| {
+                slots
+                    .get(db_index)
+                    .map(|index| index.keys(slot).cloned().collect())
+                    .unwrap_or_default()
+            })
+        };
+        let candidates = match indexed {
+            Some(keys) => keys,
+            None => self
+                .get_all_keys_in_db(db_index)?
+                .into_iter()
+                .filter(|key| key_slot(key.as_bytes()) == slot)
+                .collect(),
+        };
+
+        let mut keys = Vec::new();
+        for key in candidates {
+            if keys.len() >= count {
+                break;
+            }
+            if !self.is_expired(db, key.as_bytes())? {
+                keys.push(key);
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Get the number of databases
+    pub fn db_count(&self) -> Result<usize> {
+        Ok(self.databases.len())
+    }
+
+    /// Get how many times a database has been flushed
+    pub fn generation_of_db(&self, db_index: usize) -> Result<u64> {
+        Ok(self
+            .generations
+            .get(db_index)
+            .map_or(0, |generation| generation.load(Ordering::SeqCst)))
+    }
+
     /// Clear a specific database
     pub fn flush_db(&self, db_index: usize) -> Result<()> {
         if db_index >= self.databases.len() {
@@ -879,6 +1155,12 @@ impl AiDbStorageAdapter {
                 .map_err(|e| AikvError::Storage(format!("Failed to delete key: {}", e)))?;
         }
 
+        if let Ok(mut slots) = self.slots.write() {
+            if let Some(index) = slots.as_mut().and_then(|slots| slots.get_mut(db_index)) {
+                index.clear();
+            }
+        }
+        self.generations[db_index].fetch_add(1, Ordering::SeqCst);
         Ok(())
     }
 
@@ -936,6 +1218,7 @@ impl AiDbStorageAdapter {
         // Copy to destination
         dst.put(key_bytes, &value)
             .map_err(|e| AikvError::Storage(format!("Failed to put value: {}", e)))?;
+        self.index_key(dst_db, key);
 
         // Copy expiration if exists
         let expire_key = Self::expiration_key(key_bytes);
@@ -950,6 +1233,7 @@ impl AiDbStorageAdapter {
         // Delete from source
         src.delete(key_bytes)
             .map_err(|e| AikvError::Storage(format!("Failed to delete from source: {}", e)))?;
+        self.unindex_key(src_db, key);
         let _ = src.delete(&expire_key);
 
         Ok(true)
@@ -981,24 +1265,35 @@ impl AiDbStorageAdapter {
             None => return Ok(false),
         };
 
+        // Renaming a key to itself leaves it untouched
+        if old_key_bytes == new_key_bytes {
+            return Ok(true);
+        }
+
         // Set new key
         db.put(new_key_bytes, &value)
             .map_err(|e| AikvError::Storage(format!("Failed to put value: {}", e)))?;
+        self.index_key(db_index, new_key);
 
-        // Copy expiration if exists
+        // Move the expiration, replacing any TTL the destination had
         let old_expire_key = Self::expiration_key(old_key_bytes);
         let new_expire_key = Self::expiration_key(new_key_bytes);
-        if let Some(expire_bytes) = db
+        match db
             .get(&old_expire_key)
             .map_err(|e| AikvError::Storage(format!("Failed to get expiration: {}", e)))?
         {
-            db.put(&new_expire_key, &expire_bytes)
-                .map_err(|e| AikvError::Storage(format!("Failed to put expiration: {}", e)))?;
+            Some(expire_bytes) => db
+                .put(&new_expire_key, &expire_bytes)
+                .map_err(|e| AikvError::Storage(format!("Failed to put expiration: {}", e)))?,
+            None => {
+                let _ = db.delete(&new_expire_key);
+            }
         }
 
         // Delete old key
         db.delete(old_key_bytes)
             .map_err(|e| AikvError::Storage(format!("Failed to delete old key: {}", e)))?;
+        self.unindex_key(db_index, old_key);
         let _ = db.delete(&old_expire_key);
 
         Ok(true)
@@ -1016,11 +1311,12 @@ impl AiDbStorageAdapter {
         let db = &self.databases[db_index];
         let new_key_bytes = new_key.as_bytes();
 
-        // Check if new key exists
-        if db
-            .get(new_key_bytes)
-            .map_err(|e| AikvError::Storage(format!("Failed to check new key: {}", e)))?
-            .is_some()
+        // Check if new key exists; an expired one doesn't count
+        if !self.is_expired(db, new_key_bytes)?
+            && db
+                .get(new_key_bytes)
+                .map_err(|e| AikvError::Storage(format!("Failed to check new key: {}", e)))?
+                .is_some()
         {
             return Ok(false);
         }
@@ -1075,6 +1371,7 @@ impl AiDbStorageAdapter {
         // Copy to destination
         dst.put(dst_key_bytes, &value)
             .map_err(|e| AikvError::Storage(format!("Failed to put value: {}", e)))?;
+        self.index_key(dst_db, dst_key);
 
         // Copy expiration if exists
         let src_expire_key = Self::expiration_key(src_key_bytes);
@@ -1267,6 +1564,49 @@ mod tests {
     // Tests for new serialization-based storage (all data types)
     // ========================================================================
 
+    #[test]
+    fn test_slot_key_counts() {
+        use crate::storage::slots::key_slot;
+
+        let (_dir, storage) = create_temp_storage();
+        let slot = key_slot(b"user");
+        for key in ["{user}:1", "{user}:2", "{user}:3"] {
+            storage
+                .set_in_db(0, key.to_string(), Bytes::from("v"))
+                .unwrap();
+        }
+        storage.delete_from_db(0, "{user}:2").unwrap();
+        storage
+            .write_batch(
+                0,
+                vec![
+                    ("{user}:4".to_string(), BatchOp::Set(Bytes::from("v"))),
+                    ("{user}:3".to_string(), BatchOp::Delete),
+                ],
+            )
+            .unwrap();
+        assert_eq!(storage.count_keys_in_slot(0, slot).unwrap(), 2);
+
+        storage.set_expire_in_db(0, "{user}:4", 50).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(storage.count_keys_in_slot(0, slot).unwrap(), 1);
+        assert_eq!(
+            storage.get_keys_in_slot(0, slot, 10).unwrap(),
+            vec!["{user}:1"]
+        );
+
+        // The index cluster mode builds agrees, and follows later writes
+        #[cfg(feature = "cluster")]
+        {
+            storage.enable_slot_index().unwrap();
+            assert_eq!(storage.count_keys_in_slot(0, slot).unwrap(), 1);
+            storage
+                .set_in_db(0, "{user}:5".to_string(), Bytes::from("v"))
+                .unwrap();
+            assert_eq!(storage.count_keys_in_slot(0, slot).unwrap(), 2);
+        }
+    }
+
     #[test]
     fn test_stored_value_string() {
         let (_dir, storage) = create_temp_storage();
@@ -0,0 +1,579 @@
+//! Object Encodings - Redis-compatible OBJECT ENCODING reporting
+//!
+//! AiKv keeps every data type in a single general-purpose structure, but clients,
+//! tooling and tests expect the encoding names Redis reports for its compact and
+//! full representations. This module classifies values into those encodings using
+//! the same thresholds Redis exposes through CONFIG (`zset-max-listpack-entries`,
+//! `hash-max-listpack-value`, ...).
+//!
+//! Container encodings are sticky: once a value has grown into its full encoding
+//! (`skiplist`, `hashtable`, `quicklist`) it keeps it even after shrinking back
+//! under the thresholds, matching Redis behavior. Sets have two steps: an
+//! integer-only set starts as `intset` and, once it outgrows
+//! `set-max-intset-entries` or gains a non-integer member, moves to `listpack`
+//! if it fits the listpack limits and to `hashtable` otherwise, never going back. The memory adapter records the
+//! encoding on every write; values without a recorded encoding (e.g. freshly
+//! loaded from AiDb) are classified from their contents.
+//!
+//! MEMORY USAGE estimates are derived from the same encodings, using the sizes
+//! of the structures Redis would allocate for them on a 64-bit build.
+
+use crate::error::{AikvError, Result};
+use crate::storage::ValueType;
+
+/// Longest string reported as `embstr` (matches Redis `OBJ_ENCODING_EMBSTR_SIZE_LIMIT`)
+const EMBSTR_SIZE_LIMIT: usize = 44;
+
+/// Byte limit of a list node when `list-max-listpack-size` caps entries rather
+/// than bytes (matches Redis `SIZE_SAFETY_LIMIT`)
+const LIST_SIZE_SAFETY_LIMIT: usize = 8192;
+
+/// Size of an object header (`robj`)
+const OBJECT_HEADER_SIZE: usize = 16;
+
+/// Size of a hash table entry (`dictEntry`)
+const DICT_ENTRY_SIZE: usize = 24;
+
+/// Size of a hash table (`dict`), excluding its buckets
+const DICT_SIZE: usize = 56;
+
+/// Size of a hash table bucket pointer
+const POINTER_SIZE: usize = 8;
+
+/// Size of a listpack header and terminator
+const LISTPACK_OVERHEAD: usize = 7;
+
+/// Size of an intset header
+const INTSET_HEADER_SIZE: usize = 8;
+
+/// Size of a quicklist and of each of its nodes
+const QUICKLIST_SIZE: usize = 40;
+const QUICKLIST_NODE_SIZE: usize = 32;
+
+/// Size of a skiplist and of an average skiplist node (score, member, backward
+/// pointer and about 1.33 levels)
+const SKIPLIST_SIZE: usize = 32;
+const SKIPLIST_NODE_SIZE: usize = 48;
+
+/// Number of elements MEMORY USAGE samples by default
+pub const DEFAULT_MEMORY_SAMPLES: usize = 5;
+
+/// Object encoding as reported by OBJECT ENCODING
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// String holding a 64-bit signed integer
+    Int,
+    /// Short string
+    Embstr,
+    /// Long string
+    Raw,
+    /// Compact encoding for small sets of integers
+    Intset,
+    /// Compact encoding for small lists, hashes, sets and sorted sets
+    Listpack,
+    /// Full list encoding
+    Quicklist,
+    /// Full sorted set encoding
+    Skiplist,
+    /// Full hash and set encoding
+    Hashtable,
+}
+
+impl Encoding {
+    /// Get the encoding name as reported by OBJECT ENCODING
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Int => "int",
+            Encoding::Embstr => "embstr",
+            Encoding::Raw => "raw",
+            Encoding::Intset => "intset",
+            Encoding::Listpack => "listpack",
+            Encoding::Quicklist => "quicklist",
+            Encoding::Skiplist => "skiplist",
+            Encoding::Hashtable => "hashtable",
+        }
+    }
+}
+
+/// Thresholds deciding when a container leaves its compact encoding.
+///
+/// Defaults match Redis 7.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodingThresholds {
+    pub hash_max_listpack_entries: usize,
+    pub hash_max_listpack_value: usize,
+    pub set_max_intset_entries: usize,
+    pub set_max_listpack_entries: usize,
+    pub set_max_listpack_value: usize,
+    pub zset_max_listpack_entries: usize,
+    pub zset_max_listpack_value: usize,
+    /// Positive: max entries per list node; negative: max node size (-1 = 4kb ... -5 = 64kb)
+    pub list_max_listpack_size: i64,
+}
+
+impl Default for EncodingThresholds {
+    fn default() -> Self {
+        Self {
+            hash_max_listpack_entries: 128,
+            hash_max_listpack_value: 64,
+            set_max_intset_entries: 512,
+            set_max_listpack_entries: 128,
+            set_max_listpack_value: 64,
+            zset_max_listpack_entries: 128,
+            zset_max_listpack_value: 64,
+            list_max_listpack_size: -2,
+        }
+    }
+}
+
+impl EncodingThresholds {
+    /// CONFIG parameters backed by the thresholds, with their current values
+    pub fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "hash-max-listpack-entries",
+                self.hash_max_listpack_entries.to_string(),
+            ),
+            (
+                "hash-max-listpack-value",
+                self.hash_max_listpack_value.to_string(),
+            ),
+            (
+                "set-max-intset-entries",
+                self.set_max_intset_entries.to_string(),
+            ),
+            (
+                "set-max-listpack-entries",
+                self.set_max_listpack_entries.to_string(),
+            ),
+            (
+                "set-max-listpack-value",
+                self.set_max_listpack_value.to_string(),
+            ),
+            (
+                "zset-max-listpack-entries",
+                self.zset_max_listpack_entries.to_string(),
+            ),
+            (
+                "zset-max-listpack-value",
+                self.zset_max_listpack_value.to_string(),
+            ),
+            (
+                "list-max-listpack-size",
+                self.list_max_listpack_size.to_string(),
+            ),
+        ]
+    }
+
+    /// Set the threshold behind a CONFIG parameter.
+    ///
+    /// Returns `Ok(false)` when `parameter` is not an encoding threshold.
+    pub fn set_parameter(&mut self, parameter: &str, value: &str) -> Result<bool> {
+        let invalid = || {
+            AikvError::InvalidArgument(format!(
+                "ERR CONFIG SET failed (possibly related to argument '{}') - argument couldn't be parsed into an integer",
+                parameter
+            ))
+        };
+        let field = match parameter {
+            "hash-max-listpack-entries" => &mut self.hash_max_listpack_entries,
+            "hash-max-listpack-value" => &mut self.hash_max_listpack_value,
+            "set-max-intset-entries" => &mut self.set_max_intset_entries,
+            "set-max-listpack-entries" => &mut self.set_max_listpack_entries,
+            "set-max-listpack-value" => &mut self.set_max_listpack_value,
+            "zset-max-listpack-entries" => &mut self.zset_max_listpack_entries,
+            "zset-max-listpack-value" => &mut self.zset_max_listpack_value,
+            "list-max-listpack-size" => {
+                self.list_max_listpack_size = value.parse().map_err(|_| invalid())?;
+                return Ok(true);
+            }
+            _ => return Ok(false),
+        };
+        *field = value.parse().map_err(|_| invalid())?;
+        Ok(true)
+    }
+
+    /// Classify a value, never downgrading a container below its `previous` encoding.
+    pub fn classify(&self, value: &ValueType, previous: Option<Encoding>) -> Encoding {
+        match value {
+            ValueType::String(data) => Self::classify_string(data),
+            ValueType::List(list) => {
+                if previous == Some(Encoding::Quicklist) {
+                    return Encoding::Quicklist;
+                }
+                // A single large element can exceed the byte limit on its own,
+                // so bytes are checked even when the threshold counts entries
+                let bytes = list.iter().map(|item| item.len()).sum::<usize>();
+                let fits = if self.list_max_listpack_size > 0 {
+                    list.len() <= self.list_max_listpack_size as usize
+                        && bytes <= LIST_SIZE_SAFETY_LIMIT
+                } else {
+                    bytes <= self.list_max_listpack_bytes()
+                };
+                if fits {
+                    Encoding::Listpack
+                } else {
+                    Encoding::Quicklist
+                }
+            }
+            ValueType::Hash(hash) => {
+                if previous == Some(Encoding::Hashtable) {
+                    return Encoding::Hashtable;
+                }
+                let max_value = self.hash_max_listpack_value;
+                if hash.len() <= self.hash_max_listpack_entries
+                    && hash
+                        .iter()
+                        .all(|(field, value)| field.len() <= max_value && value.len() <= max_value)
+                {
+                    Encoding::Listpack
+                } else {
+                    Encoding::Hashtable
+                }
+            }
+            ValueType::Set(set) => {
+                if previous == Some(Encoding::Hashtable) {
+                    return Encoding::Hashtable;
+                }
+                // Only a set that has never left intset can still use it
+                if matches!(previous, None | Some(Encoding::Intset))
+                    && set.len() <= self.set_max_intset_entries
+                    && set.iter().all(|member| is_integer(member))
+                {
+                    return Encoding::Intset;
+                }
+                if set.len() <= self.set_max_listpack_entries
+                    && set
+                        .iter()
+                        .all(|member| member.len() <= self.set_max_listpack_value)
+                {
+                    Encoding::Listpack
+                } else {
+                    Encoding::Hashtable
+                }
+            }
+            ValueType::ZSet(zset) => {
+                if previous == Some(Encoding::Skiplist) {
+                    return Encoding::Skiplist;
+                }
+                if zset.len() <= self.zset_max_listpack_entries
+                    && zset
+                        .keys()
+                        .all(|member| member.len() <= self.zset_max_listpack_value)
+                {
+                    Encoding::Listpack
+                } else {
+                    Encoding::Skiplist
+                }
+            }
+        }
+    }
+
+    /// Byte limit of a list node for a negative `list-max-listpack-size`
+    fn list_max_listpack_bytes(&self) -> usize {
+        let level = self.list_max_listpack_size.clamp(-5, -1).unsigned_abs();
+        4096 << (level - 1)
+    }
+
+    /// Estimate the bytes a value occupies, for MEMORY USAGE.
+    ///
+    /// Container elements are sized individually; at most `samples` of them are
+    /// measured and their average is scaled by the element count, while
+    /// `samples == 0` measures every element for an exact estimate.
+    pub fn memory_usage(&self, value: &ValueType, encoding: Encoding, samples: usize) -> usize {
+        let body = match (value, encoding) {
+            // Integers live in the object header itself
+            (ValueType::String(_), Encoding::Int) => 0,
+            (ValueType::String(data), _) => sds_size(data.len()),
+            (ValueType::List(list), Encoding::Listpack) => {
+                LISTPACK_OVERHEAD
+                    + sampled_size(
+                        list.iter().map(|item| listpack_entry_size(item.len())),
+                        samples,
+                    )
+            }
+            (ValueType::List(list), _) => {
+                let bytes = sampled_size(
+                    list.iter().map(|item| listpack_entry_size(item.len())),
+                    samples,
+                );
+                let node_bytes = if self.list_max_listpack_size > 0 {
+                    LIST_SIZE_SAFETY_LIMIT
+                } else {
+                    self.list_max_listpack_bytes()
+                };
+                let nodes = bytes.div_ceil(node_bytes).max(1);
+                QUICKLIST_SIZE + nodes * (QUICKLIST_NODE_SIZE + LISTPACK_OVERHEAD) + bytes
+            }
+            (ValueType::Hash(hash), Encoding::Listpack) => {
+                LISTPACK_OVERHEAD
+                    + sampled_size(
+                        hash.iter().map(|(field, value)| {
+                            listpack_entry_size(field.len()) + listpack_entry_size(value.len())
+                        }),
+                        samples,
+                    )
+            }
+            (ValueType::Hash(hash), _) => {
+                dict_size(hash.len())
+                    + sampled_size(
+                        hash.iter().map(|(field, value)| {
+                            DICT_ENTRY_SIZE + sds_size(field.len()) + sds_size(value.len())
+                        }),
+                        samples,
+                    )
+            }
+            (ValueType::Set(set), Encoding::Intset) => INTSET_HEADER_SIZE + set.len() * 8,
+            (ValueType::Set(set), Encoding::Listpack) => {
+                LISTPACK_OVERHEAD
+                    + sampled_size(set.iter().map(|m| listpack_entry_size(m.len())), samples)
+            }
+            (ValueType::Set(set), _) => {
+                dict_size(set.len())
+                    + sampled_size(
+                        set.iter().map(|m| DICT_ENTRY_SIZE + sds_size(m.len())),
+                        samples,
+                    )
+            }
+            (ValueType::ZSet(zset), Encoding::Listpack) => {
+                // Each member is followed by its score
+                LISTPACK_OVERHEAD
+                    + sampled_size(
+                        zset.keys().map(|m| listpack_entry_size(m.len()) + 9),
+                        samples,
+                    )
+            }
+            (ValueType::ZSet(zset), _) => {
+                dict_size(zset.len())
+                    + SKIPLIST_SIZE
+                    + sampled_size(
+                        zset.keys()
+                            .map(|m| DICT_ENTRY_SIZE + SKIPLIST_NODE_SIZE + sds_size(m.len())),
+                        samples,
+                    )
+            }
+        };
+
+        OBJECT_HEADER_SIZE + body
+    }
+
+    fn classify_string(data: &[u8]) -> Encoding {
+        if is_integer(data) {
+            Encoding::Int
+        } else if data.len() <= EMBSTR_SIZE_LIMIT {
+            Encoding::Embstr
+        } else {
+            Encoding::Raw
+        }
+    }
+}
+
+/// Whether `data` is the canonical form of a 64-bit signed integer, the only
+/// strings Redis stores as integers (`007` and `+7` are not)
+fn is_integer(data: &[u8]) -> bool {
+    data.len() <= 20
+        && std::str::from_utf8(data)
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok())
+            .is_some_and(|n| n.to_string().as_bytes() == data)
+}
+
+/// Bytes the keyspace spends on a key name: its sds string and dict entry
+pub fn key_memory_usage(key_len: usize) -> usize {
+    DICT_ENTRY_SIZE + sds_size(key_len)
+}
+
+/// Allocation size of an sds string of `len` bytes
+fn sds_size(len: usize) -> usize {
+    let header = match len {
+        0..=255 => 3,
+        256..=65535 => 5,
+        _ => 9,
+    };
+    header + len + 1
+}
+
+/// Size of a listpack entry holding `len` bytes (encoding, data and back-length)
+fn listpack_entry_size(len: usize) -> usize {
+    let encoding = match len {
+        0..=63 => 1,
+        64..=4095 => 2,
+        _ => 5,
+    };
+    let entry = encoding + len;
+    entry + if entry < 128 { 1 } else { 2 }
+}
+
+/// Size of a hash table sized for `len` entries, excluding the entries
+fn dict_size(len: usize) -> usize {
+    DICT_SIZE + len.next_power_of_two() * POINTER_SIZE
+}
+
+/// Total of the element sizes, measuring at most `samples` elements and
+/// scaling their average to the element count (`samples == 0` measures all)
+fn sampled_size<I>(sizes: I, samples: usize) -> usize
+where
+    I: ExactSizeIterator<Item = usize>,
+{
+    let len = sizes.len();
+    let measured = if samples == 0 { len } else { samples.min(len) };
+    if measured == 0 {
+        return 0;
+    }
+
+    let total: usize = sizes.take(measured).sum();
+    total * len / measured
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_string_encodings() {
+        let thresholds = EncodingThresholds::default();
+        let classify = |s: &str| {
+            thresholds.classify(
+                &ValueType::String(Bytes::copy_from_slice(s.as_bytes())),
+                None,
+            )
+        };
+
+        assert_eq!(classify("12345"), Encoding::Int);
+        assert_eq!(classify("-7"), Encoding::Int);
+        assert_eq!(classify("007"), Encoding::Embstr);
+        assert_eq!(classify("hello"), Encoding::Embstr);
+        assert_eq!(classify(&"x".repeat(45)), Encoding::Raw);
+    }
+
+    #[test]
+    fn test_zset_encoding_is_sticky() {
+        let thresholds = EncodingThresholds {
+            zset_max_listpack_entries: 2,
+            ..Default::default()
+        };
+        let mut zset = BTreeMap::new();
+        zset.insert(b"a".to_vec(), 1.0);
+        zset.insert(b"b".to_vec(), 2.0);
+        assert_eq!(
+            thresholds.classify(&ValueType::ZSet(zset.clone()), None),
+            Encoding::Listpack
+        );
+
+        zset.insert(b"c".to_vec(), 3.0);
+        let grown = thresholds.classify(&ValueType::ZSet(zset.clone()), Some(Encoding::Listpack));
+        assert_eq!(grown, Encoding::Skiplist);
+
+        zset.remove(b"c".as_slice());
+        assert_eq!(
+            thresholds.classify(&ValueType::ZSet(zset), Some(grown)),
+            Encoding::Skiplist
+        );
+    }
+
+    #[test]
+    fn test_set_leaves_intset_in_two_steps() {
+        let set = |members: &[&str]| {
+            ValueType::Set(members.iter().map(|m| m.as_bytes().to_vec()).collect())
+        };
+        let thresholds = EncodingThresholds {
+            set_max_intset_entries: 2,
+            set_max_listpack_entries: 3,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            thresholds.classify(&set(&["1", "2"]), None),
+            Encoding::Intset
+        );
+        assert_eq!(
+            thresholds.classify(&set(&["1", "007"]), None),
+            Encoding::Listpack
+        );
+
+        // Outgrowing the intset limit moves to listpack while it still fits...
+        let grown = thresholds.classify(&set(&["1", "2", "3"]), Some(Encoding::Intset));
+        assert_eq!(grown, Encoding::Listpack);
+        // ...and never back to intset
+        assert_eq!(
+            thresholds.classify(&set(&["1", "2"]), Some(grown)),
+            Encoding::Listpack
+        );
+
+        // Past the listpack limit as well it goes straight to hashtable
+        let thresholds = EncodingThresholds {
+            set_max_intset_entries: 2,
+            set_max_listpack_entries: 2,
+            ..Default::default()
+        };
+        let grown = thresholds.classify(&set(&["1", "2", "3"]), Some(Encoding::Intset));
+        assert_eq!(grown, Encoding::Hashtable);
+        assert_eq!(
+            thresholds.classify(&set(&["1"]), Some(grown)),
+            Encoding::Hashtable
+        );
+    }
+
+    #[test]
+    fn test_list_large_element_flips_to_quicklist() {
+        let list = |sizes: &[usize]| {
+            ValueType::List(
+                sizes
+                    .iter()
+                    .map(|&size| Bytes::from("x".repeat(size)))
+                    .collect(),
+            )
+        };
+
+        // Byte-limited nodes (default -2 = 8kb)
+        let thresholds = EncodingThresholds::default();
+        assert_eq!(
+            thresholds.classify(&list(&[1, 1]), None),
+            Encoding::Listpack
+        );
+        assert_eq!(
+            thresholds.classify(&list(&[1, 9000]), Some(Encoding::Listpack)),
+            Encoding::Quicklist
+        );
+
+        // Count-limited nodes still respect the byte safety limit
+        let thresholds = EncodingThresholds {
+            list_max_listpack_size: 128,
+            ..Default::default()
+        };
+        assert_eq!(
+            thresholds.classify(&list(&[1, 1]), None),
+            Encoding::Listpack
+        );
+        assert_eq!(
+            thresholds.classify(&list(&[1, 9000]), Some(Encoding::Listpack)),
+            Encoding::Quicklist
+        );
+    }
+
+    #[test]
+    fn test_memory_usage_sampling() {
+        let thresholds = EncodingThresholds::default();
+        let list = ValueType::List(
+            ["a", "a", "xxxxxxxxxx", "xxxxxxxxxx"]
+                .iter()
+                .map(|item| Bytes::from(*item))
+                .collect(),
+        );
+
+        // Two samples see only the short head; all samples see every element
+        let sampled = thresholds.memory_usage(&list, Encoding::Listpack, 2);
+        let exact = thresholds.memory_usage(&list, Encoding::Listpack, 0);
+        assert!(exact > sampled);
+        assert_eq!(exact, thresholds.memory_usage(&list, Encoding::Listpack, 4));
+
+        // Integers are stored in the object header
+        let int = ValueType::String(Bytes::from("42"));
+        assert_eq!(
+            thresholds.memory_usage(&int, Encoding::Int, 0),
+            OBJECT_HEADER_SIZE
+        );
+    }
+}
@@ -36,6 +36,9 @@
 //! ```
 
 use crate::error::{AikvError, Result};
+use crate::storage::encoding::{key_memory_usage, Encoding, EncodingThresholds};
+use crate::storage::slots::SlotIndex;
+use crate::storage::ExpiryListener;
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
@@ -47,6 +50,8 @@ use std::time::{SystemTime, UNIX_EPOCH};
 pub enum BatchOp {
     /// Set a key to a value
     Set(Bytes),
+    /// Set a key to a value of any type, including its expiration
+    SetValue(StoredValue),
     /// Delete a key
     Delete,
 }
@@ -79,6 +84,8 @@ pub struct StoredValue {
     pub(crate) value: ValueType,
     /// Expiration time in milliseconds since UNIX epoch
     pub(crate) expires_at: Option<u64>,
+    /// Encoding recorded when the value was last stored (None if never classified)
+    pub(crate) encoding: Option<Encoding>,
 }
 
 // Serializable versions for storage (optimized for bincode)
@@ -146,6 +153,7 @@ impl StoredValue {
         Self {
             value,
             expires_at: serializable.expires_at,
+            encoding: None,
         }
     }
 }
@@ -155,6 +163,7 @@ impl StoredValue {
         Self {
             value: ValueType::String(data),
             expires_at: None,
+            encoding: None,
         }
     }
 
@@ -162,6 +171,7 @@ impl StoredValue {
         Self {
             value: ValueType::List(list),
             expires_at: None,
+            encoding: None,
         }
     }
 
@@ -169,6 +179,7 @@ impl StoredValue {
         Self {
             value: ValueType::Hash(hash),
             expires_at: None,
+            encoding: None,
         }
     }
 
@@ -176,6 +187,7 @@ impl StoredValue {
         Self {
             value: ValueType::Set(set),
             expires_at: None,
+            encoding: None,
         }
     }
 
@@ -183,6 +195,7 @@ impl StoredValue {
         Self {
             value: ValueType::ZSet(zset),
             expires_at: None,
+            encoding: None,
         }
     }
 
@@ -190,6 +203,7 @@ impl StoredValue {
         Self {
             value,
             expires_at: Some(expires_at),
+            encoding: None,
         }
     }
 
@@ -215,6 +229,20 @@ impl StoredValue {
         }
     }
 
+    /// Whether this is a list, hash, set or sorted set with no elements.
+    ///
+    /// Redis deletes a container once its last element is removed, so the
+    /// storage layer never keeps one: writing an empty container deletes the key.
+    pub fn is_empty_container(&self) -> bool {
+        match &self.value {
+            ValueType::String(_) => false,
+            ValueType::List(list) => list.is_empty(),
+            ValueType::Hash(hash) => hash.is_empty(),
+            ValueType::Set(set) => set.is_empty(),
+            ValueType::ZSet(zset) => zset.is_empty(),
+        }
+    }
+
     /// Get reference to the underlying value
     pub fn value(&self) -> &ValueType {
         &self.value
@@ -324,10 +352,102 @@ impl StoredValue {
     pub fn set_expiration(&mut self, expires_at: Option<u64>) {
         self.expires_at = expires_at;
     }
+
+    /// Get the encoding reported by OBJECT ENCODING.
+    ///
+    /// Uses the recorded encoding when available, otherwise classifies the
+    /// value from its contents.
+    pub fn encoding(&self, thresholds: &EncodingThresholds) -> Encoding {
+        match (&self.value, self.encoding) {
+            (ValueType::String(_), _) | (_, None) => thresholds.classify(&self.value, None),
+            (_, Some(encoding)) => encoding,
+        }
+    }
+
+    /// Estimate the bytes this value and its `key` use, as reported by MEMORY USAGE.
+    ///
+    /// At most `samples` container elements are measured (0 measures all).
+    pub fn memory_usage(
+        &self,
+        key: &str,
+        thresholds: &EncodingThresholds,
+        samples: usize,
+    ) -> usize {
+        let encoding = self.encoding(thresholds);
+        key_memory_usage(key.len()) + thresholds.memory_usage(&self.value, encoding, samples)
+    }
+
+    /// Record the encoding of this value, never downgrading below `previous`.
+    pub(crate) fn record_encoding(
+        &mut self,
+        previous: Option<Encoding>,
+        thresholds: &EncodingThresholds,
+    ) {
+        self.encoding = match self.value {
+            // String encodings follow the contents and are never recorded
+            ValueType::String(_) => None,
+            _ => Some(thresholds.classify(&self.value, previous)),
+        };
+    }
+}
+
+/// Database containing key-value pairs.
+///
+/// Reads go straight to the map; writes go through the methods below so the
+/// per-slot key index stays in step with the keys.
+#[derive(Default)]
+struct Database {
+    entries: HashMap<String, StoredValue>,
+    slots: SlotIndex,
+    /// Bumped every time the database is flushed
+    generation: u64,
+}
+
+impl Database {
+    fn insert(&mut self, key: String, value: StoredValue) -> Option<StoredValue> {
+        if !self.entries.contains_key(&key) {
+            self.slots.insert(&key);
+        }
+        self.entries.insert(key, value)
+    }
+
+    fn remove(&mut self, key: &str) -> Option<StoredValue> {
+        let removed = self.entries.remove(key);
+        if removed.is_some() {
+            self.slots.remove(key);
+        }
+        removed
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut StoredValue> {
+        self.entries.get_mut(key)
+    }
+
+    fn retain(&mut self, mut f: impl FnMut(&String, &StoredValue) -> bool) {
+        let slots = &mut self.slots;
+        self.entries.retain(|key, value| {
+            let keep = f(key, value);
+            if !keep {
+                slots.remove(key);
+            }
+            keep
+        });
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.slots.clear();
+        self.generation = self.generation.wrapping_add(1);
+    }
 }
 
-/// Database containing key-value pairs
-type Database = HashMap<String, StoredValue>;
+impl std::ops::Deref for Database {
+    type Target = HashMap<String, StoredValue>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entries
+    }
+}
 
 /// Simple in-memory storage adapter
 /// This will be replaced with AiDb integration in the future
@@ -335,6 +455,10 @@ type Database = HashMap<String, StoredValue>;
 pub struct StorageAdapter {
     /// Multiple databases (default: 16 databases like Redis)
     databases: Arc<RwLock<Vec<Database>>>,
+    /// Thresholds used to classify object encodings on write
+    encoding_thresholds: Arc<RwLock<EncodingThresholds>>,
+    /// Told about every key removed because its TTL passed
+    expiry_listener: Arc<RwLock<Option<ExpiryListener>>>,
 }
 
 impl StorageAdapter {
@@ -345,13 +469,35 @@ impl StorageAdapter {
     pub fn with_db_count(count: usize) -> Self {
         let mut databases = Vec::with_capacity(count);
         for _ in 0..count {
-            databases.push(HashMap::new());
+            databases.push(Database::default());
         }
         Self {
             databases: Arc::new(RwLock::new(databases)),
+            encoding_thresholds: Arc::new(RwLock::new(EncodingThresholds::default())),
+            expiry_listener: Arc::new(RwLock::new(None)),
         }
     }
 
+    /// Get the thresholds used to classify object encodings
+    pub fn encoding_thresholds(&self) -> Result<EncodingThresholds> {
+        let thresholds = self
+            .encoding_thresholds
+            .read()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        Ok(*thresholds)
+    }
+
+    /// Set the threshold behind a CONFIG parameter, returning `Ok(false)` if there is none.
+    ///
+    /// Only later writes are classified with the new value; recorded encodings stay as they are.
+    pub fn set_encoding_threshold(&self, parameter: &str, value: &str) -> Result<bool> {
+        let mut thresholds = self
+            .encoding_thresholds
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        thresholds.set_parameter(parameter, value)
+    }
+
     /// Get current time in milliseconds
     fn current_time_ms() -> u64 {
         SystemTime::now()
@@ -375,6 +521,64 @@ impl StorageAdapter {
         Ok(())
     }
 
+    /// Register the listener told about every key removed by expiry
+    pub fn set_expiry_listener(&self, listener: ExpiryListener) -> Result<()> {
+        let mut current = self
+            .expiry_listener
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        *current = Some(listener);
+        Ok(())
+    }
+
+    /// Tell the expiry listener, if any, that `key` expired
+    fn notify_expired(&self, db_index: usize, key: &str) {
+        let listener = match self.expiry_listener.read() {
+            Ok(listener) => listener.clone(),
+            Err(_) => None,
+        };
+        if let Some(listener) = listener {
+            listener(db_index, key);
+        }
+    }
+
+    /// Delete a key if it has logically expired, returning whether it was removed.
+    ///
+    /// Every read path calls this before looking at a key, so an expired key is
+    /// reclaimed on first access and never reported by TYPE, EXISTS, TTL, OBJECT
+    /// or any other read.
+    pub fn expire_if_due(&self, db_index: usize, key: &str) -> Result<bool> {
+        {
+            let databases = self
+                .databases
+                .read()
+                .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+            match databases.get(db_index).and_then(|db| db.get(key)) {
+                Some(stored) if stored.is_expired() => {}
+                _ => return Ok(false),
+            }
+        }
+
+        let removed = {
+            let mut databases = self
+                .databases
+                .write()
+                .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+            match databases.get_mut(db_index) {
+                // Re-check under the write lock; the key may have been rewritten
+                Some(db) if db.get(key).is_some_and(|stored| stored.is_expired()) => {
+                    db.remove(key).is_some()
+                }
+                _ => false,
+            }
+        };
+
+        if removed {
+            self.notify_expired(db_index, key);
+        }
+        Ok(removed)
+    }
+
     // ========================================================================
     // CORE STORAGE METHODS (Minimal Interface Post-Refactoring)
     // ========================================================================
@@ -405,6 +609,8 @@ impl StorageAdapter {
     /// }
     /// ```
     pub fn get_value(&self, db_index: usize, key: &str) -> Result<Option<StoredValue>> {
+        self.expire_if_due(db_index, key)?;
+
         let databases = self
             .databases
             .read()
@@ -443,13 +649,20 @@ impl StorageAdapter {
     /// let value = StoredValue::new_hash(hash);
     /// storage.set_value(0, "myhash".to_string(), value)?;
     /// ```
-    pub fn set_value(&self, db_index: usize, key: String, value: StoredValue) -> Result<()> {
+    pub fn set_value(&self, db_index: usize, key: String, mut value: StoredValue) -> Result<()> {
+        let thresholds = self.encoding_thresholds()?;
         let mut databases = self
             .databases
             .write()
             .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
 
         if let Some(db) = databases.get_mut(db_index) {
+            // Carry over the encoding of the value being replaced so growth is sticky
+            let previous = db
+                .get(&key)
+                .filter(|prev| !prev.is_expired() && prev.get_type_name() == value.get_type_name())
+                .and_then(|prev| prev.encoding);
+            value.record_encoding(previous, &thresholds);
             db.insert(key, value);
             Ok(())
         } else {
@@ -530,6 +743,7 @@ impl StorageAdapter {
     where
         F: FnOnce(&mut StoredValue) -> Result<()>,
     {
+        let thresholds = self.encoding_thresholds()?;
         let mut databases = self
             .databases
             .write()
@@ -541,13 +755,67 @@ impl StorageAdapter {
                     db.remove(key);
                     return Ok(false);
                 }
+                let previous = stored.encoding;
                 f(stored)?;
+                if stored.is_empty_container() {
+                    db.remove(key);
+                } else {
+                    stored.record_encoding(previous, &thresholds);
+                }
                 return Ok(true);
             }
         }
         Ok(false)
     }
 
+    /// Atomically update a value, starting from `default` if the key is missing.
+    ///
+    /// Unlike `update_value`, the missing-key case is handled under the same
+    /// lock, so concurrent callers such as INCR never lose an update. If the
+    /// closure fails on a missing key, nothing is written.
+    pub fn upsert_value<F>(
+        &self,
+        db_index: usize,
+        key: &str,
+        default: StoredValue,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut StoredValue) -> Result<()>,
+    {
+        let thresholds = self.encoding_thresholds()?;
+        let mut databases = self
+            .databases
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+
+        let db = databases
+            .get_mut(db_index)
+            .ok_or_else(|| AikvError::Storage(format!("Invalid database index: {}", db_index)))?;
+
+        if let Some(stored) = db.get_mut(key) {
+            if !stored.is_expired() {
+                let previous = stored.encoding;
+                f(stored)?;
+                if stored.is_empty_container() {
+                    db.remove(key);
+                } else {
+                    stored.record_encoding(previous, &thresholds);
+                }
+                return Ok(());
+            }
+            db.remove(key);
+        }
+
+        let mut value = default;
+        f(&mut value)?;
+        if !value.is_empty_container() {
+            value.record_encoding(None, &thresholds);
+            db.insert(key.to_string(), value);
+        }
+        Ok(())
+    }
+
     /// Write a batch of operations atomically.
     ///
     /// For MemoryAdapter, this provides in-memory atomicity. All operations
@@ -577,6 +845,7 @@ impl StorageAdapter {
             return Ok(());
         }
 
+        let thresholds = self.encoding_thresholds()?;
         let mut databases = self
             .databases
             .write()
@@ -588,6 +857,16 @@ impl StorageAdapter {
                     BatchOp::Set(value) => {
                         db.insert(key, StoredValue::new_string(value));
                     }
+                    BatchOp::SetValue(mut value) => {
+                        let previous = db
+                            .get(&key)
+                            .filter(|prev| {
+                                !prev.is_expired() && prev.get_type_name() == value.get_type_name()
+                            })
+                            .and_then(|prev| prev.encoding);
+                        value.record_encoding(previous, &thresholds);
+                        db.insert(key, value);
+                    }
                     BatchOp::Delete => {
                         db.remove(&key);
                     }
@@ -604,6 +883,8 @@ impl StorageAdapter {
 
     /// Get a value by key from a specific database
     pub fn get_from_db(&self, db_index: usize, key: &str) -> Result<Option<Bytes>> {
+        self.expire_if_due(db_index, key)?;
+
         let databases = self
             .databases
             .read()
@@ -702,33 +983,42 @@ impl StorageAdapter {
         Ok(false)
     }
 
-    /// Set expiration at absolute timestamp in milliseconds
+    /// Set expiration at absolute timestamp in milliseconds.
+    ///
+    /// A timestamp that has already passed expires the key at once.
     pub fn set_expire_at_in_db(
         &self,
         db_index: usize,
         key: &str,
         timestamp_ms: u64,
     ) -> Result<bool> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        {
+            let mut databases = self
+                .databases
+                .write()
+                .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
 
-        if let Some(db) = databases.get_mut(db_index) {
-            if let Some(stored) = db.get_mut(key) {
-                if stored.is_expired() {
-                    db.remove(key);
-                    return Ok(false);
-                }
-                stored.expires_at = Some(timestamp_ms);
-                return Ok(true);
+            let Some(db) = databases.get_mut(db_index) else {
+                return Ok(false);
+            };
+            let Some(stored) = db.get_mut(key) else {
+                return Ok(false);
+            };
+            if stored.is_expired() {
+                db.remove(key);
+                return Ok(false);
             }
+            stored.expires_at = Some(timestamp_ms);
         }
-        Ok(false)
+
+        self.expire_if_due(db_index, key)?;
+        Ok(true)
     }
 
     /// Get TTL in milliseconds
     pub fn get_ttl_in_db(&self, db_index: usize, key: &str) -> Result<i64> {
+        self.expire_if_due(db_index, key)?;
+
         let databases = self
             .databases
             .read()
@@ -756,6 +1046,8 @@ impl StorageAdapter {
 
     /// Get expiration timestamp in milliseconds
     pub fn get_expire_time_in_db(&self, db_index: usize, key: &str) -> Result<i64> {
+        self.expire_if_due(db_index, key)?;
+
         let databases = self
             .databases
             .read()
@@ -819,6 +1111,8 @@ impl StorageAdapter {
 
     /// Check if a key exists in a specific database
     pub fn exists_in_db(&self, db_index: usize, key: &str) -> Result<bool> {
+        self.expire_if_due(db_index, key)?;
+
         let databases = self
             .databases
             .read()
@@ -871,6 +1165,89 @@ impl StorageAdapter {
         }
     }
 
+    /// Count the keys with an expiration set in a database
+    pub fn expires_count_in_db(&self, db_index: usize) -> Result<usize> {
+        let databases = self
+            .databases
+            .read()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+
+        if let Some(db) = databases.get(db_index) {
+            let count = db
+                .values()
+                .filter(|v| v.expires_at.is_some() && !v.is_expired())
+                .count();
+            Ok(count)
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Count the keys in a cluster hash slot.
+    ///
+    /// Only the keys bucketed in `slot` are looked at, never the whole database.
+    pub fn count_keys_in_slot(&self, db_index: usize, slot: u16) -> Result<usize> {
+        let databases = self
+            .databases
+            .read()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+
+        if let Some(db) = databases.get(db_index) {
+            let count = db
+                .slots
+                .keys(slot)
+                .filter(|key| db.get(*key).is_some_and(|v| !v.is_expired()))
+                .count();
+            Ok(count)
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Get up to `count` keys in a cluster hash slot
+    pub fn get_keys_in_slot(
+        &self,
+        db_index: usize,
+        slot: u16,
+        count: usize,
+    ) -> Result<Vec<String>> {
+        let databases = self
+            .databases
+            .read()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+
+        if let Some(db) = databases.get(db_index) {
+            let keys = db
+                .slots
+                .keys(slot)
+                .filter(|key| db.get(*key).is_some_and(|v| !v.is_expired()))
+                .take(count)
+                .cloned()
+                .collect();
+            Ok(keys)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Get the number of databases
+    pub fn db_count(&self) -> Result<usize> {
+        let databases = self
+            .databases
+            .read()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        Ok(databases.len())
+    }
+
+    /// Get how many times a database has been flushed
+    pub fn generation_of_db(&self, db_index: usize) -> Result<u64> {
+        let databases = self
+            .databases
+            .read()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        Ok(databases.get(db_index).map_or(0, |db| db.generation))
+    }
+
     /// Clear a specific database
     pub fn flush_db(&self, db_index: usize) -> Result<()> {
         let mut databases = self
@@ -991,7 +1368,7 @@ impl StorageAdapter {
             .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
 
         if let Some(db) = databases.get_mut(db_index) {
-            if db.contains_key(new_key) {
+            if db.get(new_key).is_some_and(|value| !value.is_expired()) {
                 return Ok(false);
             }
             if let Some(value) = db.remove(old_key) {
@@ -1163,4 +1540,59 @@ mod tests {
         assert_eq!(value2.unwrap().as_string().unwrap(), &Bytes::from("value2"));
         assert!(value3.is_none());
     }
+
+    #[test]
+    fn test_slot_key_counts() {
+        use crate::storage::slots::key_slot;
+
+        let storage = StorageAdapter::new();
+        let slot = key_slot(b"user");
+        for key in ["{user}:1", "{user}:2", "{user}:3"] {
+            storage
+                .set_in_db(0, key.to_string(), Bytes::from("v"))
+                .unwrap();
+        }
+        storage
+            .set_in_db(0, "other".to_string(), Bytes::from("v"))
+            .unwrap();
+        assert_eq!(storage.count_keys_in_slot(0, slot).unwrap(), 3);
+        assert_eq!(storage.count_keys_in_slot(1, slot).unwrap(), 0);
+
+        // Overwrites don't count twice
+        storage
+            .set_in_db(0, "{user}:1".to_string(), Bytes::from("w"))
+            .unwrap();
+        assert_eq!(storage.count_keys_in_slot(0, slot).unwrap(), 3);
+
+        storage.delete_from_db(0, "{user}:2").unwrap();
+        assert_eq!(storage.count_keys_in_slot(0, slot).unwrap(), 2);
+        let mut keys = storage.get_keys_in_slot(0, slot, 10).unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["{user}:1", "{user}:3"]);
+        assert_eq!(storage.get_keys_in_slot(0, slot, 1).unwrap().len(), 1);
+
+        // Expired keys stop counting before and after they are reclaimed
+        let expires_at = StorageAdapter::current_time_ms() + 50;
+        storage
+            .set_with_expiration_in_db(0, "{user}:3".to_string(), Bytes::from("v"), expires_at)
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(storage.count_keys_in_slot(0, slot).unwrap(), 1);
+        assert!(storage.expire_if_due(0, "{user}:3").unwrap());
+        assert_eq!(storage.count_keys_in_slot(0, slot).unwrap(), 1);
+        assert_eq!(
+            storage.get_keys_in_slot(0, slot, 10).unwrap(),
+            vec!["{user}:1"]
+        );
+
+        // Keys follow RENAME and MOVE, and FLUSHDB forgets them all
+        storage.rename_in_db(0, "{user}:1", "moved").unwrap();
+        assert_eq!(storage.count_keys_in_slot(0, slot).unwrap(), 0);
+        storage.rename_in_db(0, "moved", "{user}:1").unwrap();
+        storage.move_key(0, 1, "{user}:1").unwrap();
+        assert_eq!(storage.count_keys_in_slot(0, slot).unwrap(), 0);
+        assert_eq!(storage.count_keys_in_slot(1, slot).unwrap(), 1);
+        storage.flush_db(1).unwrap();
+        assert_eq!(storage.count_keys_in_slot(1, slot).unwrap(), 0);
+    }
 }
@@ -1,5 +1,7 @@
 pub mod aidb_adapter;
+pub mod encoding;
 pub mod memory_adapter;
+pub mod slots;
 
 // Re-export the memory adapter as StorageAdapter for backward compatibility
 // In production, you would switch to aidb_adapter::AiDbStorageAdapter
@@ -11,8 +13,15 @@ pub use aidb_adapter::AiDbStorageAdapter;
 // Export the core storage types for command implementations
 pub use memory_adapter::{BatchOp, SerializableStoredValue, StoredValue, ValueType};
 
+// Export object encoding types for OBJECT ENCODING and CONFIG
+pub use encoding::{Encoding, EncodingThresholds};
+
 use crate::error::Result;
 use bytes::Bytes;
+use std::sync::Arc;
+
+/// Called with the database and key of every key removed because its TTL passed
+pub type ExpiryListener = Arc<dyn Fn(usize, &str) + Send + Sync>;
 
 /// Unified storage engine that wraps both memory and AiDb adapters.
 /// This enum allows seamless switching between storage backends via configuration.
@@ -41,6 +50,26 @@ impl StorageEngine {
     // CORE STORAGE METHODS
     // ========================================================================
 
+    /// Register the listener told about every key removed by expiry.
+    ///
+    /// Clones of the engine share the listener; setting it again replaces it.
+    pub fn set_expiry_listener(&self, listener: ExpiryListener) -> Result<()> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.set_expiry_listener(listener),
+            StorageEngine::AiDb(adapter) => adapter.set_expiry_listener(listener),
+        }
+    }
+
+    /// Delete a key if it has logically expired, returning whether it was removed.
+    ///
+    /// All read methods below apply this first, so expiry is enforced in one place.
+    pub fn expire_if_due(&self, db_index: usize, key: &str) -> Result<bool> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.expire_if_due(db_index, key),
+            StorageEngine::AiDb(adapter) => adapter.expire_if_due(db_index, key),
+        }
+    }
+
     /// Get a stored value by key from a specific database.
     pub fn get_value(&self, db_index: usize, key: &str) -> Result<Option<StoredValue>> {
         match self {
@@ -50,13 +79,35 @@ impl StorageEngine {
     }
 
     /// Set a value for a key in a specific database.
+    ///
+    /// Writing an empty list, hash, set or sorted set deletes the key instead.
     pub fn set_value(&self, db_index: usize, key: String, value: StoredValue) -> Result<()> {
+        if value.is_empty_container() {
+            self.delete_from_db(db_index, &key)?;
+            return Ok(());
+        }
         match self {
             StorageEngine::Memory(adapter) => adapter.set_value(db_index, key, value),
             StorageEngine::AiDb(adapter) => adapter.set_value(db_index, key, value),
         }
     }
 
+    /// Replace whatever a key holds with a new value.
+    ///
+    /// `set_value` keeps the encoding a container has grown into, as commands
+    /// write back the values they modify through it. A value that overwrites
+    /// its key instead starts from the encoding its own contents call for, as
+    /// Redis creates a new object for it.
+    pub fn replace_value(&self, db_index: usize, key: String, value: StoredValue) -> Result<()> {
+        self.write_batch(
+            db_index,
+            vec![
+                (key.clone(), BatchOp::Delete),
+                (key, BatchOp::SetValue(value)),
+            ],
+        )
+    }
+
     /// Atomically delete a key and return its value.
     pub fn delete_and_get(&self, db_index: usize, key: &str) -> Result<Option<StoredValue>> {
         match self {
@@ -76,14 +127,56 @@ impl StorageEngine {
         }
     }
 
+    /// Atomically update a value, starting from `default` if the key is missing.
+    pub fn upsert_value<F>(
+        &self,
+        db_index: usize,
+        key: &str,
+        default: StoredValue,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut StoredValue) -> Result<()>,
+    {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.upsert_value(db_index, key, default, f),
+            StorageEngine::AiDb(adapter) => adapter.upsert_value(db_index, key, default, f),
+        }
+    }
+
     /// Write a batch of operations atomically.
+    ///
+    /// Like `set_value`, writing an empty container deletes the key.
     pub fn write_batch(&self, db_index: usize, operations: Vec<(String, BatchOp)>) -> Result<()> {
+        let operations = operations
+            .into_iter()
+            .map(|(key, op)| match op {
+                BatchOp::SetValue(value) if value.is_empty_container() => (key, BatchOp::Delete),
+                op => (key, op),
+            })
+            .collect();
         match self {
             StorageEngine::Memory(adapter) => adapter.write_batch(db_index, operations),
             StorageEngine::AiDb(adapter) => adapter.write_batch(db_index, operations),
         }
     }
 
+    /// Get the thresholds used to classify object encodings
+    pub fn encoding_thresholds(&self) -> Result<EncodingThresholds> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.encoding_thresholds(),
+            StorageEngine::AiDb(adapter) => adapter.encoding_thresholds(),
+        }
+    }
+
+    /// Set the threshold behind a CONFIG parameter, returning `Ok(false)` if there is none
+    pub fn set_encoding_threshold(&self, parameter: &str, value: &str) -> Result<bool> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.set_encoding_threshold(parameter, value),
+            StorageEngine::AiDb(adapter) => adapter.set_encoding_threshold(parameter, value),
+        }
+    }
+
     // ========================================================================
     // LEGACY METHODS (Backward compatibility)
     // ========================================================================
@@ -235,6 +328,69 @@ impl StorageEngine {
         }
     }
 
+    /// Count the keys with an expiration set in a database
+    pub fn expires_count_in_db(&self, db_index: usize) -> Result<usize> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.expires_count_in_db(db_index),
+            StorageEngine::AiDb(adapter) => adapter.expires_count_in_db(db_index),
+        }
+    }
+
+    /// Index keys by cluster hash slot, for a server running in cluster mode.
+    ///
+    /// The memory engine always buckets its keys; AiDb scans its databases once
+    /// here instead of on every open.
+    #[cfg(feature = "cluster")]
+    pub fn enable_slot_index(&self) -> Result<()> {
+        match self {
+            StorageEngine::Memory(_) => Ok(()),
+            StorageEngine::AiDb(adapter) => adapter.enable_slot_index(),
+        }
+    }
+
+    /// Count the keys in a cluster hash slot
+    pub fn count_keys_in_slot(&self, db_index: usize, slot: u16) -> Result<usize> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.count_keys_in_slot(db_index, slot),
+            StorageEngine::AiDb(adapter) => adapter.count_keys_in_slot(db_index, slot),
+        }
+    }
+
+    /// Get up to `count` keys in a cluster hash slot
+    pub fn get_keys_in_slot(
+        &self,
+        db_index: usize,
+        slot: u16,
+        count: usize,
+    ) -> Result<Vec<String>> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.get_keys_in_slot(db_index, slot, count),
+            StorageEngine::AiDb(adapter) => adapter.get_keys_in_slot(db_index, slot, count),
+        }
+    }
+
+    /// Estimate the memory used by a database's keys and values.
+    ///
+    /// This is the sum of what MEMORY USAGE reports for every key.
+    pub fn used_memory_in_db(&self, db_index: usize) -> Result<usize> {
+        let thresholds = self.encoding_thresholds()?;
+        let mut total = 0;
+        for key in self.get_all_keys_in_db(db_index)? {
+            if let Some(stored) = self.get_value(db_index, &key)? {
+                total += stored.memory_usage(&key, &thresholds, encoding::DEFAULT_MEMORY_SAMPLES);
+            }
+        }
+        Ok(total)
+    }
+
+    /// Get the number of databases
+    pub fn db_count(&self) -> Result<usize> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.db_count(),
+            StorageEngine::AiDb(adapter) => adapter.db_count(),
+        }
+    }
+
     /// Clear a specific database
     pub fn flush_db(&self, db_index: usize) -> Result<()> {
         match self {
@@ -302,6 +458,16 @@ impl StorageEngine {
         }
     }
 
+    /// Get how many times a database has been flushed.
+    ///
+    /// SCAN tags its cursors with this so a cursor issued before a flush ends cleanly.
+    pub fn generation_of_db(&self, db_index: usize) -> Result<u64> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.generation_of_db(db_index),
+            StorageEngine::AiDb(adapter) => adapter.generation_of_db(db_index),
+        }
+    }
+
     /// Get a random key from a database
     pub fn random_key_in_db(&self, db_index: usize) -> Result<Option<String>> {
         match self {
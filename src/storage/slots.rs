@@ -0,0 +1,122 @@
+//! Cluster Slots - per-slot key index for CLUSTER COUNTKEYSINSLOT/GETKEYSINSLOT
+//!
+//! Redis Cluster splits the keyspace into 16384 hash slots. Counting or listing
+//! the keys of one slot by hashing every key is O(n) per call, so the storage
+//! adapters bucket each key by slot once, when it is inserted, and drop it from
+//! its bucket when it is removed. Counting a slot then only looks at that slot.
+//! The memory adapter always does; the AiDb adapter, which would have to scan
+//! its databases on disk first, only once a cluster-mode server enables it.
+
+use std::collections::{HashMap, HashSet};
+
+/// Redis Cluster has 16384 slots
+pub const SLOT_COUNT: u16 = 16384;
+
+/// Compute the hash slot of a key, as CLUSTER KEYSLOT reports it.
+///
+/// Only the part between the first `{` and the next `}` is hashed when it is
+/// non-empty (a hash tag), so related keys can be kept in the same slot.
+pub fn key_slot(key: &[u8]) -> u16 {
+    let hashed = key
+        .iter()
+        .position(|&c| c == b'{')
+        .and_then(|open| {
+            let tag = &key[open + 1..];
+            tag.iter()
+                .position(|&c| c == b'}')
+                .filter(|&close| close > 0)
+                .map(|close| &tag[..close])
+        })
+        .unwrap_or(key);
+    crc16(hashed) % SLOT_COUNT
+}
+
+/// CRC16-CCITT (XMODEM), the checksum Redis Cluster uses for key slots
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, &byte| {
+        (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Keys of one database bucketed by hash slot
+#[derive(Debug, Clone, Default)]
+pub struct SlotIndex {
+    slots: HashMap<u16, HashSet<String>>,
+}
+
+impl SlotIndex {
+    /// Record a newly inserted key
+    pub fn insert(&mut self, key: &str) {
+        self.slots
+            .entry(key_slot(key.as_bytes()))
+            .or_default()
+            .insert(key.to_string());
+    }
+
+    /// Forget a removed key
+    pub fn remove(&mut self, key: &str) {
+        let slot = key_slot(key.as_bytes());
+        if let Some(keys) = self.slots.get_mut(&slot) {
+            keys.remove(key);
+            if keys.is_empty() {
+                self.slots.remove(&slot);
+            }
+        }
+    }
+
+    /// Forget every key
+    pub fn clear(&mut self) {
+        self.slots.clear();
+    }
+
+    /// Iterate over the keys in a slot, in no particular order
+    pub fn keys(&self, slot: u16) -> impl Iterator<Item = &String> {
+        self.slots.get(&slot).into_iter().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_slot() {
+        assert_eq!(key_slot(b"123456789"), 12739);
+        assert_eq!(key_slot(b"foo"), 12182);
+        assert_eq!(key_slot(b""), 0);
+
+        // Hash tags
+        assert_eq!(key_slot(b"{user1000}.following"), key_slot(b"user1000"));
+        assert_eq!(key_slot(b"{user1000}.followers"), key_slot(b"user1000"));
+        assert_eq!(key_slot(b"foo{}{bar}"), key_slot(b"foo{}{bar}"));
+        assert_ne!(key_slot(b"foo{}{bar}"), key_slot(b"bar"));
+        assert_eq!(key_slot(b"foo{{bar}}zap"), key_slot(b"{bar"));
+        assert_eq!(key_slot(b"foo{bar}{zap}"), key_slot(b"bar"));
+    }
+
+    #[test]
+    fn test_slot_index() {
+        let mut index = SlotIndex::default();
+        index.insert("{a}1");
+        index.insert("{a}2");
+        index.insert("{a}2");
+        index.insert("b");
+
+        let slot = key_slot(b"a");
+        assert_eq!(index.keys(slot).count(), 2);
+        assert_eq!(index.keys(key_slot(b"b")).count(), 1);
+
+        index.remove("{a}1");
+        index.remove("missing");
+        assert_eq!(index.keys(slot).collect::<Vec<_>>(), vec!["{a}2"]);
+
+        index.clear();
+        assert_eq!(index.keys(slot).count(), 0);
+    }
+}
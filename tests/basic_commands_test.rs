@@ -1,6 +1,7 @@
-use aikv::command::CommandExecutor;
+use aikv::command::transaction::CommandGate;
+use aikv::command::{CommandExecutor, CONNECTION_COMMANDS};
 use aikv::protocol::RespValue;
-use aikv::StorageEngine;
+use aikv::{AikvError, StorageEngine};
 use bytes::Bytes;
 
 #[test]
@@ -85,6 +86,224 @@ fn test_database_commands() {
     assert_eq!(result, RespValue::integer(0));
 }
 
+#[test]
+fn test_scan_uses_selected_database() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // Iterate SCAN to completion and collect every key it returns
+    let scan_all = |current_db: &mut usize| {
+        let mut cursor = "0".to_string();
+        let mut keys = Vec::new();
+        loop {
+            let result = executor
+                .execute(
+                    "SCAN",
+                    &[Bytes::from(cursor.clone())],
+                    current_db,
+                    client_id,
+                )
+                .unwrap();
+            match result {
+                RespValue::Array(Some(mut reply)) => {
+                    if let RespValue::Array(Some(batch)) = reply.pop().unwrap() {
+                        keys.extend(batch);
+                    }
+                    match reply.pop().unwrap() {
+                        RespValue::BulkString(Some(next)) => {
+                            cursor = String::from_utf8_lossy(&next).to_string()
+                        }
+                        other => panic!("Unexpected cursor: {:?}", other),
+                    }
+                }
+                other => panic!("Unexpected SCAN reply: {:?}", other),
+            }
+            if cursor == "0" {
+                return keys;
+            }
+        }
+    };
+
+    executor
+        .execute("SELECT", &[Bytes::from("1")], &mut current_db, client_id)
+        .unwrap();
+    executor
+        .execute(
+            "MSET",
+            &[
+                Bytes::from("a"),
+                Bytes::from("1"),
+                Bytes::from("b"),
+                Bytes::from("2"),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+
+    let mut keys = scan_all(&mut current_db);
+    keys.sort_by_key(|k| format!("{:?}", k));
+    assert_eq!(
+        keys,
+        vec![RespValue::bulk_string("a"), RespValue::bulk_string("b")]
+    );
+
+    // Database 0 holds none of them
+    executor
+        .execute("SELECT", &[Bytes::from("0")], &mut current_db, client_id)
+        .unwrap();
+    assert!(scan_all(&mut current_db).is_empty());
+    let result = executor
+        .execute("KEYS", &[Bytes::from("*")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::array(vec![]));
+    let result = executor
+        .execute("RANDOMKEY", &[], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::null_bulk_string());
+}
+
+#[test]
+fn test_scan_while_keys_are_deleted() {
+    use rand::Rng;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage.clone());
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut args = Vec::new();
+    for i in 0..200 {
+        for prefix in ["keep", "temp"] {
+            args.push(Bytes::from(format!("{}:{}", prefix, i)));
+            args.push(Bytes::from("v"));
+        }
+    }
+    executor
+        .execute("MSET", &args, &mut current_db, client_id)
+        .unwrap();
+
+    let done = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        // Another client deletes the temp keys in random order
+        scope.spawn(|| {
+            let deleter = CommandExecutor::new(storage.clone());
+            let mut current_db = 0;
+            let mut rng = rand::thread_rng();
+            // Bounded, so a failing scan below cannot leave it running forever
+            for _ in 0..100_000 {
+                if done.load(Ordering::Relaxed) {
+                    break;
+                }
+                let key = format!("temp:{}", rng.gen_range(0..200));
+                deleter
+                    .execute("DEL", &[Bytes::from(key)], &mut current_db, 2)
+                    .unwrap();
+            }
+        });
+
+        // Every full iteration returns all of the keys that are never deleted
+        for _ in 0..20 {
+            let mut seen = HashSet::new();
+            let mut cursor = "0".to_string();
+            loop {
+                let reply = executor
+                    .execute(
+                        "SCAN",
+                        &[
+                            Bytes::from(cursor.clone()),
+                            Bytes::from("COUNT"),
+                            Bytes::from("5"),
+                        ],
+                        &mut current_db,
+                        client_id,
+                    )
+                    .unwrap();
+                let RespValue::Array(Some(reply)) = reply else {
+                    panic!("Unexpected SCAN reply: {:?}", reply);
+                };
+                if let RespValue::BulkString(Some(next)) = &reply[0] {
+                    cursor = String::from_utf8_lossy(next).to_string();
+                }
+                if let RespValue::Array(Some(keys)) = &reply[1] {
+                    for key in keys {
+                        if let RespValue::BulkString(Some(key)) = key {
+                            seen.insert(String::from_utf8_lossy(key).to_string());
+                        }
+                    }
+                }
+                if cursor == "0" {
+                    break;
+                }
+            }
+
+            let missing: Vec<String> = (0..200)
+                .map(|i| format!("keep:{}", i))
+                .filter(|key| !seen.contains(key))
+                .collect();
+            assert!(missing.is_empty(), "SCAN skipped {:?}", missing);
+        }
+        done.store(true, Ordering::Relaxed);
+    });
+}
+
+#[test]
+fn test_scan_ends_cleanly_after_flushdb() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut args = Vec::new();
+    for i in 0..100 {
+        args.push(Bytes::from(format!("key:{}", i)));
+        args.push(Bytes::from("v"));
+    }
+    executor
+        .execute("MSET", &args, &mut current_db, client_id)
+        .unwrap();
+
+    let scan = |cursor: &str, current_db: &mut usize| match executor
+        .execute(
+            "SCAN",
+            &[
+                Bytes::from(cursor.to_string()),
+                Bytes::from("COUNT"),
+                Bytes::from("5"),
+            ],
+            current_db,
+            client_id,
+        )
+        .unwrap()
+    {
+        RespValue::Array(Some(reply)) => reply,
+        other => panic!("Unexpected SCAN reply: {:?}", other),
+    };
+
+    let cursor = match &scan("0", &mut current_db)[0] {
+        RespValue::BulkString(Some(next)) => String::from_utf8_lossy(next).to_string(),
+        other => panic!("Unexpected cursor: {:?}", other),
+    };
+    assert_ne!(cursor, "0");
+
+    // The old cursor belongs to the flushed keyspace, even once it is refilled
+    executor
+        .execute("FLUSHDB", &[], &mut current_db, client_id)
+        .unwrap();
+    executor
+        .execute("MSET", &args, &mut current_db, client_id)
+        .unwrap();
+
+    assert_eq!(
+        scan(&cursor, &mut current_db),
+        vec![RespValue::bulk_string("0"), RespValue::array(vec![])]
+    );
+}
+
 #[test]
 fn test_key_commands() {
     let storage = StorageEngine::new_memory(16);
@@ -178,8 +397,10 @@ fn test_key_commands() {
         .unwrap();
     if let RespValue::Array(Some(scan_result)) = result {
         if let RespValue::Array(Some(keys)) = &scan_result[1] {
-            // Should return at most 1 key
-            assert!(keys.len() <= 1);
+            // COUNT is only a hint: one bucket is scanned, however many keys it holds
+            for key in keys {
+                assert!(matches!(key, RespValue::BulkString(Some(_))));
+            }
         } else {
             panic!("Expected keys array");
         }
@@ -266,6 +487,144 @@ fn test_key_commands() {
     assert_eq!(result, RespValue::bulk_string("Bob"));
 }
 
+#[test]
+fn test_rename_keeps_ttl() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut run = |command: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(command, &args, &mut current_db, client_id)
+    };
+
+    // The TTL moves with the value and replaces the destination's
+    run("SET", &["src", "value", "PX", "50000"]).unwrap();
+    run("SET", &["dst", "old", "EX", "5"]).unwrap();
+    assert_eq!(run("RENAME", &["src", "dst"]).unwrap(), RespValue::ok());
+    assert_eq!(
+        run("GET", &["dst"]).unwrap(),
+        RespValue::bulk_string("value")
+    );
+    let pttl = run("PTTL", &["dst"]).unwrap();
+    assert!(
+        matches!(pttl, RespValue::Integer(pttl) if pttl > 5000 && pttl <= 50000),
+        "PTTL after RENAME: {:?}",
+        pttl
+    );
+    assert_eq!(run("EXISTS", &["src"]).unwrap(), RespValue::integer(0));
+
+    // A key without a TTL clears the destination's
+    run("SET", &["persistent", "value"]).unwrap();
+    assert_eq!(
+        run("RENAME", &["persistent", "dst"]).unwrap(),
+        RespValue::ok()
+    );
+    assert_eq!(run("PTTL", &["dst"]).unwrap(), RespValue::integer(-1));
+
+    // Renaming a key to itself keeps it
+    assert_eq!(run("RENAME", &["dst", "dst"]).unwrap(), RespValue::ok());
+    assert_eq!(
+        run("GET", &["dst"]).unwrap(),
+        RespValue::bulk_string("value")
+    );
+
+    // RENAMENX keeps its TTL too, and refuses an existing destination
+    run("SET", &["src", "value", "EX", "100"]).unwrap();
+    assert_eq!(
+        run("RENAMENX", &["src", "dst"]).unwrap(),
+        RespValue::integer(0)
+    );
+    assert_eq!(
+        run("RENAMENX", &["src", "new"]).unwrap(),
+        RespValue::integer(1)
+    );
+    assert!(matches!(run("TTL", &["new"]).unwrap(), RespValue::Integer(ttl) if ttl > 0));
+
+    for command in ["RENAME", "RENAMENX"] {
+        let err = run(command, &["missing", "other"]).unwrap_err();
+        assert!(
+            err.to_string().contains("no such key"),
+            "{}: {}",
+            command,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_keys_glob_patterns() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    for key in ["hello", "hallo", "hillo", "a*b", "axb"] {
+        executor
+            .execute(
+                "SET",
+                &[Bytes::from(key), Bytes::from("v")],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+    }
+
+    let mut keys = |pattern: &str| -> Vec<RespValue> {
+        match executor
+            .execute(
+                "KEYS",
+                &[Bytes::from(pattern.to_string())],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap()
+        {
+            RespValue::Array(Some(mut keys)) => {
+                keys.sort_by_key(|k| format!("{:?}", k));
+                keys
+            }
+            other => panic!("Expected array, got {:?}", other),
+        }
+    };
+
+    assert_eq!(
+        keys("h[ae]llo"),
+        vec![
+            RespValue::bulk_string("hallo"),
+            RespValue::bulk_string("hello")
+        ]
+    );
+    assert_eq!(keys("h[^ae]llo"), vec![RespValue::bulk_string("hillo")]);
+    assert_eq!(keys("h[a-e]llo").len(), 2);
+    assert_eq!(keys("a\\*b"), vec![RespValue::bulk_string("a*b")]);
+    assert_eq!(keys("a?b").len(), 2);
+
+    // SCAN MATCH uses the same matcher
+    let result = executor
+        .execute(
+            "SCAN",
+            &[
+                Bytes::from("0"),
+                Bytes::from("MATCH"),
+                Bytes::from("a\\*b"),
+                Bytes::from("COUNT"),
+                Bytes::from("100"),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(
+        result,
+        RespValue::array(vec![
+            RespValue::bulk_string("0"),
+            RespValue::array(vec![RespValue::bulk_string("a*b")]),
+        ])
+    );
+}
+
 #[test]
 fn test_expiration_commands() {
     let storage = StorageEngine::new_memory(16);
@@ -382,6 +741,105 @@ fn test_expiration_commands() {
         )
         .unwrap();
     assert_eq!(result, RespValue::integer(-2));
+
+    // A missing key reports -2 and a persistent key -1 from every variant
+    executor
+        .execute(
+            "PERSIST",
+            &[Bytes::from("key1")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    for command in ["TTL", "PTTL", "EXPIRETIME", "PEXPIRETIME"] {
+        let result = executor
+            .execute(
+                command,
+                &[Bytes::from("nonexistent")],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+        assert_eq!(
+            result,
+            RespValue::integer(-2),
+            "{} on a missing key",
+            command
+        );
+
+        let result = executor
+            .execute(command, &[Bytes::from("key1")], &mut current_db, client_id)
+            .unwrap();
+        assert_eq!(
+            result,
+            RespValue::integer(-1),
+            "{} on a persistent key",
+            command
+        );
+    }
+}
+
+#[test]
+fn test_lazy_expiry_on_reads() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    executor
+        .execute(
+            "RPUSH",
+            &[Bytes::from("mylist"), Bytes::from("a")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    let result = executor
+        .execute("TYPE", &[Bytes::from("mylist")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::simple_string("list"));
+
+    executor
+        .execute(
+            "PEXPIRE",
+            &[Bytes::from("mylist"), Bytes::from("1")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(10));
+
+    // Every read sees the key as gone
+    let result = executor
+        .execute("TYPE", &[Bytes::from("mylist")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::simple_string("none"));
+    let result = executor
+        .execute(
+            "EXISTS",
+            &[Bytes::from("mylist")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::integer(0));
+    let result = executor
+        .execute("PTTL", &[Bytes::from("mylist")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::integer(-2));
+    let result = executor
+        .execute(
+            "OBJECT",
+            &[Bytes::from("ENCODING"), Bytes::from("mylist")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::null_bulk_string());
+    let result = executor
+        .execute("DBSIZE", &[], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::integer(0));
 }
 
 #[test]
@@ -440,7 +898,11 @@ fn test_server_commands() {
     // Register the client first (simulating what Connection does)
     executor
         .server_commands()
-        .register_client(client_id, "127.0.0.1:12345".to_string())
+        .register_client(
+            client_id,
+            "127.0.0.1:12345".to_string(),
+            "127.0.0.1:6379".to_string(),
+        )
         .unwrap();
 
     // Test INFO
@@ -458,7 +920,15 @@ fn test_server_commands() {
             client_id,
         )
         .unwrap();
-    if let RespValue::Array(Some(arr)) = result {
+    assert_eq!(
+        result,
+        RespValue::map(vec![(
+            RespValue::bulk_string("server"),
+            RespValue::bulk_string("aikv")
+        )])
+    );
+    // RESP2 connections receive the same pairs as a flat array
+    if let RespValue::Array(Some(arr)) = result.into_resp2() {
         assert_eq!(arr.len(), 2);
         assert_eq!(arr[0], RespValue::bulk_string("server"));
         assert_eq!(arr[1], RespValue::bulk_string("aikv"));
@@ -508,24 +978,292 @@ fn test_server_commands() {
 }
 
 #[test]
-fn test_scan_iteration() {
+fn test_wait_without_replicas() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
     let mut current_db = 0;
     let client_id = 1;
 
-    // Set up test data with many keys
-    for i in 0..25 {
-        executor
-            .execute(
-                "SET",
-                &[
-                    Bytes::from(format!("key:{}", i)),
-                    Bytes::from(format!("value:{}", i)),
-                ],
-                &mut current_db,
-                client_id,
-            )
+    // A standalone server has no replicas to wait for
+    let result = executor
+        .execute(
+            "WAIT",
+            &[Bytes::from("1"), Bytes::from("100")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::integer(0));
+
+    let result = executor.execute(
+        "WAIT",
+        &[Bytes::from("1"), Bytes::from("-1")],
+        &mut current_db,
+        client_id,
+    );
+    assert!(result.is_err());
+
+    let result = executor.execute(
+        "WAIT",
+        &[Bytes::from("many"), Bytes::from("0")],
+        &mut current_db,
+        client_id,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_info_keyspace() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // Empty databases are not listed
+    let result = executor
+        .execute(
+            "INFO",
+            &[Bytes::from("keyspace")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string("# Keyspace"));
+
+    for key in ["a", "b"] {
+        executor
+            .execute(
+                "SET",
+                &[Bytes::from(key), Bytes::from("v")],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+    }
+    executor
+        .execute(
+            "SET",
+            &[
+                Bytes::from("c"),
+                Bytes::from("v"),
+                Bytes::from("EX"),
+                Bytes::from("100"),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+
+    let mut db1 = 1;
+    executor
+        .execute(
+            "SET",
+            &[Bytes::from("d"), Bytes::from("v")],
+            &mut db1,
+            client_id,
+        )
+        .unwrap();
+
+    let result = executor
+        .execute(
+            "INFO",
+            &[Bytes::from("keyspace")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    let info = match result {
+        RespValue::BulkString(Some(info)) => String::from_utf8_lossy(&info).to_string(),
+        other => panic!("Expected bulk string, got {:?}", other),
+    };
+    let lines: Vec<&str> = info.split("\r\n").collect();
+    assert_eq!(
+        lines,
+        vec![
+            "# Keyspace",
+            "db0:keys=3,expires=1,avg_ttl=0",
+            "db1:keys=1,expires=0,avg_ttl=0",
+        ]
+    );
+}
+
+/// Read one `field:value` line from INFO persistence
+fn persistence_field(executor: &CommandExecutor, current_db: &mut usize, name: &str) -> String {
+    let info = match executor
+        .execute("INFO", &[Bytes::from("persistence")], current_db, 1)
+        .unwrap()
+    {
+        RespValue::BulkString(Some(info)) => String::from_utf8_lossy(&info).to_string(),
+        other => panic!("Expected bulk string, got {:?}", other),
+    };
+    info.lines()
+        .find_map(|line| line.strip_prefix(&format!("{}:", name)))
+        .unwrap_or_else(|| panic!("{} missing from INFO persistence", name))
+        .to_string()
+}
+
+/// Point `dir` at a new temporary directory so SAVE and BGSAVE don't write
+/// into the working directory
+fn use_scratch_dir(executor: &CommandExecutor, current_db: &mut usize) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let args = [
+        Bytes::from("SET"),
+        Bytes::from("dir"),
+        Bytes::from(dir.path().to_string_lossy().into_owned()),
+    ];
+    let result = executor.execute("CONFIG", &args, current_db, 1).unwrap();
+    assert_eq!(result, RespValue::ok());
+    dir
+}
+
+#[test]
+fn test_info_persistence() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+    let _dir = use_scratch_dir(&executor, &mut current_db);
+
+    for (name, value) in [
+        ("loading", "0"),
+        ("aof_enabled", "0"),
+        ("aof_rewrite_in_progress", "0"),
+        ("aof_last_write_status", "ok"),
+        ("rdb_last_bgsave_status", "ok"),
+        ("rdb_changes_since_last_save", "0"),
+    ] {
+        assert_eq!(persistence_field(&executor, &mut current_db, name), value);
+    }
+
+    // Successful writes are counted, reads and failed writes are not
+    executor
+        .execute(
+            "SET",
+            &[Bytes::from("a"), Bytes::from("1")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    executor
+        .execute(
+            "LPUSH",
+            &[Bytes::from("list"), Bytes::from("x")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    executor
+        .execute("GET", &[Bytes::from("a")], &mut current_db, client_id)
+        .unwrap();
+    assert!(executor
+        .execute("INCR", &[Bytes::from("list")], &mut current_db, client_id)
+        .is_err());
+    assert_eq!(
+        persistence_field(&executor, &mut current_db, "rdb_changes_since_last_save"),
+        "2"
+    );
+
+    // SAVE resets the counter and records the save time
+    let result = executor
+        .execute("SAVE", &[], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::ok());
+    assert_eq!(
+        persistence_field(&executor, &mut current_db, "rdb_changes_since_last_save"),
+        "0"
+    );
+    let last_save = match executor
+        .execute("LASTSAVE", &[], &mut current_db, client_id)
+        .unwrap()
+    {
+        RespValue::Integer(time) => time.to_string(),
+        other => panic!("Expected integer, got {:?}", other),
+    };
+    assert_eq!(
+        persistence_field(&executor, &mut current_db, "rdb_last_save_time"),
+        last_save
+    );
+}
+
+#[test]
+fn test_client_name_validation() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    executor
+        .server_commands()
+        .register_client(
+            client_id,
+            "127.0.0.1:12345".to_string(),
+            "127.0.0.1:6379".to_string(),
+        )
+        .unwrap();
+
+    // GETNAME before any SETNAME returns an empty name
+    let result = executor
+        .execute(
+            "CLIENT",
+            &[Bytes::from("GETNAME")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string(""));
+
+    // Names with spaces or newlines are rejected
+    for name in ["my client", "my\nclient"] {
+        let result = executor.execute(
+            "CLIENT",
+            &[Bytes::from("SETNAME"), Bytes::from(name)],
+            &mut current_db,
+            client_id,
+        );
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("Client names cannot contain spaces, newlines or special characters"));
+    }
+
+    let result = executor
+        .execute(
+            "CLIENT",
+            &[Bytes::from("SETNAME"), Bytes::from("worker-1")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::ok());
+
+    let result = executor
+        .execute(
+            "CLIENT",
+            &[Bytes::from("GETNAME")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string("worker-1"));
+}
+
+#[test]
+fn test_scan_iteration() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // Set up test data with many keys
+    for i in 0..25 {
+        executor
+            .execute(
+                "SET",
+                &[
+                    Bytes::from(format!("key:{}", i)),
+                    Bytes::from(format!("value:{}", i)),
+                ],
+                &mut current_db,
+                client_id,
+            )
             .unwrap();
     }
 
@@ -547,64 +1285,403 @@ fn test_scan_iteration() {
                 client_id,
             )
             .unwrap();
-
-        if let RespValue::Array(Some(scan_result)) = result {
-            // Get next cursor
-            if let RespValue::BulkString(Some(cursor_bytes)) = &scan_result[0] {
-                let cursor_str = String::from_utf8_lossy(cursor_bytes);
-                cursor = cursor_str.parse::<usize>().unwrap();
-            }
-
-            // Collect keys
-            if let RespValue::Array(Some(keys)) = &scan_result[1] {
-                for key in keys {
-                    if let RespValue::BulkString(Some(key_bytes)) = key {
-                        all_keys.push(String::from_utf8_lossy(key_bytes).to_string());
-                    }
+
+        if let RespValue::Array(Some(scan_result)) = result {
+            // Get next cursor
+            if let RespValue::BulkString(Some(cursor_bytes)) = &scan_result[0] {
+                let cursor_str = String::from_utf8_lossy(cursor_bytes);
+                cursor = cursor_str.parse::<usize>().unwrap();
+            }
+
+            // Collect keys
+            if let RespValue::Array(Some(keys)) = &scan_result[1] {
+                for key in keys {
+                    if let RespValue::BulkString(Some(key_bytes)) = key {
+                        all_keys.push(String::from_utf8_lossy(key_bytes).to_string());
+                    }
+                }
+            }
+        }
+
+        iterations += 1;
+        if cursor == 0 || iterations > 100 {
+            // Prevent infinite loop in case of bugs
+            break;
+        }
+    }
+
+    // Should have collected all 25 keys
+    assert_eq!(all_keys.len(), 25);
+    // Cursor should be 0 (iteration complete)
+    assert_eq!(cursor, 0);
+
+    // Test SCAN with MATCH
+    let result = executor
+        .execute(
+            "SCAN",
+            &[
+                Bytes::from("0"),
+                Bytes::from("MATCH"),
+                Bytes::from("key:1*"),
+                Bytes::from("COUNT"),
+                Bytes::from("20"),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+
+    if let RespValue::Array(Some(scan_result)) = result {
+        if let RespValue::Array(Some(keys)) = &scan_result[1] {
+            // Should return keys matching key:1* (key:1, key:10-19)
+            assert!(!keys.is_empty() && keys.len() <= 11);
+            // Verify all returned keys match the pattern
+            for key in keys {
+                if let RespValue::BulkString(Some(key_bytes)) = key {
+                    let key_str = String::from_utf8_lossy(key_bytes);
+                    assert!(key_str.starts_with("key:1"));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_scan_count_is_a_hint() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // Enough keys that some buckets hold several of them
+    for i in 0..1000 {
+        executor
+            .execute(
+                "SET",
+                &[Bytes::from(format!("dense:{}", i)), Bytes::from("v")],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+    }
+
+    let mut cursor = String::from("0");
+    let mut seen = std::collections::HashSet::new();
+    let mut max_batch = 0;
+    loop {
+        let result = executor
+            .execute(
+                "SCAN",
+                &[
+                    Bytes::from(cursor.clone()),
+                    Bytes::from("COUNT"),
+                    Bytes::from("1"),
+                ],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+
+        let RespValue::Array(Some(scan_result)) = result else {
+            panic!("Expected array for SCAN result");
+        };
+        if let RespValue::BulkString(Some(next)) = &scan_result[0] {
+            cursor = String::from_utf8_lossy(next).to_string();
+        }
+        if let RespValue::Array(Some(keys)) = &scan_result[1] {
+            max_batch = max_batch.max(keys.len());
+            for key in keys {
+                if let RespValue::BulkString(Some(key)) = key {
+                    assert!(seen.insert(key.clone()), "key returned twice");
+                }
+            }
+        }
+
+        if cursor == "0" {
+            break;
+        }
+    }
+
+    // Every key is returned exactly once, and a dense bucket is returned whole
+    // even though it holds more keys than COUNT
+    assert_eq!(seen.len(), 1000);
+    assert!(max_batch > 1);
+}
+
+#[test]
+fn test_scan_without_count_takes_small_steps() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut args = Vec::new();
+    for i in 0..1000 {
+        args.push(Bytes::from(format!("key:{}", i)));
+        args.push(Bytes::from("v"));
+    }
+    executor
+        .execute("MSET", &args, &mut current_db, client_id)
+        .unwrap();
+
+    // Without COUNT each call visits 10 buckets, as in Redis
+    let mut cursor = String::from("0");
+    let mut seen = std::collections::HashSet::new();
+    let mut calls = 0;
+    loop {
+        let result = executor
+            .execute(
+                "SCAN",
+                &[Bytes::from(cursor.clone())],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+
+        let RespValue::Array(Some(scan_result)) = result else {
+            panic!("Expected array for SCAN result");
+        };
+        if let RespValue::BulkString(Some(next)) = &scan_result[0] {
+            cursor = String::from_utf8_lossy(next).to_string();
+        }
+        if let RespValue::Array(Some(keys)) = &scan_result[1] {
+            assert!(
+                keys.len() < 100,
+                "SCAN returned {} keys at once",
+                keys.len()
+            );
+            for key in keys {
+                if let RespValue::BulkString(Some(key)) = key {
+                    seen.insert(key.clone());
+                }
+            }
+        }
+
+        calls += 1;
+        if cursor == "0" {
+            break;
+        }
+    }
+
+    assert_eq!(seen.len(), 1000);
+    assert!(calls >= 50, "SCAN finished in {} calls", calls);
+}
+
+#[test]
+fn test_debug_populate() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let result = executor
+        .execute(
+            "DEBUG",
+            &[Bytes::from("POPULATE"), Bytes::from("1000")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::ok());
+    let result = executor
+        .execute("DBSIZE", &[], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::integer(1000));
+    let result = executor
+        .execute("GET", &[Bytes::from("key:999")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string("value:999"));
+
+    // SCAN sees every populated key
+    let mut cursor = String::from("0");
+    let mut seen = std::collections::HashSet::new();
+    loop {
+        let result = executor
+            .execute(
+                "SCAN",
+                &[
+                    Bytes::from(cursor.clone()),
+                    Bytes::from("COUNT"),
+                    Bytes::from("100"),
+                ],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+        let RespValue::Array(Some(scan_result)) = result else {
+            panic!("Expected array for SCAN result");
+        };
+        if let RespValue::BulkString(Some(next)) = &scan_result[0] {
+            cursor = String::from_utf8_lossy(next).to_string();
+        }
+        if let RespValue::Array(Some(keys)) = &scan_result[1] {
+            for key in keys {
+                if let RespValue::BulkString(Some(key)) = key {
+                    seen.insert(key.clone());
                 }
             }
         }
-
-        iterations += 1;
-        if cursor == 0 || iterations > 100 {
-            // Prevent infinite loop in case of bugs
+        if cursor == "0" {
             break;
         }
     }
+    assert_eq!(seen.len(), 1000);
 
-    // Should have collected all 25 keys
-    assert_eq!(all_keys.len(), 25);
-    // Cursor should be 0 (iteration complete)
-    assert_eq!(cursor, 0);
-
-    // Test SCAN with MATCH
-    let result = executor
+    // A prefix and a value size, keeping keys that already exist
+    executor
         .execute(
-            "SCAN",
+            "SET",
+            &[Bytes::from("big:0"), Bytes::from("mine")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    executor
+        .execute(
+            "DEBUG",
             &[
-                Bytes::from("0"),
-                Bytes::from("MATCH"),
-                Bytes::from("key:1*"),
-                Bytes::from("COUNT"),
-                Bytes::from("20"),
+                Bytes::from("POPULATE"),
+                Bytes::from("2"),
+                Bytes::from("big"),
+                Bytes::from("64"),
             ],
             &mut current_db,
             client_id,
         )
         .unwrap();
+    let result = executor
+        .execute("GET", &[Bytes::from("big:0")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string("mine"));
+    let result = executor
+        .execute(
+            "STRLEN",
+            &[Bytes::from("big:1")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::integer(64));
 
-    if let RespValue::Array(Some(scan_result)) = result {
-        if let RespValue::Array(Some(keys)) = &scan_result[1] {
-            // Should return keys matching key:1* (key:1, key:10-19)
-            assert!(!keys.is_empty() && keys.len() <= 11);
-            // Verify all returned keys match the pattern
-            for key in keys {
-                if let RespValue::BulkString(Some(key_bytes)) = key {
-                    let key_str = String::from_utf8_lossy(key_bytes);
-                    assert!(key_str.starts_with("key:1"));
-                }
-            }
-        }
+    let result = executor.execute(
+        "DEBUG",
+        &[Bytes::from("POPULATE"), Bytes::from("-1")],
+        &mut current_db,
+        client_id,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_keys_returns_large_keyspace() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut args = Vec::new();
+    for i in 0..10_000 {
+        args.push(Bytes::from(format!("key:{}", i)));
+        args.push(Bytes::from("v"));
+    }
+    executor
+        .execute("MSET", &args, &mut current_db, client_id)
+        .unwrap();
+
+    let start = std::time::Instant::now();
+    let result = executor
+        .execute("KEYS", &[Bytes::from("*")], &mut current_db, client_id)
+        .unwrap();
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+
+    let RespValue::Array(Some(keys)) = result else {
+        panic!("Expected array for KEYS result");
+    };
+    let keys: std::collections::HashSet<_> = keys
+        .into_iter()
+        .map(|key| match key {
+            RespValue::BulkString(Some(key)) => key,
+            other => panic!("Unexpected key: {:?}", other),
+        })
+        .collect();
+    assert_eq!(keys.len(), 10_000);
+    assert!(keys.contains(&Bytes::from("key:9999")));
+}
+
+#[test]
+fn test_expire_edge_cases() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // Setting a TTL on a missing key does nothing
+    for (command, value) in [
+        ("EXPIRE", "100"),
+        ("PEXPIRE", "100000"),
+        ("EXPIREAT", "1"),
+        ("PEXPIREAT", "1"),
+        ("EXPIRE", "-1"),
+    ] {
+        let result = executor
+            .execute(
+                command,
+                &[Bytes::from("missing"), Bytes::from(value)],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+        assert_eq!(result, RespValue::integer(0), "{} {}", command, value);
+    }
+    let result = executor
+        .execute(
+            "EXISTS",
+            &[Bytes::from("missing")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::integer(0));
+
+    // PERSIST only reports keys that had a TTL
+    executor
+        .execute(
+            "SET",
+            &[Bytes::from("plain"), Bytes::from("v")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    for key in ["plain", "missing"] {
+        let result = executor
+            .execute("PERSIST", &[Bytes::from(key)], &mut current_db, client_id)
+            .unwrap();
+        assert_eq!(result, RespValue::integer(0));
+    }
+
+    // A timestamp in the past removes the key at once
+    for command in ["EXPIREAT", "PEXPIREAT"] {
+        executor
+            .execute(
+                "SET",
+                &[Bytes::from("past"), Bytes::from("v")],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+        let result = executor
+            .execute(
+                command,
+                &[Bytes::from("past"), Bytes::from("1000")],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+        assert_eq!(result, RespValue::integer(1));
+        let result = executor
+            .execute("EXISTS", &[Bytes::from("past")], &mut current_db, client_id)
+            .unwrap();
+        assert_eq!(result, RespValue::integer(0));
     }
 }
 
@@ -666,6 +1743,202 @@ fn test_set_with_expire_options() {
     } else {
         panic!("Expected integer PTTL");
     }
+
+    let mut run = |command: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(command, &args, &mut current_db, client_id)
+    };
+
+    // NX fails with nil when the key is present, XX when it is absent
+    let result = run("SET", &["key1", "other", "NX"]).unwrap();
+    assert_eq!(result, RespValue::null_bulk_string());
+    assert_eq!(
+        run("GET", &["key1"]).unwrap(),
+        RespValue::bulk_string("value1")
+    );
+    let result = run("SET", &["key3", "value3", "XX"]).unwrap();
+    assert_eq!(result, RespValue::null_bulk_string());
+    assert_eq!(run("EXISTS", &["key3"]).unwrap(), RespValue::integer(0));
+    assert_eq!(
+        run("SET", &["key3", "value3", "NX"]).unwrap(),
+        RespValue::ok()
+    );
+    assert_eq!(run("SET", &["key3", "new", "XX"]).unwrap(), RespValue::ok());
+    assert_eq!(
+        run("GET", &["key3"]).unwrap(),
+        RespValue::bulk_string("new")
+    );
+
+    // EXAT and PXAT set an absolute expiry
+    let expire_at = 4_102_444_800i64; // 2100-01-01
+    let result = run("SET", &["key4", "value4", "EXAT", &expire_at.to_string()]).unwrap();
+    assert_eq!(result, RespValue::ok());
+    assert_eq!(
+        run("EXPIRETIME", &["key4"]).unwrap(),
+        RespValue::integer(expire_at)
+    );
+    let pxat = (expire_at * 1000 + 500).to_string();
+    let result = run("SET", &["key5", "value5", "PXAT", &pxat]).unwrap();
+    assert_eq!(result, RespValue::ok());
+    assert_eq!(
+        run("PEXPIRETIME", &["key5"]).unwrap(),
+        RespValue::integer(expire_at * 1000 + 500)
+    );
+
+    // Conflicting, incomplete and unknown options are syntax errors
+    for args in [
+        &["key6", "v", "NX", "XX"][..],
+        &["key6", "v", "EX", "10", "PX", "10000"][..],
+        &["key6", "v", "PX", "10000", "EXAT", "4102444800"][..],
+        &["key6", "v", "EXAT", "4102444800", "PXAT", "4102444800000"][..],
+        &["key6", "v", "EX"][..],
+        &["key6", "v", "BOGUS"][..],
+    ] {
+        let err = run("SET", args).unwrap_err();
+        assert!(
+            err.to_string().contains("syntax error"),
+            "SET {:?} failed with {}",
+            args,
+            err
+        );
+    }
+    assert_eq!(run("EXISTS", &["key6"]).unwrap(), RespValue::integer(0));
+}
+
+#[test]
+fn test_set_ttl_handling() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut set = |args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute("SET", &args, &mut current_db, client_id)
+    };
+    assert_eq!(set(&["key", "v1", "EX", "100"]).unwrap(), RespValue::ok());
+    // A plain SET clears the TTL
+    assert_eq!(set(&["key", "v2"]).unwrap(), RespValue::ok());
+    // KEEPTTL keeps a TTL set since
+    assert_eq!(set(&["key", "v3", "PX", "50000"]).unwrap(), RespValue::ok());
+    assert_eq!(set(&["key", "v4", "KEEPTTL"]).unwrap(), RespValue::ok());
+    // Expiration options can't be combined, and must be positive
+    for args in [
+        &["key", "v", "EX", "10", "KEEPTTL"][..],
+        &["key", "v", "KEEPTTL", "PX", "10"][..],
+        &["key", "v", "EX", "10", "PX", "10"][..],
+        &["key", "v", "NX", "XX"][..],
+    ] {
+        assert!(
+            matches!(set(args), Err(ref e) if e.to_string().contains("syntax error")),
+            "SET {:?} was accepted",
+            args
+        );
+    }
+    assert!(set(&["key", "v", "EX", "0"])
+        .unwrap_err()
+        .to_string()
+        .contains("invalid expire time"));
+
+    let ttl = |current_db: &mut usize| {
+        executor
+            .execute("PTTL", &[Bytes::from("key")], current_db, client_id)
+            .unwrap()
+    };
+    let pttl = match ttl(&mut current_db) {
+        RespValue::Integer(pttl) => pttl,
+        other => panic!("Expected integer PTTL, got {:?}", other),
+    };
+    assert!(pttl > 0 && pttl <= 50000, "PTTL {} after KEEPTTL", pttl);
+    let value = executor
+        .execute("GET", &[Bytes::from("key")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(value, RespValue::bulk_string("v4"));
+
+    // EX replaces the TTL, a plain SET removes it again
+    executor
+        .execute(
+            "SET",
+            &[
+                Bytes::from("key"),
+                Bytes::from("v5"),
+                Bytes::from("EX"),
+                Bytes::from("1000"),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert!(matches!(ttl(&mut current_db), RespValue::Integer(pttl) if pttl > 50000));
+    executor
+        .execute(
+            "SET",
+            &[Bytes::from("key"), Bytes::from("v6")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(ttl(&mut current_db), RespValue::Integer(-1));
+
+    // APPEND and INCR keep the TTL
+    for (command, args) in [
+        ("SET", vec!["counter", "1", "EX", "100"]),
+        ("INCR", vec!["counter"]),
+        ("APPEND", vec!["counter", "0"]),
+    ] {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor
+            .execute(command, &args, &mut current_db, client_id)
+            .unwrap();
+    }
+    let result = executor
+        .execute("TTL", &[Bytes::from("counter")], &mut current_db, client_id)
+        .unwrap();
+    assert!(matches!(result, RespValue::Integer(ttl) if ttl > 0 && ttl <= 100));
+}
+
+#[test]
+fn test_set_get_option() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut run = |command: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(command, &args, &mut current_db, client_id)
+    };
+
+    // SET GET returns the previous value, or nil for a new key
+    let result = run("SET", &["key", "v1", "GET"]).unwrap();
+    assert_eq!(result, RespValue::null_bulk_string());
+    let result = run("SET", &["key", "v2", "GET"]).unwrap();
+    assert_eq!(result, RespValue::bulk_string("v1"));
+
+    // NX GET returns the old value without overwriting it
+    let result = run("SET", &["key", "v3", "NX", "GET"]).unwrap();
+    assert_eq!(result, RespValue::bulk_string("v2"));
+    assert_eq!(run("GET", &["key"]).unwrap(), RespValue::bulk_string("v2"));
+    let result = run("SET", &["new", "v1", "NX", "GET"]).unwrap();
+    assert_eq!(result, RespValue::null_bulk_string());
+    assert_eq!(run("GET", &["new"]).unwrap(), RespValue::bulk_string("v1"));
+
+    // XX GET only writes existing keys
+    let result = run("SET", &["missing", "v1", "XX", "GET"]).unwrap();
+    assert_eq!(result, RespValue::null_bulk_string());
+    assert_eq!(run("EXISTS", &["missing"]).unwrap(), RespValue::integer(0));
+    let result = run("SET", &["key", "v4", "XX", "GET", "EX", "100"]).unwrap();
+    assert_eq!(result, RespValue::bulk_string("v2"));
+    assert!(matches!(run("TTL", &["key"]).unwrap(), RespValue::Integer(ttl) if ttl > 0));
+
+    // Non-string keys are left alone
+    run("RPUSH", &["list", "a"]).unwrap();
+    let err = run("SET", &["list", "v1", "GET"]).unwrap_err();
+    assert!(matches!(err, AikvError::WrongType(_)));
+    assert_eq!(
+        run("TYPE", &["list"]).unwrap(),
+        RespValue::simple_string("list")
+    );
 }
 
 #[test]
@@ -809,6 +2082,69 @@ fn test_dump_and_restore_commands() {
     assert_eq!(result, RespValue::null_bulk_string());
 }
 
+#[test]
+fn test_restore_ttl() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut run = |command: &str, args: &[Bytes]| {
+        executor
+            .execute(command, args, &mut current_db, client_id)
+            .unwrap()
+    };
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+
+    run("SET", &[Bytes::from("source"), Bytes::from("value")]);
+    let dump = match run("DUMP", &[Bytes::from("source")]) {
+        RespValue::BulkString(Some(data)) => data,
+        other => panic!("Expected DUMP payload, got {:?}", other),
+    };
+    let restore = |key: &str, ttl: String, options: &[&str]| {
+        let mut args = vec![Bytes::from(key.to_string()), Bytes::from(ttl), dump.clone()];
+        args.extend(options.iter().map(|o| Bytes::from(o.to_string())));
+        args
+    };
+
+    // A TTL of 0 leaves the key persistent
+    let args = restore("persistent", "0".to_string(), &[]);
+    assert_eq!(run("RESTORE", &args), RespValue::ok());
+    assert_eq!(
+        run("PTTL", &[Bytes::from("persistent")]),
+        RespValue::Integer(-1)
+    );
+
+    // ABSTTL in the future sets the expiry at that time
+    let args = restore("future", (now_ms + 60_000).to_string(), &["ABSTTL"]);
+    assert_eq!(run("RESTORE", &args), RespValue::ok());
+    match run("PTTL", &[Bytes::from("future")]) {
+        RespValue::Integer(pttl) => assert!(pttl > 50_000 && pttl <= 60_000, "{}", pttl),
+        other => panic!("Expected integer PTTL, got {:?}", other),
+    }
+
+    // ABSTTL in the past restores nothing, and REPLACE still drops the old key
+    let args = restore("past", (now_ms - 1_000).to_string(), &["ABSTTL"]);
+    assert_eq!(run("RESTORE", &args), RespValue::ok());
+    assert_eq!(run("EXISTS", &[Bytes::from("past")]), RespValue::Integer(0));
+
+    let args = restore("persistent", "1".to_string(), &["ABSTTL", "REPLACE"]);
+    assert_eq!(run("RESTORE", &args), RespValue::ok());
+    assert_eq!(
+        run("EXISTS", &[Bytes::from("persistent")]),
+        RespValue::Integer(0)
+    );
+
+    // Negative TTLs are rejected
+    let args = restore("negative", "-1".to_string(), &[]);
+    assert!(executor
+        .execute("RESTORE", &args, &mut current_db, client_id)
+        .is_err());
+}
+
 #[test]
 fn test_dump_restore_with_complex_types() {
     let storage = StorageEngine::new_memory(16);
@@ -919,6 +2255,67 @@ fn test_dump_restore_with_complex_types() {
     assert_eq!(result, RespValue::bulk_string("value1"));
 }
 
+#[test]
+fn test_restore_reclassifies_encoding() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut run = |command: &str, args: &[Bytes]| {
+        executor
+            .execute(command, args, &mut current_db, client_id)
+            .unwrap()
+    };
+
+    let mut small = vec![Bytes::from("small")];
+    let mut large = vec![Bytes::from("large")];
+    for i in 0..200 {
+        if i < 3 {
+            small.push(Bytes::from(format!("field:{}", i)));
+            small.push(Bytes::from("v"));
+        }
+        large.push(Bytes::from(format!("field:{}", i)));
+        large.push(Bytes::from("v"));
+    }
+    run("HSET", &small);
+    run("HSET", &large);
+    let payload = |reply: RespValue| match reply {
+        RespValue::BulkString(Some(data)) => data,
+        other => panic!("Expected DUMP payload, got {:?}", other),
+    };
+    let small_dump = payload(run("DUMP", &[Bytes::from("small")]));
+    let large_dump = payload(run("DUMP", &[Bytes::from("large")]));
+
+    // A restored hash is classified by its size
+    let args = [Bytes::from("small2"), Bytes::from("0"), small_dump.clone()];
+    assert_eq!(run("RESTORE", &args), RespValue::ok());
+    let args = [Bytes::from("large2"), Bytes::from("0"), large_dump];
+    assert_eq!(run("RESTORE", &args), RespValue::ok());
+    assert_eq!(
+        run("OBJECT", &[Bytes::from("ENCODING"), Bytes::from("small2")]),
+        RespValue::bulk_string("listpack")
+    );
+    assert_eq!(
+        run("OBJECT", &[Bytes::from("ENCODING"), Bytes::from("large2")]),
+        RespValue::bulk_string("hashtable")
+    );
+
+    // Replacing a large hash doesn't pass its encoding on to the restored one
+    let args = [
+        Bytes::from("large2"),
+        Bytes::from("0"),
+        small_dump,
+        Bytes::from("REPLACE"),
+    ];
+    assert_eq!(run("RESTORE", &args), RespValue::ok());
+    assert_eq!(
+        run("OBJECT", &[Bytes::from("ENCODING"), Bytes::from("large2")]),
+        RespValue::bulk_string("listpack")
+    );
+    assert_eq!(run("HLEN", &[Bytes::from("large2")]), RespValue::integer(3));
+}
+
 #[test]
 fn test_migrate_command() {
     let storage = StorageEngine::new_memory(16);
@@ -1268,12 +2665,558 @@ fn test_command_commands() {
     assert!(matches!(result, RespValue::Array(Some(_))));
 }
 
+#[test]
+fn test_command_table_matches_dispatch() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+    let _dir = use_scratch_dir(&executor, &mut current_db);
+
+    let names: Vec<String> = match executor
+        .execute("COMMAND", &[], &mut current_db, client_id)
+        .unwrap()
+    {
+        RespValue::Array(Some(commands)) => commands
+            .into_iter()
+            .map(|info| match info {
+                RespValue::Array(Some(fields)) => match &fields[0] {
+                    RespValue::BulkString(Some(name)) => {
+                        String::from_utf8_lossy(name).to_uppercase()
+                    }
+                    other => panic!("Expected command name, got {:?}", other),
+                },
+                other => panic!("Expected command info array, got {:?}", other),
+            })
+            .collect(),
+        other => panic!("Expected array for COMMAND, got {:?}", other),
+    };
+
+    // COMMAND COUNT agrees with the listed commands
+    let result = executor
+        .execute(
+            "COMMAND",
+            &[Bytes::from("COUNT")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::Integer(names.len() as i64));
+
+    // Every listed command is dispatchable (argument errors are fine)
+    for name in &names {
+        if CONNECTION_COMMANDS.contains(&name.as_str()) {
+            continue;
+        }
+        let result = executor.execute(name, &[], &mut current_db, client_id);
+        assert!(
+            !CommandExecutor::is_unknown_command(&result),
+            "{} is listed by COMMAND but not dispatched",
+            name
+        );
+    }
+
+    // Unknown commands are reported as such
+    let result = executor.execute("NOSUCHCOMMAND", &[], &mut current_db, client_id);
+    assert!(CommandExecutor::is_unknown_command(&result));
+}
+
+#[test]
+fn test_command_getkeys() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut getkeys = |args: &[&str]| {
+        let mut request = vec![Bytes::from("GETKEYS")];
+        request.extend(args.iter().map(|a| Bytes::from(a.to_string())));
+        executor.execute("COMMAND", &request, &mut current_db, client_id)
+    };
+    let keys = |names: &[&str]| {
+        RespValue::array(
+            names
+                .iter()
+                .map(|n| RespValue::bulk_string(n.to_string()))
+                .collect(),
+        )
+    };
+
+    // Fixed positions, including stepped key/value pairs
+    assert_eq!(
+        getkeys(&["ZADD", "zset", "1", "a"]).unwrap(),
+        keys(&["zset"])
+    );
+    assert_eq!(
+        getkeys(&["MSET", "a", "1", "b", "2"]).unwrap(),
+        keys(&["a", "b"])
+    );
+    assert_eq!(
+        getkeys(&["LMOVE", "src", "dst", "LEFT", "RIGHT"]).unwrap(),
+        keys(&["src", "dst"])
+    );
+
+    // EVAL keys are driven by numkeys
+    assert_eq!(
+        getkeys(&["EVAL", "return 1", "2", "k1", "k2", "arg"]).unwrap(),
+        keys(&["k1", "k2"])
+    );
+    assert_eq!(
+        getkeys(&["EVALSHA", "abc", "1", "k1", "arg"]).unwrap(),
+        keys(&["k1"])
+    );
+
+    // MIGRATE takes either its key argument or everything after KEYS
+    assert_eq!(
+        getkeys(&["MIGRATE", "host", "6379", "key", "0", "100"]).unwrap(),
+        keys(&["key"])
+    );
+    assert_eq!(
+        getkeys(&["MIGRATE", "host", "6379", "", "0", "100", "AUTH", "KEYS", "KEYS", "k1", "k2"])
+            .unwrap(),
+        keys(&["k1", "k2"])
+    );
+
+    // Commands without keys and wrong arities are rejected
+    assert!(getkeys(&["EVAL", "return 1", "0"]).is_err());
+    assert!(getkeys(&["PING"]).is_err());
+    assert!(getkeys(&["GET"]).is_err());
+    assert!(getkeys(&["NOSUCHCOMMAND", "key"]).is_err());
+}
+
+#[test]
+fn test_command_docs() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let field = |map: &RespValue, name: &str| match map {
+        RespValue::Map(pairs) => pairs
+            .iter()
+            .find(|(key, _)| *key == RespValue::bulk_string(name.to_string()))
+            .map(|(_, value)| value.clone()),
+        other => panic!("Expected map, got {:?}", other),
+    };
+
+    let result = executor
+        .execute(
+            "COMMAND",
+            &[
+                Bytes::from("DOCS"),
+                Bytes::from("GET"),
+                Bytes::from("NOSUCHCOMMAND"),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    let docs = match &result {
+        RespValue::Map(pairs) => {
+            // Unknown commands are left out
+            assert_eq!(pairs.len(), 1);
+            field(&result, "get").unwrap()
+        }
+        other => panic!("Expected map for COMMAND DOCS, got {:?}", other),
+    };
+
+    match field(&docs, "summary") {
+        Some(RespValue::BulkString(Some(summary))) => assert!(!summary.is_empty()),
+        other => panic!("Unexpected summary: {:?}", other),
+    }
+    assert_eq!(
+        field(&docs, "group"),
+        Some(RespValue::bulk_string("string"))
+    );
+    assert_eq!(field(&docs, "arity"), Some(RespValue::Integer(2)));
+    match field(&docs, "arguments") {
+        Some(RespValue::Array(Some(arguments))) => {
+            assert_eq!(arguments.len(), 1);
+            assert_eq!(
+                field(&arguments[0], "name"),
+                Some(RespValue::bulk_string("key"))
+            );
+            assert_eq!(
+                field(&arguments[0], "type"),
+                Some(RespValue::bulk_string("key"))
+            );
+        }
+        other => panic!("Unexpected arguments: {:?}", other),
+    }
+
+    // Without arguments every command is documented
+    let result = executor
+        .execute(
+            "COMMAND",
+            &[Bytes::from("DOCS")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    let count = executor
+        .execute(
+            "COMMAND",
+            &[Bytes::from("COUNT")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    match (result, count) {
+        (RespValue::Map(pairs), RespValue::Integer(count)) => {
+            assert_eq!(pairs.len() as i64, count);
+            for (name, docs) in &pairs {
+                assert!(
+                    matches!(field(docs, "group"), Some(RespValue::BulkString(Some(ref g))) if !g.is_empty()),
+                    "{:?} has no group",
+                    name
+                );
+            }
+        }
+        other => panic!("Unexpected COMMAND DOCS reply: {:?}", other),
+    }
+}
+
+#[test]
+fn test_exec_is_atomic_under_concurrent_writers() {
+    let storage = StorageEngine::new_memory(16);
+    let gate = CommandGate::new();
+    let writers = 8;
+    let increments = 500;
+    let transactions = 200;
+
+    let counter = |reply: &RespValue| match reply {
+        RespValue::BulkString(Some(value)) => {
+            String::from_utf8_lossy(value).parse::<i64>().unwrap()
+        }
+        RespValue::BulkString(None) => 0,
+        RespValue::Integer(value) => *value,
+        other => panic!("Unexpected reply: {:?}", other),
+    };
+
+    std::thread::scope(|scope| {
+        for client_id in 0..writers {
+            let mut executor = CommandExecutor::new(storage.clone());
+            executor.set_command_gate(gate.clone());
+            scope.spawn(move || {
+                let mut current_db = 0;
+                for _ in 0..increments {
+                    executor
+                        .execute(
+                            "INCR",
+                            &[Bytes::from("counter")],
+                            &mut current_db,
+                            client_id,
+                        )
+                        .unwrap();
+                }
+            });
+        }
+
+        // Reads and writes inside one EXEC never see another connection's INCR
+        let mut executor = CommandExecutor::new(storage.clone());
+        executor.set_command_gate(gate.clone());
+        let mut current_db = 0;
+        let client_id = writers;
+        for _ in 0..transactions {
+            executor
+                .execute("MULTI", &[], &mut current_db, client_id)
+                .unwrap();
+            for command in ["GET", "INCR", "GET"] {
+                executor
+                    .execute(
+                        command,
+                        &[Bytes::from("counter")],
+                        &mut current_db,
+                        client_id,
+                    )
+                    .unwrap();
+            }
+            let replies = match executor
+                .execute("EXEC", &[], &mut current_db, client_id)
+                .unwrap()
+            {
+                RespValue::Array(Some(replies)) => replies,
+                other => panic!("Unexpected EXEC reply: {:?}", other),
+            };
+            let before = counter(&replies[0]);
+            assert_eq!(counter(&replies[1]), before + 1);
+            assert_eq!(counter(&replies[2]), before + 1);
+        }
+    });
+
+    let mut current_db = 0;
+    let result = CommandExecutor::new(storage)
+        .execute("GET", &[Bytes::from("counter")], &mut current_db, 0)
+        .unwrap();
+    assert_eq!(
+        counter(&result),
+        (writers * increments + transactions) as i64
+    );
+}
+
+#[test]
+fn test_incr_never_loses_updates_under_contention() {
+    let storage = StorageEngine::new_memory(16);
+    let clients = 16;
+    let increments = 1000;
+
+    // No shared command gate, so only storage keeps these INCRs from interleaving
+    std::thread::scope(|scope| {
+        for client_id in 0..clients {
+            let executor = CommandExecutor::new(storage.clone());
+            scope.spawn(move || {
+                let mut current_db = 0;
+                for _ in 0..increments {
+                    executor
+                        .execute("INCR", &[Bytes::from("hot")], &mut current_db, client_id)
+                        .unwrap();
+                }
+            });
+        }
+    });
+
+    let mut current_db = 0;
+    let result = CommandExecutor::new(storage)
+        .execute("GET", &[Bytes::from("hot")], &mut current_db, 0)
+        .unwrap();
+    assert_eq!(
+        result,
+        RespValue::bulk_string((clients * increments).to_string())
+    );
+}
+
+#[test]
+fn test_writes_share_the_command_gate() {
+    let storage = StorageEngine::new_memory(16);
+    let gate = CommandGate::new();
+    let mut executor = CommandExecutor::new(storage);
+    executor.set_command_gate(gate.clone());
+    let mut current_db = 0;
+
+    // Another connection's command holds the gate: only EXEC and scripts wait for it
+    let _other = gate.shared();
+    let result = executor
+        .execute(
+            "SET",
+            &[Bytes::from("key"), Bytes::from("value")],
+            &mut current_db,
+            1,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::ok());
+    executor.execute("MULTI", &[], &mut current_db, 1).unwrap();
+    let result = executor
+        .execute(
+            "SET",
+            &[Bytes::from("key"), Bytes::from("other")],
+            &mut current_db,
+            1,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::simple_string("QUEUED"));
+    executor
+        .execute("DISCARD", &[], &mut current_db, 1)
+        .unwrap();
+}
+
+#[test]
+fn test_multi_exec_with_select() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let result = executor
+        .execute("MULTI", &[], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::ok());
+
+    // Nested MULTI is rejected without closing the transaction
+    assert!(executor
+        .execute("MULTI", &[], &mut current_db, client_id)
+        .is_err());
+
+    let queued: [(&str, Vec<Bytes>); 4] = [
+        ("SELECT", vec![Bytes::from("1")]),
+        ("SET", vec![Bytes::from("k"), Bytes::from("v")]),
+        ("SELECT", vec![Bytes::from("0")]),
+        ("GET", vec![Bytes::from("k")]),
+    ];
+    for (command, args) in &queued {
+        let result = executor
+            .execute(command, args, &mut current_db, client_id)
+            .unwrap();
+        assert_eq!(result, RespValue::simple_string("QUEUED"));
+    }
+
+    // Nothing runs until EXEC
+    assert_eq!(current_db, 0);
+
+    let result = executor
+        .execute("EXEC", &[], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(
+        result,
+        RespValue::array(vec![
+            RespValue::ok(),
+            RespValue::ok(),
+            RespValue::ok(),
+            RespValue::null_bulk_string(),
+        ])
+    );
+    assert_eq!(current_db, 0);
+
+    // The SET landed in db 1
+    let mut db1 = 1;
+    let result = executor
+        .execute("GET", &[Bytes::from("k")], &mut db1, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string(Bytes::from("v")));
+
+    // EXEC and DISCARD need an open transaction
+    assert!(executor
+        .execute("EXEC", &[], &mut current_db, client_id)
+        .is_err());
+    assert!(executor
+        .execute("DISCARD", &[], &mut current_db, client_id)
+        .is_err());
+
+    // DISCARD drops the queue
+    executor
+        .execute("MULTI", &[], &mut current_db, client_id)
+        .unwrap();
+    executor
+        .execute(
+            "SET",
+            &[Bytes::from("k"), Bytes::from("discarded")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    let result = executor
+        .execute("DISCARD", &[], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::ok());
+    let result = executor
+        .execute("GET", &[Bytes::from("k")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::null_bulk_string());
+}
+
+#[test]
+fn test_exec_aborts_after_queuing_error() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    executor
+        .execute("MULTI", &[], &mut current_db, client_id)
+        .unwrap();
+    let result = executor
+        .execute(
+            "SET",
+            &[Bytes::from("k"), Bytes::from("v")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::simple_string("QUEUED"));
+
+    // An unknown command and a wrong arity are rejected but keep the transaction open
+    assert!(executor
+        .execute("NOSUCHCOMMAND", &[], &mut current_db, client_id)
+        .is_err());
+    assert!(executor
+        .execute("GET", &[], &mut current_db, client_id)
+        .is_err());
+    assert!(executor.in_transaction());
+
+    let err = executor
+        .execute("EXEC", &[], &mut current_db, client_id)
+        .unwrap_err();
+    assert_eq!(
+        err.to_resp(),
+        RespValue::error("EXECABORT Transaction discarded because of previous errors.")
+    );
+    assert!(!executor.in_transaction());
+
+    // Nothing ran
+    let result = executor
+        .execute("GET", &[Bytes::from("k")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::null_bulk_string());
+
+    // The flag does not outlive the transaction
+    executor
+        .execute("MULTI", &[], &mut current_db, client_id)
+        .unwrap();
+    assert!(executor
+        .execute("NOSUCHCOMMAND", &[], &mut current_db, client_id)
+        .is_err());
+    executor
+        .execute("DISCARD", &[], &mut current_db, client_id)
+        .unwrap();
+    executor
+        .execute("MULTI", &[], &mut current_db, client_id)
+        .unwrap();
+    let result = executor
+        .execute("EXEC", &[], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::array(vec![]));
+}
+
+#[test]
+fn test_exec_runtime_error_is_inline() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    executor
+        .execute(
+            "SET",
+            &[Bytes::from("k"), Bytes::from("abc")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+
+    executor
+        .execute("MULTI", &[], &mut current_db, client_id)
+        .unwrap();
+    // INCR on a non-integer only fails once it runs
+    let queued: [(&str, Vec<Bytes>); 3] = [
+        ("INCR", vec![Bytes::from("k")]),
+        ("SET", vec![Bytes::from("other"), Bytes::from("1")]),
+        ("GET", vec![Bytes::from("other")]),
+    ];
+    for (command, args) in &queued {
+        let result = executor
+            .execute(command, args, &mut current_db, client_id)
+            .unwrap();
+        assert_eq!(result, RespValue::simple_string("QUEUED"));
+    }
+
+    let result = executor
+        .execute("EXEC", &[], &mut current_db, client_id)
+        .unwrap();
+    let RespValue::Array(Some(replies)) = result else {
+        panic!("Expected array from EXEC, got {:?}", result);
+    };
+    assert_eq!(replies.len(), 3);
+    assert!(matches!(&replies[0], RespValue::Error(e) if e.contains("not an integer")));
+    assert_eq!(replies[1], RespValue::ok());
+    assert_eq!(replies[2], RespValue::bulk_string(Bytes::from("1")));
+}
+
 #[test]
 fn test_save_lastsave_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
     let mut current_db = 0;
     let client_id = 1;
+    let dir = use_scratch_dir(&executor, &mut current_db);
 
     // Test LASTSAVE - should return a timestamp
     let result = executor
@@ -1286,13 +3229,16 @@ fn test_save_lastsave_commands() {
         panic!("Expected integer for LASTSAVE");
     }
 
-    // Test SAVE
+    // Test SAVE - writes the snapshot
     let result = executor
         .execute("SAVE", &[], &mut current_db, client_id)
         .unwrap();
     assert_eq!(result, RespValue::ok());
+    let snapshot = dir.path().join("dump.rdb");
+    assert!(snapshot.exists());
+    std::fs::remove_file(&snapshot).unwrap();
 
-    // Test BGSAVE
+    // Test BGSAVE - writes it again on another thread
     let result = executor
         .execute("BGSAVE", &[], &mut current_db, client_id)
         .unwrap();
@@ -1300,6 +3246,17 @@ fn test_save_lastsave_commands() {
         result,
         RespValue::simple_string("Background saving started")
     );
+    for _ in 0..50 {
+        if persistence_field(&executor, &mut current_db, "rdb_bgsave_in_progress") == "0" {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    assert_eq!(
+        persistence_field(&executor, &mut current_db, "rdb_last_bgsave_status"),
+        "ok"
+    );
+    assert!(snapshot.exists());
 
     // Test LASTSAVE after save - should have updated timestamp
     let result = executor
@@ -0,0 +1,678 @@
+//! Tests for CLIENT commands over real client connections
+//!
+//! The client registry is shared by every connection of a server, so these
+//! tests start an AiKv server on a free local port and talk RESP to it.
+
+use aikv::protocol::{RespParser, RespValue};
+use aikv::{Server, StorageEngine};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, timeout, Duration};
+
+/// Start a server on a free port and return its address
+async fn start_server() -> String {
+    start_server_with(|_| {}).await
+}
+
+/// Start a server configured by `configure` on a free port and return its address
+async fn start_server_with(configure: impl FnOnce(&mut Server)) -> String {
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+
+    let mut server = Server::new(addr.clone(), StorageEngine::new_memory(16));
+    configure(&mut server);
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+
+    for _ in 0..50 {
+        if TcpStream::connect(&addr).await.is_ok() {
+            return addr;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Server did not start on {}", addr);
+}
+
+/// Encode a command as a RESP array of bulk strings
+fn encode(args: &[&str]) -> String {
+    let mut request = format!("*{}\r\n", args.len());
+    for arg in args {
+        request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    request
+}
+
+/// Send a command and read its reply
+async fn send(stream: &mut TcpStream, parser: &mut RespParser, args: &[&str]) -> RespValue {
+    stream.write_all(encode(args).as_bytes()).await.unwrap();
+    read_reply(stream, parser).await
+}
+
+/// Send a command and read the raw bytes of its (short) reply
+async fn send_raw(stream: &mut TcpStream, args: &[&str]) -> String {
+    stream.write_all(encode(args).as_bytes()).await.unwrap();
+    let mut reply = Vec::new();
+    while !reply.ends_with(b"\r\n") {
+        let mut buf = [0u8; 64];
+        let n = stream.read(&mut buf).await.unwrap();
+        assert!(n > 0, "Connection closed before a reply was read");
+        reply.extend_from_slice(&buf[..n]);
+    }
+    String::from_utf8(reply).unwrap()
+}
+
+/// Read the next reply
+async fn read_reply(stream: &mut TcpStream, parser: &mut RespParser) -> RespValue {
+    loop {
+        if let Some(reply) = parser.parse().unwrap() {
+            return reply;
+        }
+        let n = stream.read_buf(parser.buffer_mut()).await.unwrap();
+        assert!(n > 0, "Connection closed before a reply was read");
+    }
+}
+
+/// Connect a client and make sure the server has registered it
+async fn connect(addr: &str) -> (TcpStream, RespParser) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut parser = RespParser::new(4096);
+    let reply = send(&mut stream, &mut parser, &["PING"]).await;
+    assert_eq!(reply, RespValue::simple_string("PONG"));
+    (stream, parser)
+}
+
+/// Assert the server closes the connection
+async fn assert_closed(stream: &mut TcpStream) {
+    let mut buf = [0u8; 1024];
+    let result = timeout(Duration::from_secs(5), stream.read(&mut buf))
+        .await
+        .expect("Connection was not closed");
+    assert!(
+        matches!(result, Ok(0) | Err(_)),
+        "Unexpected data: {:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_client_kill_by_type() {
+    let addr = start_server().await;
+    let (mut caller, mut parser) = connect(&addr).await;
+    let (mut subscriber, mut sub_parser) = connect(&addr).await;
+    let (mut other, mut other_parser) = connect(&addr).await;
+
+    send(&mut subscriber, &mut sub_parser, &["SUBSCRIBE", "news"]).await;
+
+    // TYPE pubsub only kills subscribers
+    let killed = send(
+        &mut caller,
+        &mut parser,
+        &["CLIENT", "KILL", "TYPE", "pubsub"],
+    )
+    .await;
+    assert_eq!(killed, RespValue::integer(1));
+    assert_closed(&mut subscriber).await;
+    let reply = send(&mut other, &mut other_parser, &["PING"]).await;
+    assert_eq!(reply, RespValue::simple_string("PONG"));
+
+    // SKIPME defaults to yes, protecting the caller
+    let killed = send(
+        &mut caller,
+        &mut parser,
+        &["CLIENT", "KILL", "TYPE", "normal"],
+    )
+    .await;
+    assert_eq!(killed, RespValue::integer(1));
+    assert_closed(&mut other).await;
+    let reply = send(&mut caller, &mut parser, &["PING"]).await;
+    assert_eq!(reply, RespValue::simple_string("PONG"));
+
+    // No replicas or masters ever match
+    let killed = send(
+        &mut caller,
+        &mut parser,
+        &["CLIENT", "KILL", "TYPE", "replica"],
+    )
+    .await;
+    assert_eq!(killed, RespValue::integer(0));
+
+    // SKIPME no lets the caller kill itself once the reply is sent
+    let killed = send(
+        &mut caller,
+        &mut parser,
+        &["CLIENT", "KILL", "TYPE", "normal", "SKIPME", "no"],
+    )
+    .await;
+    assert_eq!(killed, RespValue::integer(1));
+    assert_closed(&mut caller).await;
+}
+
+#[tokio::test]
+async fn test_client_kill_by_maxage() {
+    let addr = start_server().await;
+    let (mut caller, mut parser) = connect(&addr).await;
+    let (mut old, _) = connect(&addr).await;
+    sleep(Duration::from_millis(1100)).await;
+    let (mut young, mut young_parser) = connect(&addr).await;
+
+    // Both the caller and `old` are over a second old, but SKIPME protects the caller
+    let killed = send(&mut caller, &mut parser, &["CLIENT", "KILL", "MAXAGE", "1"]).await;
+    assert_eq!(killed, RespValue::integer(1));
+    assert_closed(&mut old).await;
+    let reply = send(&mut young, &mut young_parser, &["PING"]).await;
+    assert_eq!(reply, RespValue::simple_string("PONG"));
+}
+
+#[tokio::test]
+async fn test_client_kill_by_address() {
+    let addr = start_server().await;
+    let (mut caller, mut parser) = connect(&addr).await;
+    let (mut target, _) = connect(&addr).await;
+    let (mut other, _) = connect(&addr).await;
+    let target_addr = target.local_addr().unwrap().to_string();
+
+    // Filters combine: the LADDR matches everyone, the ADDR only the target
+    let killed = send(
+        &mut caller,
+        &mut parser,
+        &["CLIENT", "KILL", "ADDR", &target_addr, "LADDR", &addr],
+    )
+    .await;
+    assert_eq!(killed, RespValue::integer(1));
+    assert_closed(&mut target).await;
+
+    // The old form replies OK, or an error once nothing matches
+    let other_addr = other.local_addr().unwrap().to_string();
+    let reply = send(&mut caller, &mut parser, &["CLIENT", "KILL", &other_addr]).await;
+    assert_eq!(reply, RespValue::ok());
+    assert_closed(&mut other).await;
+    let reply = send(&mut caller, &mut parser, &["CLIENT", "KILL", &other_addr]).await;
+    assert!(matches!(reply, RespValue::Error(ref e) if e.contains("No such client")));
+
+    for (args, error) in [
+        (&["ID", "0"][..], "client-id should be greater than 0"),
+        (&["TYPE", "bogus"][..], "Unknown client type 'bogus'"),
+        (&["USER", "nobody"][..], "No such user 'nobody'"),
+        (&["SKIPME", "maybe"][..], "syntax error"),
+        (&["ID", "5", "TYPE"][..], "syntax error"),
+    ] {
+        let mut command = vec!["CLIENT", "KILL"];
+        command.extend_from_slice(args);
+        let reply = send(&mut caller, &mut parser, &command).await;
+        assert!(
+            matches!(reply, RespValue::Error(ref e) if e.contains(error)),
+            "CLIENT KILL {:?} replied {:?}",
+            args,
+            reply
+        );
+    }
+
+    // USER default matches every connection; only the skipped caller is left
+    let killed = send(
+        &mut caller,
+        &mut parser,
+        &["CLIENT", "KILL", "USER", "default"],
+    )
+    .await;
+    assert_eq!(killed, RespValue::integer(0));
+}
+
+/// Look up a field of a HELLO reply, in either its RESP3 map or RESP2 array form
+fn hello_field(reply: &RespValue, field: &str) -> RespValue {
+    let pairs: Vec<(RespValue, RespValue)> = match reply {
+        RespValue::Map(pairs) => pairs.clone(),
+        RespValue::Array(Some(items)) => items
+            .chunks(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect(),
+        other => panic!("Unexpected HELLO reply: {:?}", other),
+    };
+    pairs
+        .into_iter()
+        .find(|(key, _)| *key == RespValue::bulk_string(field.to_string()))
+        .map(|(_, value)| value)
+        .unwrap_or_else(|| panic!("{} missing from HELLO reply", field))
+}
+
+#[tokio::test]
+async fn test_hello_without_arguments() {
+    let addr = start_server().await;
+    let (mut stream, mut parser) = connect(&addr).await;
+
+    // A bare HELLO reports the current protocol, RESP2 by default
+    let reply = send(&mut stream, &mut parser, &["HELLO"]).await;
+    assert!(matches!(reply, RespValue::Array(_)));
+    assert_eq!(hello_field(&reply, "proto"), RespValue::integer(2));
+    let id = hello_field(&reply, "id");
+    assert!(matches!(id, RespValue::Integer(id) if id > 0));
+
+    let reply = send(&mut stream, &mut parser, &["HELLO", "3"]).await;
+    assert_eq!(hello_field(&reply, "proto"), RespValue::integer(3));
+    assert_eq!(hello_field(&reply, "id"), id);
+
+    // ...and doesn't switch back once RESP3 is negotiated
+    let reply = send(&mut stream, &mut parser, &["HELLO"]).await;
+    assert!(matches!(reply, RespValue::Map(_)));
+    assert_eq!(hello_field(&reply, "proto"), RespValue::integer(3));
+    assert_eq!(hello_field(&reply, "id"), id);
+    assert_eq!(
+        hello_field(&reply, "role"),
+        RespValue::bulk_string("master")
+    );
+
+    let reply = send(&mut stream, &mut parser, &["HELLO", "4"]).await;
+    assert!(matches!(reply, RespValue::Error(ref e) if e.starts_with("NOPROTO")));
+    let reply = send(&mut stream, &mut parser, &["HELLO"]).await;
+    assert_eq!(hello_field(&reply, "proto"), RespValue::integer(3));
+}
+
+#[tokio::test]
+async fn test_hello_rejects_unsupported_protocol() {
+    let addr = start_server().await;
+    let (mut stream, mut parser) = connect(&addr).await;
+
+    // Neither a bad version nor a non-numeric one touches the protocol
+    let reply = send(&mut stream, &mut parser, &["HELLO", "4"]).await;
+    assert_eq!(
+        reply,
+        RespValue::error("NOPROTO unsupported protocol version")
+    );
+    let reply = send(&mut stream, &mut parser, &["HELLO", "abc"]).await;
+    assert!(
+        matches!(reply, RespValue::Error(ref e) if e.contains("Protocol version is not an integer")),
+        "{:?}",
+        reply
+    );
+    let reply = send(&mut stream, &mut parser, &["HELLO", "1"]).await;
+    assert!(matches!(reply, RespValue::Error(ref e) if e.starts_with("NOPROTO")));
+
+    let reply = send(&mut stream, &mut parser, &["SET", "key", "value"]).await;
+    assert_eq!(reply, RespValue::ok());
+    let reply = send(&mut stream, &mut parser, &["HELLO"]).await;
+    assert!(matches!(reply, RespValue::Array(_)));
+    assert_eq!(hello_field(&reply, "proto"), RespValue::integer(2));
+}
+
+#[tokio::test]
+async fn test_hello_setname() {
+    let addr = start_server().await;
+    let (mut stream, mut parser) = connect(&addr).await;
+    let (mut other, mut other_parser) = connect(&addr).await;
+
+    // SETNAME names the connection as part of the handshake
+    let reply = send(
+        &mut stream,
+        &mut parser,
+        &["HELLO", "3", "SETNAME", "myname"],
+    )
+    .await;
+    assert_eq!(hello_field(&reply, "proto"), RespValue::integer(3));
+    let reply = send(&mut stream, &mut parser, &["CLIENT", "GETNAME"]).await;
+    assert_eq!(reply, RespValue::bulk_string("myname"));
+    let list = send(&mut other, &mut other_parser, &["CLIENT", "LIST"]).await;
+    assert!(matches!(list, RespValue::BulkString(Some(ref l))
+        if String::from_utf8_lossy(l).contains("name=myname")));
+
+    // An invalid name fails the whole HELLO, keeping RESP2 and the old name
+    let reply = send(
+        &mut other,
+        &mut other_parser,
+        &["HELLO", "3", "SETNAME", "my name"],
+    )
+    .await;
+    assert!(
+        matches!(reply, RespValue::Error(ref e) if e.contains("Client names cannot contain spaces"))
+    );
+    let reply = send(&mut other, &mut other_parser, &["HELLO"]).await;
+    assert!(matches!(reply, RespValue::Array(_)));
+    assert_eq!(hello_field(&reply, "proto"), RespValue::integer(2));
+    let reply = send(&mut other, &mut other_parser, &["CLIENT", "GETNAME"]).await;
+    assert_eq!(reply, RespValue::bulk_string(""));
+
+    let reply = send(&mut stream, &mut parser, &["HELLO", "2", "SETNAME"]).await;
+    assert!(
+        matches!(reply, RespValue::Error(ref e) if e.contains("Syntax error in HELLO option 'SETNAME'"))
+    );
+    let reply = send(&mut stream, &mut parser, &["HELLO"]).await;
+    assert_eq!(hello_field(&reply, "proto"), RespValue::integer(3));
+}
+
+#[tokio::test]
+async fn test_auth_unlocks_pipelined_commands() {
+    let addr = start_server_with(|server| server.set_requirepass("secret")).await;
+    let mut stream = TcpStream::connect(&addr).await.unwrap();
+    let mut parser = RespParser::new(4096);
+
+    let reply = send(&mut stream, &mut parser, &["SET", "k", "v"]).await;
+    assert!(matches!(reply, RespValue::Error(ref e) if e.starts_with("NOAUTH")));
+
+    // AUTH takes effect before the next command in the same write
+    let pipeline = [encode(&["AUTH", "secret"]), encode(&["SET", "k", "v"])].concat();
+    stream.write_all(pipeline.as_bytes()).await.unwrap();
+    assert_eq!(read_reply(&mut stream, &mut parser).await, RespValue::ok());
+    assert_eq!(read_reply(&mut stream, &mut parser).await, RespValue::ok());
+    let reply = send(&mut stream, &mut parser, &["GET", "k"]).await;
+    assert_eq!(reply, RespValue::bulk_string("v"));
+
+    // RESET forgets the authentication
+    send(&mut stream, &mut parser, &["RESET"]).await;
+    let reply = send(&mut stream, &mut parser, &["GET", "k"]).await;
+    assert!(matches!(reply, RespValue::Error(ref e) if e.starts_with("NOAUTH")));
+
+    // Wrong credentials are rejected; HELLO can authenticate as it switches protocol
+    let mut other = TcpStream::connect(&addr).await.unwrap();
+    let mut other_parser = RespParser::new(4096);
+    for args in [&["AUTH", "wrong"][..], &["AUTH", "someone", "secret"][..]] {
+        let reply = send(&mut other, &mut other_parser, args).await;
+        assert!(matches!(reply, RespValue::Error(ref e) if e.starts_with("WRONGPASS")));
+    }
+    let reply = send(&mut other, &mut other_parser, &["HELLO", "3"]).await;
+    assert!(matches!(reply, RespValue::Error(ref e) if e.starts_with("NOAUTH")));
+    let reply = send(
+        &mut other,
+        &mut other_parser,
+        &["HELLO", "3", "AUTH", "default", "secret"],
+    )
+    .await;
+    assert_eq!(hello_field(&reply, "proto"), RespValue::integer(3));
+    let reply = send(&mut other, &mut other_parser, &["GET", "k"]).await;
+    assert_eq!(reply, RespValue::bulk_string("v"));
+}
+
+#[tokio::test]
+async fn test_bind_addresses_under_protected_mode() {
+    let extra = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let addr = start_server_with(|server| {
+        server.add_bind_address(extra.clone());
+        server.set_protected_mode(true);
+    })
+    .await;
+
+    // Loopback clients are served on every address without a password
+    let (mut stream, mut parser) = connect(&addr).await;
+    send(&mut stream, &mut parser, &["SET", "k", "v"]).await;
+    let (mut stream, mut parser) = connect(&extra).await;
+    let reply = send(&mut stream, &mut parser, &["GET", "k"]).await;
+    assert_eq!(reply, RespValue::bulk_string("v"));
+}
+
+#[tokio::test]
+async fn test_auth_without_password() {
+    let addr = start_server().await;
+    let (mut stream, mut parser) = connect(&addr).await;
+
+    let reply = send(&mut stream, &mut parser, &["AUTH", "secret"]).await;
+    assert!(
+        matches!(reply, RespValue::Error(ref e) if e.contains("without any password configured"))
+    );
+    let reply = send(&mut stream, &mut parser, &["AUTH", "default", "anything"]).await;
+    assert_eq!(reply, RespValue::ok());
+}
+
+#[tokio::test]
+async fn test_client_info_counts_commands() {
+    let addr = start_server().await;
+    let (mut stream, mut parser) = connect(&addr).await;
+    let (mut other, mut other_parser) = connect(&addr).await;
+
+    send(&mut stream, &mut parser, &["SET", "k", "v"]).await;
+    send(&mut stream, &mut parser, &["GET", "k"]).await;
+
+    // connect() sent a PING first; CLIENT INFO counts itself, as in Redis
+    let info = match send(&mut stream, &mut parser, &["CLIENT", "INFO"]).await {
+        RespValue::BulkString(Some(info)) => String::from_utf8_lossy(&info).to_string(),
+        other => panic!("Unexpected CLIENT INFO reply: {:?}", other),
+    };
+    assert!(info.contains(" tot-cmds=4 "), "{}", info);
+    assert!(info.ends_with(" cmd=client|info"), "{}", info);
+
+    // Other connections see the last command in CLIENT LIST
+    let list = match send(&mut other, &mut other_parser, &["CLIENT", "LIST"]).await {
+        RespValue::BulkString(Some(list)) => String::from_utf8_lossy(&list).to_string(),
+        other => panic!("Unexpected CLIENT LIST reply: {:?}", other),
+    };
+    let own = info.split(' ').next().unwrap();
+    let line = list
+        .lines()
+        .find(|line| line.starts_with(&format!("{} ", own)))
+        .unwrap_or_else(|| panic!("{} missing from {}", own, list));
+    assert!(line.ends_with(" tot-cmds=4 cmd=client|info"), "{}", line);
+
+    send(&mut stream, &mut parser, &["GET", "k"]).await;
+    let list = match send(&mut other, &mut other_parser, &["CLIENT", "LIST"]).await {
+        RespValue::BulkString(Some(list)) => String::from_utf8_lossy(&list).to_string(),
+        other => panic!("Unexpected CLIENT LIST reply: {:?}", other),
+    };
+    assert!(list.contains(" tot-cmds=5 cmd=get"), "{}", list);
+}
+
+#[tokio::test]
+async fn test_blpop_serves_blocked_clients_in_order() {
+    let addr = start_server().await;
+    let (mut pusher, mut parser) = connect(&addr).await;
+
+    // Wait until the server reports `count` clients blocked
+    async fn wait_blocked(stream: &mut TcpStream, parser: &mut RespParser, count: usize) {
+        let expected = format!("blocked_clients:{}", count);
+        for _ in 0..100 {
+            if let RespValue::BulkString(Some(info)) =
+                send(stream, parser, &["INFO", "clients"]).await
+            {
+                if String::from_utf8_lossy(&info).contains(&expected) {
+                    return;
+                }
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Expected {} blocked clients", count);
+    }
+
+    // Three clients block on the same key, one after the other
+    let mut blockers = Vec::new();
+    for i in 0..3 {
+        let (mut stream, parser_) = connect(&addr).await;
+        stream
+            .write_all(encode(&["BLPOP", "queue", "0"]).as_bytes())
+            .await
+            .unwrap();
+        wait_blocked(&mut pusher, &mut parser, i + 1).await;
+        blockers.push((stream, parser_));
+    }
+
+    // Two elements wake the two longest-waiting clients, in order
+    let reply = send(&mut pusher, &mut parser, &["RPUSH", "queue", "a", "b"]).await;
+    assert_eq!(reply, RespValue::integer(2));
+    for (i, element) in ["a", "b"].into_iter().enumerate() {
+        let (stream, parser_) = &mut blockers[i];
+        assert_eq!(
+            read_reply(stream, parser_).await,
+            RespValue::array(vec![
+                RespValue::bulk_string("queue"),
+                RespValue::bulk_string(element),
+            ])
+        );
+    }
+    assert_eq!(
+        send(&mut pusher, &mut parser, &["LLEN", "queue"]).await,
+        RespValue::integer(0)
+    );
+
+    // The third stays blocked until the next push
+    wait_blocked(&mut pusher, &mut parser, 1).await;
+    let (stream, parser_) = &mut blockers[2];
+    assert!(
+        timeout(Duration::from_millis(200), read_reply(stream, parser_))
+            .await
+            .is_err()
+    );
+    send(&mut pusher, &mut parser, &["LPUSH", "queue", "c"]).await;
+    let (stream, parser_) = &mut blockers[2];
+    assert_eq!(
+        read_reply(stream, parser_).await,
+        RespValue::array(vec![
+            RespValue::bulk_string("queue"),
+            RespValue::bulk_string("c"),
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_blpop_timeout() {
+    let addr = start_server().await;
+    let (mut stream, mut parser) = connect(&addr).await;
+
+    let reply = send(&mut stream, &mut parser, &["BLPOP", "missing", "0.1"]).await;
+    assert_eq!(reply, RespValue::null_array());
+    let reply = send(&mut stream, &mut parser, &["BRPOP", "missing", "-1"]).await;
+    assert!(matches!(reply, RespValue::Error(ref e) if e.contains("timeout is negative")));
+    let reply = send(&mut stream, &mut parser, &["BRPOP", "missing", "soon"]).await;
+    assert!(matches!(reply, RespValue::Error(ref e) if e.contains("timeout is not a float")));
+
+    // A list with elements is popped at once, from the right end for BRPOP
+    send(&mut stream, &mut parser, &["RPUSH", "list", "a", "b"]).await;
+    let reply = send(&mut stream, &mut parser, &["BRPOP", "missing", "list", "0"]).await;
+    assert_eq!(
+        reply,
+        RespValue::array(vec![
+            RespValue::bulk_string("list"),
+            RespValue::bulk_string("b"),
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_null_replies_per_protocol() {
+    let addr = start_server().await;
+    let (mut stream, mut parser) = connect(&addr).await;
+
+    // RESP2 tells a missing value from a missing list of values
+    assert_eq!(send_raw(&mut stream, &["GET", "missing"]).await, "$-1\r\n");
+    assert_eq!(
+        send_raw(&mut stream, &["LPOP", "missing", "2"]).await,
+        "*-1\r\n"
+    );
+    assert_eq!(
+        send_raw(&mut stream, &["BLPOP", "missing", "0.01"]).await,
+        "*-1\r\n"
+    );
+
+    // RESP3 has a single null for both
+    let reply = send(&mut stream, &mut parser, &["HELLO", "3"]).await;
+    assert!(matches!(reply, RespValue::Map(_)));
+    assert_eq!(send_raw(&mut stream, &["GET", "missing"]).await, "_\r\n");
+    assert_eq!(
+        send_raw(&mut stream, &["LPOP", "missing", "2"]).await,
+        "_\r\n"
+    );
+    assert_eq!(
+        send_raw(&mut stream, &["BLPOP", "missing", "0.01"]).await,
+        "_\r\n"
+    );
+    assert_eq!(
+        send_raw(&mut stream, &["MGET", "missing"]).await,
+        "*1\r\n_\r\n"
+    );
+}
+
+#[tokio::test]
+async fn test_boolean_replies_per_protocol() {
+    let addr = start_server().await;
+    let (mut stream, mut parser) = connect(&addr).await;
+
+    // RESP2 has no booleans
+    assert_eq!(
+        send_raw(&mut stream, &["DEBUG", "PROTOCOL", "true"]).await,
+        ":1\r\n"
+    );
+    assert_eq!(
+        send_raw(&mut stream, &["DEBUG", "PROTOCOL", "false"]).await,
+        ":0\r\n"
+    );
+
+    let reply = send(&mut stream, &mut parser, &["HELLO", "3"]).await;
+    assert!(matches!(reply, RespValue::Map(_)));
+    assert_eq!(
+        send_raw(&mut stream, &["DEBUG", "PROTOCOL", "true"]).await,
+        "#t\r\n"
+    );
+    assert_eq!(
+        send_raw(&mut stream, &["DEBUG", "PROTOCOL", "false"]).await,
+        "#f\r\n"
+    );
+
+    // As in Redis, yes/no commands keep replying with integers under RESP3
+    let commands: [&[&str]; 8] = [
+        &["SETNX", "key", "value"],
+        &["SETNX", "key", "value"],
+        &["EXPIRE", "key", "100"],
+        &["SADD", "set", "member"],
+        &["SISMEMBER", "set", "member"],
+        &["SISMEMBER", "set", "other"],
+        &["HSET", "hash", "field", "value"],
+        &["HEXISTS", "hash", "field"],
+    ];
+    let replies = [":1", ":0", ":1", ":1", ":1", ":0", ":1", ":1"];
+    for (command, reply) in commands.iter().zip(replies) {
+        assert_eq!(
+            send_raw(&mut stream, command).await,
+            format!("{}\r\n", reply),
+            "{:?}",
+            command
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_script_kill_reaches_a_script_holding_the_gate() {
+    let addr = start_server().await;
+    let (mut admin, mut admin_parser) = connect(&addr).await;
+    let (mut script, mut script_parser) = connect(&addr).await;
+    let mut writers = Vec::new();
+    for _ in 0..4 {
+        writers.push(connect(&addr).await);
+    }
+
+    // Without a time limit only SCRIPT KILL ends the script
+    let reply = send(
+        &mut admin,
+        &mut admin_parser,
+        &["CONFIG", "SET", "lua-time-limit", "0"],
+    )
+    .await;
+    assert_eq!(reply, RespValue::ok());
+    script
+        .write_all(encode(&["EVAL", "while true do end", "0"]).as_bytes())
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(100)).await;
+
+    // More writers queue on the gate than the runtime has worker threads
+    for (stream, _) in &mut writers {
+        stream
+            .write_all(encode(&["SET", "key", "value"]).as_bytes())
+            .await
+            .unwrap();
+    }
+    sleep(Duration::from_millis(100)).await;
+
+    let reply = timeout(
+        Duration::from_secs(5),
+        send(&mut admin, &mut admin_parser, &["SCRIPT", "KILL"]),
+    )
+    .await
+    .expect("SCRIPT KILL was starved by the clients waiting on the gate");
+    assert_eq!(reply, RespValue::ok());
+    let reply = read_reply(&mut script, &mut script_parser).await;
+    assert!(matches!(reply, RespValue::Error(ref e) if e.contains("Script killed")));
+
+    // and the queued writers run once the script has let go
+    for (stream, parser) in &mut writers {
+        assert_eq!(read_reply(stream, parser).await, RespValue::ok());
+    }
+}
@@ -10,12 +10,45 @@
 #[cfg(test)]
 #[cfg(feature = "cluster")]
 mod cluster_tests {
-    use aikv::cluster::{ClusterCommands, ClusterConfig, ClusterNode, MultiRaftNode, Router};
+    use aikv::cluster::{
+        ClusterCommands, ClusterConfig, ClusterNode, ForgetBlacklist, MultiRaftNode, Router,
+    };
     use aikv::error::Result;
     use openraft::Config as RaftConfig;
     use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
     use tokio::time::{sleep, Duration};
 
+    /// Send a command and return the raw text of its reply, which is one
+    /// line or a bulk string
+    async fn send_raw(stream: &mut TcpStream, args: &[&str]) -> Result<String> {
+        let mut request = format!("*{}\r\n", args.len());
+        for arg in args {
+            request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+        }
+        stream.write_all(request.as_bytes()).await?;
+
+        let mut reply = Vec::new();
+        read_line(stream, &mut reply).await?;
+        // A bulk string's body follows its length line
+        if reply.starts_with(b"$") && !reply.starts_with(b"$-1") {
+            read_line(stream, &mut reply).await?;
+        }
+        Ok(String::from_utf8_lossy(&reply).into_owned())
+    }
+
+    /// Read up to and including the next CRLF onto `reply`
+    async fn read_line(stream: &mut TcpStream, reply: &mut Vec<u8>) -> Result<()> {
+        let start = reply.len();
+        while !reply[start..].ends_with(b"\r\n") {
+            let mut byte = [0u8; 1];
+            stream.read_exact(&mut byte).await?;
+            reply.push(byte[0]);
+        }
+        Ok(())
+    }
+
     /// Test that MetaRaftNode correctly syncs add_node operations
     #[tokio::test]
     async fn test_meta_raft_add_node_sync() -> Result<()> {
@@ -229,6 +262,121 @@ mod cluster_tests {
         Ok(())
     }
 
+    /// Test that CLUSTER ADDSLOTSRANGE and DELSLOTSRANGE assign and remove
+    /// whole ranges, and reject bad or overlapping ones
+    #[tokio::test]
+    async fn test_cluster_slots_range() -> Result<()> {
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_slotsrange").await;
+
+        let config = RaftConfig::default();
+
+        let mut node = MultiRaftNode::new(1, "/tmp/test_slotsrange", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        node.init_meta_raft(config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50221".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+
+        let meta = node.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+
+        sleep(Duration::from_millis(500)).await;
+
+        meta.create_group(1, vec![1])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        sleep(Duration::from_millis(500)).await;
+
+        let router = Arc::new(Router::new(meta.get_cluster_meta()));
+        let cmd = ClusterCommands::new(1, meta.clone(), node.clone(), router);
+
+        // Invalid ranges are rejected before anything is assigned
+        assert!(cmd.cluster_addslotsrange(vec![(10, 5)]).await.is_err());
+        assert!(cmd.cluster_addslotsrange(vec![(0, 16384)]).await.is_err());
+        assert!(cmd
+            .cluster_addslotsrange(vec![(0, 10), (20, 30), (10, 15)])
+            .await
+            .is_err());
+        assert!(meta.get_cluster_meta().slots.iter().all(|&g| g == 0));
+
+        let result = cmd.cluster_addslotsrange(vec![(0, 5461)]).await?;
+        assert_eq!(result, aikv::protocol::RespValue::simple_string("OK"));
+        sleep(Duration::from_millis(500)).await;
+
+        let cluster_meta = meta.get_cluster_meta();
+        for slot in 0..=5461 {
+            assert_eq!(
+                cluster_meta.slots[slot], 1,
+                "Slot {} should be assigned",
+                slot
+            );
+        }
+        assert_eq!(cluster_meta.slots[5462], 0);
+
+        cmd.cluster_delslotsrange(vec![(0, 99), (5000, 5461)])
+            .await?;
+        sleep(Duration::from_millis(500)).await;
+
+        let cluster_meta = meta.get_cluster_meta();
+        let owned: Vec<usize> = (0..16384).filter(|&s| cluster_meta.slots[s] == 1).collect();
+        assert_eq!(owned, (100..5000).collect::<Vec<_>>());
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_slotsrange").await;
+
+        Ok(())
+    }
+
+    /// Test that ADDSLOTSRANGE argument errors reach clients with a single ERR
+    #[tokio::test]
+    async fn test_cluster_slots_range_error_replies() -> Result<()> {
+        use tokio::net::TcpListener;
+
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_slotsrange_replies").await;
+
+        let addr = {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            listener.local_addr()?.to_string()
+        };
+        let mut server = aikv::Server::new(addr.clone(), aikv::StorageEngine::new_memory(16));
+        server
+            .initialize_cluster("/tmp/test_slotsrange_replies", "127.0.0.1:50261", true, &[])
+            .await?;
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        sleep(Duration::from_millis(500)).await;
+
+        let mut stream = TcpStream::connect(&addr).await?;
+        assert_eq!(
+            send_raw(&mut stream, &["CLUSTER", "ADDSLOTSRANGE", "10", "5"]).await?,
+            "-ERR start slot number 10 is greater than end slot number 5\r\n"
+        );
+        assert_eq!(
+            send_raw(
+                &mut stream,
+                &["CLUSTER", "ADDSLOTSRANGE", "0", "10", "10", "15"]
+            )
+            .await?,
+            "-ERR Slot 10 specified multiple times\r\n"
+        );
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_slotsrange_replies").await;
+
+        Ok(())
+    }
+
     /// Test CLUSTER INFO returns correct state
     #[tokio::test]
     async fn test_cluster_info() -> Result<()> {
@@ -407,6 +555,100 @@ mod cluster_tests {
         Ok(())
     }
 
+    /// Test CLUSTER COUNTKEYSINSLOT and GETKEYSINSLOT against local keys
+    #[tokio::test]
+    async fn test_cluster_keys_in_slot() -> Result<()> {
+        use aikv::protocol::RespValue;
+        use aikv::storage::StoredValue;
+        use aikv::StorageEngine;
+        use bytes::Bytes;
+
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_keys_in_slot").await;
+
+        let config = RaftConfig::default();
+
+        let mut node = MultiRaftNode::new(1, "/tmp/test_keys_in_slot", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        node.init_meta_raft(config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50121".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+
+        let meta = node.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+        let cluster_meta = meta.get_cluster_meta();
+        let router = Arc::new(Router::new(cluster_meta));
+        let mut cmd = ClusterCommands::new(1, meta.clone(), node, router);
+
+        let storage = StorageEngine::new_memory(16);
+        for key in ["{user}:1", "{user}:2", "{user}:3", "other"] {
+            storage.set_value(
+                0,
+                key.to_string(),
+                StoredValue::new_string(Bytes::from("v")),
+            )?;
+        }
+        cmd.set_storage(storage);
+
+        let run = |args: &[&str]| {
+            let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+            cmd.execute(&args)
+        };
+
+        let slot = Router::key_to_slot(b"{user}:1").to_string();
+        let other = Router::key_to_slot(b"other").to_string();
+        assert_eq!(run(&["COUNTKEYSINSLOT", &slot])?, RespValue::Integer(3));
+        assert_eq!(run(&["COUNTKEYSINSLOT", &other])?, RespValue::Integer(1));
+
+        // GETKEYSINSLOT returns at most count keys of the slot
+        match run(&["GETKEYSINSLOT", &slot, "2"])? {
+            RespValue::Array(Some(keys)) => {
+                assert_eq!(keys.len(), 2);
+                for key in keys {
+                    match key {
+                        RespValue::BulkString(Some(key)) => assert!(key.starts_with(b"{user}:")),
+                        other => panic!("Expected BulkString, got {:?}", other),
+                    }
+                }
+            }
+            other => panic!("Expected Array, got {:?}", other),
+        }
+        assert_eq!(
+            run(&["GETKEYSINSLOT", &other, "10"])?,
+            RespValue::Array(Some(vec![RespValue::BulkString(Some(Bytes::from(
+                "other"
+            )))]))
+        );
+
+        // Slots outside 0-16383 are rejected
+        for args in [
+            &["COUNTKEYSINSLOT", "16384"][..],
+            &["COUNTKEYSINSLOT", "-1"],
+            &["GETKEYSINSLOT", "16384", "10"],
+            &["GETKEYSINSLOT", "0", "-1"],
+        ] {
+            let err = run(args).unwrap_err().to_string();
+            assert!(err.contains("Invalid slot"), "{:?}: {}", args, err);
+        }
+        assert!(run(&["COUNTKEYSINSLOT", "abc"])
+            .unwrap_err()
+            .to_string()
+            .contains("not an integer"));
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_keys_in_slot").await;
+
+        Ok(())
+    }
+
     /// Test ClusterNode initialization
     #[tokio::test]
     async fn test_cluster_node_init() -> Result<()> {
@@ -446,4 +688,987 @@ mod cluster_tests {
 
         Ok(())
     }
+
+    /// Test that CLUSTER MYID reports the same identity as the myself line of CLUSTER NODES
+    #[tokio::test]
+    async fn test_cluster_myid_matches_nodes_myself() -> Result<()> {
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_myid").await;
+
+        let addr = "127.0.0.1:50131";
+        let node_id = ClusterCommands::resolve_node_id(addr);
+        assert_eq!(node_id, ClusterCommands::resolve_node_id(addr));
+
+        let config = RaftConfig::default();
+
+        let mut node = MultiRaftNode::new(node_id, "/tmp/test_myid", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.init_meta_raft(config)
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(node_id, addr.to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+        let meta = node.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+        let router = Arc::new(Router::new(meta.get_cluster_meta()));
+        let cmd = ClusterCommands::new(node_id, meta.clone(), node.clone(), router);
+
+        // Wait for bootstrap
+        sleep(Duration::from_millis(500)).await;
+
+        let myid = match cmd.cluster_myid()? {
+            aikv::protocol::RespValue::BulkString(Some(id)) => {
+                String::from_utf8_lossy(&id).to_string()
+            }
+            _ => panic!("Expected BulkString"),
+        };
+        assert_eq!(myid.len(), 40);
+        assert_eq!(myid, ClusterCommands::format_node_id(node_id));
+
+        let nodes = match cmd.cluster_nodes()? {
+            aikv::protocol::RespValue::BulkString(Some(nodes)) => {
+                String::from_utf8_lossy(&nodes).to_string()
+            }
+            _ => panic!("Expected BulkString"),
+        };
+        let myself = nodes
+            .split("\r\n")
+            .find(|line| line.contains("myself"))
+            .expect("CLUSTER NODES should contain a myself line");
+        assert_eq!(myself.split(' ').next(), Some(myid.as_str()));
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_myid").await;
+
+        Ok(())
+    }
+
+    /// Test that CLUSTER SETSLOT NODE hands a slot over and advances the epoch
+    #[tokio::test]
+    async fn test_cluster_setslot_node_transfers_ownership() -> Result<()> {
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_setslot").await;
+
+        let config = RaftConfig::default();
+
+        let mut node = MultiRaftNode::new(1, "/tmp/test_setslot", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.init_meta_raft(config)
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50141".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+        let meta = node.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+        let router = Arc::new(Router::new(meta.get_cluster_meta()));
+        let cmd = ClusterCommands::new(1, meta.clone(), node.clone(), router);
+
+        // Wait for bootstrap
+        sleep(Duration::from_millis(500)).await;
+
+        // Node 1 owns slot 100, node 2 joins as the migration target
+        cmd.cluster_addslots(vec![100]).await?;
+        cmd.cluster_meet("127.0.0.1".to_string(), 50142, Some(2))
+            .await?;
+        sleep(Duration::from_millis(500)).await;
+        let epoch_before = meta.get_cluster_meta().config_version;
+
+        // Migration completes: node 2 becomes the owner
+        cmd.cluster_setslot_node(100, 2).await?;
+        sleep(Duration::from_millis(500)).await;
+
+        let cluster_meta = meta.get_cluster_meta();
+        let owner_group = cluster_meta.slots[100];
+        assert!(cluster_meta.groups[&owner_group].replicas.contains(&2));
+        assert!(!cluster_meta.groups[&owner_group].replicas.contains(&1));
+        assert!(cluster_meta.config_version > epoch_before);
+        let epoch_after_transfer = cluster_meta.config_version;
+
+        // Moving it back to self supersedes node 2's claim again
+        cmd.cluster_setslot_node(100, 1).await?;
+        sleep(Duration::from_millis(500)).await;
+
+        let cluster_meta = meta.get_cluster_meta();
+        let owner_group = cluster_meta.slots[100];
+        assert!(cluster_meta.groups[&owner_group].replicas.contains(&1));
+        assert!(cluster_meta.config_version > epoch_after_transfer);
+
+        // Unknown target nodes are rejected
+        assert!(cmd.cluster_setslot_node(100, 99).await.is_err());
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_setslot").await;
+
+        Ok(())
+    }
+
+    /// Test that a cluster node only serves database 0
+    #[tokio::test]
+    async fn test_cluster_select_only_db_zero() -> Result<()> {
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_cluster_select").await;
+
+        let config = RaftConfig::default();
+
+        let mut node = MultiRaftNode::new(1, "/tmp/test_cluster_select", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.init_meta_raft(config)
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50241".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+        let meta = node.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+        let router = Arc::new(Router::new(meta.get_cluster_meta()));
+        let cmd = ClusterCommands::new(1, meta.clone(), node.clone(), router);
+
+        let mut executor = aikv::command::CommandExecutor::new(aikv::StorageEngine::new_memory(16));
+        executor.set_cluster_commands(cmd);
+
+        let mut db = 0;
+        let select = |db: &mut usize, index: &str| {
+            executor.execute("SELECT", &[bytes::Bytes::from(index.to_string())], db, 1)
+        };
+        let err = select(&mut db, "1").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("SELECT is not allowed in cluster mode"));
+        assert_eq!(db, 0);
+        assert_eq!(select(&mut db, "0")?, aikv::protocol::RespValue::ok());
+        assert_eq!(db, 0);
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_cluster_select").await;
+
+        Ok(())
+    }
+
+    /// Test that CLUSTER SETSLOT IMPORTING/MIGRATING check the node before refusing
+    #[tokio::test]
+    async fn test_cluster_setslot_migration_checks_node() -> Result<()> {
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_setslot_migration").await;
+
+        let config = RaftConfig::default();
+
+        let mut node = MultiRaftNode::new(1, "/tmp/test_setslot_migration", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.init_meta_raft(config)
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50231".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+        let meta = node.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+        let router = Arc::new(Router::new(meta.get_cluster_meta()));
+        let cmd = ClusterCommands::new(1, meta.clone(), node.clone(), router);
+
+        // Wait for bootstrap
+        sleep(Duration::from_millis(500)).await;
+
+        cmd.cluster_addslots(vec![100]).await?;
+        cmd.cluster_meet("127.0.0.1".to_string(), 50232, Some(2))
+            .await?;
+        sleep(Duration::from_millis(500)).await;
+
+        // Unknown peers are rejected like SETSLOT NODE does
+        let err = cmd
+            .cluster_setslot_migration(100, "IMPORTING", 99)
+            .unwrap_err();
+        assert!(err.to_string().contains("I don't know about node"));
+        assert!(cmd
+            .cluster_setslot_migration(16384, "IMPORTING", 2)
+            .is_err());
+
+        // A known master passes the check, but no migration state is kept
+        let err = cmd
+            .cluster_setslot_migration(100, "MIGRATING", 2)
+            .unwrap_err();
+        assert_eq!(
+            err.to_resp(),
+            aikv::protocol::RespValue::error("ERR CLUSTER SETSLOT MIGRATING is not supported")
+        );
+        assert_eq!(meta.get_cluster_meta().slots[100], 1);
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_setslot_migration").await;
+
+        Ok(())
+    }
+
+    /// Test that WAIT counts a replica once it has matched the leader's log
+    #[tokio::test]
+    async fn test_wait_counts_caught_up_replica() -> Result<()> {
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_wait_node1").await;
+        let _ = tokio::fs::remove_dir_all("/tmp/test_wait_node2").await;
+
+        let config = RaftConfig::default();
+
+        // Node 1 bootstraps MetaRaft and leads the data group
+        let mut node1 = MultiRaftNode::new(1, "/tmp/test_wait_node1", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node1
+            .init_meta_raft(config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node1
+            .initialize_meta_cluster(vec![(1, "127.0.0.1:50151".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node1
+            .start(true, None)
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        let node1 = Arc::new(node1);
+
+        // Node 2 joins as the replica
+        let mut node2 = MultiRaftNode::new(2, "/tmp/test_wait_node2", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node2
+            .init_meta_raft(config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node2
+            .start(false, Some("127.0.0.1:50151".to_string()))
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        let node2 = Arc::new(node2);
+
+        let meta = node1.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+        let router = Arc::new(Router::new(meta.get_cluster_meta()));
+        let cmd = ClusterCommands::new(1, meta.clone(), node1.clone(), router);
+
+        sleep(Duration::from_millis(500)).await;
+        cmd.cluster_meet("127.0.0.1".to_string(), 50152, Some(2))
+            .await?;
+        cmd.cluster_addslots((0..16384).collect()).await?;
+        sleep(Duration::from_millis(500)).await;
+
+        // Node 2 replicates node 1's group
+        let replica_cmd = ClusterCommands::new(
+            2,
+            node2.meta_raft().unwrap().clone(),
+            node2.clone(),
+            Arc::new(Router::new(meta.get_cluster_meta())),
+        );
+        replica_cmd.cluster_replicate(1).await?;
+        sleep(Duration::from_millis(500)).await;
+
+        // A write on the leader is acknowledged by the replica promptly
+        node1
+            .put(b"wait-key".to_vec(), b"value".to_vec())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let start = std::time::Instant::now();
+        let result = cmd
+            .wait_replicas(1, Some(Duration::from_millis(2000)))
+            .await?;
+        assert_eq!(result, aikv::protocol::RespValue::Integer(1));
+        assert!(start.elapsed() < Duration::from_millis(2000));
+
+        // No more replicas exist, so waiting for two times out with one
+        let result = cmd
+            .wait_replicas(2, Some(Duration::from_millis(100)))
+            .await?;
+        assert_eq!(result, aikv::protocol::RespValue::Integer(1));
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_wait_node1").await;
+        let _ = tokio::fs::remove_dir_all("/tmp/test_wait_node2").await;
+
+        Ok(())
+    }
+
+    /// Test that CLUSTER NODES lists a freshly met node that owns no slots
+    #[tokio::test]
+    async fn test_cluster_nodes_slotless_node() -> Result<()> {
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_nodes_slotless").await;
+
+        let config = RaftConfig::default();
+
+        let mut node = MultiRaftNode::new(1, "/tmp/test_nodes_slotless", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.init_meta_raft(config)
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50161".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+        let meta = node.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+        let router = Arc::new(Router::new(meta.get_cluster_meta()));
+        let cmd = ClusterCommands::new(1, meta.clone(), node.clone(), router);
+
+        // Wait for bootstrap
+        sleep(Duration::from_millis(500)).await;
+
+        // Node 1 owns slots 0-99, node 2 is known but owns nothing
+        cmd.cluster_addslots((0..100).collect()).await?;
+        cmd.cluster_meet("127.0.0.1".to_string(), 50162, Some(2))
+            .await?;
+        sleep(Duration::from_millis(500)).await;
+
+        let nodes = match cmd.cluster_nodes()? {
+            aikv::protocol::RespValue::BulkString(Some(nodes)) => {
+                String::from_utf8_lossy(&nodes).to_string()
+            }
+            _ => panic!("Expected BulkString"),
+        };
+        let find_line = |node_id| {
+            let id = ClusterCommands::format_node_id(node_id);
+            nodes
+                .split("\r\n")
+                .find(|line| line.starts_with(&id))
+                .unwrap_or_else(|| panic!("Node {} missing from CLUSTER NODES: {}", node_id, nodes))
+                .to_string()
+        };
+
+        let owner = find_line(1);
+        let fields: Vec<&str> = owner.split(' ').collect();
+        assert_eq!(fields[2], "myself,master");
+        assert_eq!(&fields[8..], ["0-99"]);
+
+        // The slot-less node keeps its flags and has no slot column at all
+        let slotless = find_line(2);
+        let fields: Vec<&str> = slotless.split(' ').collect();
+        assert_eq!(fields.len(), 8, "Unexpected slot ranges: {}", slotless);
+        assert_eq!(fields[2], "master");
+        assert_eq!(fields[3], "-");
+        assert!(!slotless.ends_with(' '));
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_nodes_slotless").await;
+
+        Ok(())
+    }
+
+    /// Test that shard channels are only served by the node owning their slot
+    #[tokio::test]
+    async fn test_ssubscribe_slot_validation() -> Result<()> {
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_ssubscribe").await;
+
+        let config = RaftConfig::default();
+
+        let mut node = MultiRaftNode::new(1, "/tmp/test_ssubscribe", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.init_meta_raft(config)
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50171".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+        let meta = node.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+        let router = Arc::new(Router::new(meta.get_cluster_meta()));
+        let cmd = ClusterCommands::new(1, meta.clone(), node.clone(), router);
+
+        // Wait for bootstrap
+        sleep(Duration::from_millis(500)).await;
+
+        // Node 1 serves the local channel's slot, node 2 the foreign one
+        let local_slot = Router::key_to_slot(b"local-news");
+        let foreign_slot = Router::key_to_slot(b"foreign-news");
+        assert_ne!(local_slot, foreign_slot);
+        cmd.cluster_addslots(vec![local_slot, foreign_slot]).await?;
+        cmd.cluster_meet("127.0.0.1".to_string(), 50172, Some(2))
+            .await?;
+        sleep(Duration::from_millis(500)).await;
+        cmd.cluster_setslot_node(foreign_slot, 2).await?;
+        sleep(Duration::from_millis(500)).await;
+
+        let mut executor = aikv::command::CommandExecutor::new(aikv::StorageEngine::new_memory(16));
+        executor.set_cluster_commands(cmd);
+        let cmd = executor.cluster_commands().unwrap();
+
+        // SSUBSCRIBE to the local channel is accepted
+        assert!(cmd.check_key_slot(b"local-news").is_ok());
+
+        // The foreign channel is redirected to node 2
+        match cmd.check_key_slot(b"foreign-news") {
+            Err(aikv::error::AikvError::Moved(slot, addr)) => {
+                assert_eq!(slot, foreign_slot);
+                assert_eq!(addr, "127.0.0.1:50172");
+            }
+            other => panic!("Expected MOVED, got {:?}", other),
+        }
+
+        // SPUBLISH goes through the same check
+        let mut db = 0;
+        let args = [
+            bytes::Bytes::from("foreign-news"),
+            bytes::Bytes::from("hello"),
+        ];
+        assert!(matches!(
+            executor.execute("SPUBLISH", &args, &mut db, 1),
+            Err(aikv::error::AikvError::Moved(..))
+        ));
+        let args = [
+            bytes::Bytes::from("local-news"),
+            bytes::Bytes::from("hello"),
+        ];
+        assert_eq!(
+            executor.execute("SPUBLISH", &args, &mut db, 1)?,
+            aikv::protocol::RespValue::integer(0)
+        );
+
+        // PUBSUB reports a shard channel subscription as a shard channel only
+        let (sender, _receiver) = aikv::server::pubsub::message_queue();
+        executor
+            .pubsub_broker()
+            .ssubscribe(7, &sender, bytes::Bytes::from("local-news"))?;
+        let pubsub = |subcommand: &str, arg: &str| {
+            let args = [
+                bytes::Bytes::from(subcommand.to_string()),
+                bytes::Bytes::from(arg.to_string()),
+            ];
+            executor.execute("PUBSUB", &args, &mut 0, 1)
+        };
+        assert_eq!(
+            pubsub("SHARDCHANNELS", "local-*")?,
+            aikv::protocol::RespValue::array(vec![aikv::protocol::RespValue::bulk_string(
+                "local-news"
+            )])
+        );
+        assert_eq!(
+            pubsub("SHARDNUMSUB", "local-news")?,
+            aikv::protocol::RespValue::array(vec![
+                aikv::protocol::RespValue::bulk_string("local-news"),
+                aikv::protocol::RespValue::integer(1),
+            ])
+        );
+        assert_eq!(
+            pubsub("CHANNELS", "local-*")?,
+            aikv::protocol::RespValue::array(vec![])
+        );
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_ssubscribe").await;
+
+        Ok(())
+    }
+
+    /// Test that a forgotten node cannot be met again until its blacklist entry expires
+    #[tokio::test]
+    async fn test_cluster_forget_blacklist() -> Result<()> {
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_forget_blacklist").await;
+
+        let config = RaftConfig::default();
+
+        let mut node = MultiRaftNode::new(1, "/tmp/test_forget_blacklist", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.init_meta_raft(config)
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50181".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+        let meta = node.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+        let router = Arc::new(Router::new(meta.get_cluster_meta()));
+        let mut cmd = ClusterCommands::new(1, meta.clone(), node.clone(), router);
+        cmd.set_forget_blacklist(Arc::new(ForgetBlacklist::with_ttl(Duration::from_secs(2))));
+
+        // Wait for bootstrap
+        sleep(Duration::from_millis(500)).await;
+
+        let peer_id = ClusterCommands::generate_node_id_from_addr("127.0.0.1:50182");
+        cmd.cluster_meet("127.0.0.1".to_string(), 50182, None)
+            .await?;
+        sleep(Duration::from_millis(500)).await;
+        assert!(meta.get_cluster_meta().nodes.contains_key(&peer_id));
+
+        // Meeting the same address right after FORGET is refused
+        cmd.cluster_forget(peer_id).await?;
+        sleep(Duration::from_millis(500)).await;
+        assert!(cmd
+            .cluster_meet("127.0.0.1".to_string(), 50182, None)
+            .await
+            .is_err());
+        sleep(Duration::from_millis(500)).await;
+        assert!(!meta.get_cluster_meta().nodes.contains_key(&peer_id));
+
+        // Once the window has passed the node can join again
+        sleep(Duration::from_millis(1500)).await;
+        cmd.cluster_meet("127.0.0.1".to_string(), 50182, None)
+            .await?;
+        sleep(Duration::from_millis(500)).await;
+        assert!(meta.get_cluster_meta().nodes.contains_key(&peer_id));
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_forget_blacklist").await;
+
+        Ok(())
+    }
+
+    /// Test that CLUSTER MIGRATE-SLOT refuses slots it can't hand over
+    #[tokio::test]
+    async fn test_cluster_migrate_slot_checks() -> Result<()> {
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_migrate_slot").await;
+
+        let config = RaftConfig::default();
+
+        let mut node = MultiRaftNode::new(1, "/tmp/test_migrate_slot", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.init_meta_raft(config)
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50191".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+        let meta = node.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+        let router = Arc::new(Router::new(meta.get_cluster_meta()));
+        let cmd = ClusterCommands::new(1, meta.clone(), node.clone(), router);
+
+        // Wait for bootstrap
+        sleep(Duration::from_millis(500)).await;
+
+        cmd.cluster_addslots((0..100).collect()).await?;
+        cmd.cluster_meet("127.0.0.1".to_string(), 50192, Some(2))
+            .await?;
+        sleep(Duration::from_millis(500)).await;
+
+        let error = |result: Result<aikv::protocol::RespValue>| match result {
+            Err(e) => e.to_string(),
+            Ok(reply) => panic!("Unexpected reply: {:?}", reply),
+        };
+
+        // Unknown targets and slots this node doesn't serve are refused
+        assert!(error(cmd.cluster_migrate_slot(5, 3).await).contains("I don't know about node"));
+        assert!(error(cmd.cluster_migrate_slot(200, 2).await)
+            .contains("I'm not the owner of hash slot 200"));
+        assert!(error(cmd.cluster_migrate_slot(16384, 2).await).contains("Invalid slot"));
+
+        // Without the node's storage there are no keys to move
+        assert!(error(cmd.cluster_migrate_slot(5, 2).await).contains("not enabled"));
+        assert_eq!(meta.get_cluster_meta().slots[5], 1);
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_migrate_slot").await;
+
+        Ok(())
+    }
+
+    /// Test that CLUSTER MIGRATE-SLOT refuses commands on the slot's keys
+    /// until the slot has been handed over
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_cluster_migrate_slot_refuses_writes() -> Result<()> {
+        use aikv::cluster::MigratingSlots;
+        use aikv::command::transaction::CommandGate;
+        use aikv::command::CommandExecutor;
+        use aikv::protocol::RespValue;
+        use aikv::StorageEngine;
+        use bytes::Bytes;
+        use tokio::net::TcpListener;
+
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_migrate_slot_writes").await;
+
+        // The target is a plain server behind a proxy that holds the first
+        // batch back until the writes below have been tried
+        let target_addr = {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            listener.local_addr()?.to_string()
+        };
+        let target_storage = StorageEngine::new_memory(16);
+        let target = aikv::Server::new(target_addr.clone(), target_storage.clone());
+        tokio::spawn(async move {
+            let _ = target.run().await;
+        });
+
+        let proxy = TcpListener::bind("127.0.0.1:0").await?;
+        let proxy_port = proxy.local_addr()?.port();
+        let (connected_tx, connected_rx) = tokio::sync::oneshot::channel();
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let (mut inbound, _) = proxy.accept().await.unwrap();
+            let _ = connected_tx.send(());
+            let _ = release_rx.await;
+            let mut outbound = TcpStream::connect(&target_addr).await.unwrap();
+            let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+        });
+
+        let config = RaftConfig::default();
+
+        let mut node = MultiRaftNode::new(1, "/tmp/test_migrate_slot_writes", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.init_meta_raft(config)
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50271".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+        let meta = node.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+        let router = Arc::new(Router::new(meta.get_cluster_meta()));
+
+        // The connection and the migration share the slot set and the gate,
+        // as connections of one server do
+        let storage = StorageEngine::new_memory(16);
+        let migrating = Arc::new(MigratingSlots::new());
+        let gate = CommandGate::new();
+        let cluster_commands = || {
+            let mut cmd = ClusterCommands::new(1, meta.clone(), node.clone(), router.clone());
+            cmd.set_storage(storage.clone());
+            cmd.set_migrating_slots(Arc::clone(&migrating));
+            cmd.set_command_gate(gate.clone());
+            cmd
+        };
+        let cmd = Arc::new(cluster_commands());
+        let mut executor = CommandExecutor::new(storage.clone());
+        executor.set_command_gate(gate.clone());
+        executor.set_cluster_commands(cluster_commands());
+
+        // Wait for bootstrap
+        sleep(Duration::from_millis(500)).await;
+
+        let slot = Router::key_to_slot(b"{moving}:a");
+        cmd.cluster_addslots(vec![slot]).await?;
+        cmd.cluster_meet("127.0.0.1".to_string(), proxy_port, Some(2))
+            .await?;
+        sleep(Duration::from_millis(500)).await;
+
+        let mut db = 0;
+        let mut execute = |command: &str, args: &[&str]| {
+            let args: Vec<Bytes> = args
+                .iter()
+                .map(|arg| Bytes::from(arg.to_string()))
+                .collect();
+            executor.execute(command, &args, &mut db, 1)
+        };
+        assert_eq!(execute("SET", &["{moving}:a", "1"])?, RespValue::ok());
+
+        let migration = tokio::spawn({
+            let cmd = Arc::clone(&cmd);
+            async move { cmd.cluster_migrate_slot(slot, 2).await }
+        });
+        connected_rx.await.unwrap();
+
+        // The first batch is on its way: the slot's keys are refused, others are not
+        let refused = RespValue::error(format!("TRYAGAIN Hash slot {} is being migrated", slot));
+        assert_eq!(
+            execute("SET", &["{moving}:b", "2"]).unwrap_err().to_resp(),
+            refused
+        );
+        assert_eq!(
+            execute("GET", &["{moving}:a"]).unwrap_err().to_resp(),
+            refused
+        );
+        assert_eq!(execute("SET", &["elsewhere", "3"])?, RespValue::ok());
+
+        release_tx.send(()).unwrap();
+        let reply = migration
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))??;
+        assert_eq!(reply, RespValue::SimpleString("OK".to_string()));
+
+        // Only the key written before the migration moved, and nothing was left behind
+        assert!(target_storage.exists_in_db(0, "{moving}:a")?);
+        assert!(!target_storage.exists_in_db(0, "{moving}:b")?);
+        assert!(!storage.exists_in_db(0, "{moving}:a")?);
+        assert!(!storage.exists_in_db(0, "{moving}:b")?);
+        assert_eq!(meta.get_cluster_meta().slots[slot as usize], 2);
+        assert!(!migrating.contains(slot));
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_migrate_slot_writes").await;
+
+        Ok(())
+    }
+
+    /// Test that SCRIPT LOAD and SCRIPT FLUSH on one node reach the nodes it knows
+    #[tokio::test]
+    async fn test_script_load_reaches_other_nodes() -> Result<()> {
+        use tokio::net::TcpListener;
+
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_script_propagation").await;
+
+        // The follower is a plain server the leader is told about with CLUSTER MEET
+        let follower_addr = {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            listener.local_addr()?
+        };
+        let follower = aikv::Server::new(
+            follower_addr.to_string(),
+            aikv::StorageEngine::new_memory(16),
+        );
+        tokio::spawn(async move {
+            let _ = follower.run().await;
+        });
+
+        let leader_addr = {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            listener.local_addr()?.to_string()
+        };
+        let mut leader =
+            aikv::Server::new(leader_addr.clone(), aikv::StorageEngine::new_memory(16));
+        leader
+            .initialize_cluster("/tmp/test_script_propagation", "127.0.0.1:50281", true, &[])
+            .await?;
+        tokio::spawn(async move {
+            let _ = leader.run().await;
+        });
+        sleep(Duration::from_millis(500)).await;
+
+        let mut on_leader = TcpStream::connect(&leader_addr).await?;
+        let mut on_follower = TcpStream::connect(follower_addr).await?;
+        let port = follower_addr.port().to_string();
+        assert_eq!(
+            send_raw(
+                &mut on_leader,
+                &["CLUSTER", "MEET", "127.0.0.1", port.as_str()]
+            )
+            .await?,
+            "+OK\r\n"
+        );
+
+        let loaded = send_raw(
+            &mut on_leader,
+            &["SCRIPT", "LOAD", "return 'from the leader'"],
+        )
+        .await?;
+        let sha1 = loaded
+            .strip_prefix("$40\r\n")
+            .and_then(|digest| digest.strip_suffix("\r\n"))
+            .expect("SCRIPT LOAD replies with the digest");
+
+        assert_eq!(
+            send_raw(&mut on_follower, &["EVALSHA", sha1, "0"]).await?,
+            "$15\r\nfrom the leader\r\n"
+        );
+
+        // Flushing on the leader empties the follower's cache too
+        assert_eq!(
+            send_raw(&mut on_leader, &["SCRIPT", "FLUSH"]).await?,
+            "+OK\r\n"
+        );
+        assert!(send_raw(&mut on_follower, &["EVALSHA", sha1, "0"])
+            .await?
+            .starts_with("-NOSCRIPT"));
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_script_propagation").await;
+
+        Ok(())
+    }
+
+    /// Test that a replica reports its role to HELLO and INFO replication
+    #[tokio::test]
+    async fn test_replica_reports_role() -> Result<()> {
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_role_node1").await;
+        let _ = tokio::fs::remove_dir_all("/tmp/test_role_node2").await;
+
+        let config = RaftConfig::default();
+
+        let mut node1 = MultiRaftNode::new(1, "/tmp/test_role_node1", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node1
+            .init_meta_raft(config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node1
+            .initialize_meta_cluster(vec![(1, "127.0.0.1:50201".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node1
+            .start(true, None)
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        let node1 = Arc::new(node1);
+
+        let mut node2 = MultiRaftNode::new(2, "/tmp/test_role_node2", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node2
+            .init_meta_raft(config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node2
+            .start(false, Some("127.0.0.1:50201".to_string()))
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        let node2 = Arc::new(node2);
+
+        let meta = node1.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+        let router = Arc::new(Router::new(meta.get_cluster_meta()));
+        let cmd = ClusterCommands::new(1, meta.clone(), node1.clone(), router);
+
+        sleep(Duration::from_millis(500)).await;
+        cmd.cluster_meet("127.0.0.1".to_string(), 50202, Some(2))
+            .await?;
+        cmd.cluster_addslots((0..16384).collect()).await?;
+        sleep(Duration::from_millis(500)).await;
+
+        let replica_cmd = ClusterCommands::new(
+            2,
+            node2.meta_raft().unwrap().clone(),
+            node2.clone(),
+            Arc::new(Router::new(meta.get_cluster_meta())),
+        );
+        replica_cmd.cluster_replicate(1).await?;
+        sleep(Duration::from_millis(500)).await;
+
+        // HELLO reports the executor's role; INFO uses Redis' "slave"
+        let mut db = 0;
+        let info_args = [bytes::Bytes::from("replication")];
+        for (cmd, replica, info_role) in [
+            (cmd, false, "role:master"),
+            (replica_cmd, true, "role:slave"),
+        ] {
+            let mut executor =
+                aikv::command::CommandExecutor::new(aikv::StorageEngine::new_memory(16));
+            executor.set_cluster_commands(cmd);
+            assert_eq!(executor.is_replica(), replica);
+
+            let info = match executor.execute("INFO", &info_args, &mut db, 1)? {
+                aikv::protocol::RespValue::BulkString(Some(info)) => {
+                    String::from_utf8_lossy(&info).to_string()
+                }
+                other => panic!("Unexpected INFO reply: {:?}", other),
+            };
+            assert!(info.contains(info_role), "{}", info);
+        }
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_role_node1").await;
+        let _ = tokio::fs::remove_dir_all("/tmp/test_role_node2").await;
+
+        Ok(())
+    }
+
+    /// Test that CLUSTER NODES reports when each peer last answered a heartbeat
+    #[tokio::test]
+    async fn test_cluster_nodes_pong_recv() -> Result<()> {
+        // Cleanup before test
+        let _ = tokio::fs::remove_dir_all("/tmp/test_pong_node1").await;
+        let _ = tokio::fs::remove_dir_all("/tmp/test_pong_node2").await;
+
+        let config = RaftConfig::default();
+
+        let mut node1 = MultiRaftNode::new(1, "/tmp/test_pong_node1", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node1
+            .init_meta_raft(config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node1
+            .initialize_meta_cluster(vec![(1, "127.0.0.1:50211".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node1
+            .start(true, None)
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        let node1 = Arc::new(node1);
+
+        let mut node2 = MultiRaftNode::new(2, "/tmp/test_pong_node2", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node2
+            .init_meta_raft(config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node2
+            .start(false, Some("127.0.0.1:50211".to_string()))
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let meta = node1.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+        let router = Arc::new(Router::new(meta.get_cluster_meta()));
+        let cmd = ClusterCommands::new(1, meta.clone(), node1.clone(), router);
+
+        sleep(Duration::from_millis(500)).await;
+        cmd.cluster_meet("127.0.0.1".to_string(), 50212, Some(2))
+            .await?;
+        // Let a few heartbeats run
+        sleep(Duration::from_millis(1000)).await;
+
+        let nodes = match cmd.cluster_nodes()? {
+            aikv::protocol::RespValue::BulkString(Some(bytes)) => {
+                String::from_utf8_lossy(&bytes).to_string()
+            }
+            other => panic!("Unexpected CLUSTER NODES reply: {:?}", other),
+        };
+        let now_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        for line in nodes.lines() {
+            let fields: Vec<&str> = line.split(' ').collect();
+            let pong_recv: u64 = fields[5].parse().unwrap();
+            if fields[2].contains("myself") {
+                // Redis reports 0 0 for the node itself
+                assert_eq!(&fields[4..6], &["0", "0"], "{}", line);
+            } else {
+                assert!(pong_recv > 0, "{}", line);
+                assert!(now_ms - pong_recv < 5000, "{}", line);
+            }
+        }
+        assert_eq!(nodes.lines().count(), 2, "{}", nodes);
+
+        // Cleanup
+        let _ = tokio::fs::remove_dir_all("/tmp/test_pong_node1").await;
+        let _ = tokio::fs::remove_dir_all("/tmp/test_pong_node2").await;
+
+        Ok(())
+    }
 }
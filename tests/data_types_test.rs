@@ -1,6 +1,6 @@
 use aikv::command::CommandExecutor;
 use aikv::protocol::RespValue;
-use aikv::StorageEngine;
+use aikv::{AikvError, StorageEngine};
 use bytes::Bytes;
 
 #[test]
@@ -37,6 +37,85 @@ fn test_list_commands() {
     assert_eq!(result.unwrap(), RespValue::Integer(2));
 }
 
+#[test]
+fn test_lpop_rpop_with_count() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let args = vec![
+        Bytes::from("mylist"),
+        Bytes::from("a"),
+        Bytes::from("b"),
+        Bytes::from("c"),
+        Bytes::from("d"),
+    ];
+    executor
+        .execute("RPUSH", &args, &mut current_db, client_id)
+        .unwrap();
+
+    // LPOP with count returns the first elements as an array
+    let args = vec![Bytes::from("mylist"), Bytes::from("2")];
+    let result = executor
+        .execute("LPOP", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(
+        result,
+        RespValue::array(vec![
+            RespValue::bulk_string("a"),
+            RespValue::bulk_string("b"),
+        ])
+    );
+
+    // LPOP with count 0 returns an empty array and leaves the list intact
+    let args = vec![Bytes::from("mylist"), Bytes::from("0")];
+    let result = executor
+        .execute("LPOP", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::array(vec![]));
+
+    // RPOP with count 1 still returns an array
+    let args = vec![Bytes::from("mylist"), Bytes::from("1")];
+    let result = executor
+        .execute("RPOP", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::array(vec![RespValue::bulk_string("d")]));
+
+    // The no-count form returns a single bulk string
+    let args = vec![Bytes::from("mylist")];
+    let result = executor
+        .execute("LPOP", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string("c"));
+
+    // The list is gone once emptied
+    let args = vec![Bytes::from("mylist")];
+    let result = executor
+        .execute("EXISTS", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::Integer(0));
+
+    // Missing key: nil bulk without count, nil array with count
+    let args = vec![Bytes::from("nokey")];
+    let result = executor
+        .execute("LPOP", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::null_bulk_string());
+
+    let args = vec![Bytes::from("nokey"), Bytes::from("2")];
+    let result = executor
+        .execute("LPOP", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::null_array());
+
+    // Negative count is rejected
+    let args = vec![Bytes::from("nokey"), Bytes::from("-1")];
+    assert!(executor
+        .execute("RPOP", &args, &mut current_db, client_id)
+        .is_err());
+}
+
 #[test]
 fn test_linsert_command() {
     let storage = StorageEngine::new_memory(16);
@@ -214,6 +293,145 @@ fn test_hash_commands() {
     }
 }
 
+#[test]
+fn test_hincrbyfloat() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let args = vec![
+        Bytes::from("myhash"),
+        Bytes::from("name"),
+        Bytes::from("ada"),
+    ];
+    executor
+        .execute("HSET", &args, &mut current_db, client_id)
+        .unwrap();
+
+    let mut hincrbyfloat = |field: &str, increment: &str| {
+        let args = vec![
+            Bytes::from("myhash"),
+            Bytes::from(field.to_string()),
+            Bytes::from(increment.to_string()),
+        ];
+        executor.execute("HINCRBYFLOAT", &args, &mut current_db, client_id)
+    };
+
+    // A missing field starts from 0
+    let result = hincrbyfloat("price", "10.5").unwrap();
+    assert_eq!(result, RespValue::bulk_string("10.5"));
+
+    // Results are formatted without trailing zeros
+    let result = hincrbyfloat("price", "0.1").unwrap();
+    assert_eq!(result, RespValue::bulk_string("10.6"));
+    let result = hincrbyfloat("price", "-0.6").unwrap();
+    assert_eq!(result, RespValue::bulk_string("10"));
+    let result = hincrbyfloat("price", "5.0e3").unwrap();
+    assert_eq!(result, RespValue::bulk_string("5010"));
+
+    // Non-numeric fields, bad increments and infinite results are errors
+    let err = hincrbyfloat("name", "1").unwrap_err();
+    assert!(
+        err.to_string().contains("hash value is not a float"),
+        "{}",
+        err
+    );
+    let err = hincrbyfloat("price", "abc").unwrap_err();
+    assert!(
+        err.to_string().contains("value is not a valid float"),
+        "{}",
+        err
+    );
+    let err = hincrbyfloat("price", "nan").unwrap_err();
+    assert!(
+        err.to_string().contains("value is not a valid float"),
+        "{}",
+        err
+    );
+    let err = hincrbyfloat("price", "inf").unwrap_err();
+    assert!(err.to_string().contains("NaN or Infinity"), "{}", err);
+
+    // Failed increments leave the field as it was
+    let args = vec![Bytes::from("myhash"), Bytes::from("price")];
+    let result = executor
+        .execute("HGET", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string("5010"));
+}
+
+#[test]
+fn test_hset_counts_new_fields() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // Two new fields
+    let args = vec![
+        Bytes::from("counthash"),
+        Bytes::from("a"),
+        Bytes::from("1"),
+        Bytes::from("b"),
+        Bytes::from("2"),
+    ];
+    let result = executor.execute("HSET", &args, &mut current_db, client_id);
+    assert_eq!(result.unwrap(), RespValue::Integer(2));
+
+    // One updated field and one new field
+    let args = vec![
+        Bytes::from("counthash"),
+        Bytes::from("a"),
+        Bytes::from("10"),
+        Bytes::from("c"),
+        Bytes::from("3"),
+    ];
+    let result = executor.execute("HSET", &args, &mut current_db, client_id);
+    assert_eq!(result.unwrap(), RespValue::Integer(1));
+
+    // A field repeated in one call is counted once, and the last value wins
+    let args = vec![
+        Bytes::from("counthash"),
+        Bytes::from("d"),
+        Bytes::from("4"),
+        Bytes::from("d"),
+        Bytes::from("5"),
+    ];
+    let result = executor.execute("HSET", &args, &mut current_db, client_id);
+    assert_eq!(result.unwrap(), RespValue::Integer(1));
+    let args = vec![Bytes::from("counthash"), Bytes::from("d")];
+    let result = executor.execute("HGET", &args, &mut current_db, client_id);
+    assert_eq!(result.unwrap(), RespValue::bulk_string("5"));
+
+    // HMSET replies OK whether or not it added fields
+    let args = vec![
+        Bytes::from("counthash"),
+        Bytes::from("a"),
+        Bytes::from("100"),
+        Bytes::from("e"),
+        Bytes::from("5"),
+    ];
+    let result = executor.execute("HMSET", &args, &mut current_db, client_id);
+    assert_eq!(result.unwrap(), RespValue::ok());
+
+    let args = vec![Bytes::from("counthash")];
+    let result = executor.execute("HLEN", &args, &mut current_db, client_id);
+    assert_eq!(result.unwrap(), RespValue::Integer(5));
+
+    // HSET against a key of another type is rejected
+    executor
+        .execute(
+            "SET",
+            &[Bytes::from("notahash"), Bytes::from("v")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    let args = vec![Bytes::from("notahash"), Bytes::from("f"), Bytes::from("v")];
+    let result = executor.execute("HSET", &args, &mut current_db, client_id);
+    assert!(result.is_err());
+}
+
 #[test]
 fn test_hmset_command() {
     let storage = StorageEngine::new_memory(16);
@@ -478,6 +696,528 @@ fn test_zset_commands() {
     }
 }
 
+#[test]
+fn test_zset_scores_are_doubles() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let args = vec![
+        Bytes::from("scores"),
+        Bytes::from("2"),
+        Bytes::from("two"),
+        Bytes::from("1.5"),
+        Bytes::from("half"),
+        Bytes::from("+inf"),
+        Bytes::from("top"),
+    ];
+    executor
+        .execute("ZADD", &args, &mut current_db, client_id)
+        .unwrap();
+
+    // Scores are RESP3 doubles; RESP2 connections get Redis-formatted bulk strings
+    for (member, score, resp2) in [
+        ("two", 2.0, "2"),
+        ("half", 1.5, "1.5"),
+        ("top", f64::INFINITY, "inf"),
+    ] {
+        let args = vec![Bytes::from("scores"), Bytes::from(member)];
+        let result = executor
+            .execute("ZSCORE", &args, &mut current_db, client_id)
+            .unwrap();
+        assert_eq!(result, RespValue::Double(score));
+        assert_eq!(result.into_resp2(), RespValue::bulk_string(resp2));
+    }
+
+    let args = vec![
+        Bytes::from("scores"),
+        Bytes::from("-inf"),
+        Bytes::from("two"),
+    ];
+    let result = executor
+        .execute("ZINCRBY", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::Double(f64::NEG_INFINITY));
+    assert_eq!(result.serialize(), Bytes::from(",-inf\r\n"));
+    assert_eq!(result.into_resp2(), RespValue::bulk_string("-inf"));
+
+    let args = vec![
+        Bytes::from("scores"),
+        Bytes::from("0"),
+        Bytes::from("0"),
+        Bytes::from("WITHSCORES"),
+    ];
+    let result = executor
+        .execute("ZRANGE", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(
+        result.into_resp2(),
+        RespValue::array(vec![
+            RespValue::bulk_string("two"),
+            RespValue::bulk_string("-inf")
+        ])
+    );
+}
+
+#[test]
+fn test_zadd_scores_and_options() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut run = |args: &[&str]| {
+        let command = args[0];
+        let args: Vec<Bytes> = args[1..]
+            .iter()
+            .map(|a| Bytes::from(a.to_string()))
+            .collect();
+        executor.execute(command, &args, &mut current_db, client_id)
+    };
+    let is_error = |result: aikv::Result<RespValue>, message: &str| matches!(result, Err(ref e) if e.to_string().contains(message));
+
+    // NaN is rejected, infinities and exponents are not
+    assert!(is_error(
+        run(&["ZADD", "z", "nan", "a"]),
+        "not a valid float"
+    ));
+    assert!(is_error(
+        run(&["ZADD", "z", "1", "a", "x", "b"]),
+        "not a valid float"
+    ));
+    assert_eq!(run(&["EXISTS", "z"]).unwrap(), RespValue::Integer(0));
+    assert_eq!(
+        run(&["ZADD", "z", "+inf", "top", "1e3", "k", "-inf", "bottom"]).unwrap(),
+        RespValue::Integer(3)
+    );
+    assert_eq!(
+        run(&["ZSCORE", "z", "k"]).unwrap(),
+        RespValue::Double(1000.0)
+    );
+    assert_eq!(
+        run(&["ZRANGE", "z", "0", "-1"]).unwrap(),
+        RespValue::array(vec![
+            RespValue::bulk_string("bottom"),
+            RespValue::bulk_string("k"),
+            RespValue::bulk_string("top"),
+        ])
+    );
+    assert!(is_error(
+        run(&["ZINCRBY", "z", "-inf", "top"]),
+        "resulting score is not a number"
+    ));
+
+    // GT and LT compare against infinite scores too
+    assert_eq!(
+        run(&["ZADD", "z", "GT", "CH", "5", "top"]).unwrap(),
+        RespValue::Integer(0)
+    );
+    assert_eq!(
+        run(&["ZADD", "z", "LT", "CH", "5", "top"]).unwrap(),
+        RespValue::Integer(1)
+    );
+    assert_eq!(
+        run(&["ZADD", "z", "GT", "CH", "inf", "top"]).unwrap(),
+        RespValue::Integer(1)
+    );
+    assert_eq!(
+        run(&["ZADD", "z", "LT", "CH", "-inf", "bottom"]).unwrap(),
+        RespValue::Integer(0)
+    );
+    // ...without stopping new members from being added
+    assert_eq!(
+        run(&["ZADD", "z", "GT", "2", "new"]).unwrap(),
+        RespValue::Integer(1)
+    );
+
+    // NX and XX
+    assert_eq!(
+        run(&["ZADD", "z", "NX", "9", "k", "9", "nx"]).unwrap(),
+        RespValue::Integer(1)
+    );
+    assert_eq!(
+        run(&["ZSCORE", "z", "k"]).unwrap(),
+        RespValue::Double(1000.0)
+    );
+    assert_eq!(
+        run(&["ZADD", "z", "XX", "CH", "9", "k", "9", "xx"]).unwrap(),
+        RespValue::Integer(1)
+    );
+    assert_eq!(run(&["ZSCORE", "z", "xx"]).unwrap(), RespValue::Null);
+    assert_eq!(
+        run(&["ZADD", "missing", "XX", "1", "a"]).unwrap(),
+        RespValue::Integer(0)
+    );
+    assert_eq!(run(&["EXISTS", "missing"]).unwrap(), RespValue::Integer(0));
+
+    // INCR replies with the new score, or nil when an option holds it back
+    assert_eq!(
+        run(&["ZADD", "z", "INCR", "1.5", "k"]).unwrap(),
+        RespValue::Double(10.5)
+    );
+    assert_eq!(
+        run(&["ZADD", "z", "NX", "INCR", "1", "k"]).unwrap(),
+        RespValue::Null
+    );
+    assert_eq!(
+        run(&["ZADD", "z", "LT", "INCR", "1", "k"]).unwrap(),
+        RespValue::Null
+    );
+
+    for (args, message) in [
+        (&["ZADD", "z", "NX", "XX", "1", "a"][..], "not compatible"),
+        (&["ZADD", "z", "GT", "LT", "1", "a"][..], "not compatible"),
+        (&["ZADD", "z", "NX", "GT", "1", "a"][..], "not compatible"),
+        (
+            &["ZADD", "z", "INCR", "1", "a", "2", "b"][..],
+            "single increment-element pair",
+        ),
+        (&["ZADD", "z", "1", "a", "2"][..], "syntax error"),
+        (&["ZADD", "z", "NX", "CH"][..], "syntax error"),
+    ] {
+        assert!(is_error(run(args), message), "{:?}", args);
+    }
+}
+
+#[test]
+fn test_zset_encoding_transition() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let encoding = |current_db: &mut usize| {
+        let args = vec![Bytes::from("ENCODING"), Bytes::from("myzset")];
+        executor
+            .execute("OBJECT", &args, current_db, client_id)
+            .unwrap()
+    };
+
+    // Small sorted sets use listpack
+    let args = vec![Bytes::from("myzset"), Bytes::from("0"), Bytes::from("m0")];
+    executor
+        .execute("ZADD", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(
+        encoding(&mut current_db),
+        RespValue::bulk_string("listpack")
+    );
+
+    // Crossing zset-max-listpack-entries (128) flips to skiplist
+    let mut args = vec![Bytes::from("myzset")];
+    for i in 1..=128 {
+        args.push(Bytes::from(i.to_string()));
+        args.push(Bytes::from(format!("m{}", i)));
+    }
+    executor
+        .execute("ZADD", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(
+        encoding(&mut current_db),
+        RespValue::bulk_string("skiplist")
+    );
+
+    // Shrinking back under the threshold keeps skiplist
+    let mut args = vec![Bytes::from("myzset")];
+    for i in 0..=120 {
+        args.push(Bytes::from(format!("m{}", i)));
+    }
+    let result = executor
+        .execute("ZREM", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::Integer(121));
+    assert_eq!(
+        encoding(&mut current_db),
+        RespValue::bulk_string("skiplist")
+    );
+
+    // A long member also exceeds zset-max-listpack-value (64)
+    let args = vec![
+        Bytes::from("otherzset"),
+        Bytes::from("1"),
+        Bytes::from("x".repeat(65)),
+    ];
+    executor
+        .execute("ZADD", &args, &mut current_db, client_id)
+        .unwrap();
+    let args = vec![Bytes::from("ENCODING"), Bytes::from("otherzset")];
+    let result = executor
+        .execute("OBJECT", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string("skiplist"));
+
+    // Missing keys return nil
+    let args = vec![Bytes::from("ENCODING"), Bytes::from("nokey")];
+    let result = executor
+        .execute("OBJECT", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::null_bulk_string());
+}
+
+#[test]
+fn test_list_large_element_encoding() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let encoding = |current_db: &mut usize| {
+        let args = vec![Bytes::from("ENCODING"), Bytes::from("mylist")];
+        executor
+            .execute("OBJECT", &args, current_db, client_id)
+            .unwrap()
+    };
+
+    let args = vec![Bytes::from("mylist"), Bytes::from("a"), Bytes::from("b")];
+    executor
+        .execute("RPUSH", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(
+        encoding(&mut current_db),
+        RespValue::bulk_string("listpack")
+    );
+
+    // One element larger than the 8kb node limit flips a tiny list
+    let args = vec![Bytes::from("mylist"), Bytes::from("x".repeat(10_000))];
+    executor
+        .execute("RPUSH", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(
+        encoding(&mut current_db),
+        RespValue::bulk_string("quicklist")
+    );
+}
+
+#[test]
+fn test_encoding_threshold_config() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let hset = |current_db: &mut usize, key: &str, fields: std::ops::Range<usize>| {
+        let mut args = vec![Bytes::from(key.to_string())];
+        for i in fields {
+            args.push(Bytes::from(format!("field{}", i)));
+            args.push(Bytes::from("value"));
+        }
+        executor
+            .execute("HSET", &args, current_db, client_id)
+            .unwrap();
+    };
+    let encoding = |current_db: &mut usize, key: &str| {
+        let args = vec![Bytes::from("ENCODING"), Bytes::from(key.to_string())];
+        executor
+            .execute("OBJECT", &args, current_db, client_id)
+            .unwrap()
+    };
+    let config = |current_db: &mut usize, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute("CONFIG", &args, current_db, client_id)
+    };
+
+    hset(&mut current_db, "old", 0..8);
+    assert_eq!(
+        encoding(&mut current_db, "old"),
+        RespValue::bulk_string("listpack")
+    );
+
+    assert_eq!(
+        config(&mut current_db, &["SET", "hash-max-listpack-entries", "5"]).unwrap(),
+        RespValue::ok()
+    );
+    assert_eq!(
+        config(&mut current_db, &["GET", "hash-max-listpack-entries"]).unwrap(),
+        RespValue::map(vec![(
+            RespValue::bulk_string("hash-max-listpack-entries"),
+            RespValue::bulk_string("5"),
+        )])
+    );
+    let err = config(
+        &mut current_db,
+        &["SET", "hash-max-listpack-entries", "many"],
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("couldn't be parsed into an integer"));
+
+    // The existing hash keeps its recorded encoding
+    assert_eq!(
+        encoding(&mut current_db, "old"),
+        RespValue::bulk_string("listpack")
+    );
+
+    // while new growth is held to the lowered limit
+    hset(&mut current_db, "new", 0..5);
+    assert_eq!(
+        encoding(&mut current_db, "new"),
+        RespValue::bulk_string("listpack")
+    );
+    hset(&mut current_db, "new", 5..6);
+    assert_eq!(
+        encoding(&mut current_db, "new"),
+        RespValue::bulk_string("hashtable")
+    );
+}
+
+#[test]
+fn test_set_encoding_transition() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let encoding = |current_db: &mut usize, key: &str| {
+        let args = vec![Bytes::from("ENCODING"), Bytes::from(key.to_string())];
+        executor
+            .execute("OBJECT", &args, current_db, client_id)
+            .unwrap()
+    };
+
+    // Integers up to set-max-intset-entries (512) stay intset
+    let mut args = vec![Bytes::from("ints")];
+    args.extend((0..512).map(|i| Bytes::from(i.to_string())));
+    executor
+        .execute("SADD", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(
+        encoding(&mut current_db, "ints"),
+        RespValue::bulk_string("intset")
+    );
+
+    // One more is past set-max-listpack-entries (128) too, so it skips listpack
+    let args = vec![Bytes::from("ints"), Bytes::from("512")];
+    executor
+        .execute("SADD", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(
+        encoding(&mut current_db, "ints"),
+        RespValue::bulk_string("hashtable")
+    );
+
+    // Shrinking back keeps hashtable
+    let mut args = vec![Bytes::from("ints")];
+    args.extend((1..=512).map(|i| Bytes::from(i.to_string())));
+    executor
+        .execute("SREM", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(
+        encoding(&mut current_db, "ints"),
+        RespValue::bulk_string("hashtable")
+    );
+
+    // A small set gaining a non-integer member moves to listpack for good
+    let args = vec![Bytes::from("small"), Bytes::from("1"), Bytes::from("2")];
+    executor
+        .execute("SADD", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(
+        encoding(&mut current_db, "small"),
+        RespValue::bulk_string("intset")
+    );
+    let args = vec![Bytes::from("small"), Bytes::from("a")];
+    executor
+        .execute("SADD", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(
+        encoding(&mut current_db, "small"),
+        RespValue::bulk_string("listpack")
+    );
+    executor
+        .execute("SREM", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(
+        encoding(&mut current_db, "small"),
+        RespValue::bulk_string("listpack")
+    );
+
+    // Overwriting a set replaces it with a new one, which starts out compact
+    let args = vec![Bytes::from("ints"), Bytes::from("small")];
+    executor
+        .execute("SUNIONSTORE", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(
+        encoding(&mut current_db, "ints"),
+        RespValue::bulk_string("intset")
+    );
+}
+
+#[test]
+fn test_memory_usage() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let usage = |current_db: &mut usize, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        match executor
+            .execute("MEMORY", &args, current_db, client_id)
+            .unwrap()
+        {
+            RespValue::Integer(bytes) => bytes,
+            other => panic!("Expected integer from MEMORY USAGE, got {:?}", other),
+        }
+    };
+
+    // Lists grow with their element count
+    for (key, len) in [("small", 10), ("large", 1000)] {
+        let mut args = vec![Bytes::from(key)];
+        args.extend((0..len).map(|i| Bytes::from(format!("element:{}", i))));
+        executor
+            .execute("RPUSH", &args, &mut current_db, client_id)
+            .unwrap();
+    }
+    assert!(
+        usage(&mut current_db, &["USAGE", "large"]) > usage(&mut current_db, &["USAGE", "small"])
+    );
+
+    // The default sample only sees the short head of this list, while
+    // SAMPLES 0 measures every element
+    let mut args = vec![Bytes::from("skewed")];
+    args.extend((0..5).map(|_| Bytes::from("a")));
+    args.extend((0..100).map(|_| Bytes::from("x".repeat(100))));
+    executor
+        .execute("RPUSH", &args, &mut current_db, client_id)
+        .unwrap();
+    let sampled = usage(&mut current_db, &["USAGE", "skewed"]);
+    let exact = usage(&mut current_db, &["USAGE", "skewed", "SAMPLES", "0"]);
+    assert!(exact > sampled);
+    assert_eq!(
+        exact,
+        usage(&mut current_db, &["USAGE", "skewed", "SAMPLES", "105"])
+    );
+
+    // Integer-encoded strings cost less than short and long strings
+    for (key, value) in [
+        ("int", "12345".to_string()),
+        ("short", "hello".to_string()),
+        ("long", "x".repeat(100)),
+    ] {
+        executor
+            .execute(
+                "SET",
+                &[Bytes::from(key), Bytes::from(value)],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+    }
+    let int = usage(&mut current_db, &["USAGE", "int"]);
+    let short = usage(&mut current_db, &["USAGE", "short"]);
+    let long = usage(&mut current_db, &["USAGE", "long"]);
+    assert!(int < short && short < long);
+
+    // Missing keys have no usage
+    let args = vec![Bytes::from("USAGE"), Bytes::from("nokey")];
+    let result = executor
+        .execute("MEMORY", &args, &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::null_bulk_string());
+}
+
 #[test]
 fn test_set_operations() {
     let storage = StorageEngine::new_memory(16);
@@ -537,3 +1277,351 @@ fn test_set_operations() {
         panic!("Expected array result");
     }
 }
+
+#[test]
+fn test_empty_containers_are_deleted() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut run = |command: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor
+            .execute(command, &args, &mut current_db, client_id)
+            .unwrap()
+    };
+
+    run("RPUSH", &["list", "only"]);
+    run("SADD", &["set", "only"]);
+    run("HSET", &["hash", "only", "v"]);
+    run("ZADD", &["zset", "1", "only"]);
+    run("SET", &["dest", "v"]);
+
+    // Removing the last element deletes the key
+    assert_eq!(run("LPOP", &["list"]), RespValue::bulk_string("only"));
+    assert_eq!(run("SREM", &["set", "only"]), RespValue::Integer(1));
+    assert_eq!(run("HDEL", &["hash", "only"]), RespValue::Integer(1));
+    assert_eq!(run("ZREM", &["zset", "only"]), RespValue::Integer(1));
+
+    // An empty *STORE result removes the destination
+    assert_eq!(
+        run("SINTERSTORE", &["dest", "missing", "set"]),
+        RespValue::Integer(0)
+    );
+
+    for key in ["list", "set", "hash", "zset", "dest"] {
+        assert_eq!(run("EXISTS", &[key]), RespValue::Integer(0), "{}", key);
+        assert_eq!(run("TYPE", &[key]), RespValue::simple_string("none"));
+    }
+    assert_eq!(run("KEYS", &["*"]), RespValue::Array(Some(vec![])));
+}
+
+#[test]
+fn test_cardinality_commands_by_type() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut run = |command: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(command, &args, &mut current_db, client_id)
+    };
+
+    run("SET", &["string", "v"]).unwrap();
+    run("RPUSH", &["list", "a", "b"]).unwrap();
+    run("SADD", &["set", "a", "b"]).unwrap();
+    run("HSET", &["hash", "a", "1", "b", "2"]).unwrap();
+    run("ZADD", &["zset", "1", "a", "2", "b"]).unwrap();
+
+    // A missing key counts as empty, any other type is an error
+    let keys = ["string", "list", "set", "hash", "zset"];
+    for (command, own_key) in [
+        ("LLEN", "list"),
+        ("SCARD", "set"),
+        ("HLEN", "hash"),
+        ("ZCARD", "zset"),
+    ] {
+        assert_eq!(
+            run(command, &["missing"]).unwrap(),
+            RespValue::Integer(0),
+            "{}",
+            command
+        );
+        for key in keys {
+            let result = run(command, &[key]);
+            if key == own_key {
+                assert_eq!(
+                    result.unwrap(),
+                    RespValue::Integer(2),
+                    "{} {}",
+                    command,
+                    key
+                );
+            } else {
+                assert!(
+                    matches!(result, Err(AikvError::WrongType(_))),
+                    "{} {}: {:?}",
+                    command,
+                    key,
+                    result
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_seeded_srandmember() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let args = vec![
+        Bytes::from("myset"),
+        Bytes::from("apple"),
+        Bytes::from("banana"),
+        Bytes::from("cherry"),
+        Bytes::from("date"),
+        Bytes::from("elderberry"),
+    ];
+    executor
+        .execute("SADD", &args, &mut current_db, client_id)
+        .unwrap();
+
+    let mut draw = || {
+        let args = vec![Bytes::from("SET-RNG-SEED"), Bytes::from("42")];
+        let result = executor
+            .execute("DEBUG", &args, &mut current_db, client_id)
+            .unwrap();
+        assert_eq!(result, RespValue::ok());
+
+        let args = vec![Bytes::from("myset")];
+        let single = executor
+            .execute("SRANDMEMBER", &args, &mut current_db, client_id)
+            .unwrap();
+        let args = vec![Bytes::from("myset"), Bytes::from("3")];
+        let several = executor
+            .execute("SRANDMEMBER", &args, &mut current_db, client_id)
+            .unwrap();
+        (single, several)
+    };
+
+    // A known seed picks a known member, and reseeding replays the picks
+    let (single, several) = draw();
+    assert_eq!(single, RespValue::bulk_string("banana"));
+    assert_eq!(draw(), (single, several));
+}
+
+#[test]
+fn test_smove_intercard_and_mpop() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut run = |command: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor
+            .execute(command, &args, &mut current_db, client_id)
+            .unwrap()
+    };
+    let bulk = |s: &str| RespValue::bulk_string(Bytes::from(s.to_string()));
+
+    run("SADD", &["s1", "a", "b", "c"]);
+    run("SADD", &["s2", "b", "c", "d"]);
+
+    // SMOVE
+    assert_eq!(run("SMOVE", &["s1", "s2", "a"]), RespValue::Integer(1));
+    assert_eq!(run("SMOVE", &["s1", "s2", "a"]), RespValue::Integer(0));
+    assert_eq!(run("SISMEMBER", &["s2", "a"]), RespValue::Integer(1));
+    assert_eq!(run("SMOVE", &["s2", "s2", "a"]), RespValue::Integer(1));
+    assert_eq!(run("SMOVE", &["s1", "new", "b"]), RespValue::Integer(1));
+    assert_eq!(run("SMOVE", &["s1", "new", "c"]), RespValue::Integer(1));
+    assert_eq!(run("EXISTS", &["s1"]), RespValue::Integer(0));
+    assert_eq!(run("SCARD", &["new"]), RespValue::Integer(2));
+
+    // SINTERCARD, with and without LIMIT
+    assert_eq!(
+        run("SINTERCARD", &["2", "s2", "new"]),
+        RespValue::Integer(2)
+    );
+    assert_eq!(
+        run("SINTERCARD", &["2", "s2", "new", "LIMIT", "1"]),
+        RespValue::Integer(1)
+    );
+    assert_eq!(
+        run("SINTERCARD", &["2", "s2", "new", "LIMIT", "0"]),
+        RespValue::Integer(2)
+    );
+    assert_eq!(
+        run("SINTERCARD", &["2", "s2", "missing"]),
+        RespValue::Integer(0)
+    );
+
+    // ZINTERCARD
+    run("ZADD", &["z1", "1", "a", "2", "b", "3", "c"]);
+    run("ZADD", &["z2", "1", "b", "2", "c", "3", "d"]);
+    assert_eq!(run("ZINTERCARD", &["2", "z1", "z2"]), RespValue::Integer(2));
+    assert_eq!(
+        run("ZINTERCARD", &["2", "z1", "z2", "LIMIT", "1"]),
+        RespValue::Integer(1)
+    );
+
+    // LMPOP skips empty keys and pops from the first non-empty list
+    run("RPUSH", &["list", "a", "b", "c"]);
+    assert_eq!(
+        run("LMPOP", &["2", "missing", "list", "LEFT"]),
+        RespValue::array(vec![bulk("list"), RespValue::array(vec![bulk("a")])])
+    );
+    assert_eq!(
+        run("LMPOP", &["1", "list", "RIGHT", "COUNT", "5"]),
+        RespValue::array(vec![
+            bulk("list"),
+            RespValue::array(vec![bulk("c"), bulk("b")])
+        ])
+    );
+    assert_eq!(run("EXISTS", &["list"]), RespValue::Integer(0));
+    assert_eq!(
+        run("LMPOP", &["1", "list", "LEFT"]),
+        RespValue::null_array()
+    );
+
+    // ZMPOP pops the lowest or highest scores with their scores
+    assert_eq!(
+        run("ZMPOP", &["1", "z1", "MIN", "COUNT", "2"]),
+        RespValue::array(vec![
+            bulk("z1"),
+            RespValue::array(vec![
+                RespValue::array(vec![bulk("a"), RespValue::double(1.0)]),
+                RespValue::array(vec![bulk("b"), RespValue::double(2.0)]),
+            ])
+        ])
+    );
+    assert_eq!(
+        run("ZMPOP", &["2", "z1", "z2", "MAX"]),
+        RespValue::array(vec![
+            bulk("z1"),
+            RespValue::array(vec![RespValue::array(vec![
+                bulk("c"),
+                RespValue::double(3.0)
+            ])])
+        ])
+    );
+    assert_eq!(run("ZMPOP", &["1", "z1", "MAX"]), RespValue::null_array());
+
+    // Wrong types are still reported
+    run("SET", &["str", "v"]);
+    for (command, args) in [
+        ("SMOVE", vec!["str", "new", "b"]),
+        ("SMOVE", vec!["new", "str", "b"]),
+        ("SINTERCARD", vec!["2", "new", "str"]),
+        ("LMPOP", vec!["1", "str", "LEFT"]),
+        ("ZMPOP", vec!["1", "str", "MIN"]),
+    ] {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        assert!(matches!(
+            executor.execute(command, &args, &mut current_db, client_id),
+            Err(AikvError::WrongType(_))
+        ));
+    }
+}
+
+#[test]
+fn test_numkeys_argument_validation() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut error = |command: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor
+            .execute(command, &args, &mut current_db, client_id)
+            .unwrap_err()
+            .to_string()
+    };
+
+    let cases: &[(&str, &[&str], &str)] = &[
+        // numkeys 0 or not a number
+        (
+            "SINTERCARD",
+            &["0", "a"],
+            "numkeys should be greater than 0",
+        ),
+        (
+            "SINTERCARD",
+            &["x", "a"],
+            "numkeys should be greater than 0",
+        ),
+        (
+            "LMPOP",
+            &["0", "a", "LEFT"],
+            "numkeys should be greater than 0",
+        ),
+        (
+            "ZMPOP",
+            &["-1", "a", "MIN"],
+            "numkeys should be greater than 0",
+        ),
+        (
+            "ZINTERCARD",
+            &["0", "a"],
+            "at least 1 input key is needed for 'zintercard' command",
+        ),
+        // numkeys that doesn't match the keys given
+        (
+            "SINTERCARD",
+            &["3", "a", "b"],
+            "Number of keys can't be greater than number of args",
+        ),
+        ("SINTERCARD", &["1", "a", "b"], "syntax error"),
+        ("ZINTERCARD", &["3", "a", "b"], "syntax error"),
+        ("LMPOP", &["2", "a", "LEFT"], "syntax error"),
+        ("LMPOP", &["1", "a", "b", "LEFT"], "syntax error"),
+        ("ZMPOP", &["3", "a", "b", "MIN"], "syntax error"),
+        // Bad LIMIT and COUNT values and options
+        (
+            "SINTERCARD",
+            &["1", "a", "LIMIT", "-1"],
+            "LIMIT can't be negative",
+        ),
+        (
+            "ZINTERCARD",
+            &["1", "a", "LIMIT", "-1"],
+            "LIMIT can't be negative",
+        ),
+        ("SINTERCARD", &["1", "a", "LIMIT"], "syntax error"),
+        (
+            "LMPOP",
+            &["1", "a", "LEFT", "COUNT", "0"],
+            "count should be greater than 0",
+        ),
+        (
+            "ZMPOP",
+            &["1", "a", "MAX", "COUNT", "-2"],
+            "count should be greater than 0",
+        ),
+        (
+            "LMPOP",
+            &["1", "a", "LEFT", "COUNT", "1", "COUNT", "2"],
+            "syntax error",
+        ),
+        ("ZMPOP", &["1", "a", "UP"], "syntax error"),
+    ];
+    for (command, args, expected) in cases {
+        let message = error(command, args);
+        assert!(
+            message.contains(expected),
+            "{} {:?}: {}",
+            command,
+            args,
+            message
+        );
+    }
+
+    // SMOVE takes exactly three arguments
+    assert!(error("SMOVE", &["a", "b"]).contains("Wrong number of arguments"));
+}
@@ -0,0 +1,77 @@
+//! Tests for the error replies clients receive
+//!
+//! These tests start an AiKv server on a free local port and compare the raw
+//! RESP text of error replies, as a client library sees them.
+
+use aikv::{Server, StorageEngine};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, Duration};
+
+/// Start a server on a free port and connect to it
+async fn start_server() -> TcpStream {
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+
+    let server = Server::new(addr.clone(), StorageEngine::new_memory(16));
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+
+    for _ in 0..50 {
+        if let Ok(stream) = TcpStream::connect(&addr).await {
+            return stream;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Server did not start on {}", addr);
+}
+
+/// Send a command and return the raw text of its one-line reply
+async fn send_raw(stream: &mut TcpStream, args: &[&str]) -> String {
+    let mut request = format!("*{}\r\n", args.len());
+    for arg in args {
+        request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut reply = Vec::new();
+    while !reply.ends_with(b"\r\n") {
+        let mut byte = [0u8; 1];
+        let n = stream.read(&mut byte).await.unwrap();
+        assert!(n > 0, "Connection closed before a reply was read");
+        reply.push(byte[0]);
+    }
+    String::from_utf8(reply).unwrap()
+}
+
+#[tokio::test]
+async fn test_err_worded_arguments_are_sent_unchanged() {
+    let mut stream = start_server().await;
+
+    assert_eq!(
+        send_raw(&mut stream, &["SELECT", "abc"]).await,
+        "-ERR invalid DB index\r\n"
+    );
+    assert_eq!(
+        send_raw(&mut stream, &["SELECT", "99"]).await,
+        "-ERR DB index is out of range\r\n"
+    );
+}
+
+#[tokio::test]
+async fn test_wait_argument_errors() {
+    let mut stream = start_server().await;
+
+    assert_eq!(
+        send_raw(&mut stream, &["WAIT", "1", "-1"]).await,
+        "-ERR timeout is negative\r\n"
+    );
+    assert_eq!(
+        send_raw(&mut stream, &["WAIT", "one", "0"]).await,
+        "-ERR value is not an integer or out of range\r\n"
+    );
+    assert_eq!(send_raw(&mut stream, &["WAIT", "0", "0"]).await, ":0\r\n");
+}
@@ -0,0 +1,122 @@
+//! Tests for moving keys to another server
+//!
+//! CLUSTER MIGRATE-SLOT streams a slot's keys to their new owner, so these
+//! tests start an AiKv server on a free local port as the target and move keys
+//! into it from a separate storage.
+
+use aikv::command::migrate::migrate_keys;
+use aikv::command::CommandExecutor;
+use aikv::protocol::{RespParser, RespValue};
+use aikv::{Server, StorageEngine};
+use bytes::Bytes;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, Duration};
+
+/// Start a server on a free port and return its address
+async fn start_server() -> String {
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+
+    let server = Server::new(addr.clone(), StorageEngine::new_memory(16));
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+
+    for _ in 0..50 {
+        if TcpStream::connect(&addr).await.is_ok() {
+            return addr;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Server did not start on {}", addr);
+}
+
+/// Send a command and read its reply
+async fn send(stream: &mut TcpStream, parser: &mut RespParser, args: &[&str]) -> RespValue {
+    let mut request = format!("*{}\r\n", args.len());
+    for arg in args {
+        request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    loop {
+        if let Some(reply) = parser.parse().unwrap() {
+            return reply;
+        }
+        let n = stream.read_buf(parser.buffer_mut()).await.unwrap();
+        assert!(n > 0, "Connection closed before a reply was read");
+    }
+}
+
+#[tokio::test]
+async fn test_migrate_keys_moves_data_with_ttl() {
+    let target = start_server().await;
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage.clone());
+    let mut current_db = 0;
+    let mut run = |command: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor
+            .execute(command, &args, &mut current_db, 1)
+            .unwrap()
+    };
+
+    run("SET", &["temp", "value", "PX", "100000"]);
+    run("RPUSH", &["list", "a", "b"]);
+    let keys = ["temp", "list", "missing"].map(String::from);
+    let moved = migrate_keys(&storage, 0, &keys, &target, Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(moved, 2);
+
+    // The keys are gone from the source
+    assert_eq!(run("EXISTS", &["temp", "list"]), RespValue::integer(0));
+
+    // and on the target with their values and TTL
+    let mut stream = TcpStream::connect(&target).await.unwrap();
+    let mut parser = RespParser::new(4096);
+    assert_eq!(
+        send(&mut stream, &mut parser, &["GET", "temp"]).await,
+        RespValue::bulk_string("value")
+    );
+    match send(&mut stream, &mut parser, &["PTTL", "temp"]).await {
+        RespValue::Integer(ttl) => assert!(ttl > 90_000 && ttl <= 100_000, "TTL {}", ttl),
+        other => panic!("Expected a TTL, got {:?}", other),
+    }
+    assert_eq!(
+        send(&mut stream, &mut parser, &["LRANGE", "list", "0", "-1"]).await,
+        RespValue::array(vec![
+            RespValue::bulk_string("a"),
+            RespValue::bulk_string("b")
+        ])
+    );
+    assert_eq!(
+        send(&mut stream, &mut parser, &["PTTL", "list"]).await,
+        RespValue::integer(-1)
+    );
+}
+
+#[tokio::test]
+async fn test_migrate_keys_keeps_keys_when_target_is_down() {
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let storage = StorageEngine::new_memory(16);
+    storage
+        .set_in_db(0, "key".to_string(), Bytes::from("value"))
+        .unwrap();
+
+    let keys = ["key".to_string()];
+    let err = migrate_keys(&storage, 0, &keys, &addr, Duration::from_secs(1))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("connecting to target instance"));
+    assert_eq!(
+        storage.get_from_db(0, "key").unwrap(),
+        Some(Bytes::from("value"))
+    );
+}
@@ -0,0 +1,574 @@
+//! Tests for Pub/Sub over real client connections
+//!
+//! Subscriptions live on the connection, so these tests start an AiKv server on
+//! a free local port and drive SUBSCRIBE, RESET and disconnects through it.
+
+use aikv::protocol::{RespParser, RespValue};
+use aikv::{Server, StorageEngine};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, timeout, Duration};
+
+/// Start a server on a free port and return its address
+async fn start_server() -> String {
+    start_configured_server(|_| {}).await
+}
+
+/// Start a server on a free port after configuring it
+async fn start_configured_server(configure: impl FnOnce(&Server)) -> String {
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+
+    let server = Server::new(addr.clone(), StorageEngine::new_memory(16));
+    configure(&server);
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+
+    for _ in 0..50 {
+        if TcpStream::connect(&addr).await.is_ok() {
+            return addr;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Server did not start on {}", addr);
+}
+
+/// Send a command without waiting for a reply
+async fn write_command(stream: &mut TcpStream, args: &[&str]) {
+    let mut request = format!("*{}\r\n", args.len());
+    for arg in args {
+        request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    stream.write_all(request.as_bytes()).await.unwrap();
+}
+
+/// Read the next reply
+async fn read_reply(stream: &mut TcpStream, parser: &mut RespParser) -> RespValue {
+    loop {
+        if let Some(reply) = parser.parse().unwrap() {
+            return reply;
+        }
+        let n = stream.read_buf(parser.buffer_mut()).await.unwrap();
+        assert!(n > 0, "Connection closed before a reply was read");
+    }
+}
+
+/// Send a command and read its reply
+async fn send(stream: &mut TcpStream, parser: &mut RespParser, args: &[&str]) -> RespValue {
+    write_command(stream, args).await;
+    read_reply(stream, parser).await
+}
+
+fn bulk(s: &str) -> RespValue {
+    RespValue::bulk_string(s.to_string())
+}
+
+#[tokio::test]
+async fn test_subscribe_and_publish() {
+    let addr = start_server().await;
+    let mut subscriber = TcpStream::connect(&addr).await.unwrap();
+    let mut sub_parser = RespParser::new(4096);
+    let mut client = TcpStream::connect(&addr).await.unwrap();
+    let mut parser = RespParser::new(4096);
+
+    write_command(&mut subscriber, &["SUBSCRIBE", "news", "sports"]).await;
+    assert_eq!(
+        read_reply(&mut subscriber, &mut sub_parser).await,
+        RespValue::array(vec![bulk("subscribe"), bulk("news"), RespValue::integer(1)])
+    );
+    assert_eq!(
+        read_reply(&mut subscriber, &mut sub_parser).await,
+        RespValue::array(vec![
+            bulk("subscribe"),
+            bulk("sports"),
+            RespValue::integer(2)
+        ])
+    );
+
+    let receivers = send(&mut client, &mut parser, &["PUBLISH", "news", "hello"]).await;
+    assert_eq!(receivers, RespValue::integer(1));
+    assert_eq!(
+        read_reply(&mut subscriber, &mut sub_parser).await,
+        RespValue::array(vec![bulk("message"), bulk("news"), bulk("hello")])
+    );
+
+    // Only subscribe-family commands, PING, QUIT and RESET work while subscribed
+    let reply = send(&mut subscriber, &mut sub_parser, &["GET", "key"]).await;
+    assert!(matches!(reply, RespValue::Error(ref e) if e.contains("only (P|S)SUBSCRIBE")));
+    let reply = send(&mut subscriber, &mut sub_parser, &["PING"]).await;
+    assert_eq!(reply, RespValue::array(vec![bulk("pong"), bulk("")]));
+}
+
+#[tokio::test]
+async fn test_reset_removes_subscriptions() {
+    let addr = start_server().await;
+    let mut subscriber = TcpStream::connect(&addr).await.unwrap();
+    let mut sub_parser = RespParser::new(4096);
+    let mut client = TcpStream::connect(&addr).await.unwrap();
+    let mut parser = RespParser::new(4096);
+
+    send(
+        &mut subscriber,
+        &mut sub_parser,
+        &["SUBSCRIBE", "reset-channel"],
+    )
+    .await;
+    let channels = send(&mut client, &mut parser, &["PUBSUB", "CHANNELS"]).await;
+    assert_eq!(channels, RespValue::array(vec![bulk("reset-channel")]));
+
+    let reply = send(&mut subscriber, &mut sub_parser, &["RESET"]).await;
+    assert_eq!(reply, RespValue::simple_string("RESET"));
+
+    let channels = send(&mut client, &mut parser, &["PUBSUB", "CHANNELS"]).await;
+    assert_eq!(channels, RespValue::array(vec![]));
+
+    // The connection is back in normal mode
+    let reply = send(&mut subscriber, &mut sub_parser, &["PING"]).await;
+    assert_eq!(reply, RespValue::simple_string("PONG"));
+}
+
+#[tokio::test]
+async fn test_disconnect_removes_subscriptions() {
+    let addr = start_server().await;
+    let mut subscriber = TcpStream::connect(&addr).await.unwrap();
+    let mut sub_parser = RespParser::new(4096);
+    let mut client = TcpStream::connect(&addr).await.unwrap();
+    let mut parser = RespParser::new(4096);
+
+    send(&mut subscriber, &mut sub_parser, &["SUBSCRIBE", "gone"]).await;
+    let numsub = send(&mut client, &mut parser, &["PUBSUB", "NUMSUB", "gone"]).await;
+    assert_eq!(
+        numsub,
+        RespValue::array(vec![bulk("gone"), RespValue::integer(1)])
+    );
+
+    drop(subscriber);
+
+    let mut numsub = RespValue::Null;
+    for _ in 0..50 {
+        numsub = send(&mut client, &mut parser, &["PUBSUB", "NUMSUB", "gone"]).await;
+        if numsub == RespValue::array(vec![bulk("gone"), RespValue::integer(0)]) {
+            break;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(
+        numsub,
+        RespValue::array(vec![bulk("gone"), RespValue::integer(0)])
+    );
+}
+
+#[tokio::test]
+async fn test_subscribe_rejected_in_transaction() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(&addr).await.unwrap();
+    let mut parser = RespParser::new(4096);
+    let mut client = TcpStream::connect(&addr).await.unwrap();
+    let mut client_parser = RespParser::new(4096);
+
+    assert_eq!(
+        send(&mut stream, &mut parser, &["MULTI"]).await,
+        RespValue::ok()
+    );
+    for command in ["SUBSCRIBE", "PSUBSCRIBE", "UNSUBSCRIBE", "PUNSUBSCRIBE"] {
+        let reply = send(&mut stream, &mut parser, &[command, "tx-channel"]).await;
+        assert!(
+            matches!(reply, RespValue::Error(ref e) if e.contains("is not allowed in transactions")),
+            "{} was not rejected: {:?}",
+            command,
+            reply
+        );
+    }
+
+    // Nothing was subscribed
+    let channels = send(&mut client, &mut client_parser, &["PUBSUB", "CHANNELS"]).await;
+    assert_eq!(channels, RespValue::array(vec![]));
+
+    // The transaction is still open for other commands
+    let reply = send(&mut stream, &mut parser, &["SET", "tx-key", "v"]).await;
+    assert_eq!(reply, RespValue::simple_string("QUEUED"));
+    let reply = send(&mut stream, &mut parser, &["EXEC"]).await;
+    assert_eq!(reply, RespValue::array(vec![RespValue::ok()]));
+    let reply = send(&mut stream, &mut parser, &["GET", "tx-key"]).await;
+    assert_eq!(reply, bulk("v"));
+}
+
+#[tokio::test]
+async fn test_slow_subscriber_is_disconnected() {
+    let addr = start_configured_server(|server| {
+        server.pubsub_broker().set_buffer_limit(256 * 1024);
+    })
+    .await;
+    let mut subscriber = TcpStream::connect(&addr).await.unwrap();
+    let mut sub_parser = RespParser::new(4096);
+    let mut client = TcpStream::connect(&addr).await.unwrap();
+    let mut parser = RespParser::new(4096);
+
+    send(&mut subscriber, &mut sub_parser, &["SUBSCRIBE", "firehose"]).await;
+
+    // The subscriber stops reading. Publishing never waits on it; once the
+    // socket buffers and its queue fill up it stops receiving messages.
+    let payload = "x".repeat(64 * 1024);
+    let mut dropped = false;
+    for _ in 0..2000 {
+        let receivers = timeout(
+            Duration::from_secs(5),
+            send(&mut client, &mut parser, &["PUBLISH", "firehose", &payload]),
+        )
+        .await
+        .expect("PUBLISH blocked on a slow subscriber");
+        if receivers == RespValue::integer(0) {
+            dropped = true;
+            break;
+        }
+    }
+    assert!(dropped, "Slow subscriber was never cut off");
+
+    // Its connection is closed: reading drains what was sent, then hits EOF
+    let mut buf = vec![0u8; 1024 * 1024];
+    let closed = timeout(Duration::from_secs(10), async {
+        loop {
+            match subscriber.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+    })
+    .await;
+    assert!(closed.is_ok(), "Slow subscriber connection was not closed");
+
+    let mut numsub = RespValue::Null;
+    for _ in 0..50 {
+        numsub = send(&mut client, &mut parser, &["PUBSUB", "NUMSUB", "firehose"]).await;
+        if numsub == RespValue::array(vec![bulk("firehose"), RespValue::integer(0)]) {
+            break;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(
+        numsub,
+        RespValue::array(vec![bulk("firehose"), RespValue::integer(0)])
+    );
+}
+
+#[tokio::test]
+async fn test_keyspace_notification_classes() {
+    let addr = start_server().await;
+    let mut subscriber = TcpStream::connect(&addr).await.unwrap();
+    let mut sub_parser = RespParser::new(4096);
+    let mut client = TcpStream::connect(&addr).await.unwrap();
+    let mut parser = RespParser::new(4096);
+
+    send(
+        &mut subscriber,
+        &mut sub_parser,
+        &["PSUBSCRIBE", "__key*__:*"],
+    )
+    .await;
+    let pmessage = |channel: &str, payload: &str| {
+        RespValue::array(vec![
+            bulk("pmessage"),
+            bulk("__key*__:*"),
+            bulk(channel),
+            bulk(payload),
+        ])
+    };
+
+    // Only list events, on both channels
+    let reply = send(
+        &mut client,
+        &mut parser,
+        &["CONFIG", "SET", "notify-keyspace-events", "KEl"],
+    )
+    .await;
+    assert_eq!(reply, RespValue::ok());
+    let reply = send(
+        &mut client,
+        &mut parser,
+        &["CONFIG", "GET", "notify-keyspace-events"],
+    )
+    .await;
+    assert_eq!(
+        reply,
+        RespValue::array(vec![bulk("notify-keyspace-events"), bulk("lKE")])
+    );
+
+    // SET fires nothing, so LPUSH's events arrive first
+    send(&mut client, &mut parser, &["SET", "string", "value"]).await;
+    send(&mut client, &mut parser, &["LPUSH", "list", "a"]).await;
+    assert_eq!(
+        read_reply(&mut subscriber, &mut sub_parser).await,
+        pmessage("__keyspace@0__:list", "lpush")
+    );
+    assert_eq!(
+        read_reply(&mut subscriber, &mut sub_parser).await,
+        pmessage("__keyevent@0__:lpush", "list")
+    );
+
+    // Popping the last element removes the list, a generic event
+    let reply = send(
+        &mut client,
+        &mut parser,
+        &["CONFIG", "SET", "notify-keyspace-events", "Elg"],
+    )
+    .await;
+    assert_eq!(reply, RespValue::ok());
+    send(&mut client, &mut parser, &["RPOP", "list"]).await;
+    assert_eq!(
+        read_reply(&mut subscriber, &mut sub_parser).await,
+        pmessage("__keyevent@0__:rpop", "list")
+    );
+    assert_eq!(
+        read_reply(&mut subscriber, &mut sub_parser).await,
+        pmessage("__keyevent@0__:del", "list")
+    );
+
+    // Expired keys fire once they are reclaimed
+    let reply = send(
+        &mut client,
+        &mut parser,
+        &["CONFIG", "SET", "notify-keyspace-events", "Ex"],
+    )
+    .await;
+    assert_eq!(reply, RespValue::ok());
+    send(&mut client, &mut parser, &["LPUSH", "list", "a"]).await;
+    send(&mut client, &mut parser, &["SET", "temp", "v", "PX", "50"]).await;
+    sleep(Duration::from_millis(100)).await;
+    let reply = send(&mut client, &mut parser, &["GET", "temp"]).await;
+    assert_eq!(reply, RespValue::BulkString(None));
+    assert_eq!(
+        read_reply(&mut subscriber, &mut sub_parser).await,
+        pmessage("__keyevent@0__:expired", "temp")
+    );
+
+    let reply = send(
+        &mut client,
+        &mut parser,
+        &["CONFIG", "SET", "notify-keyspace-events", "KEq"],
+    )
+    .await;
+    assert!(matches!(reply, RespValue::Error(ref e) if e.contains("Invalid event class")));
+}
+
+#[tokio::test]
+async fn test_script_writes_fire_keyspace_events() {
+    let addr = start_server().await;
+    let mut subscriber = TcpStream::connect(&addr).await.unwrap();
+    let mut sub_parser = RespParser::new(4096);
+    let mut client = TcpStream::connect(&addr).await.unwrap();
+    let mut parser = RespParser::new(4096);
+
+    send(
+        &mut subscriber,
+        &mut sub_parser,
+        &["PSUBSCRIBE", "__keyspace@0__:*"],
+    )
+    .await;
+    let reply = send(
+        &mut client,
+        &mut parser,
+        &["CONFIG", "SET", "notify-keyspace-events", "KA"],
+    )
+    .await;
+    assert_eq!(reply, RespValue::ok());
+    let pmessage = |key: &str, event: &str| {
+        RespValue::array(vec![
+            bulk("pmessage"),
+            bulk("__keyspace@0__:*"),
+            bulk(&format!("__keyspace@0__:{}", key)),
+            bulk(event),
+        ])
+    };
+
+    // Writes made through redis.call are announced once the script commits
+    let script = "redis.call('set', KEYS[1], 'v') \
+                  redis.call('lpush', KEYS[2], 'a') \
+                  return redis.call('rpop', KEYS[2])";
+    let reply = send(
+        &mut client,
+        &mut parser,
+        &["EVAL", script, "2", "string", "list"],
+    )
+    .await;
+    assert_eq!(reply, bulk("a"));
+    for (key, event) in [
+        ("string", "set"),
+        ("list", "lpush"),
+        ("list", "rpop"),
+        ("list", "del"),
+    ] {
+        assert_eq!(
+            read_reply(&mut subscriber, &mut sub_parser).await,
+            pmessage(key, event)
+        );
+    }
+
+    // A failed script is rolled back, so its writes are never announced
+    let script = "redis.call('set', KEYS[1], 'v') error('boom')";
+    let reply = send(
+        &mut client,
+        &mut parser,
+        &["EVAL", script, "1", "rolled-back"],
+    )
+    .await;
+    assert!(matches!(reply, RespValue::Error(_)));
+    send(&mut client, &mut parser, &["SET", "after", "v"]).await;
+    assert_eq!(
+        read_reply(&mut subscriber, &mut sub_parser).await,
+        pmessage("after", "set")
+    );
+}
+
+#[tokio::test]
+async fn test_pubsub_shard_channel_introspection() {
+    let addr = start_server().await;
+    let mut subscriber = TcpStream::connect(&addr).await.unwrap();
+    let mut sub_parser = RespParser::new(4096);
+    let mut client = TcpStream::connect(&addr).await.unwrap();
+    let mut parser = RespParser::new(4096);
+
+    send(&mut subscriber, &mut sub_parser, &["SSUBSCRIBE", "news"]).await;
+    send(&mut subscriber, &mut sub_parser, &["SUBSCRIBE", "chat"]).await;
+
+    // Shard channels are reported apart from regular channels
+    let reply = send(&mut client, &mut parser, &["PUBSUB", "SHARDCHANNELS"]).await;
+    assert_eq!(reply, RespValue::array(vec![bulk("news")]));
+    let reply = send(&mut client, &mut parser, &["PUBSUB", "SHARDCHANNELS", "c*"]).await;
+    assert_eq!(reply, RespValue::array(vec![]));
+    let reply = send(
+        &mut client,
+        &mut parser,
+        &["PUBSUB", "SHARDNUMSUB", "news", "chat"],
+    )
+    .await;
+    assert_eq!(
+        reply,
+        RespValue::array(vec![
+            bulk("news"),
+            RespValue::Integer(1),
+            bulk("chat"),
+            RespValue::Integer(0),
+        ])
+    );
+    let reply = send(&mut client, &mut parser, &["PUBSUB", "CHANNELS"]).await;
+    assert_eq!(reply, RespValue::array(vec![bulk("chat")]));
+    let reply = send(&mut client, &mut parser, &["PUBSUB", "NUMSUB", "news"]).await;
+    assert_eq!(
+        reply,
+        RespValue::array(vec![bulk("news"), RespValue::Integer(0)])
+    );
+}
+
+#[tokio::test]
+async fn test_expire_in_the_past_fires_expired() {
+    let addr = start_server().await;
+    let mut subscriber = TcpStream::connect(&addr).await.unwrap();
+    let mut sub_parser = RespParser::new(4096);
+    let mut client = TcpStream::connect(&addr).await.unwrap();
+    let mut parser = RespParser::new(4096);
+
+    send(
+        &mut subscriber,
+        &mut sub_parser,
+        &[
+            "SUBSCRIBE",
+            "__keyevent@0__:expire",
+            "__keyevent@0__:expired",
+        ],
+    )
+    .await;
+    read_reply(&mut subscriber, &mut sub_parser).await;
+    let reply = send(
+        &mut client,
+        &mut parser,
+        &["CONFIG", "SET", "notify-keyspace-events", "Egx"],
+    )
+    .await;
+    assert_eq!(reply, RespValue::ok());
+    let message = |channel: &str, payload: &str| {
+        RespValue::array(vec![bulk("message"), bulk(channel), bulk(payload)])
+    };
+
+    // A past deadline removes the key at once, as an expiry rather than a TTL change
+    send(&mut client, &mut parser, &["SET", "past", "v"]).await;
+    let reply = send(&mut client, &mut parser, &["EXPIREAT", "past", "1"]).await;
+    assert_eq!(reply, RespValue::Integer(1));
+    assert_eq!(
+        read_reply(&mut subscriber, &mut sub_parser).await,
+        message("__keyevent@0__:expired", "past")
+    );
+    let reply = send(&mut client, &mut parser, &["EXISTS", "past"]).await;
+    assert_eq!(reply, RespValue::Integer(0));
+
+    // The next event is the TTL set on a live key
+    send(&mut client, &mut parser, &["SET", "live", "v"]).await;
+    send(&mut client, &mut parser, &["EXPIRE", "live", "100"]).await;
+    assert_eq!(
+        read_reply(&mut subscriber, &mut sub_parser).await,
+        message("__keyevent@0__:expire", "live")
+    );
+}
+
+#[tokio::test]
+async fn test_pattern_subscriptions_share_the_count() {
+    let addr = start_server().await;
+    let mut subscriber = TcpStream::connect(&addr).await.unwrap();
+    let mut sub_parser = RespParser::new(4096);
+    let mut client = TcpStream::connect(&addr).await.unwrap();
+    let mut parser = RespParser::new(4096);
+
+    // Channels and patterns add up to one count
+    let reply = send(&mut subscriber, &mut sub_parser, &["SUBSCRIBE", "news"]).await;
+    assert_eq!(
+        reply,
+        RespValue::array(vec![bulk("subscribe"), bulk("news"), RespValue::integer(1)])
+    );
+    let reply = send(&mut subscriber, &mut sub_parser, &["PSUBSCRIBE", "n*"]).await;
+    assert_eq!(
+        reply,
+        RespValue::array(vec![bulk("psubscribe"), bulk("n*"), RespValue::integer(2)])
+    );
+
+    // A message matching both arrives once per subscription
+    let receivers = send(&mut client, &mut parser, &["PUBLISH", "news", "hi"]).await;
+    assert_eq!(receivers, RespValue::integer(2));
+    assert_eq!(
+        read_reply(&mut subscriber, &mut sub_parser).await,
+        RespValue::array(vec![bulk("message"), bulk("news"), bulk("hi")])
+    );
+    assert_eq!(
+        read_reply(&mut subscriber, &mut sub_parser).await,
+        RespValue::array(vec![bulk("pmessage"), bulk("n*"), bulk("news"), bulk("hi")])
+    );
+
+    let reply = send(&mut subscriber, &mut sub_parser, &["PUNSUBSCRIBE", "n*"]).await;
+    assert_eq!(
+        reply,
+        RespValue::array(vec![
+            bulk("punsubscribe"),
+            bulk("n*"),
+            RespValue::integer(1)
+        ])
+    );
+
+    // RESP3 clients get the same frames as pushes
+    send(&mut subscriber, &mut sub_parser, &["HELLO", "3"]).await;
+    let reply = send(&mut subscriber, &mut sub_parser, &["PSUBSCRIBE", "s*"]).await;
+    assert_eq!(
+        reply,
+        RespValue::push(vec![bulk("psubscribe"), bulk("s*"), RespValue::integer(2)])
+    );
+    send(&mut client, &mut parser, &["PUBLISH", "sports", "goal"]).await;
+    assert_eq!(
+        read_reply(&mut subscriber, &mut sub_parser).await,
+        RespValue::push(vec![
+            bulk("pmessage"),
+            bulk("s*"),
+            bulk("sports"),
+            bulk("goal")
+        ])
+    );
+}
@@ -1,3 +1,5 @@
+use aikv::command::script::ScriptCache;
+use aikv::command::transaction::CommandGate;
 use aikv::command::CommandExecutor;
 use aikv::protocol::RespValue;
 use aikv::StorageEngine;
@@ -195,141 +197,1469 @@ fn test_eval_redis_call() {
 }
 
 #[test]
-fn test_evalsha() {
+fn test_eval_redis_call_counters() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
     let mut current_db = 0;
     let client_id = 1;
 
-    // Load a script
-    let script = "return 'cached script result'";
-    let load_result = executor
+    let script = r#"
+        local total = redis.call('INCRBY', KEYS[1], ARGV[1])
+        return {type(total), total}
+    "#;
+    let result = executor
         .execute(
-            "SCRIPT",
-            &[Bytes::from("LOAD"), Bytes::from(script)],
+            "EVAL",
+            &[
+                Bytes::from(script),
+                Bytes::from("1"),
+                Bytes::from("counter"),
+                Bytes::from("5"),
+            ],
             &mut current_db,
             client_id,
         )
         .unwrap();
+    // The reply reaches Lua as a number, not a string
+    assert_eq!(
+        result,
+        RespValue::array(vec![
+            RespValue::bulk_string("number"),
+            RespValue::Integer(5)
+        ])
+    );
 
-    let sha1 = if let RespValue::BulkString(Some(sha)) = load_result {
-        sha
-    } else {
-        panic!("Expected BulkString");
-    };
-
-    // Execute the cached script using EVALSHA
+    let script = r#"
+        redis.call('INCR', KEYS[1])
+        redis.call('DECR', KEYS[1])
+        return redis.call('DECRBY', KEYS[1], ARGV[1])
+    "#;
     let result = executor
         .execute(
-            "EVALSHA",
-            &[sha1, Bytes::from("0")],
+            "EVAL",
+            &[
+                Bytes::from(script),
+                Bytes::from("1"),
+                Bytes::from("counter"),
+                Bytes::from("2"),
+            ],
             &mut current_db,
             client_id,
         )
         .unwrap();
+    assert_eq!(result, RespValue::Integer(3));
+    let result = executor
+        .execute("GET", &[Bytes::from("counter")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string("3"));
 
-    if let RespValue::BulkString(Some(value)) = result {
-        assert_eq!(String::from_utf8_lossy(&value), "cached script result");
-    } else {
-        panic!("Expected BulkString");
+    // A bad increment raises the standard error in the script
+    let script = "return redis.call('INCRBY', KEYS[1], ARGV[1])";
+    let result = executor.execute(
+        "EVAL",
+        &[
+            Bytes::from(script),
+            Bytes::from("1"),
+            Bytes::from("counter"),
+            Bytes::from("lots"),
+        ],
+        &mut current_db,
+        client_id,
+    );
+    match result {
+        Err(e) => assert!(
+            e.to_string()
+                .contains("ERR value is not an integer or out of range"),
+            "{}",
+            e
+        ),
+        Ok(reply) => panic!("Expected the script to fail, got {:?}", reply),
     }
+    let result = executor
+        .execute("GET", &[Bytes::from("counter")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string("3"));
 }
 
 #[test]
-fn test_evalsha_not_found() {
+fn test_eval_rejects_connection_state_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
     let mut current_db = 0;
     let client_id = 1;
 
-    // Try to execute a non-existent script
-    let result = executor.execute(
-        "EVALSHA",
-        &[
-            Bytes::from("0000000000000000000000000000000000000000"),
-            Bytes::from("0"),
-        ],
-        &mut current_db,
-        client_id,
+    // SELECT fails the script instead of switching databases
+    let script = "redis.call('SELECT', '1') redis.call('SET', 'k', 'v') return 1";
+    let err = executor
+        .execute(
+            "EVAL",
+            &[Bytes::from(script), Bytes::from("0")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("This Redis command is not allowed from script"),
+        "{}",
+        err
     );
+    assert_eq!(current_db, 0);
+    for db in ["0", "1"] {
+        let result = executor
+            .execute(
+                "EVAL",
+                &[
+                    Bytes::from("return redis.call('EXISTS', KEYS[1])"),
+                    Bytes::from("1"),
+                    Bytes::from("k"),
+                ],
+                &mut db.parse().unwrap(),
+                client_id,
+            )
+            .unwrap();
+        assert_eq!(result, RespValue::integer(0));
+    }
 
-    assert!(result.is_err());
+    for command in ["SWAPDB", "SUBSCRIBE", "MULTI", "WATCH", "BLPOP"] {
+        let result = executor
+            .execute(
+                "EVAL",
+                &[
+                    Bytes::from("return redis.pcall(ARGV[1])['err']"),
+                    Bytes::from("0"),
+                    Bytes::from(command),
+                ],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+        assert_eq!(
+            result,
+            RespValue::bulk_string("ERR This Redis command is not allowed from script"),
+            "{}",
+            command
+        );
+    }
+
+    let result = executor
+        .execute(
+            "EVAL",
+            &[
+                Bytes::from("return redis.pcall('NOSUCHCOMMAND')['err']"),
+                Bytes::from("0"),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(
+        result,
+        RespValue::bulk_string("ERR Unknown Redis command called from script")
+    );
 }
 
 #[test]
-fn test_script_flush() {
+fn test_eval_pcall_error_table() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
     let mut current_db = 0;
     let client_id = 1;
 
-    // Load a script
-    let script = "return 1";
-    let load_result = executor
+    executor
         .execute(
-            "SCRIPT",
-            &[Bytes::from("LOAD"), Bytes::from(script)],
+            "SET",
+            &[Bytes::from("text"), Bytes::from("not a number")],
             &mut current_db,
             client_id,
         )
         .unwrap();
 
-    let sha1 = if let RespValue::BulkString(Some(sha)) = load_result {
-        sha
-    } else {
-        panic!("Expected BulkString");
-    };
+    // redis.pcall hands the error back as a table with an err field
+    let script = r#"
+        local reply = redis.pcall('INCR', KEYS[1])
+        return {type(reply), reply['err']}
+    "#;
+    let result = executor
+        .execute(
+            "EVAL",
+            &[Bytes::from(script), Bytes::from("1"), Bytes::from("text")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    match result {
+        RespValue::Array(Some(items)) => {
+            assert_eq!(items[0], RespValue::bulk_string("table"));
+            assert_eq!(
+                items[1],
+                RespValue::bulk_string("ERR value is not an integer or out of range")
+            );
+        }
+        other => panic!("Expected array, got {:?}", other),
+    }
 
-    // Verify script exists
+    // Errors with their own code keep it rather than gaining an ERR prefix
     let result = executor
         .execute(
-            "SCRIPT",
-            &[Bytes::from("EXISTS"), sha1.clone()],
+            "EVAL",
+            &[
+                Bytes::from("return redis.pcall('LPUSH', KEYS[1], 'x')['err']"),
+                Bytes::from("1"),
+                Bytes::from("text"),
+            ],
             &mut current_db,
             client_id,
         )
         .unwrap();
+    assert_eq!(
+        result,
+        RespValue::bulk_string("WRONGTYPE Operation against a key holding the wrong kind of value")
+    );
 
-    if let RespValue::Array(Some(arr)) = result {
-        assert_eq!(arr[0], RespValue::Integer(1));
+    // Returning the error table replies with the error itself
+    let script = "return redis.pcall('INCR', KEYS[1])";
+    let result = executor
+        .execute(
+            "EVAL",
+            &[Bytes::from(script), Bytes::from("1"), Bytes::from("text")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(
+        result,
+        RespValue::error("ERR value is not an integer or out of range")
+    );
+
+    // redis.call raises the error and aborts the script
+    let script = "redis.call('INCR', KEYS[1]) return 'unreachable'";
+    let result = executor.execute(
+        "EVAL",
+        &[Bytes::from(script), Bytes::from("1"), Bytes::from("text")],
+        &mut current_db,
+        client_id,
+    );
+    match result {
+        Err(e) => assert!(e.to_string().contains("not an integer")),
+        Ok(reply) => panic!("Expected the script to fail, got {:?}", reply),
     }
+}
 
-    // Flush all scripts
+#[test]
+fn test_eval_reply_helpers() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut eval = |script: &str| {
+        executor
+            .execute(
+                "EVAL",
+                &[Bytes::from(script.to_string()), Bytes::from("0")],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap()
+    };
+
+    assert_eq!(
+        eval("return redis.sha1hex('')"),
+        RespValue::bulk_string("da39a3ee5e6b4b0d3255bfef95601890afd80709")
+    );
+    assert_eq!(
+        eval("return redis.status_reply('PONG')"),
+        RespValue::SimpleString("PONG".to_string())
+    );
+    assert_eq!(
+        eval("return redis.error_reply('My Error')"),
+        RespValue::Error("My Error".to_string())
+    );
+
+    // Literal tables follow the same convention
+    assert_eq!(
+        eval("return {err='boom'}"),
+        RespValue::Error("boom".to_string())
+    );
+    assert_eq!(
+        eval("return {ok='FINE'}"),
+        RespValue::SimpleString("FINE".to_string())
+    );
+
+    // The helpers build plain tables, which the script can still inspect
+    assert_eq!(
+        eval("local reply = redis.status_reply('x') return {type(reply), reply['ok']}"),
+        RespValue::Array(Some(vec![
+            RespValue::bulk_string("table"),
+            RespValue::bulk_string("x"),
+        ]))
+    );
+}
+
+#[test]
+fn test_eval_cjson() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut eval = |script: &str| {
+        executor
+            .execute(
+                "EVAL",
+                &[Bytes::from(script.to_string()), Bytes::from("0")],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap()
+    };
+
+    // A nested table survives a round trip
+    let result = eval(
+        r#"
+        local t = {name = 'aikv', count = 3, ratio = 2.5, enabled = true,
+                   list = {1, 'two', {x = 'y'}}, empty = {}}
+        local d = cjson.decode(cjson.encode(t))
+        return {d.name, d.count, math.type(d.count), tostring(d.ratio),
+                tostring(d.enabled), #d.list, d.list[2], d.list[3].x,
+                cjson.encode(d.list), cjson.encode(d.empty)}
+        "#,
+    );
+    assert_eq!(
+        result,
+        RespValue::array(vec![
+            RespValue::bulk_string("aikv"),
+            RespValue::Integer(3),
+            RespValue::bulk_string("integer"),
+            RespValue::bulk_string("2.5"),
+            RespValue::bulk_string("true"),
+            RespValue::Integer(3),
+            RespValue::bulk_string("two"),
+            RespValue::bulk_string("y"),
+            RespValue::bulk_string(r#"[1,"two",{"x":"y"}]"#),
+            RespValue::bulk_string("{}"),
+        ])
+    );
+
+    // Whole floats encode as integers, like script replies
+    assert_eq!(
+        eval("return cjson.encode({3.0, 2.5, -1, false})"),
+        RespValue::bulk_string("[3,2.5,-1,false]")
+    );
+
+    // JSON null decodes to cjson.null and keeps array positions
+    assert_eq!(
+        eval(
+            "local t = cjson.decode('[1,null,3]') \
+             return {#t, tostring(t[2] == cjson.null), cjson.encode(t)}"
+        ),
+        RespValue::array(vec![
+            RespValue::Integer(3),
+            RespValue::bulk_string("true"),
+            RespValue::bulk_string("[1,null,3]"),
+        ])
+    );
+
+    // Bad input and unsupported values raise script errors
+    for script in [
+        "return cjson.decode('{not json')",
+        "return cjson.encode({f = function() end})",
+        "cjson.encode = nil",
+    ] {
+        assert!(
+            executor
+                .execute(
+                    "EVAL",
+                    &[Bytes::from(script), Bytes::from("0")],
+                    &mut current_db,
+                    client_id,
+                )
+                .is_err(),
+            "{} did not fail",
+            script
+        );
+    }
+}
+
+#[test]
+fn test_eval_ro_rejects_writes() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    executor
+        .execute(
+            "SET",
+            &[Bytes::from("key"), Bytes::from("value")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+
+    // Reads work as in EVAL
+    let script = "return redis.call('GET', KEYS[1])";
     let result = executor
         .execute(
-            "SCRIPT",
-            &[Bytes::from("FLUSH")],
+            "EVAL_RO",
+            &[Bytes::from(script), Bytes::from("1"), Bytes::from("key")],
             &mut current_db,
             client_id,
         )
         .unwrap();
+    assert_eq!(result, RespValue::bulk_string("value"));
 
-    assert_eq!(result, RespValue::simple_string("OK"));
+    // Writes fail the script, and nothing is written
+    let script = "redis.call('SET', KEYS[1], 'changed') return 'unreachable'";
+    let err = executor
+        .execute(
+            "EVAL_RO",
+            &[Bytes::from(script), Bytes::from("1"), Bytes::from("key")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Write commands are not allowed from read-only scripts"));
+    let result = executor
+        .execute("GET", &[Bytes::from("key")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string("value"));
 
-    // Verify script no longer exists
+    // redis.pcall hands the error back instead
+    let script = "return redis.pcall('DEL', KEYS[1])";
     let result = executor
+        .execute(
+            "EVAL_RO",
+            &[Bytes::from(script), Bytes::from("1"), Bytes::from("key")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert!(matches!(result, RespValue::Error(ref e) if e.contains("read-only scripts")));
+
+    // EVALSHA_RO runs cached scripts under the same rule
+    let sha = match executor
         .execute(
             "SCRIPT",
-            &[Bytes::from("EXISTS"), sha1],
+            &[
+                Bytes::from("LOAD"),
+                Bytes::from("return redis.call('DEL', KEYS[1])"),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap()
+    {
+        RespValue::BulkString(Some(sha)) => sha,
+        other => panic!("Expected SHA1, got {:?}", other),
+    };
+    let result = executor.execute(
+        "EVALSHA_RO",
+        &[sha.clone(), Bytes::from("1"), Bytes::from("key")],
+        &mut current_db,
+        client_id,
+    );
+    assert!(result.is_err());
+    let result = executor
+        .execute(
+            "EVALSHA",
+            &[sha, Bytes::from("1"), Bytes::from("key")],
             &mut current_db,
             client_id,
         )
         .unwrap();
+    assert_eq!(result, RespValue::integer(1));
+}
 
-    if let RespValue::Array(Some(arr)) = result {
-        assert_eq!(arr[0], RespValue::Integer(0));
+#[test]
+fn test_eval_state_does_not_leak() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let mut eval = |script: &str, keys: &[&str]| {
+        let mut args = vec![
+            Bytes::from(script.to_string()),
+            Bytes::from(keys.len().to_string()),
+        ];
+        args.extend(keys.iter().map(|key| Bytes::from(key.to_string())));
+        executor.execute("EVAL", &args, &mut current_db, client_id)
+    };
+
+    // Globals set by one script are gone for the next
+    let script = "counter = (counter or 0) + 1 return counter";
+    assert_eq!(eval(script, &[]).unwrap(), RespValue::integer(1));
+    assert_eq!(eval(script, &[]).unwrap(), RespValue::integer(1));
+    eval("_G.leaked = 'yes'", &[]).unwrap();
+    assert_eq!(eval("return leaked", &[]).unwrap(), RespValue::Null);
+
+    // as are the keys and arguments
+    eval("return KEYS[1]", &["key"]).unwrap();
+    assert_eq!(eval("return #KEYS", &[]).unwrap(), RespValue::integer(0));
+
+    // The libraries cannot be changed
+    let err = eval("redis.call = nil", &[]).unwrap_err();
+    assert!(err.to_string().contains("readonly table"));
+    let err = eval("string.upper = nil", &[]).unwrap_err();
+    assert!(err.to_string().contains("readonly table"));
+    assert_eq!(
+        eval("return string.upper(redis.call('PING'))", &[]).unwrap(),
+        RespValue::bulk_string("PONG")
+    );
+
+    // not even by going around the proxies; the executor keeps reusing the
+    // same pooled interpreter, so a change would show up in the next script
+    let err = eval(
+        "rawset(redis, 'call', function() return 'hijacked' end)",
+        &[],
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("readonly table"));
+    let err = eval("rawset(string, 'upper', string.lower)", &[]).unwrap_err();
+    assert!(err.to_string().contains("readonly table"));
+    let err = eval("getmetatable('').__index.upper = string.lower", &[]).unwrap_err();
+    assert!(err.to_string().contains("attempt to index"));
+    assert_eq!(
+        eval("return getmetatable('')", &[]).unwrap(),
+        RespValue::Null
+    );
+    assert_eq!(
+        eval("return ('pong'):upper() .. redis.call('PING')", &[]).unwrap(),
+        RespValue::bulk_string("PONGPONG")
+    );
+
+    // rawset still works on the script's own tables
+    assert_eq!(
+        eval("local t = {} rawset(t, 'k', 'v') return t.k", &[]).unwrap(),
+        RespValue::bulk_string("v")
+    );
+}
+
+#[test]
+fn test_eval_many_trivial_scripts() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // Interpreters are reused, so 10k scripts take well under the time a
+    // fresh interpreter per script would
+    let start = std::time::Instant::now();
+    for i in 0..10_000 {
+        let result = executor
+            .execute(
+                "EVAL",
+                &[
+                    Bytes::from("return ARGV[1]"),
+                    Bytes::from("0"),
+                    Bytes::from(i.to_string()),
+                ],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+        assert_eq!(result, RespValue::bulk_string(i.to_string()));
     }
+    assert!(
+        start.elapsed() < std::time::Duration::from_secs(10),
+        "10k EVALs took {:?}",
+        start.elapsed()
+    );
 }
 
 #[test]
-fn test_script_kill() {
+fn test_evalsha() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
     let mut current_db = 0;
     let client_id = 1;
 
-    // Test SCRIPT KILL (should return NOTBUSY since no script is running)
-    let result = executor.execute("SCRIPT", &[Bytes::from("KILL")], &mut current_db, client_id);
+    // Load a script
+    let script = "return 'cached script result'";
+    let load_result = executor
+        .execute(
+            "SCRIPT",
+            &[Bytes::from("LOAD"), Bytes::from(script)],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
 
-    assert!(result.is_err());
+    let sha1 = if let RespValue::BulkString(Some(sha)) = load_result {
+        sha
+    } else {
+        panic!("Expected BulkString");
+    };
+
+    // Execute the cached script using EVALSHA
+    let result = executor
+        .execute(
+            "EVALSHA",
+            &[sha1, Bytes::from("0")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+
+    if let RespValue::BulkString(Some(value)) = result {
+        assert_eq!(String::from_utf8_lossy(&value), "cached script result");
+    } else {
+        panic!("Expected BulkString");
+    }
+}
+
+#[test]
+fn test_script_cache_shared_between_connections() {
+    let storage = StorageEngine::new_memory(16);
+    let cache = ScriptCache::new();
+    let mut loader = CommandExecutor::new(storage.clone());
+    loader.set_script_cache(cache.clone());
+    let mut runner = CommandExecutor::new(storage);
+    runner.set_script_cache(cache);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let sha1 = loader
+        .execute(
+            "SCRIPT",
+            &[Bytes::from("LOAD"), Bytes::from("return 'shared'")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    let sha1 = match sha1 {
+        RespValue::BulkString(Some(sha)) => sha,
+        other => panic!("Expected BulkString, got {:?}", other),
+    };
+
+    // A script loaded on one connection runs on another
+    let result = runner
+        .execute(
+            "EVALSHA",
+            &[sha1.clone(), Bytes::from("0")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string("shared"));
+
+    // ...and SCRIPT FLUSH on either connection removes it for both
+    runner
+        .execute(
+            "SCRIPT",
+            &[Bytes::from("FLUSH")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert!(loader
+        .execute(
+            "EVALSHA",
+            &[sha1, Bytes::from("0")],
+            &mut current_db,
+            client_id
+        )
+        .is_err());
+}
+
+#[test]
+fn test_evalsha_not_found() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // Try to execute a non-existent script
+    let result = executor.execute(
+        "EVALSHA",
+        &[
+            Bytes::from("0000000000000000000000000000000000000000"),
+            Bytes::from("0"),
+        ],
+        &mut current_db,
+        client_id,
+    );
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_script_flush() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // Load a script
+    let script = "return 1";
+    let load_result = executor
+        .execute(
+            "SCRIPT",
+            &[Bytes::from("LOAD"), Bytes::from(script)],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+
+    let sha1 = if let RespValue::BulkString(Some(sha)) = load_result {
+        sha
+    } else {
+        panic!("Expected BulkString");
+    };
+
+    // Verify script exists
+    let result = executor
+        .execute(
+            "SCRIPT",
+            &[Bytes::from("EXISTS"), sha1.clone()],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+
+    if let RespValue::Array(Some(arr)) = result {
+        assert_eq!(arr[0], RespValue::Integer(1));
+    }
+
+    // Flush all scripts
+    let result = executor
+        .execute(
+            "SCRIPT",
+            &[Bytes::from("FLUSH")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+
+    assert_eq!(result, RespValue::simple_string("OK"));
+
+    // Verify script no longer exists
+    let result = executor
+        .execute(
+            "SCRIPT",
+            &[Bytes::from("EXISTS"), sha1],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+
+    if let RespValue::Array(Some(arr)) = result {
+        assert_eq!(arr[0], RespValue::Integer(0));
+    }
+}
+
+#[test]
+fn test_script_kill() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // Test SCRIPT KILL (should return NOTBUSY since no script is running)
+    let result = executor.execute("SCRIPT", &[Bytes::from("KILL")], &mut current_db, client_id);
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_script_time_limit() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let config = |current_db: &mut usize, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor
+            .execute("CONFIG", &args, current_db, client_id)
+            .unwrap()
+    };
+    config(&mut current_db, &["SET", "lua-time-limit", "100"]);
+    assert_eq!(
+        config(&mut current_db, &["GET", "lua-time-limit"]),
+        RespValue::map(vec![(
+            RespValue::bulk_string("lua-time-limit"),
+            RespValue::bulk_string("100"),
+        )])
+    );
+
+    // A script running past the limit is aborted, and its writes rolled back
+    let start = std::time::Instant::now();
+    let script = "redis.call('SET', KEYS[1], 'v') while true do end";
+    let err = executor
+        .execute(
+            "EVAL",
+            &[Bytes::from(script), Bytes::from("1"), Bytes::from("key")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("lua-time-limit"), "{}", err);
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    let result = executor
+        .execute("EXISTS", &[Bytes::from("key")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::integer(0));
+
+    // Later scripts run as usual
+    let result = executor
+        .execute(
+            "EVAL",
+            &[Bytes::from("return 1"), Bytes::from("0")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::integer(1));
+}
+
+#[test]
+fn test_script_kill_stops_running_script() {
+    let storage = StorageEngine::new_memory(16);
+    let cache = ScriptCache::new();
+    let gate = CommandGate::new();
+    let mut runner = CommandExecutor::new(storage.clone());
+    runner.set_script_cache(cache.clone());
+    runner.set_command_gate(gate.clone());
+    let mut killer = CommandExecutor::new(storage);
+    killer.set_script_cache(cache.clone());
+    killer.set_command_gate(gate);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // Without a time limit the script only stops when killed
+    cache.set_time_limit_ms(0);
+    let script = std::thread::spawn(move || {
+        let mut current_db = 0;
+        runner.execute(
+            "EVAL",
+            &[Bytes::from("while true do end"), Bytes::from("0")],
+            &mut current_db,
+            1,
+        )
+    });
+
+    // SCRIPT KILL gets past the script holding the gate
+    let start = std::time::Instant::now();
+    loop {
+        let result = killer.execute("SCRIPT", &[Bytes::from("KILL")], &mut current_db, client_id);
+        match result {
+            Ok(reply) => {
+                assert_eq!(reply, RespValue::ok());
+                break;
+            }
+            Err(e) => assert!(e.to_string().contains("NOTBUSY"), "{}", e),
+        }
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    let err = script.join().unwrap().unwrap_err();
+    assert!(err.to_string().contains("Script killed by user"), "{}", err);
+
+    // Nothing is running any more
+    let result = killer.execute("SCRIPT", &[Bytes::from("KILL")], &mut current_db, client_id);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_eval_rate_limiter() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let script = r#"
+        local c = redis.call('INCR', KEYS[1])
+        if c == 1 then
+            redis.call('EXPIRE', KEYS[1], ARGV[1])
+        end
+        return c
+    "#;
+    let args = [
+        Bytes::from(script),
+        Bytes::from("1"),
+        Bytes::from("ratelimit"),
+        Bytes::from("60"),
+    ];
+
+    for expected in 1..=3 {
+        let result = executor
+            .execute("EVAL", &args, &mut current_db, client_id)
+            .unwrap();
+        assert_eq!(result, RespValue::Integer(expected));
+
+        // The TTL set by the first call survives the later increments
+        let ttl = executor
+            .execute(
+                "TTL",
+                &[Bytes::from("ratelimit")],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+        match ttl {
+            RespValue::Integer(ttl) => assert!(ttl > 0 && ttl <= 60),
+            other => panic!("Expected Integer TTL, got {:?}", other),
+        }
+    }
+
+    let result = executor
+        .execute(
+            "GET",
+            &[Bytes::from("ratelimit")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string(Bytes::from("3")));
+}
+
+#[test]
+fn test_eval_atomic_commands() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let script = r#"
+        local first = redis.call('SETNX', KEYS[1], 'v1')
+        local second = redis.call('SETNX', KEYS[1], 'v2')
+        local value = redis.call('GETEX', KEYS[1], 'EX', 100)
+        local taken = redis.call('GETDEL', KEYS[1])
+        local missing = redis.call('GETDEL', KEYS[1])
+        local fields = redis.call('HSET', KEYS[2], 'a', '1', 'b', '2')
+        local len = redis.call('LPUSH', KEYS[3], 'x', 'y')
+        return {first, second, value, taken, missing, fields, len}
+    "#;
+
+    let result = executor
+        .execute(
+            "EVAL",
+            &[
+                Bytes::from(script),
+                Bytes::from("3"),
+                Bytes::from("str"),
+                Bytes::from("hash"),
+                Bytes::from("list"),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+
+    assert_eq!(
+        result,
+        RespValue::Array(Some(vec![
+            RespValue::Integer(1),
+            RespValue::Integer(0),
+            RespValue::bulk_string(Bytes::from("v1")),
+            RespValue::bulk_string(Bytes::from("v1")),
+            // A nil reply becomes false in Lua and is returned as nil
+            RespValue::Null,
+            RespValue::Integer(2),
+            RespValue::Integer(2),
+        ]))
+    );
+
+    // Writes from the script are committed
+    let result = executor
+        .execute("EXISTS", &[Bytes::from("str")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::Integer(0));
+
+    let result = executor
+        .execute(
+            "HGET",
+            &[Bytes::from("hash"), Bytes::from("b")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string(Bytes::from("2")));
+
+    let result = executor
+        .execute("LLEN", &[Bytes::from("list")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::Integer(2));
+}
+
+#[test]
+fn test_eval_hash_and_list_read_back() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // HGETALL reaches Lua as a flat array of fields and values
+    let script = r#"
+        redis.call('HSET', KEYS[1], 'name', 'ada', 'lang', 'lua')
+        redis.call('HDEL', KEYS[1], 'lang')
+        local all = redis.call('HGETALL', KEYS[1])
+        local name = redis.call('HGET', KEYS[1], 'name')
+        redis.call('RPUSH', KEYS[2], 'b', 'c')
+        redis.call('LPUSH', KEYS[2], 'a')
+        local range = redis.call('LRANGE', KEYS[2], 0, -1)
+        local len = redis.call('LLEN', KEYS[2])
+        return {#all, all[1], all[2], name, len, range[1], range[2], range[3]}
+    "#;
+    let result = executor
+        .execute(
+            "EVAL",
+            &[
+                Bytes::from(script),
+                Bytes::from("2"),
+                Bytes::from("user"),
+                Bytes::from("letters"),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(
+        result,
+        RespValue::array(vec![
+            RespValue::Integer(2),
+            RespValue::bulk_string("name"),
+            RespValue::bulk_string("ada"),
+            RespValue::bulk_string("ada"),
+            RespValue::Integer(3),
+            RespValue::bulk_string("a"),
+            RespValue::bulk_string("b"),
+            RespValue::bulk_string("c"),
+        ])
+    );
+
+    let result = executor
+        .execute(
+            "HGETALL",
+            &[Bytes::from("user")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(
+        result,
+        RespValue::array(vec![
+            RespValue::bulk_string("name"),
+            RespValue::bulk_string("ada")
+        ])
+    );
+    let result = executor
+        .execute(
+            "LRANGE",
+            &[Bytes::from("letters"), Bytes::from("0"), Bytes::from("-1")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(
+        result,
+        RespValue::array(vec![
+            RespValue::bulk_string("a"),
+            RespValue::bulk_string("b"),
+            RespValue::bulk_string("c"),
+        ])
+    );
+}
+
+#[test]
+fn test_eval_empty_and_binary_values() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let script = r#"
+        redis.call('SET', KEYS[1], ARGV[1])
+        return redis.call('GET', KEYS[1])
+    "#;
+
+    // An empty string is a value, not nil
+    let result = executor
+        .execute(
+            "EVAL",
+            &[
+                Bytes::from(script),
+                Bytes::from("1"),
+                Bytes::from("empty"),
+                Bytes::from(""),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string(Bytes::new()));
+
+    let result = executor
+        .execute("GET", &[Bytes::from("empty")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string(Bytes::new()));
+
+    // Embedded NUL bytes are preserved
+    let binary = Bytes::from_static(b"a\0b\0\0c");
+    let result = executor
+        .execute(
+            "EVAL",
+            &[
+                Bytes::from(script),
+                Bytes::from("1"),
+                Bytes::from("binary"),
+                binary.clone(),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string(binary.clone()));
+
+    let result = executor
+        .execute("GET", &[Bytes::from("binary")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string(binary));
+
+    // Values built inside the script round-trip as well
+    let result = executor
+        .execute(
+            "EVAL",
+            &[Bytes::from("return ''"), Bytes::from("0")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string(Bytes::new()));
+
+    let result = executor
+        .execute(
+            "EVAL",
+            &[Bytes::from("return 'x\\0y'"), Bytes::from("0")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string(Bytes::from_static(b"x\0y")));
+
+    // Invalid UTF-8 in keys and arguments reaches the script byte for byte
+    let key = Bytes::from_static(b"key\xff\xfe");
+    let value = Bytes::from_static(b"\x80value\xff");
+    executor
+        .execute(
+            "SET",
+            &[key.clone(), value.clone()],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    let result = executor
+        .execute(
+            "EVAL",
+            &[
+                Bytes::from("return {KEYS[1], ARGV[1], redis.call('GET', KEYS[1])}"),
+                Bytes::from("1"),
+                key.clone(),
+                value.clone(),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(
+        result,
+        RespValue::Array(Some(vec![
+            RespValue::bulk_string(key),
+            RespValue::bulk_string(value.clone()),
+            RespValue::bulk_string(value),
+        ]))
+    );
+}
+
+#[test]
+fn test_eval_emptied_container_leaves_no_key() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // Creating a set and emptying it within one script leaves no phantom key
+    let script = "redis.call('SADD', KEYS[1], 'x'); \
+                  redis.call('SREM', KEYS[1], 'x'); \
+                  return redis.call('EXISTS', KEYS[1])";
+    let result = executor
+        .execute(
+            "EVAL",
+            &[Bytes::from(script), Bytes::from("1"), Bytes::from("k")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::Integer(0));
+
+    let result = executor
+        .execute("EXISTS", &[Bytes::from("k")], &mut current_db, client_id)
+        .unwrap();
+    assert_eq!(result, RespValue::Integer(0));
+
+    // TYPE and OBJECT ENCODING never see an emptied container
+    for (fill, empty) in [
+        (vec!["RPUSH", "c", "x"], vec!["LPOP", "c"]),
+        (vec!["HSET", "c", "f", "x"], vec!["HDEL", "c", "f"]),
+        (vec!["SADD", "c", "x"], vec!["SPOP", "c"]),
+        (vec!["ZADD", "c", "1", "x"], vec!["ZREM", "c", "x"]),
+    ] {
+        for command in [fill, empty] {
+            let args: Vec<Bytes> = command[1..].iter().map(|a| Bytes::from(*a)).collect();
+            executor
+                .execute(command[0], &args, &mut current_db, client_id)
+                .unwrap();
+        }
+
+        let result = executor
+            .execute("TYPE", &[Bytes::from("c")], &mut current_db, client_id)
+            .unwrap();
+        assert_eq!(result, RespValue::simple_string("none"));
+        let result = executor
+            .execute(
+                "OBJECT",
+                &[Bytes::from("ENCODING"), Bytes::from("c")],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+        assert_eq!(result, RespValue::null_bulk_string());
+    }
+}
+
+#[test]
+fn test_script_size_limit() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    let result = executor
+        .execute(
+            "CONFIG",
+            &[
+                Bytes::from("SET"),
+                Bytes::from("lua-max-script-size"),
+                Bytes::from("16"),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::ok());
+
+    // Rejected before it is compiled, whether run or loaded
+    let script = Bytes::from("return 'far too long'");
+    let err = executor
+        .execute(
+            "EVAL",
+            &[script.clone(), Bytes::from("0")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("script too large"), "{}", err);
+    let err = executor
+        .execute(
+            "SCRIPT",
+            &[Bytes::from("LOAD"), script],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("script too large"), "{}", err);
+
+    let result = executor
+        .execute(
+            "EVAL",
+            &[Bytes::from("return 1"), Bytes::from("0")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::integer(1));
+}
+
+#[test]
+fn test_script_cache_evicts_least_recently_used() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // Room for two of the 8 byte scripts below
+    executor
+        .execute(
+            "CONFIG",
+            &[
+                Bytes::from("SET"),
+                Bytes::from("lua-script-cache-size"),
+                Bytes::from("20"),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+
+    let mut digests = Vec::new();
+    for script in ["return 1", "return 2"] {
+        // EVAL caches the scripts it runs
+        executor
+            .execute(
+                "EVAL",
+                &[Bytes::from(script), Bytes::from("0")],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+        let digest = executor
+            .execute(
+                "SCRIPT",
+                &[Bytes::from("LOAD"), Bytes::from(script)],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+        digests.push(digest);
+    }
+
+    // Running the first script makes the second the least recently used
+    let sha1 = |digest: &RespValue| match digest {
+        RespValue::BulkString(Some(sha1)) => sha1.clone(),
+        other => panic!("Expected a digest, got {:?}", other),
+    };
+    executor
+        .execute(
+            "EVALSHA",
+            &[sha1(&digests[0]), Bytes::from("0")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    let third = executor
+        .execute(
+            "SCRIPT",
+            &[Bytes::from("LOAD"), Bytes::from("return 3")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+
+    let result = executor
+        .execute(
+            "SCRIPT",
+            &[
+                Bytes::from("EXISTS"),
+                sha1(&digests[0]),
+                sha1(&digests[1]),
+                sha1(&third),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(
+        result,
+        RespValue::array(vec![
+            RespValue::integer(1),
+            RespValue::integer(0),
+            RespValue::integer(1),
+        ])
+    );
+
+    // Shrinking the cap evicts right away
+    executor
+        .execute(
+            "CONFIG",
+            &[
+                Bytes::from("SET"),
+                Bytes::from("lua-script-cache-size"),
+                Bytes::from("0"),
+            ],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    let result = executor
+        .execute(
+            "SCRIPT",
+            &[Bytes::from("EXISTS"), sha1(&digests[0])],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::array(vec![RespValue::integer(0)]));
+}
+
+#[test]
+fn test_script_cache_survives_rdb_snapshot() {
+    use aikv::persistence::{load_rdb_with_scripts, save_rdb_with_scripts};
+    use std::collections::HashMap;
+    use tempfile::NamedTempFile;
+
+    let script = "return redis.call('GET', KEYS[1])";
+    let mut current_db = 0;
+    let client_id = 1;
+
+    // Load a script and snapshot it with the data it reads
+    let cache = ScriptCache::new();
+    let mut executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    executor.set_script_cache(cache.clone());
+    let sha = match executor
+        .execute(
+            "SCRIPT",
+            &[Bytes::from("LOAD"), Bytes::from(script)],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap()
+    {
+        RespValue::BulkString(Some(sha)) => sha,
+        other => panic!("Expected SHA1 from SCRIPT LOAD, got {:?}", other),
+    };
+
+    let mut db0 = HashMap::new();
+    db0.insert("greeting".to_string(), (Bytes::from("hello"), None));
+    let snapshot = NamedTempFile::new().unwrap();
+    save_rdb_with_scripts(snapshot.path(), &[db0], &cache.scripts().unwrap()).unwrap();
+
+    // Rebuild a fresh engine and script cache from the snapshot
+    let (databases, scripts) = load_rdb_with_scripts(snapshot.path()).unwrap();
+    assert_eq!(scripts, vec![script.to_string()]);
+
+    let cache = ScriptCache::new();
+    assert_eq!(cache.restore(scripts).unwrap(), 1);
+    let mut executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    executor.set_script_cache(cache);
+    for (key, (value, _)) in &databases[0] {
+        executor
+            .execute(
+                "SET",
+                &[Bytes::from(key.clone()), value.clone()],
+                &mut current_db,
+                client_id,
+            )
+            .unwrap();
+    }
+
+    let result = executor
+        .execute(
+            "EVALSHA",
+            &[sha, Bytes::from("1"), Bytes::from("greeting")],
+            &mut current_db,
+            client_id,
+        )
+        .unwrap();
+    assert_eq!(result, RespValue::bulk_string("hello"));
+
+    // A snapshot saved without scripts restores none
+    let plain = NamedTempFile::new().unwrap();
+    aikv::persistence::save_rdb(plain.path(), &databases).unwrap();
+    assert!(load_rdb_with_scripts(plain.path()).unwrap().1.is_empty());
 }
@@ -0,0 +1,347 @@
+//! Tests for server-wide statistics reported over real client connections
+//!
+//! These tests start an AiKv server on a free local port and talk RESP to it
+//! directly, so the connection read/write paths feed the metrics under test.
+
+use aikv::protocol::{RespParser, RespValue};
+use aikv::{Server, StorageEngine};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, Duration};
+
+/// Start a server on a free port and connect a client to it
+async fn start_server() -> TcpStream {
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+
+    let server = Server::new(addr.clone(), StorageEngine::new_memory(16));
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+
+    for _ in 0..50 {
+        if let Ok(stream) = TcpStream::connect(&addr).await {
+            return stream;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Server did not start on {}", addr);
+}
+
+/// Send a command and read its reply
+async fn send(stream: &mut TcpStream, parser: &mut RespParser, args: &[&str]) -> RespValue {
+    let mut request = format!("*{}\r\n", args.len());
+    for arg in args {
+        request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    loop {
+        if let Some(reply) = parser.parse().unwrap() {
+            return reply;
+        }
+        let n = stream.read_buf(parser.buffer_mut()).await.unwrap();
+        assert!(n > 0, "Connection closed before a reply was read");
+    }
+}
+
+/// Read an INFO section
+async fn info_section(stream: &mut TcpStream, parser: &mut RespParser, section: &str) -> String {
+    match send(stream, parser, &["INFO", section]).await {
+        RespValue::BulkString(Some(info)) => String::from_utf8(info.to_vec()).unwrap(),
+        other => panic!("Expected bulk string for INFO, got {:?}", other),
+    }
+}
+
+/// Read a numeric field from INFO stats
+async fn stats_field(stream: &mut TcpStream, parser: &mut RespParser, field: &str) -> u64 {
+    let info = info_section(stream, parser, "stats").await;
+
+    info.lines()
+        .find_map(|line| line.strip_prefix(&format!("{}:", field)))
+        .unwrap_or_else(|| panic!("{} missing from INFO stats", field))
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_info_stats_net_bytes() {
+    let mut stream = start_server().await;
+    let mut parser = RespParser::new(4096);
+
+    send(&mut stream, &mut parser, &["SET", "key", "value"]).await;
+    let input = stats_field(&mut stream, &mut parser, "total_net_input_bytes").await;
+    let output = stats_field(&mut stream, &mut parser, "total_net_output_bytes").await;
+    assert!(input > 0);
+    assert!(output > 0);
+
+    for _ in 0..10 {
+        send(&mut stream, &mut parser, &["GET", "key"]).await;
+    }
+    let more_input = stats_field(&mut stream, &mut parser, "total_net_input_bytes").await;
+    let more_output = stats_field(&mut stream, &mut parser, "total_net_output_bytes").await;
+    assert!(more_input > input);
+    assert!(more_output > output);
+}
+
+/// Read an INFO section as field/value pairs
+async fn section_info(
+    stream: &mut TcpStream,
+    parser: &mut RespParser,
+    section: &str,
+) -> Vec<(String, String)> {
+    let info = match send(stream, parser, &["INFO", section]).await {
+        RespValue::BulkString(Some(info)) => String::from_utf8(info.to_vec()).unwrap(),
+        other => panic!("Expected bulk string for INFO, got {:?}", other),
+    };
+
+    info.lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(field, value)| (field.to_string(), value.to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_info_server_section() {
+    let mut stream = start_server().await;
+    let port = stream.peer_addr().unwrap().port();
+    let mut parser = RespParser::new(4096);
+
+    let info = section_info(&mut stream, &mut parser, "server").await;
+    let field = |name: &str| {
+        info.iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.clone())
+            .unwrap_or_else(|| panic!("{} missing from INFO server", name))
+    };
+
+    // Clients gate features on redis_version, so it must be a plain semver
+    let version: Vec<u32> = field("redis_version")
+        .split('.')
+        .map(|part| part.parse().unwrap())
+        .collect();
+    assert_eq!(version.len(), 3);
+    assert!(version[0] >= 6);
+
+    assert_eq!(field("tcp_port"), port.to_string());
+    assert_eq!(field("process_id"), std::process::id().to_string());
+    assert!(["standalone", "cluster"].contains(&field("redis_mode").as_str()));
+    assert!(!field("executable").is_empty());
+    field("uptime_in_seconds").parse::<u64>().unwrap();
+    field("config_file");
+
+    let run_id = field("run_id");
+    assert_eq!(run_id.len(), 40);
+    assert!(run_id.chars().all(|c| c.is_ascii_hexdigit()));
+
+    // The run id belongs to the server, not to the connection
+    let mut other = TcpStream::connect(stream.peer_addr().unwrap())
+        .await
+        .unwrap();
+    let mut other_parser = RespParser::new(4096);
+    let other_info = section_info(&mut other, &mut other_parser, "server").await;
+    assert!(other_info.contains(&("run_id".to_string(), run_id)));
+}
+
+#[tokio::test]
+async fn test_info_uptime_advances() {
+    let mut stream = start_server().await;
+    let mut parser = RespParser::new(4096);
+
+    // Read uptime_in_seconds and server_time_usec
+    async fn read_times(stream: &mut TcpStream, parser: &mut RespParser) -> (u128, u128) {
+        let info = section_info(stream, parser, "server").await;
+        let field = |name: &str| -> u128 {
+            info.iter()
+                .find(|(field, _)| field == name)
+                .unwrap_or_else(|| panic!("{} missing from INFO server", name))
+                .1
+                .parse()
+                .unwrap()
+        };
+        (field("uptime_in_seconds"), field("server_time_usec"))
+    }
+
+    // Uptime counts from server start, not from the INFO call
+    let (uptime, server_time) = read_times(&mut stream, &mut parser).await;
+    assert!(uptime < 5);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_micros();
+    assert!(now.abs_diff(server_time) < 5_000_000);
+
+    sleep(Duration::from_millis(1100)).await;
+    let (later_uptime, later_server_time) = read_times(&mut stream, &mut parser).await;
+    assert!(later_uptime > uptime);
+    assert!(later_server_time >= server_time + 1_000_000);
+}
+
+#[tokio::test]
+async fn test_info_memory_and_cpu_sections() {
+    let mut stream = start_server().await;
+    let mut parser = RespParser::new(4096);
+
+    for i in 0..100 {
+        let key = format!("key:{}", i);
+        send(&mut stream, &mut parser, &["SET", &key, "some value"]).await;
+    }
+
+    let memory = section_info(&mut stream, &mut parser, "memory").await;
+    let field = |info: &[(String, String)], name: &str| {
+        info.iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.clone())
+            .unwrap_or_else(|| panic!("{} missing from INFO", name))
+    };
+
+    let used_memory: u64 = field(&memory, "used_memory").parse().unwrap();
+    assert!(used_memory > 0);
+    let peak: u64 = field(&memory, "used_memory_peak").parse().unwrap();
+    assert!(peak >= used_memory);
+    field(&memory, "used_memory_rss").parse::<u64>().unwrap();
+    field(&memory, "mem_fragmentation_ratio")
+        .parse::<f64>()
+        .unwrap();
+
+    let cpu = section_info(&mut stream, &mut parser, "cpu").await;
+    for name in ["used_cpu_sys", "used_cpu_user"] {
+        assert!(field(&cpu, name).parse::<f64>().unwrap() >= 0.0);
+    }
+}
+
+#[tokio::test]
+async fn test_info_stats_pubsub() {
+    let mut stream = start_server().await;
+    let mut parser = RespParser::new(4096);
+    let mut subscriber = TcpStream::connect(stream.peer_addr().unwrap())
+        .await
+        .unwrap();
+    let mut sub_parser = RespParser::new(4096);
+
+    send(&mut subscriber, &mut sub_parser, &["SUBSCRIBE", "news"]).await;
+    send(&mut subscriber, &mut sub_parser, &["SUBSCRIBE", "sports"]).await;
+    send(&mut subscriber, &mut sub_parser, &["PSUBSCRIBE", "n*"]).await;
+
+    // Publishes count whether or not anyone is listening
+    for channel in ["news", "sports", "weather"] {
+        send(&mut stream, &mut parser, &["PUBLISH", channel, "hello"]).await;
+    }
+
+    assert_eq!(
+        stats_field(&mut stream, &mut parser, "pubsub_channels").await,
+        2
+    );
+    assert_eq!(
+        stats_field(&mut stream, &mut parser, "pubsub_patterns").await,
+        1
+    );
+    assert_eq!(
+        stats_field(&mut stream, &mut parser, "total_messages_published").await,
+        3
+    );
+}
+
+#[tokio::test]
+async fn test_info_errorstats() {
+    let mut stream = start_server().await;
+    let mut parser = RespParser::new(4096);
+
+    send(&mut stream, &mut parser, &["LPUSH", "list", "a"]).await;
+    for _ in 0..3 {
+        let reply = send(&mut stream, &mut parser, &["GET", "list"]).await;
+        assert!(matches!(reply, RespValue::Error(ref e) if e.starts_with("WRONGTYPE ")));
+    }
+    send(&mut stream, &mut parser, &["NOSUCHCOMMAND"]).await;
+
+    let info = info_section(&mut stream, &mut parser, "errorstats").await;
+    assert!(info.contains("errorstat_WRONGTYPE:count=3"), "{}", info);
+    assert!(info.contains("errorstat_ERR:count=1"), "{}", info);
+    assert_eq!(
+        stats_field(&mut stream, &mut parser, "total_error_replies").await,
+        4
+    );
+
+    // CONFIG RESETSTAT clears the counts
+    let reply = send(&mut stream, &mut parser, &["CONFIG", "RESETSTAT"]).await;
+    assert_eq!(reply, RespValue::ok());
+    let info = info_section(&mut stream, &mut parser, "errorstats").await;
+    assert!(!info.contains("errorstat_"), "{}", info);
+}
+
+#[tokio::test]
+async fn test_save_points_trigger_background_save() {
+    let mut stream = start_server().await;
+    let mut parser = RespParser::new(4096);
+
+    let changes = |info: String| -> u64 {
+        info.lines()
+            .find_map(|line| line.strip_prefix("rdb_changes_since_last_save:"))
+            .expect("rdb_changes_since_last_save missing from INFO persistence")
+            .parse()
+            .unwrap()
+    };
+
+    let reply = send(&mut stream, &mut parser, &["CONFIG", "GET", "save"]).await;
+    assert_eq!(
+        reply,
+        RespValue::array(vec![
+            RespValue::bulk_string("save"),
+            RespValue::bulk_string(""),
+        ])
+    );
+    let reply = send(&mut stream, &mut parser, &["CONFIG", "SET", "save", "1"]).await;
+    assert!(matches!(reply, RespValue::Error(ref e) if e.contains("Invalid save parameters")));
+
+    // Snapshots are written to dbfilename within dir
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().to_string_lossy().into_owned();
+    let reply = send(&mut stream, &mut parser, &["CONFIG", "SET", "dir", &path]).await;
+    assert_eq!(reply, RespValue::ok());
+    let reply = send(
+        &mut stream,
+        &mut parser,
+        &["CONFIG", "SET", "dbfilename", "a/b.rdb"],
+    )
+    .await;
+    assert!(matches!(reply, RespValue::Error(ref e) if e.contains("can't be a path")));
+    let reply = send(
+        &mut stream,
+        &mut parser,
+        &["CONFIG", "SET", "dbfilename", "autosave.rdb"],
+    )
+    .await;
+    assert_eq!(reply, RespValue::ok());
+    let snapshot = dir.path().join("autosave.rdb");
+
+    // An empty save string disables autosave
+    let reply = send(&mut stream, &mut parser, &["CONFIG", "SET", "save", ""]).await;
+    assert_eq!(reply, RespValue::ok());
+    send(&mut stream, &mut parser, &["SET", "key", "value"]).await;
+    sleep(Duration::from_millis(1200)).await;
+    let info = info_section(&mut stream, &mut parser, "persistence").await;
+    assert_eq!(changes(info), 1);
+
+    // A single write is enough once a second has passed since the last save
+    let reply = send(&mut stream, &mut parser, &["CONFIG", "SET", "save", "1 1"]).await;
+    assert_eq!(reply, RespValue::ok());
+    let reply = send(&mut stream, &mut parser, &["CONFIG", "GET", "save"]).await;
+    assert_eq!(
+        reply,
+        RespValue::array(vec![
+            RespValue::bulk_string("save"),
+            RespValue::bulk_string("1 1"),
+        ])
+    );
+    for _ in 0..30 {
+        let info = info_section(&mut stream, &mut parser, "persistence").await;
+        if changes(info) == 0 {
+            assert!(snapshot.exists(), "Background save wrote no snapshot");
+            return;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Save point did not trigger a background save");
+}
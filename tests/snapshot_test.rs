@@ -0,0 +1,85 @@
+//! Tests for RDB snapshots across server restarts
+//!
+//! A server loads the snapshot SAVE left in its directory when it starts, so
+//! these tests start an AiKv server on a free local port, save, and start a
+//! second server on the same directory.
+
+use aikv::protocol::{RespParser, RespValue};
+use aikv::{Server, StorageEngine};
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, Duration};
+
+/// Start a server keeping its snapshot in `dir` and connect to it
+async fn start_server(dir: &Path) -> TcpStream {
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+
+    let mut server = Server::new(addr.clone(), StorageEngine::new_memory(16));
+    server.set_dir(&dir.to_string_lossy()).unwrap();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+
+    for _ in 0..50 {
+        if let Ok(stream) = TcpStream::connect(&addr).await {
+            return stream;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Server did not start on {}", addr);
+}
+
+/// Send a command and read its reply
+async fn send(stream: &mut TcpStream, parser: &mut RespParser, args: &[&str]) -> RespValue {
+    let mut request = format!("*{}\r\n", args.len());
+    for arg in args {
+        request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    loop {
+        if let Some(reply) = parser.parse().unwrap() {
+            return reply;
+        }
+        let n = stream.read_buf(parser.buffer_mut()).await.unwrap();
+        assert!(n > 0, "Connection closed before a reply was read");
+    }
+}
+
+#[tokio::test]
+async fn test_scripts_and_data_survive_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = "return redis.call('LRANGE', KEYS[1], 0, -1)";
+
+    let mut stream = start_server(dir.path()).await;
+    let mut parser = RespParser::new(4096);
+    let sha = match send(&mut stream, &mut parser, &["SCRIPT", "LOAD", script]).await {
+        RespValue::BulkString(Some(sha)) => String::from_utf8(sha.to_vec()).unwrap(),
+        other => panic!("Expected SHA1 from SCRIPT LOAD, got {:?}", other),
+    };
+    send(&mut stream, &mut parser, &["RPUSH", "list", "a", "b"]).await;
+    send(&mut stream, &mut parser, &["PEXPIRE", "list", "100000"]).await;
+    assert_eq!(
+        send(&mut stream, &mut parser, &["SAVE"]).await,
+        RespValue::ok()
+    );
+
+    // A new server on the same directory picks up the scripts and the data
+    let mut stream = start_server(dir.path()).await;
+    let mut parser = RespParser::new(4096);
+    assert_eq!(
+        send(&mut stream, &mut parser, &["EVALSHA", &sha, "1", "list"]).await,
+        RespValue::array(vec![
+            RespValue::bulk_string("a"),
+            RespValue::bulk_string("b")
+        ])
+    );
+    match send(&mut stream, &mut parser, &["PTTL", "list"]).await {
+        RespValue::Integer(ttl) => assert!(ttl > 90_000 && ttl <= 100_000, "TTL {}", ttl),
+        other => panic!("Expected a TTL, got {:?}", other),
+    }
+}
@@ -0,0 +1,87 @@
+//! Tests for serving clients on a Unix socket alongside the TCP port
+//!
+//! These tests start an AiKv server bound to both a free local port and a
+//! socket file, then talk RESP to it over each.
+
+#![cfg(unix)]
+
+use aikv::protocol::{RespParser, RespValue};
+use aikv::{Server, StorageEngine};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UnixStream};
+use tokio::time::{sleep, Duration};
+
+/// Send a command and read its reply
+async fn send<S>(stream: &mut S, parser: &mut RespParser, args: &[&str]) -> RespValue
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut request = format!("*{}\r\n", args.len());
+    for arg in args {
+        request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    loop {
+        if let Some(reply) = parser.parse().unwrap() {
+            return reply;
+        }
+        let n = stream.read_buf(parser.buffer_mut()).await.unwrap();
+        assert!(n > 0, "Connection closed before a reply was read");
+    }
+}
+
+#[tokio::test]
+async fn test_tcp_and_unix_socket_share_the_dataset() {
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let path = std::env::temp_dir().join(format!("aikv-test-{}.sock", std::process::id()));
+    // A stale socket file from an earlier run is replaced
+    std::fs::write(&path, b"").unwrap();
+
+    let mut server = Server::new(addr.clone(), StorageEngine::new_memory(16));
+    server.set_unix_socket(&path);
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+
+    let mut connected = None;
+    for _ in 0..50 {
+        if let (Ok(tcp), Ok(unix)) = (
+            TcpStream::connect(&addr).await,
+            UnixStream::connect(&path).await,
+        ) {
+            connected = Some((tcp, unix));
+            break;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    let (mut tcp, mut unix) = connected.expect("Server did not start");
+    let mut tcp_parser = RespParser::new(4096);
+    let mut unix_parser = RespParser::new(4096);
+
+    let reply = send(&mut tcp, &mut tcp_parser, &["SET", "shared", "tcp"]).await;
+    assert_eq!(reply, RespValue::ok());
+    let reply = send(&mut unix, &mut unix_parser, &["GET", "shared"]).await;
+    assert_eq!(reply, RespValue::bulk_string("tcp"));
+
+    let reply = send(&mut unix, &mut unix_parser, &["SET", "shared", "unix"]).await;
+    assert_eq!(reply, RespValue::ok());
+    let reply = send(&mut tcp, &mut tcp_parser, &["GET", "shared"]).await;
+    assert_eq!(reply, RespValue::bulk_string("unix"));
+
+    // Unix socket clients report the socket path as their address
+    let list = match send(&mut tcp, &mut tcp_parser, &["CLIENT", "LIST"]).await {
+        RespValue::BulkString(Some(list)) => String::from_utf8_lossy(&list).to_string(),
+        other => panic!("Unexpected CLIENT LIST reply: {:?}", other),
+    };
+    assert!(
+        list.contains(&format!("addr={}:0", path.display())),
+        "{}",
+        list
+    );
+
+    let _ = std::fs::remove_file(&path);
+}